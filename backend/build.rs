@@ -0,0 +1,4 @@
+// Rebuilds the binary when a migration is added, as they are embedded with `sqlx::migrate!`
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,2 @@
+-- Increased with every change of a pdf, sent as ETag so concurrent updates don't overwrite each other
+ALTER TABLE pdfs ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,11 @@
+CREATE TABLE api_tokens (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    user_id uuid NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    time_created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ,
+    last_used TIMESTAMPTZ,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,62 @@
+-- Every pdf belongs to a user. Existing pdfs are assigned to the first admin, which is created without a password if
+-- there is none yet. The backend sets its password from admin.password (ADMIN_PASSWORD) on the next start.
+INSERT INTO users (username, password_hash, is_admin)
+SELECT 'admin', '', TRUE
+WHERE EXISTS (SELECT 1 FROM pdfs) AND NOT EXISTS (SELECT 1 FROM users WHERE is_admin)
+ON CONFLICT (username) DO NOTHING;
+
+-- Pdfs stored before the storage was deduplicated keep their file name as storage key
+ALTER TABLE pdfs ADD COLUMN owner_id uuid, ADD COLUMN storage_key TEXT;
+
+UPDATE pdfs SET owner_id = (SELECT id FROM users WHERE is_admin ORDER BY time_created LIMIT 1), storage_key = file_name;
+
+ALTER TABLE pdfs
+    ALTER COLUMN owner_id SET NOT NULL,
+    ALTER COLUMN storage_key SET NOT NULL,
+    DROP CONSTRAINT pdfs_file_name_key,
+    ADD UNIQUE (owner_id, file_name),
+    ADD FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE INDEX pdfs_storage_key_idx ON pdfs (storage_key);
+
+
+CREATE TABLE groups (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    time_created TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE group_members (
+    group_id uuid,
+    user_id uuid,
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- A pdf is shared either with a single user or with all members of a group
+CREATE TABLE pdf_shares (
+    pdf_id uuid NOT NULL,
+    user_id uuid,
+    group_id uuid,
+    permission TEXT NOT NULL CHECK (permission IN ('read', 'annotate', 'edit')),
+    CHECK ((user_id IS NULL) <> (group_id IS NULL)),
+    UNIQUE (pdf_id, user_id),
+    UNIQUE (pdf_id, group_id),
+    FOREIGN KEY (pdf_id) REFERENCES pdfs(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- What the user may do with the pdf: 0 nothing, 1 read, 2 annotate, 3 edit, 4 owner
+CREATE FUNCTION pdf_access_level(pdf uuid, viewer uuid) RETURNS INTEGER AS $$
+    SELECT CASE
+        WHEN EXISTS (SELECT 1 FROM pdfs WHERE id = pdf AND owner_id = viewer) THEN 4
+        ELSE COALESCE((
+            SELECT max(CASE permission WHEN 'read' THEN 1 WHEN 'annotate' THEN 2 WHEN 'edit' THEN 3 END)
+            FROM pdf_shares
+            WHERE pdf_id = pdf
+            AND (user_id = viewer OR group_id IN (SELECT group_id FROM group_members WHERE user_id = viewer))
+        ), 0)
+    END
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,27 @@
+-- Public links to a single pdf for people without an account, only the hash of the token is stored
+CREATE TABLE share_links (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    pdf_id uuid NOT NULL,
+    created_by uuid NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    password_hash TEXT,
+    expires_at TIMESTAMPTZ,
+    max_downloads INTEGER CHECK (max_downloads > 0),
+    download_count INTEGER NOT NULL DEFAULT 0,
+    time_created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ,
+    FOREIGN KEY (pdf_id) REFERENCES pdfs(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE share_link_accesses (
+    id uuid DEFAULT uuid_generate_v4 () PRIMARY KEY,
+    share_link_id uuid NOT NULL,
+    time_accessed TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ip_address TEXT,
+    user_agent TEXT,
+    outcome TEXT NOT NULL CHECK (outcome IN ('viewed', 'downloaded', 'wrong_password', 'expired', 'revoked', 'limit_reached')),
+    FOREIGN KEY (share_link_id) REFERENCES share_links(id) ON DELETE CASCADE
+);
+
+CREATE INDEX share_link_accesses_share_link_id_idx ON share_link_accesses (share_link_id, time_accessed);
@@ -0,0 +1,27 @@
+-- Append-only record of who changed what, entries outlive the users and pdfs they refer to
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    time_created TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor_id uuid,
+    actor_name TEXT,
+    action TEXT NOT NULL,
+    target_id uuid,
+    -- The changed fields as {"field": {"old": ..., "new": ...}}
+    changes JSONB NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX audit_log_time_created_idx ON audit_log (time_created);
+CREATE INDEX audit_log_actor_id_idx ON audit_log (actor_id);
+CREATE INDEX audit_log_target_id_idx ON audit_log (target_id);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'The audit log is append-only';
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate BEFORE TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
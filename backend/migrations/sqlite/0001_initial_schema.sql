@@ -0,0 +1,52 @@
+-- Same schema as the Postgres migrations. SQLite has no UUID and timestamp types, IDs are stored as 16 byte blobs
+-- and timestamps as RFC 3339 text in UTC, which sorts in the order of time. All of them are set by the backend.
+
+CREATE TABLE pdfs (
+    id BLOB PRIMARY KEY,
+    title TEXT,
+    file_name TEXT NOT NULL UNIQUE,
+    author TEXT,
+    pages INTEGER,
+    comments TEXT,
+    time_added TEXT,
+    last_accessed TEXT,
+    picture TEXT
+);
+
+-- Index for searching the title and author, the trigram tokenizer lets it answer LIKE '%...%' queries
+CREATE VIRTUAL TABLE pdfs_search USING fts5 (
+    title,
+    author,
+    content = 'pdfs',
+    content_rowid = 'rowid',
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER pdfs_search_insert AFTER INSERT ON pdfs BEGIN
+    INSERT INTO pdfs_search (rowid, title, author) VALUES (new.rowid, new.title, new.author);
+END;
+
+CREATE TRIGGER pdfs_search_delete AFTER DELETE ON pdfs BEGIN
+    INSERT INTO pdfs_search (pdfs_search, rowid, title, author) VALUES ('delete', old.rowid, old.title, old.author);
+END;
+
+CREATE TRIGGER pdfs_search_update AFTER UPDATE OF title, author ON pdfs BEGIN
+    INSERT INTO pdfs_search (pdfs_search, rowid, title, author) VALUES ('delete', old.rowid, old.title, old.author);
+    INSERT INTO pdfs_search (rowid, title, author) VALUES (new.rowid, new.title, new.author);
+END;
+
+
+CREATE TABLE tags (
+    name TEXT PRIMARY KEY
+);
+
+CREATE TABLE tags_to_pdfs (
+    name TEXT,
+    id BLOB,
+    PRIMARY KEY (name, id),
+    FOREIGN KEY (id) REFERENCES pdfs(id) ON DELETE CASCADE,
+    FOREIGN KEY (name) REFERENCES tags(name) ON DELETE CASCADE
+);
+
+CREATE INDEX tags_to_pdfs_id_idx ON tags_to_pdfs (id);
+
@@ -0,0 +1,12 @@
+CREATE TABLE api_tokens (
+    id BLOB PRIMARY KEY,
+    user_id BLOB NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- JSON array of the scopes
+    scopes TEXT NOT NULL,
+    time_created TEXT NOT NULL,
+    expires_at TEXT,
+    last_used TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
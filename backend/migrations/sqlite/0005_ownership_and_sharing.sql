@@ -0,0 +1,85 @@
+-- Every pdf belongs to a user. Existing pdfs are assigned to the first admin, which is created without a password if
+-- there is none yet. The backend sets its password from admin.password (ADMIN_PASSWORD) on the next start.
+INSERT OR IGNORE INTO users (id, username, password_hash, is_admin, time_created)
+SELECT randomblob(16), 'admin', '', TRUE, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+WHERE EXISTS (SELECT 1 FROM pdfs) AND NOT EXISTS (SELECT 1 FROM users WHERE is_admin);
+
+-- SQLite can't change the constraints of a table, so the table is rebuilt. The migration runs in a transaction with
+-- foreign keys enforced, dropping the old table deletes the tags of the pdfs, which are kept aside until it is replaced.
+CREATE TEMP TABLE kept_tags_to_pdfs AS SELECT name, id FROM tags_to_pdfs;
+
+CREATE TABLE pdfs_with_owner (
+    id BLOB PRIMARY KEY,
+    title TEXT,
+    file_name TEXT NOT NULL,
+    author TEXT,
+    pages INTEGER,
+    comments TEXT,
+    time_added TEXT,
+    last_accessed TEXT,
+    picture TEXT,
+    version INTEGER NOT NULL DEFAULT 1,
+    owner_id BLOB NOT NULL,
+    storage_key TEXT NOT NULL,
+    UNIQUE (owner_id, file_name),
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Pdfs stored before the storage was deduplicated keep their file name as storage key. The rowids are kept, the
+-- search index refers to them.
+INSERT INTO pdfs_with_owner (rowid, id, title, file_name, author, pages, comments, time_added, last_accessed, picture, version, owner_id, storage_key)
+SELECT rowid, id, title, file_name, author, pages, comments, time_added, last_accessed, picture, version,
+    (SELECT id FROM users WHERE is_admin ORDER BY time_created LIMIT 1), file_name
+FROM pdfs;
+
+DROP TABLE pdfs;
+ALTER TABLE pdfs_with_owner RENAME TO pdfs;
+
+INSERT INTO tags_to_pdfs (name, id) SELECT name, id FROM kept_tags_to_pdfs;
+DROP TABLE kept_tags_to_pdfs;
+
+CREATE INDEX pdfs_storage_key_idx ON pdfs (storage_key);
+
+CREATE TRIGGER pdfs_search_insert AFTER INSERT ON pdfs BEGIN
+    INSERT INTO pdfs_search (rowid, title, author) VALUES (new.rowid, new.title, new.author);
+END;
+
+CREATE TRIGGER pdfs_search_delete AFTER DELETE ON pdfs BEGIN
+    INSERT INTO pdfs_search (pdfs_search, rowid, title, author) VALUES ('delete', old.rowid, old.title, old.author);
+END;
+
+CREATE TRIGGER pdfs_search_update AFTER UPDATE OF title, author ON pdfs BEGIN
+    INSERT INTO pdfs_search (pdfs_search, rowid, title, author) VALUES ('delete', old.rowid, old.title, old.author);
+    INSERT INTO pdfs_search (rowid, title, author) VALUES (new.rowid, new.title, new.author);
+END;
+
+INSERT INTO pdfs_search (pdfs_search) VALUES ('rebuild');
+
+
+CREATE TABLE groups (
+    id BLOB PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    time_created TEXT NOT NULL
+);
+
+CREATE TABLE group_members (
+    group_id BLOB,
+    user_id BLOB,
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- A pdf is shared either with a single user or with all members of a group
+CREATE TABLE pdf_shares (
+    pdf_id BLOB NOT NULL,
+    user_id BLOB,
+    group_id BLOB,
+    permission TEXT NOT NULL CHECK (permission IN ('read', 'annotate', 'edit')),
+    CHECK ((user_id IS NULL) <> (group_id IS NULL)),
+    UNIQUE (pdf_id, user_id),
+    UNIQUE (pdf_id, group_id),
+    FOREIGN KEY (pdf_id) REFERENCES pdfs(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
@@ -0,0 +1,27 @@
+-- Public links to a single pdf for people without an account, only the hash of the token is stored
+CREATE TABLE share_links (
+    id BLOB PRIMARY KEY,
+    pdf_id BLOB NOT NULL,
+    created_by BLOB NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    password_hash TEXT,
+    expires_at TEXT,
+    max_downloads INTEGER CHECK (max_downloads > 0),
+    download_count INTEGER NOT NULL DEFAULT 0,
+    time_created TEXT NOT NULL,
+    revoked_at TEXT,
+    FOREIGN KEY (pdf_id) REFERENCES pdfs(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE share_link_accesses (
+    id BLOB PRIMARY KEY,
+    share_link_id BLOB NOT NULL,
+    time_accessed TEXT NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    outcome TEXT NOT NULL CHECK (outcome IN ('viewed', 'downloaded', 'wrong_password', 'expired', 'revoked', 'limit_reached')),
+    FOREIGN KEY (share_link_id) REFERENCES share_links(id) ON DELETE CASCADE
+);
+
+CREATE INDEX share_link_accesses_share_link_id_idx ON share_link_accesses (share_link_id, time_accessed);
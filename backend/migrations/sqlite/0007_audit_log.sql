@@ -0,0 +1,23 @@
+-- Append-only record of who changed what, entries outlive the users and pdfs they refer to
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time_created TEXT NOT NULL,
+    actor_id BLOB,
+    actor_name TEXT,
+    action TEXT NOT NULL,
+    target_id BLOB,
+    -- The changed fields as {"field": {"old": ..., "new": ...}}
+    changes TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX audit_log_time_created_idx ON audit_log (time_created);
+CREATE INDEX audit_log_actor_id_idx ON audit_log (actor_id);
+CREATE INDEX audit_log_target_id_idx ON audit_log (target_id);
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'The audit log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'The audit log is append-only');
+END;
@@ -0,0 +1,2 @@
+-- Set by the storage check for pdfs whose file is gone from the upload directory, cleared when it is back
+ALTER TABLE pdfs ADD COLUMN file_missing BOOLEAN NOT NULL DEFAULT FALSE;
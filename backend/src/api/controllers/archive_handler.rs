@@ -0,0 +1,146 @@
+use std::io::{BufWriter, Write};
+use tracing::{error, info};
+use actix_web::{web::{Data, self}, http::header::{ContentDisposition, DispositionParam, DispositionType}, HttpResponse, Responder};
+use chrono::Utc;
+use futures_util::StreamExt;
+use tempfile::NamedTempFile;
+use tokio::sync::mpsc;
+
+use crate::{AppState, domain::service::archive::ArchiveService};
+use crate::api::dto::archive::{ArchiveExportQueryDto, ArchiveImportQueryDto, ArchiveImportReportDto};
+use crate::api::dto::error::ErrorDto;
+use crate::domain::models::user::AuthenticatedUser;
+use crate::errors::ArchiveError;
+
+
+/// Hands what is written to the response stream, so the archive is sent while it is written
+struct ChannelWriter {
+    sender: mpsc::Sender<Result<web::Bytes, std::io::Error>>
+}
+
+
+impl Write for ChannelWriter {
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.sender.blocking_send(Ok(web::Bytes::copy_from_slice(buf))) {
+            Ok(_) => Ok(buf.len()),
+            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "The client closed the connection"))
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+}
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/archive",
+    tag = "archive",
+    params(ArchiveExportQueryDto),
+    responses(
+        (status = 200, description = "Tar archive with a manifest.json and the files of the pdfs of all users", content_type = "application/x-tar", body = Vec<u8>),
+        (status = 400, description = "Invalid since timestamp", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "The pdfs could not be read", body = ErrorDto)
+    )
+)]
+pub async fn export_archive(state: Data<AppState>, query: web::Query<ArchiveExportQueryDto>) -> impl Responder {
+    info!("export_archive()");
+
+    let export = match state.archive_service.export(query.since).await {
+        Ok(export) => export,
+        Err(msg) => return HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    };
+
+    let (sender, receiver) = mpsc::channel(16);
+
+    actix_web::rt::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(64 * 1024, ChannelWriter { sender: sender.clone() });
+
+        // The status is sent already, so an error can only abort the stream
+        if let Err(err) = export.write_to(writer) {
+            error!("Error writing the archive: {err}");
+            let _ = sender.blocking_send(Err(err));
+        }
+    });
+
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    let file_name = format!("pdf-store-{}.tar", Utc::now().format("%Y%m%d-%H%M%S"));
+
+    HttpResponse::Ok()
+        .content_type("application/x-tar")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)]
+        })
+        .streaming(stream)
+}
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/archive",
+    tag = "archive",
+    params(ArchiveImportQueryDto),
+    request_body(content = Vec<u8>, description = "Tar archive as exported by `GET /api/v1/archive`", content_type = "application/x-tar"),
+    responses(
+        (status = 200, description = "Outcome per pdf of the archive, pdfs of unknown owners are restored for the admin", body = ArchiveImportReportDto),
+        (status = 400, description = "The archive or its manifest can't be read", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 413, description = "The archive exceeds the maximum upload size", body = ErrorDto),
+        (status = 500, description = "The archive could not be restored", body = ErrorDto)
+    )
+)]
+pub async fn import_archive(
+    state: Data<AppState>,
+    authenticated_user: AuthenticatedUser,
+    query: web::Query<ArchiveImportQueryDto>,
+    mut payload: web::Payload
+) -> impl Responder {
+    info!("import_archive()");
+
+    let storage = &state.config.storage;
+
+    let received = match NamedTempFile::new_in(&storage.tmp_dir) {
+        Ok(received) => received,
+        Err(_) => return HttpResponse::InternalServerError().json(ErrorDto { message: "Error receiving the archive".to_string() })
+    };
+
+    let mut writer = BufWriter::new(received);
+    let mut size = 0;
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Error receiving the archive".to_string() })
+        };
+
+        size += chunk.len();
+        if size > storage.max_upload_size {
+            return HttpResponse::PayloadTooLarge().json(ErrorDto { message: "The archive exceeds the maximum upload size".to_string() });
+        }
+
+        if writer.write_all(&chunk).is_err() {
+            return HttpResponse::InternalServerError().json(ErrorDto { message: "Error receiving the archive".to_string() });
+        }
+    }
+
+    let received = match writer.into_inner() {
+        Ok(received) => received,
+        Err(_) => return HttpResponse::InternalServerError().json(ErrorDto { message: "Error receiving the archive".to_string() })
+    };
+
+    match state.archive_service.import(received.path(), query.on_conflict, &authenticated_user.user.id).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(ArchiveError::InvalidArchive(msg)) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        Err(ArchiveError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,33 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+
+use crate::{AppState, domain::service::audit::AuditService};
+use crate::api::dto::audit::{AuditLogDto, AuditLogSearchDto};
+use crate::api::dto::error::ErrorDto;
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = "audit",
+    params(AuditLogSearchDto),
+    responses(
+        (status = 200, description = "Page of the matching audit log entries, newest first", body = AuditLogDto),
+        (status = 400, description = "Paging information is missing or invalid, or an unknown action was given", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "Audit log could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_audit_log(state: Data<AppState>, search: web::Query<AuditLogSearchDto>) -> impl Responder {
+    info!("get_audit_log()");
+
+    if !matches!((search.page, search.size), (Some(page), Some(size)) if page > 0 && size > 0) {
+        return HttpResponse::BadRequest().json(ErrorDto { message: "Page number and page size must be provided and positive".to_string() });
+    }
+
+    match state.audit_service.get_entries(search.into_inner()).await {
+        Ok(audit_log) => HttpResponse::Ok().json(audit_log),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,113 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, cookie::{Cookie, SameSite, time::Duration}, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+
+use crate::{AppState, errors::AuthError, domain::service::auth::AuthService};
+use crate::api::dto::user::{LoginDto, PasswordChangeDto, SessionDto, UserDto};
+use crate::api::dto::error::ErrorDto;
+use crate::api::middleware::auth::{SESSION_COOKIE, session_token};
+use crate::domain::models::user::AuthenticatedUser;
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginDto,
+    security(),
+    responses(
+        (status = 200, description = "Logged in, the session token is also set as cookie", body = SessionDto),
+        (status = 401, description = "Invalid username or password", body = ErrorDto),
+        (status = 500, description = "Login failed", body = ErrorDto)
+    )
+)]
+pub async fn login(state: Data<AppState>, login: web::Json<LoginDto>, req: HttpRequest) -> impl Responder {
+    info!("login()");
+
+    match state.auth_service.login(login.into_inner()).await {
+        Ok(session) => {
+            let session_cookie = Cookie::build(SESSION_COOKIE, session.token.clone())
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Strict)
+                .secure(req.connection_info().scheme() == "https")
+                .max_age(Duration::seconds((session.expires_at - Utc::now()).num_seconds()))
+                .finish();
+
+            HttpResponse::Ok().cookie(session_cookie).json(session)
+        },
+        Err(AuthError::InvalidCredentials(msg)) => HttpResponse::Unauthorized().json(ErrorDto { message: msg }),
+        Err(_) => HttpResponse::InternalServerError().json(ErrorDto { message: "Error logging in".to_string() })
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The session was ended"),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 500, description = "Logout failed", body = ErrorDto)
+    )
+)]
+pub async fn logout(state: Data<AppState>, req: HttpRequest) -> impl Responder {
+    info!("logout()");
+
+    if let Some(token) = session_token(&req) {
+        if let Err(msg) = state.auth_service.logout(&token).await {
+            return HttpResponse::InternalServerError().json(ErrorDto { message: msg });
+        }
+    }
+
+    let mut removal_cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    removal_cookie.make_removal();
+
+    HttpResponse::Ok().cookie(removal_cookie).json(())
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The logged in user", body = UserDto),
+        (status = 401, description = "Not logged in", body = ErrorDto)
+    )
+)]
+pub async fn me(authenticated_user: AuthenticatedUser) -> impl Responder {
+    info!("me()");
+
+    HttpResponse::Ok().json(UserDto::from(authenticated_user.user))
+}
+
+
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/auth/password",
+    tag = "auth",
+    request_body = PasswordChangeDto,
+    responses(
+        (status = 200, description = "Password was changed, all sessions of the user are ended"),
+        (status = 400, description = "The new password is too short", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "The current password is wrong", body = ErrorDto),
+        (status = 500, description = "Password could not be changed", body = ErrorDto)
+    )
+)]
+pub async fn change_password(state: Data<AppState>, authenticated_user: AuthenticatedUser, change: web::Json<PasswordChangeDto>) -> impl Responder {
+    info!("change_password()");
+
+    match state.auth_service.change_password(&authenticated_user.user.id, change.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(AuthError::InvalidInput(msg)) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        Err(AuthError::InvalidCredentials(msg)) => HttpResponse::Forbidden().json(ErrorDto { message: msg }),
+        Err(AuthError::NotFound(msg)) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        Err(AuthError::AlreadyExists(msg)) | Err(AuthError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,29 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+
+use crate::{AppState, domain::service::fsck::FsckService};
+use crate::api::dto::fsck::{FsckQueryDto, FsckReportDto};
+use crate::api::dto::error::ErrorDto;
+use crate::domain::models::user::AuthenticatedUser;
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/fsck",
+    tag = "fsck",
+    params(FsckQueryDto),
+    responses(
+        (status = 200, description = "Issues found in the storage, repaired unless it was a dry run", body = FsckReportDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "The pdfs or the upload directory could not be read", body = ErrorDto)
+    )
+)]
+pub async fn fsck(state: Data<AppState>, authenticated_user: AuthenticatedUser, query: web::Query<FsckQueryDto>) -> impl Responder {
+    info!("fsck()");
+
+    match state.fsck_service.check(query.dry_run, &authenticated_user.user.id).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,157 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+use uuid::Uuid;
+
+use crate::{AppState, errors::AuthError, domain::service::auth::AuthService};
+use crate::api::dto::group::{CreateGroupDto, GroupDto};
+use crate::api::dto::error::ErrorDto;
+
+
+fn auth_error_response(err: AuthError) -> HttpResponse {
+    match err {
+        AuthError::InvalidInput(msg) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        AuthError::NotFound(msg) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        AuthError::AlreadyExists(msg) => HttpResponse::Conflict().json(ErrorDto { message: msg }),
+        AuthError::InvalidCredentials(msg) | AuthError::DatabaseError(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/groups",
+    tag = "groups",
+    responses(
+        (status = 200, description = "All groups with their members", body = Vec<GroupDto>),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "Groups could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_groups(state: Data<AppState>) -> impl Responder {
+    info!("get_groups()");
+
+    match state.auth_service.get_groups().await {
+        Ok(groups) => HttpResponse::Ok().json(groups),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/groups",
+    tag = "groups",
+    request_body = CreateGroupDto,
+    responses(
+        (status = 201, description = "The created group", body = GroupDto),
+        (status = 400, description = "Empty group name", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 409, description = "The group name is taken", body = ErrorDto),
+        (status = 500, description = "Group could not be created", body = ErrorDto)
+    )
+)]
+pub async fn create_group(state: Data<AppState>, group: web::Json<CreateGroupDto>) -> impl Responder {
+    info!("create_group()");
+
+    match state.auth_service.create_group(group.into_inner()).await {
+        Ok(created_group) => HttpResponse::Created().json(created_group),
+        Err(err) => auth_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/groups/{group_id}",
+    tag = "groups",
+    params(("group_id" = Uuid, Path, description = "ID of the group")),
+    responses(
+        (status = 200, description = "Group was deleted, pdfs shared with it are no longer shared with its members"),
+        (status = 400, description = "Invalid group ID", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 404, description = "No group with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Group could not be deleted", body = ErrorDto)
+    )
+)]
+pub async fn delete_group(state: Data<AppState>, id: web::Path<String>) -> impl Responder {
+    info!("delete_group()");
+
+    let group_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(group_id) => group_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid group ID given".to_string() })
+    };
+
+    match state.auth_service.delete_group(&group_id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => auth_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/groups/{group_id}/members/{user_id}",
+    tag = "groups",
+    params(("group_id" = Uuid, Path, description = "ID of the group"), ("user_id" = Uuid, Path, description = "ID of the user")),
+    responses(
+        (status = 200, description = "The user is a member of the group"),
+        (status = 400, description = "Invalid group or user ID", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 404, description = "No group or user with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Member could not be added", body = ErrorDto)
+    )
+)]
+pub async fn add_group_member(state: Data<AppState>, path: web::Path<(String, String)>) -> impl Responder {
+    info!("add_group_member()");
+
+    let (group_id, user_id) = path.into_inner();
+
+    let (group_id, user_id) = match (Uuid::parse_str(&group_id), Uuid::parse_str(&user_id)) {
+        (Ok(group_id), Ok(user_id)) => (group_id, user_id),
+        _ => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid group or user ID given".to_string() })
+    };
+
+    match state.auth_service.add_group_member(&group_id, &user_id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => auth_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/groups/{group_id}/members/{user_id}",
+    tag = "groups",
+    params(("group_id" = Uuid, Path, description = "ID of the group"), ("user_id" = Uuid, Path, description = "ID of the user")),
+    responses(
+        (status = 200, description = "The user was removed from the group"),
+        (status = 400, description = "Invalid group or user ID", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 404, description = "The user is not a member of the group", body = ErrorDto),
+        (status = 500, description = "Member could not be removed", body = ErrorDto)
+    )
+)]
+pub async fn remove_group_member(state: Data<AppState>, path: web::Path<(String, String)>) -> impl Responder {
+    info!("remove_group_member()");
+
+    let (group_id, user_id) = path.into_inner();
+
+    let (group_id, user_id) = match (Uuid::parse_str(&group_id), Uuid::parse_str(&user_id)) {
+        (Ok(group_id), Ok(user_id)) => (group_id, user_id),
+        _ => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid group or user ID given".to_string() })
+    };
+
+    match state.auth_service.remove_group_member(&group_id, &user_id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => auth_error_response(err)
+    }
+}
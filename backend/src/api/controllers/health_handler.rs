@@ -1,8 +1,60 @@
-use log::trace;
-use actix_web::{Responder, HttpResponse};
+use tracing::trace;
+use actix_web::{web::Data, Responder, HttpResponse};
 
+use crate::{AppState, domain::service::health::HealthService};
+use crate::api::dto::health::{HealthStatus, LivenessDto, ReadinessDto};
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    security(),
+    responses(
+        (status = 200, description = "Backend is running", body = String, content_type = "application/json")
+    )
+)]
 pub async fn health() -> impl Responder {
     trace!("health()");
 
     HttpResponse::Ok().json("Everything is working fine")
 }
+
+
+/// Only tells that the process is up, without looking at anything it depends on
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    security(),
+    responses(
+        (status = 200, description = "Backend is running", body = LivenessDto)
+    )
+)]
+pub async fn live() -> impl Responder {
+    trace!("live()");
+
+    HttpResponse::Ok().json(LivenessDto { status: HealthStatus::Ok })
+}
+
+
+/// Checks everything the backend depends on, with the outcome and duration of every check
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    security(),
+    responses(
+        (status = 200, description = "Backend can serve requests, some checks may warn", body = ReadinessDto),
+        (status = 503, description = "A critical check failed", body = ReadinessDto)
+    )
+)]
+pub async fn ready(state: Data<AppState>) -> impl Responder {
+    trace!("ready()");
+
+    let readiness = state.health_service.readiness().await;
+
+    match readiness.status {
+        HealthStatus::Fail => HttpResponse::ServiceUnavailable().json(readiness),
+        _ => HttpResponse::Ok().json(readiness)
+    }
+}
@@ -0,0 +1,46 @@
+use tracing::{trace, warn};
+use actix_web::{web::Data, Responder, HttpResponse};
+use prometheus::TEXT_FORMAT;
+
+use crate::{AppState, domain::service::pdf::PdfService};
+use crate::api::dto::error::ErrorDto;
+use crate::metrics::METRICS;
+
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses(
+        (status = 200, description = "Metrics in the text format of Prometheus", body = String, content_type = "text/plain; version=0.0.4"),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "Metrics could not be encoded", body = ErrorDto)
+    )
+)]
+pub async fn metrics(state: Data<AppState>) -> impl Responder {
+    trace!("metrics()");
+
+    // The gauges are read when they are scraped, a gauge that can't be read keeps its last value
+    match state.service.get_library_stats().await {
+        Ok(stats) => {
+            METRICS.library_documents.set(stats.documents);
+            METRICS.library_pages.set(stats.pages);
+            METRICS.library_bytes.set(stats.bytes as i64);
+        },
+        Err(msg) => warn!("{msg}")
+    }
+
+    if let Some(pool_usage) = state.health_service.repository.pool_usage() {
+        METRICS.db_pool_connections.with_label_values(&["idle"]).set(pool_usage.idle as i64);
+        METRICS.db_pool_connections.with_label_values(&["in_use"]).set(pool_usage.open.saturating_sub(pool_usage.idle) as i64);
+        METRICS.db_pool_max_connections.set(pool_usage.max as i64);
+    }
+
+    METRICS.pdf_engine_queued_jobs.set(state.engine.queued_jobs() as i64);
+
+    match METRICS.export() {
+        Ok(text) => HttpResponse::Ok().content_type(TEXT_FORMAT).body(text),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -1,2 +1,13 @@
+pub mod audit_handler;
+pub mod auth_handler;
+pub mod fsck_handler;
+pub mod archive_handler;
+pub mod group_handler;
 pub mod health_handler;
-pub mod pdf_handler;
\ No newline at end of file
+pub mod metrics_handler;
+pub mod pdf_handler;
+pub mod share_handler;
+pub mod share_link_handler;
+pub mod token_handler;
+pub mod user_handler;
+pub mod webdav_handler;
\ No newline at end of file
@@ -1,16 +1,85 @@
-use log::info;
-use actix_web::{web::{ Data, self }, HttpResponse, Responder};
+use tracing::info;
+use actix_web::{web::{ Data, self }, http::header::{self, ETag, EntityTag, Header, IfMatch}, HttpRequest, HttpResponse, Responder};
+use actix_files::NamedFile;
+use mime::APPLICATION_PDF;
 use actix_multipart::form::MultipartForm;
 use uuid::Uuid;
 
-use crate::{AppState, errors::PdfMetadataByIdError, api::dto::pdf::{PdfSearchDto, PdfUpdateDto}, util::{UploadForm, map_pdfs}, domain::service::pdf::PdfService};
+use crate::{AppState, errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError}, api::dto::pdf::{PdfSearchDto, PdfUpdateDto, PdfPatchDto}, util::{UploadForm, map_pdfs}, domain::service::pdf::PdfService};
+use crate::api::dto::pdf::{PdfDto, PdfMetadataDto, PdfOverviewDto, PdfUploadDto};
 use crate::api::dto::paging::PagingDto;
 use crate::api::dto::error::ErrorDto;
+use crate::api::middleware::auth::missing_scope;
+use crate::domain::models::user::{AuthenticatedUser, Scope};
+use crate::api::dto::bulk::{BulkOperationDto, BulkResultDto, BulkSelectionDto};
 
 
-pub async fn get_all(state: Data<AppState>, paging: web::Query<PagingDto>) -> impl Responder {
+/// Entity tag of the given version of the pdf details
+fn version_etag(version: i32) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+
+/// Reads the versions the client expects from the If-Match header, `None` stands for `*`.
+/// Updates without the header are rejected as they would silently overwrite concurrent changes.
+fn expected_versions(req: &HttpRequest) -> Result<Option<Vec<i32>>, Box<HttpResponse>> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Err(Box::new(HttpResponse::PreconditionRequired().json(ErrorDto { message: "The If-Match header is required".to_string() })));
+    }
+
+    match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => Ok(None),
+        Ok(IfMatch::Items(etags)) => {
+            let versions = etags.iter()
+                .filter(|etag| !etag.weak)
+                .filter_map(|etag| etag.tag().parse().ok())
+                .collect();
+            Ok(Some(versions))
+        },
+        Err(_) => Err(Box::new(HttpResponse::BadRequest().json(ErrorDto { message: "Invalid If-Match header given".to_string() })))
+    }
+}
+
+
+fn update_error_response(err: PdfUpdateError) -> HttpResponse {
+    match err {
+        PdfUpdateError::NotFound(msg) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        PdfUpdateError::Forbidden(msg) => HttpResponse::Forbidden().json(ErrorDto { message: msg }),
+        PdfUpdateError::VersionMismatch(current) => HttpResponse::PreconditionFailed().insert_header(version_etag(current.version)).json(current),
+        PdfUpdateError::DatabaseError(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+pub fn access_error_response(err: PdfAccessError) -> HttpResponse {
+    match err {
+        PdfAccessError::NotFound(msg) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        PdfAccessError::Forbidden(msg) => HttpResponse::Forbidden().json(ErrorDto { message: msg }),
+        PdfAccessError::InvalidInput(msg) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        PdfAccessError::DatabaseError(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents",
+    tag = "documents",
+    params(PagingDto),
+    responses(
+        (status = 200, description = "Page of pdf previews", body = PdfOverviewDto),
+        (status = 400, description = "Paging information is missing", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope", body = ErrorDto),
+        (status = 500, description = "Pdfs could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_all(state: Data<AppState>, authenticated_user: AuthenticatedUser, paging: web::Query<PagingDto>) -> impl Responder {
     info!("get_all()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
     if paging.page.is_none() || paging.size.is_none() {
         let error_response = ErrorDto {
             message: "Page number and page size must be provided".to_string()
@@ -18,7 +87,7 @@ pub async fn get_all(state: Data<AppState>, paging: web::Query<PagingDto>) -> im
         return HttpResponse::BadRequest().json(error_response);
     }
 
-    let paged_pds_res = state.service.get_all(PagingDto { size: paging.size, page: paging.page }).await;
+    let paged_pds_res = state.service.get_all(PagingDto { size: paging.size, page: paging.page }, &authenticated_user.user.id).await;
 
     match paged_pds_res {
         Ok(paged_pdfs) => HttpResponse::Ok().json(paged_pdfs),
@@ -28,9 +97,27 @@ pub async fn get_all(state: Data<AppState>, paging: web::Query<PagingDto>) -> im
 
 
 
-pub async fn get_metadata_by_id(state: Data<AppState>, id: web::Path<String>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/{pdf_id}",
+    tag = "documents",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "Details of the pdf", body = PdfMetadataDto,
+            headers(("ETag" = String, description = "Version of the details, to be sent as `If-Match` on updates"))),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Pdf could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_metadata_by_id(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
     info!("get_metadata_by_id()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
     let id_string = id.into_inner();
     let pdf_id = String::as_str(&id_string);
     let pdf_id = Uuid::parse_str(pdf_id);
@@ -40,10 +127,10 @@ pub async fn get_metadata_by_id(state: Data<AppState>, id: web::Path<String>) ->
         _ => ()
     }
 
-    let metadata_res = state.service.get_pdf_metadata(&pdf_id.unwrap()).await;
+    let metadata_res = state.service.get_pdf_metadata(&pdf_id.unwrap(), &authenticated_user.user.id).await;
 
-    if metadata_res.is_ok() {
-        return HttpResponse::Ok().json(metadata_res.unwrap())
+    if let Ok(metadata) = metadata_res {
+        HttpResponse::Ok().insert_header(version_etag(metadata.version)).json(metadata)
     } else {
         let err = metadata_res.err().unwrap();
 
@@ -56,9 +143,27 @@ pub async fn get_metadata_by_id(state: Data<AppState>, id: web::Path<String>) ->
 
 
 
-pub async fn get_by_id(state: Data<AppState>, id: web::Path<String>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/pdfs/{pdf_id}",
+    tag = "legacy",
+    description = "Superseded by `GET /api/v1/documents/{pdf_id}/content`, which serves the pdf itself instead of a base64 encoded copy.",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "Base64 encoded content of the pdf", body = PdfDto),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Pdf could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_by_id(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
     info!("get_by_id()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
     let id_string = id.into_inner();
     let pdf_id = String::as_str(&id_string);
     let pdf_id = Uuid::parse_str(pdf_id);
@@ -68,19 +173,75 @@ pub async fn get_by_id(state: Data<AppState>, id: web::Path<String>) -> impl Res
         _ => ()
     }
 
-    let pdf_res = state.service.get_by_id(&pdf_id.unwrap()).await;
+    let pdf_res = state.service.get_by_id(&pdf_id.unwrap(), &authenticated_user.user.id).await;
 
     match pdf_res {
-        Ok(pdf_dto) => return HttpResponse::Ok().json(pdf_dto),
-        Err(msg) => return HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+        Ok(pdf_dto) => HttpResponse::Ok().json(pdf_dto),
+        Err(PdfMetadataByIdError::NotFound(msg)) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        Err(PdfMetadataByIdError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/{pdf_id}/content",
+    tag = "documents",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "The pdf, served as `application/pdf`"),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists or its file is missing", body = ErrorDto),
+        (status = 500, description = "Pdf could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_content_by_id(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>, req: HttpRequest) -> impl Responder {
+    info!("get_content_by_id()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    let path = match state.service.get_file_path(&pdf_id, &authenticated_user.user.id).await {
+        Ok(path) => path,
+        Err(PdfMetadataByIdError::NotFound(msg)) => return HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        Err(PdfMetadataByIdError::DatabaseError(msg)) => return HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    };
+
+    match NamedFile::open_async(path).await {
+        Ok(file) => file.set_content_type(APPLICATION_PDF).into_response(&req),
+        Err(_) => HttpResponse::NotFound().json(ErrorDto { message: "The file of the pdf is missing".to_string() })
     }
 }
 
 
 
-pub async fn search(state: Data<AppState>, search: web::Query<PdfSearchDto>) -> impl Responder {
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/search",
+    tag = "documents",
+    params(PdfSearchDto),
+    responses(
+        (status = 200, description = "Page of matching pdf previews", body = PdfOverviewDto),
+        (status = 400, description = "Paging information or search parameters are missing", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope", body = ErrorDto),
+        (status = 500, description = "Search failed", body = ErrorDto)
+    )
+)]
+pub async fn search(state: Data<AppState>, authenticated_user: AuthenticatedUser, search: web::Query<PdfSearchDto>) -> impl Responder {
     info!("search()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
     if search.page.is_none() || search.size.is_none() {
         return HttpResponse::BadRequest().json(ErrorDto { message: "Paging information is required".to_string() });
     }
@@ -91,7 +252,7 @@ pub async fn search(state: Data<AppState>, search: web::Query<PdfSearchDto>) ->
 
     let search_dto = search.into_inner();
 
-    let search_res = state.service.search(&search_dto).await;
+    let search_res = state.service.search(&search_dto, &authenticated_user.user.id).await;
 
     match search_res {
         Ok(search_res_dto) => return HttpResponse::Ok().json(search_res_dto),
@@ -102,9 +263,31 @@ pub async fn search(state: Data<AppState>, search: web::Query<PdfSearchDto>) ->
 
 
 
-pub async fn update(state: Data<AppState>, update: web::Json<PdfUpdateDto>, id: web::Path<String>) -> impl Responder {
+#[utoipa::path(
+    put,
+    path = "/api/v1/documents/{pdf_id}",
+    tag = "documents",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf"), ("If-Match" = String, Header, description = "ETag of the details the change is based on, `*` accepts any version")),
+    request_body = PdfUpdateDto,
+    responses(
+        (status = 200, description = "Updated details of the pdf", body = PdfMetadataDto,
+            headers(("ETag" = String, description = "New version of the details"))),
+        (status = 400, description = "Invalid pdf ID or If-Match header", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the pdf is not shared with a permission that allows the change", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 412, description = "The pdf was modified in the meantime, contains its current details", body = PdfMetadataDto,
+            headers(("ETag" = String, description = "Current version of the details"))),
+        (status = 428, description = "The If-Match header is missing", body = ErrorDto),
+        (status = 500, description = "Pdf could not be updated", body = ErrorDto)
+    )
+)]
+pub async fn update(state: Data<AppState>, authenticated_user: AuthenticatedUser, update: web::Json<PdfUpdateDto>, id: web::Path<String>, req: HttpRequest) -> impl Responder {
     info!("update()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
     let id_string = id.into_inner();
     let pdf_id = String::as_str(&id_string);
     let pdf_id = Uuid::parse_str(pdf_id);
@@ -114,22 +297,87 @@ pub async fn update(state: Data<AppState>, update: web::Json<PdfUpdateDto>, id:
         _ => ()
     }
 
+    let expected_versions = match expected_versions(&req) {
+        Ok(versions) => versions,
+        Err(response) => return *response
+    };
+
     let update = update.into_inner();
 
-    let update_res = state.service.update(update, &pdf_id.unwrap()).await;
+    let update_res = state.service.update(update, &pdf_id.unwrap(), &authenticated_user.user.id, expected_versions).await;
 
     match update_res {
-        Ok(updated_pdf_dto) => HttpResponse::Ok().json(updated_pdf_dto),
-        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+        Ok(updated_pdf_dto) => HttpResponse::Ok().insert_header(version_etag(updated_pdf_dto.version)).json(updated_pdf_dto),
+        Err(err) => update_error_response(err)
     }
 
 }
 
 
 
-pub async fn delete(state: Data<AppState>, id: web::Path<String>) -> impl Responder {
+#[utoipa::path(
+    patch,
+    path = "/api/v1/documents/{pdf_id}",
+    tag = "documents",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf"), ("If-Match" = String, Header, description = "ETag of the details the change is based on, `*` accepts any version")),
+    request_body(content = PdfPatchDto, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Updated details of the pdf", body = PdfMetadataDto,
+            headers(("ETag" = String, description = "New version of the details"))),
+        (status = 400, description = "Invalid pdf ID or If-Match header", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the pdf is not shared with a permission that allows the change", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 412, description = "The pdf was modified in the meantime, contains its current details", body = PdfMetadataDto,
+            headers(("ETag" = String, description = "Current version of the details"))),
+        (status = 428, description = "The If-Match header is missing", body = ErrorDto),
+        (status = 500, description = "Pdf could not be updated", body = ErrorDto)
+    )
+)]
+pub async fn patch(state: Data<AppState>, authenticated_user: AuthenticatedUser, patch: web::Json<PdfPatchDto>, id: web::Path<String>, req: HttpRequest) -> impl Responder {
+    info!("patch()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    let expected_versions = match expected_versions(&req) {
+        Ok(versions) => versions,
+        Err(response) => return *response
+    };
+
+    match state.service.patch(patch.into_inner(), &pdf_id, &authenticated_user.user.id, expected_versions).await {
+        Ok(patched_pdf_dto) => HttpResponse::Ok().insert_header(version_etag(patched_pdf_dto.version)).json(patched_pdf_dto),
+        Err(err) => update_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/documents/{pdf_id}",
+    tag = "documents",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "Pdf was deleted"),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Pdf could not be deleted", body = ErrorDto)
+    )
+)]
+pub async fn delete(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
     info!("delete()");
 
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
     let id_string = id.into_inner();
     let pdf_id = String::as_str(&id_string);
     let pdf_id = Uuid::parse_str(pdf_id);
@@ -139,26 +387,74 @@ pub async fn delete(state: Data<AppState>, id: web::Path<String>) -> impl Respon
         _ => ()
     }
 
-    let delete_res = state.service.delete(&pdf_id.unwrap()).await;
+    let delete_res = state.service.delete(&pdf_id.unwrap(), &authenticated_user.user.id).await;
 
     match delete_res {
         Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/documents/bulk",
+    tag = "documents",
+    request_body = BulkOperationDto,
+    responses(
+        (status = 200, description = "Result of the operation per selected pdf", body = BulkResultDto),
+        (status = 400, description = "Search selection without search parameters", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope", body = ErrorDto),
+        (status = 500, description = "Operation failed, no pdf was changed", body = ErrorDto)
+    )
+)]
+pub async fn bulk(state: Data<AppState>, authenticated_user: AuthenticatedUser, bulk: web::Json<BulkOperationDto>) -> impl Responder {
+    info!("bulk()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    if let BulkSelectionDto::Search(search) = &bulk.selection {
+        if search.title.is_none() && search.author.is_none() && search.tag.is_none() {
+            return HttpResponse::BadRequest().json(ErrorDto { message: "Search parameters are required".to_string() });
+        }
+    }
+
+    match state.service.bulk(bulk.into_inner(), &authenticated_user.user.id).await {
+        Ok(bulk_result_dto) => HttpResponse::Ok().json(bulk_result_dto),
         Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
     }
 }
 
 
 
-pub async fn upload(state: Data<AppState>, MultipartForm(form): MultipartForm<UploadForm>,) -> impl Responder {
+#[utoipa::path(
+    post,
+    path = "/api/v1/documents",
+    tag = "documents",
+    request_body(content = PdfUploadDto, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Pdfs were uploaded"),
+        (status = 403, description = "The API token lacks the `upload` scope", body = ErrorDto),
+        (status = 500, description = "Pdfs could not be uploaded", body = ErrorDto)
+    )
+)]
+pub async fn upload(state: Data<AppState>, authenticated_user: AuthenticatedUser, MultipartForm(form): MultipartForm<UploadForm>,) -> impl Responder {
     info!("upload()");
 
-    let mapped_pdfs = map_pdfs(MultipartForm(form));
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Upload) {
+        return response;
+    }
+
+    let mapped_pdfs = map_pdfs(MultipartForm(form), state.engine.as_ref()).await;
 
     if mapped_pdfs.is_err() {
         return HttpResponse::InternalServerError().json(ErrorDto { message: mapped_pdfs.err().unwrap() });
     }
 
-    let res  = state.service.upload(mapped_pdfs.unwrap()).await;
+    let res  = state.service.upload(mapped_pdfs.unwrap(), &authenticated_user.user.id).await;
 
     match res {
         Ok(_) => HttpResponse::Created().json(()),
@@ -0,0 +1,109 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+use uuid::Uuid;
+
+use crate::{AppState, domain::service::pdf::PdfService};
+use crate::api::controllers::pdf_handler::access_error_response;
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::api::dto::error::ErrorDto;
+use crate::api::middleware::auth::missing_scope;
+use crate::domain::models::user::{AuthenticatedUser, Scope};
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/{pdf_id}/permissions",
+    tag = "sharing",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "Users and groups the pdf is shared with", body = Vec<PdfShareDto>),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Shares could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_shares(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
+    info!("get_shares()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    match state.service.get_shares(&pdf_id, &authenticated_user.user.id).await {
+        Ok(shares) => HttpResponse::Ok().json(shares),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/documents/{pdf_id}/permissions",
+    tag = "sharing",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    request_body = PdfShareDto,
+    responses(
+        (status = 200, description = "The pdf is shared with the user or group"),
+        (status = 400, description = "Invalid pdf ID, unknown user or group, or neither or both of them given", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Pdf could not be shared", body = ErrorDto)
+    )
+)]
+pub async fn share(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>, share: web::Json<PdfShareDto>) -> impl Responder {
+    info!("share()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    match state.service.set_share(&pdf_id, &authenticated_user.user.id, share.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/documents/{pdf_id}/permissions",
+    tag = "sharing",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf"), ShareTargetDto),
+    responses(
+        (status = 200, description = "The pdf is no longer shared with the user or group"),
+        (status = 400, description = "Invalid pdf ID, or neither or both of user and group given", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists, or it is not shared with the user or group", body = ErrorDto),
+        (status = 500, description = "Share could not be removed", body = ErrorDto)
+    )
+)]
+pub async fn unshare(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>, target: web::Query<ShareTargetDto>) -> impl Responder {
+    info!("unshare()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    match state.service.delete_share(&pdf_id, &authenticated_user.user.id, target.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => access_error_response(err)
+    }
+}
@@ -0,0 +1,358 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, http::header::{self, ContentDisposition, DispositionParam, DispositionType, HeaderName, HeaderValue}, HttpRequest, HttpResponse, Responder};
+use actix_files::NamedFile;
+use mime::APPLICATION_PDF;
+use uuid::Uuid;
+
+use crate::{AppState, errors::ShareLinkError, domain::service::pdf::PdfService};
+use crate::api::controllers::pdf_handler::access_error_response;
+use crate::api::dto::share_link::{CreateShareLinkDto, CreatedShareLinkDto, ShareLinkAccessDto, ShareLinkDownloadDto, ShareLinkDto, SharedPdfDto};
+use crate::api::dto::error::ErrorDto;
+use crate::api::middleware::auth::missing_scope;
+use crate::domain::models::share::ShareLinkClient;
+use crate::domain::models::user::{AuthenticatedUser, Scope};
+
+
+/// Share links are opened by people without an account, so their responses are kept out of caches, search engines and referrers
+fn with_share_link_headers(mut response: HttpResponse) -> HttpResponse {
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    headers.insert(HeaderName::from_static("x-robots-tag"), HeaderValue::from_static("noindex"));
+    headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static("default-src 'none'; style-src 'unsafe-inline'; form-action 'self'"));
+    response
+}
+
+
+const PASSWORD_INPUT: &str = "<label>Password <input type=\"password\" name=\"password\" required autofocus></label>\n";
+
+
+fn parse_ids(ids: (String, String)) -> Result<(Uuid, Uuid), Box<HttpResponse>> {
+    let pdf_id = match Uuid::parse_str(&ids.0) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return Err(Box::new(HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })))
+    };
+
+    match Uuid::parse_str(&ids.1) {
+        Ok(share_link_id) => Ok((pdf_id, share_link_id)),
+        Err(_) => Err(Box::new(HttpResponse::BadRequest().json(ErrorDto { message: "Invalid share link ID given".to_string() })))
+    }
+}
+
+
+fn share_link_client(req: &HttpRequest) -> ShareLinkClient {
+    ShareLinkClient {
+        ip_address: req.peer_addr().map(|addr| addr.ip().to_string()),
+        user_agent: req.headers().get(header::USER_AGENT).and_then(|user_agent| user_agent.to_str().ok()).map(str::to_string)
+    }
+}
+
+
+/// Browsers get a page with the details of the pdf, other clients the file itself
+fn wants_html(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>{}</title>\n<style>body {{ font-family: sans-serif; max-width: 40em; margin: 3em auto; padding: 0 1em; }} dt {{ font-weight: bold; }}</style>\n\
+        </head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+
+fn shared_pdf_page(shared_pdf: &SharedPdfDto) -> String {
+    let title = shared_pdf.title.clone().unwrap_or(shared_pdf.file_name.clone());
+
+    let mut details = vec![("File", shared_pdf.file_name.clone())];
+    if let Some(author) = &shared_pdf.author {
+        details.push(("Author", author.clone()));
+    }
+    if let Some(pages) = shared_pdf.pages {
+        details.push(("Pages", pages.to_string()));
+    }
+    if let Some(expires_at) = shared_pdf.expires_at {
+        details.push(("Available until", expires_at.format("%Y-%m-%d %H:%M UTC").to_string()));
+    }
+    if let Some(downloads_left) = shared_pdf.downloads_left {
+        details.push(("Downloads left", downloads_left.to_string()));
+    }
+
+    let details: String = details.iter()
+        .map(|(name, value)| format!("<dt>{}</dt><dd>{}</dd>\n", name, escape_html(value)))
+        .collect();
+
+    let password = match shared_pdf.password_protected {
+        true => PASSWORD_INPUT,
+        false => ""
+    };
+
+    let body = format!(
+        "<h1>{}</h1>\n<dl>\n{}</dl>\n<form method=\"post\">\n{}<button type=\"submit\">Download</button>\n</form>\n",
+        escape_html(&title),
+        details,
+        password
+    );
+
+    html_page(&title, &body)
+}
+
+
+fn share_link_error_response(err: ShareLinkError, html: bool) -> HttpResponse {
+    let password_required = matches!(err, ShareLinkError::PasswordRequired(_));
+
+    let (mut response, msg) = match err {
+        ShareLinkError::NotFound(msg) => (HttpResponse::NotFound(), msg),
+        ShareLinkError::Gone(msg) => (HttpResponse::Gone(), msg),
+        ShareLinkError::PasswordRequired(msg) => (HttpResponse::Unauthorized(), msg),
+        ShareLinkError::DatabaseError(msg) => (HttpResponse::InternalServerError(), msg)
+    };
+
+    if !html {
+        return with_share_link_headers(response.json(ErrorDto { message: msg }));
+    }
+
+    // A wrong password is shown together with the form to try again
+    let body = match password_required {
+        true => format!("<p role=\"alert\">{}</p>\n<form method=\"post\">\n{}<button type=\"submit\">Download</button>\n</form>\n", escape_html(&msg), PASSWORD_INPUT),
+        false => format!("<p>{}</p>\n", escape_html(&msg))
+    };
+
+    with_share_link_headers(response.content_type(mime::TEXT_HTML_UTF_8).body(html_page("Shared pdf", &body)))
+}
+
+
+async fn download_response(state: &AppState, token: &str, password: Option<String>, req: &HttpRequest) -> HttpResponse {
+    let (path, file_name) = match state.service.download_share_link(token, password, share_link_client(req)).await {
+        Ok(shared_file) => shared_file,
+        Err(err) => return share_link_error_response(err, wants_html(req))
+    };
+
+    let file = match NamedFile::open_async(path).await {
+        Ok(file) => file,
+        Err(_) => return share_link_error_response(ShareLinkError::NotFound("The file of the pdf is missing".to_string()), wants_html(req))
+    };
+
+    let response = file
+        .set_content_type(APPLICATION_PDF)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)]
+        })
+        .into_response(req);
+
+    with_share_link_headers(response)
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/{pdf_id}/shares",
+    tag = "share links",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    responses(
+        (status = 200, description = "Share links of the pdf, including revoked and expired ones", body = Vec<ShareLinkDto>),
+        (status = 400, description = "Invalid pdf ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Share links could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_share_links(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
+    info!("get_share_links()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    match state.service.get_share_links(&pdf_id, &authenticated_user.user.id).await {
+        Ok(share_links) => HttpResponse::Ok().json(share_links),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/documents/{pdf_id}/shares",
+    tag = "share links",
+    params(("pdf_id" = Uuid, Path, description = "ID of the pdf")),
+    request_body = CreateShareLinkDto,
+    responses(
+        (status = 201, description = "The created share link, the token is only shown once", body = CreatedShareLinkDto),
+        (status = 400, description = "Invalid pdf ID, an expiry date in the past, a download limit below 1 or an empty password", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists", body = ErrorDto),
+        (status = 500, description = "Share link could not be created", body = ErrorDto)
+    )
+)]
+pub async fn create_share_link(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>, share_link: web::Json<CreateShareLinkDto>, req: HttpRequest) -> impl Responder {
+    info!("create_share_link()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    let pdf_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(pdf_id) => pdf_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid pdf ID given".to_string() })
+    };
+
+    match state.service.create_share_link(&pdf_id, &authenticated_user.user.id, share_link.into_inner()).await {
+        Ok(mut created) => {
+            let connection_info = req.connection_info();
+            created.url = format!("{}://{}{}", connection_info.scheme(), connection_info.host(), created.url);
+            HttpResponse::Created().json(created)
+        },
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/documents/{pdf_id}/shares/{share_link_id}",
+    tag = "share links",
+    params(
+        ("pdf_id" = Uuid, Path, description = "ID of the pdf"),
+        ("share_link_id" = Uuid, Path, description = "ID of the share link")
+    ),
+    responses(
+        (status = 200, description = "The share link was revoked and can't be used anymore"),
+        (status = 400, description = "Invalid pdf or share link ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `write` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists, or it has no share link with the given ID that wasn't revoked yet", body = ErrorDto),
+        (status = 500, description = "Share link could not be revoked", body = ErrorDto)
+    )
+)]
+pub async fn revoke_share_link(state: Data<AppState>, authenticated_user: AuthenticatedUser, ids: web::Path<(String, String)>) -> impl Responder {
+    info!("revoke_share_link()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Write) {
+        return response;
+    }
+
+    let (pdf_id, share_link_id) = match parse_ids(ids.into_inner()) {
+        Ok(ids) => ids,
+        Err(response) => return *response
+    };
+
+    match state.service.revoke_share_link(&pdf_id, &authenticated_user.user.id, &share_link_id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/documents/{pdf_id}/shares/{share_link_id}/accesses",
+    tag = "share links",
+    params(
+        ("pdf_id" = Uuid, Path, description = "ID of the pdf"),
+        ("share_link_id" = Uuid, Path, description = "ID of the share link")
+    ),
+    responses(
+        (status = 200, description = "Every time the share link was opened, newest first", body = Vec<ShareLinkAccessDto>),
+        (status = 400, description = "Invalid pdf or share link ID", body = ErrorDto),
+        (status = 403, description = "The API token lacks the `read` scope, or the user doesn't own the pdf", body = ErrorDto),
+        (status = 404, description = "No pdf with the given ID exists, or it has no share link with the given ID", body = ErrorDto),
+        (status = 500, description = "Accesses could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_share_link_accesses(state: Data<AppState>, authenticated_user: AuthenticatedUser, ids: web::Path<(String, String)>) -> impl Responder {
+    info!("get_share_link_accesses()");
+
+    if let Some(response) = missing_scope(&authenticated_user, Scope::Read) {
+        return response;
+    }
+
+    let (pdf_id, share_link_id) = match parse_ids(ids.into_inner()) {
+        Ok(ids) => ids,
+        Err(response) => return *response
+    };
+
+    match state.service.get_share_link_accesses(&pdf_id, &authenticated_user.user.id, &share_link_id).await {
+        Ok(accesses) => HttpResponse::Ok().json(accesses),
+        Err(err) => access_error_response(err)
+    }
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/s/{token}",
+    tag = "share links",
+    security(),
+    params(("token" = String, Path, description = "Token of the share link")),
+    responses(
+        (status = 200, description = "Browsers get a page with the details of the pdf and a download button, other clients the file itself", content_type = "application/pdf"),
+        (status = 401, description = "The share link is password protected, the file has to be downloaded with a POST request", body = ErrorDto),
+        (status = 404, description = "No share link with the given token exists", body = ErrorDto),
+        (status = 410, description = "The share link was revoked, has expired or all downloads are used up", body = ErrorDto),
+        (status = 500, description = "Share link could not be opened", body = ErrorDto)
+    )
+)]
+pub async fn open_share_link(state: Data<AppState>, token: web::Path<String>, req: HttpRequest) -> impl Responder {
+    info!("open_share_link()");
+
+    if !wants_html(&req) {
+        return download_response(&state, &token, None, &req).await;
+    }
+
+    match state.service.open_share_link(&token, share_link_client(&req)).await {
+        Ok(shared_pdf) => with_share_link_headers(HttpResponse::Ok().content_type(mime::TEXT_HTML_UTF_8).body(shared_pdf_page(&shared_pdf))),
+        Err(err) => share_link_error_response(err, true)
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/s/{token}",
+    tag = "share links",
+    security(),
+    params(("token" = String, Path, description = "Token of the share link")),
+    request_body(content = ShareLinkDownloadDto, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The file of the pdf", content_type = "application/pdf"),
+        (status = 401, description = "The share link is password protected and no or a wrong password was given", body = ErrorDto),
+        (status = 404, description = "No share link with the given token exists", body = ErrorDto),
+        (status = 410, description = "The share link was revoked, has expired or all downloads are used up", body = ErrorDto),
+        (status = 500, description = "File could not be downloaded", body = ErrorDto)
+    )
+)]
+pub async fn download_share_link(state: Data<AppState>, token: web::Path<String>, form: Option<web::Form<ShareLinkDownloadDto>>, req: HttpRequest) -> impl Responder {
+    info!("download_share_link()");
+
+    let password = form.and_then(|form| form.into_inner().password);
+
+    download_response(&state, &token, password, &req).await
+}
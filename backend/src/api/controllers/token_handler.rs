@@ -0,0 +1,106 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+use uuid::Uuid;
+
+use crate::{AppState, errors::AuthError, domain::service::auth::AuthService};
+use crate::api::dto::api_token::{ApiTokenDto, CreateApiTokenDto, CreatedApiTokenDto};
+use crate::api::dto::error::ErrorDto;
+use crate::domain::models::user::AuthenticatedUser;
+
+
+/// API tokens can't be used to manage API tokens, so a leaked token can't be used to create further ones
+fn token_management_forbidden(authenticated_user: &AuthenticatedUser) -> Option<HttpResponse> {
+    authenticated_user.api_token_id.map(|_| {
+        HttpResponse::Forbidden().json(ErrorDto { message: "API tokens can only be managed after logging in".to_string() })
+    })
+}
+
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "API tokens of the logged in user", body = Vec<ApiTokenDto>),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Request was made with an API token", body = ErrorDto),
+        (status = 500, description = "API tokens could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_tokens(state: Data<AppState>, authenticated_user: AuthenticatedUser) -> impl Responder {
+    info!("get_tokens()");
+
+    if let Some(response) = token_management_forbidden(&authenticated_user) {
+        return response;
+    }
+
+    match state.auth_service.get_api_tokens(&authenticated_user.user.id).await {
+        Ok(api_tokens) => HttpResponse::Ok().json(api_tokens),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokens",
+    tag = "tokens",
+    request_body = CreateApiTokenDto,
+    responses(
+        (status = 201, description = "The created API token, the token is only shown once", body = CreatedApiTokenDto),
+        (status = 400, description = "Empty name, no or unavailable scopes or an expiry date in the past", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Request was made with an API token", body = ErrorDto),
+        (status = 500, description = "API token could not be created", body = ErrorDto)
+    )
+)]
+pub async fn create_token(state: Data<AppState>, authenticated_user: AuthenticatedUser, api_token: web::Json<CreateApiTokenDto>) -> impl Responder {
+    info!("create_token()");
+
+    if let Some(response) = token_management_forbidden(&authenticated_user) {
+        return response;
+    }
+
+    match state.auth_service.create_api_token(&authenticated_user.user, api_token.into_inner()).await {
+        Ok(created_token) => HttpResponse::Created().json(created_token),
+        Err(AuthError::InvalidInput(msg)) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        Err(AuthError::InvalidCredentials(msg)) | Err(AuthError::AlreadyExists(msg)) | Err(AuthError::NotFound(msg)) | Err(AuthError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tokens/{token_id}",
+    tag = "tokens",
+    params(("token_id" = Uuid, Path, description = "ID of the API token")),
+    responses(
+        (status = 200, description = "API token was revoked"),
+        (status = 400, description = "Invalid token ID", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Request was made with an API token", body = ErrorDto),
+        (status = 404, description = "The user has no API token with the given ID", body = ErrorDto),
+        (status = 500, description = "API token could not be revoked", body = ErrorDto)
+    )
+)]
+pub async fn delete_token(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
+    info!("delete_token()");
+
+    if let Some(response) = token_management_forbidden(&authenticated_user) {
+        return response;
+    }
+
+    let token_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(token_id) => token_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid token ID given".to_string() })
+    };
+
+    match state.auth_service.delete_api_token(&authenticated_user.user.id, &token_id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(AuthError::NotFound(msg)) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        Err(AuthError::InvalidInput(msg)) | Err(AuthError::InvalidCredentials(msg)) | Err(AuthError::AlreadyExists(msg)) | Err(AuthError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,92 @@
+use tracing::info;
+use actix_web::{web::{Data, self}, HttpResponse, Responder};
+use uuid::Uuid;
+
+use crate::{AppState, errors::AuthError, domain::service::auth::AuthService};
+use crate::api::dto::user::{CreateUserDto, UserDto};
+use crate::api::dto::error::ErrorDto;
+use crate::domain::models::user::AuthenticatedUser;
+
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    tag = "users",
+    responses(
+        (status = 200, description = "All user accounts", body = Vec<UserDto>),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 500, description = "Users could not be retrieved", body = ErrorDto)
+    )
+)]
+pub async fn get_users(state: Data<AppState>) -> impl Responder {
+    info!("get_users()");
+
+    match state.auth_service.get_users().await {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(msg) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/users",
+    tag = "users",
+    request_body = CreateUserDto,
+    responses(
+        (status = 201, description = "The created user", body = UserDto),
+        (status = 400, description = "Empty username or too short password", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 409, description = "The username is taken", body = ErrorDto),
+        (status = 500, description = "User could not be created", body = ErrorDto)
+    )
+)]
+pub async fn create_user(state: Data<AppState>, user: web::Json<CreateUserDto>) -> impl Responder {
+    info!("create_user()");
+
+    match state.auth_service.create_user(user.into_inner()).await {
+        Ok(created_user) => HttpResponse::Created().json(created_user),
+        Err(AuthError::InvalidInput(msg)) => HttpResponse::BadRequest().json(ErrorDto { message: msg }),
+        Err(AuthError::AlreadyExists(msg)) => HttpResponse::Conflict().json(ErrorDto { message: msg }),
+        Err(AuthError::InvalidCredentials(msg)) | Err(AuthError::NotFound(msg)) | Err(AuthError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
+
+
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{user_id}",
+    tag = "users",
+    params(("user_id" = Uuid, Path, description = "ID of the user")),
+    responses(
+        (status = 200, description = "User and the pdfs of the user were deleted"),
+        (status = 400, description = "Invalid user ID, or the admin tried to delete their own account", body = ErrorDto),
+        (status = 401, description = "Not logged in", body = ErrorDto),
+        (status = 403, description = "Not an admin", body = ErrorDto),
+        (status = 404, description = "No user with the given ID exists", body = ErrorDto),
+        (status = 500, description = "User could not be deleted", body = ErrorDto)
+    )
+)]
+pub async fn delete_user(state: Data<AppState>, authenticated_user: AuthenticatedUser, id: web::Path<String>) -> impl Responder {
+    info!("delete_user()");
+
+    let user_id = match Uuid::parse_str(&id.into_inner()) {
+        Ok(user_id) => user_id,
+        Err(_) => return HttpResponse::BadRequest().json(ErrorDto { message: "Invalid user ID given".to_string() })
+    };
+
+    // Prevents locking everyone out by deleting the last admin
+    if user_id == authenticated_user.user.id {
+        return HttpResponse::BadRequest().json(ErrorDto { message: "The own account can't be deleted".to_string() });
+    }
+
+    match state.auth_service.delete_user(&user_id, &authenticated_user.user.id).await {
+        Ok(_) => HttpResponse::Ok().json(()),
+        Err(AuthError::NotFound(msg)) => HttpResponse::NotFound().json(ErrorDto { message: msg }),
+        Err(AuthError::InvalidInput(msg)) | Err(AuthError::InvalidCredentials(msg)) | Err(AuthError::AlreadyExists(msg)) | Err(AuthError::DatabaseError(msg)) => HttpResponse::InternalServerError().json(ErrorDto { message: msg })
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use tracing::info;
+use actix_web::web::Data;
+use dav_server::{DavConfig, DavHandler, actix::{DavRequest, DavResponse}, body::Body, davpath::DavPath};
+
+use crate::AppState;
+use crate::api::webdav::library_fs::{LibraryFs, LibraryPath};
+use crate::domain::models::user::{AuthenticatedUser, Scope};
+
+pub const WEBDAV_PREFIX: &str = "/dav";
+
+
+fn forbidden() -> DavResponse {
+    let forbidden = http::Response::builder()
+        .status(403)
+        .body(Body::empty())
+        .unwrap();
+    DavResponse::from(forbidden)
+}
+
+
+/// Scope an API token needs for the WebDAV method, locks are fake and don't change anything
+fn required_scope(method: &str) -> Scope {
+    match method {
+        "GET" | "HEAD" | "OPTIONS" | "PROPFIND" | "LOCK" | "UNLOCK" => Scope::Read,
+        "PUT" => Scope::Upload,
+        _ => Scope::Write
+    }
+}
+
+
+pub async fn dav(state: Data<AppState>, dav_handler: Data<DavHandler>, authenticated_user: AuthenticatedUser, req: DavRequest) -> DavResponse {
+    info!("dav()");
+
+    if !authenticated_user.has_scope(required_scope(req.request.method().as_str())) {
+        return forbidden();
+    }
+
+    // Deleting a folder would delete every pdf in it, only single files can be deleted
+    if req.request.method().as_str() == "DELETE" {
+        let library_path = DavPath::new(req.request.uri().path())
+            .ok()
+            .and_then(|mut path| path.set_prefix(WEBDAV_PREFIX).ok().map(|_| path))
+            .and_then(|path| LibraryPath::parse(&path));
+
+        if !library_path.is_some_and(|library_path| library_path.is_file()) {
+            return forbidden();
+        }
+    }
+
+    // Each user gets their own library as filesystem
+    let library_fs = LibraryFs { service: Arc::new(state.service.clone()), config: state.config.clone(), engine: state.engine.clone(), user_id: authenticated_user.user.id };
+    let config = DavConfig::new().filesystem(Box::new(library_fs));
+
+    DavResponse::from(dav_handler.handle_with(config, req.request).await)
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::domain::models::user::{ApiToken, Scope};
+
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiTokenDto {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// The token never expires if not set
+    pub expires_at: Option<DateTime<Utc>>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiTokenDto {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub time_created: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>
+}
+
+impl From<ApiToken> for ApiTokenDto {
+    fn from(api_token: ApiToken) -> Self {
+        ApiTokenDto {
+            id: api_token.id,
+            name: api_token.name,
+            scopes: api_token.scopes.iter().filter_map(|scope| scope.parse().ok()).collect(),
+            time_created: api_token.time_created,
+            expires_at: api_token.expires_at,
+            last_used: api_token.last_used
+        }
+    }
+}
+
+/// A new API token, the token itself is only shown in this response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiTokenDto {
+    /// Is sent as `Authorization: Bearer <token>`
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiTokenDto
+}
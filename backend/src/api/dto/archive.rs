@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+
+/// Name of the manifest in the archive, the pdfs are stored next to it below `files/`
+pub const ARCHIVE_MANIFEST: &str = "manifest.json";
+
+/// Identifies the manifest of an archive of the library
+pub const ARCHIVE_FORMAT: &str = "pdf-store-archive";
+
+/// Version of the manifest that is written, archives of this or an older version can be restored
+pub const ARCHIVE_VERSION: u32 = 1;
+
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchiveExportQueryDto {
+    /// Only archive the pdfs that were added or changed since then, e.g. since the last export
+    pub since: Option<DateTime<Utc>>
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchiveImportQueryDto {
+    /// What happens to a pdf whose owner has a pdf with the same content already, `skip` by default
+    #[serde(default)]
+    pub on_conflict: ArchiveConflict
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveConflict {
+    /// Keep the pdf in the store as it is
+    #[default]
+    Skip,
+    /// Replace the title, author, comments and tags of the pdf in the store with those from the archive
+    Overwrite
+}
+
+/// `manifest.json` of an archive, which lists the pdfs of the archive with their details
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchiveManifestDto {
+    /// Always `pdf-store-archive`
+    pub format: String,
+    /// Version of the manifest, it is increased when the manifest changes in a way older backends can't read
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Set for an incremental archive, which only has the pdfs that were added or changed since then
+    pub since: Option<DateTime<Utc>>,
+    pub pdfs: Vec<ArchivePdfDto>
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivePdfDto {
+    /// Name of the owner, the pdf is restored for the user with the same name
+    pub owner: String,
+    pub file_name: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: Option<i32>,
+    pub comments: Option<String>,
+    pub time_added: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    /// Preview of the cover as base64 encoded jpeg
+    pub picture: Option<String>,
+    /// Hex encoded sha256 hash of the content, which recognizes the pdf in another store
+    pub sha256: String,
+    /// Path of the pdf in the archive, pdfs with the same content share it
+    pub file: String
+}
+
+/// Outcome of restoring an archive, per pdf of its manifest
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ArchiveImportReportDto {
+    pub on_conflict: ArchiveConflict,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub pdfs: Vec<ArchiveImportItemDto>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ArchiveImportItemDto {
+    pub file_name: String,
+    /// Who the pdf was restored for, the user restoring the archive when the owner has no account in this store
+    pub owner: String,
+    pub status: ArchiveImportStatus,
+    /// The restored pdf, or the pdf with the same content that was kept or overwritten
+    pub pdf_id: Option<Uuid>,
+    /// Why the pdf couldn't be restored
+    pub error: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveImportStatus {
+    Created,
+    /// The owner had a pdf with the same content, which was overwritten
+    Updated,
+    /// The owner had a pdf with the same content, which was kept
+    Skipped,
+    Failed
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::domain::models::audit::{AuditAction, AuditEntry};
+
+
+/// Filters of the audit log, all given filters have to match
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogSearchDto {
+    /// Username of the user that made the change
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    /// ID of the changed pdf
+    pub target_id: Option<Uuid>,
+    /// Only changes made at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only changes made before this time
+    pub to: Option<DateTime<Utc>>,
+    pub size: Option<i32>,
+    pub page: Option<i32>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEntryDto {
+    pub id: i64,
+    pub time_created: DateTime<Utc>,
+    pub actor_id: Option<Uuid>,
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub target_id: Option<Uuid>,
+    /// The changed fields as `{"field": {"old": ..., "new": ...}}`
+    #[schema(value_type = Object)]
+    pub changes: Value
+}
+
+impl TryFrom<AuditEntry> for AuditEntryDto {
+    type Error = String;
+
+    fn try_from(entry: AuditEntry) -> Result<Self, Self::Error> {
+        Ok(AuditEntryDto {
+            id: entry.id,
+            time_created: entry.time_created,
+            actor_id: entry.actor_id,
+            actor: entry.actor_name,
+            action: entry.action.parse()?,
+            target_id: entry.target_id,
+            changes: entry.changes
+        })
+    }
+}
+
+/// Page of the audit log, newest entries first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditLogDto {
+    pub entries: Vec<AuditEntryDto>,
+    /// Number of entries matching the filters
+    pub count: i64
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+
+/// Applies one action to many pdfs at once. All changes are made in a single transaction, with `dry_run` the
+/// transaction is rolled back and the result only reports what would change.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkOperationDto {
+    pub selection: BulkSelectionDto,
+    pub action: BulkActionDto,
+    #[serde(default)]
+    pub dry_run: bool
+}
+
+/// Pdfs the action is applied to, either given by their IDs or all pdfs matching a search
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkSelectionDto {
+    Ids(Vec<Uuid>),
+    Search(BulkSearchDto)
+}
+
+/// Same filters as the search endpoint, all matching pdfs are selected instead of a single page
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkSearchDto {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub tag: Option<String>
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulkActionDto {
+    AddTags { tags: Vec<String> },
+    RemoveTags { tags: Vec<String> },
+    /// A missing or `null` author clears the author
+    SetAuthor {
+        #[serde(default)]
+        author: Option<String>
+    },
+    Delete
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResultDto {
+    pub dry_run: bool,
+    pub selected: usize,
+    pub changed: usize,
+    pub results: Vec<BulkItemResultDto>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItemResultDto {
+    pub id: Uuid,
+    pub status: BulkItemStatus
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    /// The action changed the pdf, or would change it in a dry run
+    Changed,
+    /// The pdf already was in the requested state
+    Unchanged,
+    /// The pdf is shared with the user, but not with a permission that allows the action
+    Forbidden,
+    /// No pdf with the given ID exists
+    NotFound
+}
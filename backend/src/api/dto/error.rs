@@ -1,6 +1,8 @@
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize)]
+/// The responses also have a `request_id`, which is added by the `request_id` middleware
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorDto {
     pub message: String
 }
\ No newline at end of file
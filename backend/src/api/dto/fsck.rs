@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use utoipa::{IntoParams, ToSchema};
+
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FsckQueryDto {
+    /// Only report the problems without repairing them, `true` unless `false` is given
+    #[serde(default = "dry_run_by_default")]
+    pub dry_run: bool
+}
+
+fn dry_run_by_default() -> bool {
+    true
+}
+
+/// Result of comparing the pdfs in the database with the files in the upload directory
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FsckReportDto {
+    pub dry_run: bool,
+    /// Number of pdfs in the database
+    pub pdfs: usize,
+    /// Number of files in the upload directory
+    pub files: usize,
+    pub issues: Vec<FsckIssueDto>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FsckIssueDto {
+    pub kind: FsckIssueKind,
+    /// Pdf with the issue, for an orphan file the pdf it was re-imported as
+    pub pdf_id: Option<Uuid>,
+    /// Name of the file in the upload directory
+    pub storage_key: String,
+    pub repair: FsckRepairStatus,
+    /// Why the repair failed
+    pub error: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckIssueKind {
+    /// The file of the pdf is gone, the repair marks the pdf as missing its file
+    MissingFile,
+    /// The file of a pdf that was marked as missing is back, the repair removes the mark
+    RestoredFile,
+    /// No pdf uses the file, the repair imports it as a pdf of the user running the check
+    OrphanFile,
+    /// The content of the file doesn't match the hash in its name, e.g. because it was overwritten
+    ChecksumMismatch,
+    /// The pdf has no preview, the repair renders it again
+    MissingThumbnail
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckRepairStatus {
+    /// The issue would be repaired without a dry run
+    Pending,
+    Repaired,
+    Failed,
+    /// The issue can't be repaired automatically, e.g. the file has to be uploaded again
+    Manual
+}
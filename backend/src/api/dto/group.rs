@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::domain::models::user::Group;
+
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateGroupDto {
+    pub name: String
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupDto {
+    pub id: Uuid,
+    pub name: String,
+    /// Usernames of the members
+    pub members: Vec<String>,
+    pub time_created: DateTime<Utc>
+}
+
+impl From<Group> for GroupDto {
+    fn from(group: Group) -> Self {
+        GroupDto {
+            id: group.id,
+            name: group.name,
+            members: group.members,
+            time_created: group.time_created
+        }
+    }
+}
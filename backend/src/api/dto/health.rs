@@ -0,0 +1,49 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    /// Needs attention, but the backend can still serve requests
+    Warn,
+    Fail
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessDto {
+    pub status: HealthStatus
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthCheckDto {
+    pub status: HealthStatus,
+    /// Whether a failure of the check makes the backend not ready
+    pub critical: bool,
+    /// How long the check took
+    pub latency_ms: u64,
+    pub message: String
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessChecksDto {
+    /// Whether a query can be run
+    pub database: HealthCheckDto,
+    /// Whether the schema matches the migrations of the backend
+    pub migrations: HealthCheckDto,
+    /// Whether a file can be written to and read from the upload and tmp directory
+    pub storage: HealthCheckDto,
+    pub pdf_engine: HealthCheckDto,
+    /// Free space in the upload directory against `storage.min_free_space`
+    pub disk_space: HealthCheckDto,
+    /// Jobs waiting for the pdf engine against `pdf.max_queued_jobs`
+    pub job_queue: HealthCheckDto
+}
+
+/// `fail` when a critical check failed, `warn` when any other check didn't pass
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessDto {
+    pub status: HealthStatus,
+    pub checks: ReadinessChecksDto
+}
@@ -1,3 +1,13 @@
 pub mod paging;
 pub mod pdf;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod bulk;
+pub mod user;
+pub mod api_token;
+pub mod share;
+pub mod group;
+pub mod share_link;
+pub mod audit;
+pub mod health;
+pub mod fsck;
+pub mod archive;
\ No newline at end of file
@@ -1,6 +1,8 @@
 use serde::Deserialize;
+use utoipa::IntoParams;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PagingDto {
     pub size: Option<i32>,
     pub page: Option<i32>
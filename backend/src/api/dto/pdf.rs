@@ -1,17 +1,18 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::domain::models::pdf::PdfOverview;
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct PdfOverviewDto {
     pub pdfs_previews: Vec<PdfOverview>,
     pub count: Option<i64>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct PdfMetadataDto {
     pub id: Option<Uuid>,
     pub title: Option<String>,
@@ -22,15 +23,23 @@ pub struct PdfMetadataDto {
     pub uploaded: Option<DateTime<Utc>>,
     pub last_accessed: Option<DateTime<Utc>>,
     pub picture: Option<String>,
-    pub tags: Option<Vec<String>>
+    pub tags: Option<Vec<String>>,
+    /// Current version of the pdf details, also sent as the `ETag` header
+    pub version: i32,
+    /// User the pdf belongs to, only the owner may delete and share it
+    pub owner_id: Uuid,
+    /// The storage check found the file of the pdf missing, so only its details are left
+    pub file_missing: bool
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct PdfDto {
+    /// Base64 encoded content of the pdf
     pub pdf: String
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
 pub struct PdfSearchDto {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -39,7 +48,7 @@ pub struct PdfSearchDto {
     pub size: Option<i32>
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct PdfUpdateDto {
     pub id: Option<String>,
     pub title: Option<String>,
@@ -47,4 +56,38 @@ pub struct PdfUpdateDto {
     pub comments: Option<String>,
     pub tags: Option<Vec<String>>,
     pub picture: Option<String>
+}
+
+/// Partial update of a pdf following JSON Merge Patch (RFC 7396): absent fields are left unchanged and `null` clears
+/// a field. `tags` replaces all tags, afterwards `add_tags` and `remove_tags` are applied.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PdfPatchDto {
+    #[serde(default, deserialize_with = "deserialize_patch_field")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_patch_field")]
+    pub author: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_patch_field")]
+    pub comments: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_patch_field")]
+    pub picture: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_patch_field")]
+    pub tags: Option<Option<Vec<String>>>,
+    pub add_tags: Option<Vec<String>>,
+    pub remove_tags: Option<Vec<String>>
+}
+
+/// Distinguishes a field that is set to `null` (`Some(None)`) from an absent field (`None`, via `#[serde(default)]`).
+fn deserialize_patch_field<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Documents the multipart form that is accepted by the upload endpoint.
+#[derive(Debug, ToSchema)]
+pub struct PdfUploadDto {
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub file: Vec<Vec<u8>>
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::domain::models::share::{Permission, PdfShare};
+
+
+/// Share of a pdf with either a user or a group, exactly one of `user` and `group` is set.
+/// Sharing with a user or group the pdf is already shared with replaces the permission.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PdfShareDto {
+    /// Username of the user the pdf is shared with
+    pub user: Option<String>,
+    /// Name of the group the pdf is shared with
+    pub group: Option<String>,
+    pub permission: Permission
+}
+
+impl TryFrom<PdfShare> for PdfShareDto {
+    type Error = String;
+
+    fn try_from(share: PdfShare) -> Result<Self, Self::Error> {
+        Ok(PdfShareDto {
+            user: share.username,
+            group: share.group_name,
+            permission: share.permission.parse()?
+        })
+    }
+}
+
+/// User or group whose share is removed, exactly one of both has to be given
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShareTargetDto {
+    pub user: Option<String>,
+    pub group: Option<String>
+}
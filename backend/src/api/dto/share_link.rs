@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::domain::models::share::{ShareLink, ShareLinkAccess, ShareLinkOutcome};
+
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateShareLinkDto {
+    /// The link never expires if not set
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The file can be downloaded any number of times if not set
+    pub max_downloads: Option<i32>,
+    /// Has to be entered before downloading the file if set
+    pub password: Option<String>
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareLinkDto {
+    pub id: Uuid,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_downloads: Option<i32>,
+    pub download_count: i32,
+    pub password_protected: bool,
+    pub time_created: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Whether the link can still be used, it can't once it was revoked, has expired or all downloads are used up
+    pub active: bool
+}
+
+impl From<ShareLink> for ShareLinkDto {
+    fn from(share_link: ShareLink) -> Self {
+        ShareLinkDto {
+            id: share_link.id,
+            active: share_link.unusable_reason().is_none(),
+            expires_at: share_link.expires_at,
+            max_downloads: share_link.max_downloads,
+            download_count: share_link.download_count,
+            password_protected: share_link.password_hash.is_some(),
+            time_created: share_link.time_created,
+            revoked_at: share_link.revoked_at
+        }
+    }
+}
+
+/// A new share link, the token is only shown in this response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedShareLinkDto {
+    pub token: String,
+    /// Link that can be sent to people without an account
+    pub url: String,
+    #[serde(flatten)]
+    pub share_link: ShareLinkDto
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShareLinkAccessDto {
+    pub time_accessed: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: ShareLinkOutcome
+}
+
+impl TryFrom<ShareLinkAccess> for ShareLinkAccessDto {
+    type Error = String;
+
+    fn try_from(access: ShareLinkAccess) -> Result<Self, Self::Error> {
+        Ok(ShareLinkAccessDto {
+            time_accessed: access.time_accessed,
+            ip_address: access.ip_address,
+            user_agent: access.user_agent,
+            outcome: access.outcome.parse()?
+        })
+    }
+}
+
+/// What people without an account see of a shared pdf
+#[derive(Debug, Serialize)]
+pub struct SharedPdfDto {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: Option<i32>,
+    pub file_name: String,
+    pub password_protected: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Unlimited if not set
+    pub downloads_left: Option<i32>
+}
+
+/// Form that is sent to download the file of a share link
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ShareLinkDownloadDto {
+    pub password: Option<String>
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+use crate::domain::models::user::User;
+
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct LoginDto {
+    pub username: String,
+    pub password: String
+}
+
+/// A new session, the token is also set as session cookie
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionDto {
+    /// Can be sent as `Authorization: Bearer <token>` instead of the session cookie
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: UserDto
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserDto {
+    pub id: Uuid,
+    pub username: String,
+    pub is_admin: bool,
+    pub time_created: DateTime<Utc>
+}
+
+impl From<User> for UserDto {
+    fn from(user: User) -> Self {
+        UserDto {
+            id: user.id,
+            username: user.username,
+            is_admin: user.is_admin,
+            time_created: user.time_created
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateUserDto {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub is_admin: bool
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasswordChangeDto {
+    pub current_password: String,
+    pub new_password: String
+}
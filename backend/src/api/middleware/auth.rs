@@ -0,0 +1,157 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderValue},
+    middleware::Next,
+    web::Data,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse
+};
+use base64::Engine as _;
+use base64::engine::general_purpose;
+use tracing::{field, trace, Span};
+
+use crate::AppState;
+use crate::api::dto::error::ErrorDto;
+use crate::domain::models::user::{AuthenticatedUser, Scope};
+use crate::domain::service::auth::AuthService;
+
+
+pub const SESSION_COOKIE: &str = "pdfstore_session";
+
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        match req.extensions().get::<AuthenticatedUser>() {
+            Some(authenticated_user) => ready(Ok(authenticated_user.clone())),
+            None => ready(Err(actix_web::error::ErrorUnauthorized("Authentication required")))
+        }
+    }
+}
+
+
+/// Response for requests that lack the scope, `None` if the request may go ahead.
+/// Only requests authenticated with an API token can miss a scope that their user has.
+pub fn missing_scope(authenticated_user: &AuthenticatedUser, scope: Scope) -> Option<HttpResponse> {
+    if authenticated_user.has_scope(scope) {
+        return None;
+    }
+
+    Some(HttpResponse::Forbidden().json(ErrorDto { message: format!("The '{}' scope is required", scope.as_str()) }))
+}
+
+
+enum Credentials {
+    Token(String),
+    Password(String, String)
+}
+
+
+/// Session or API token of the request, sent either as session cookie or as bearer token
+pub fn session_token(req: &HttpRequest) -> Option<String> {
+    match credentials(req, false) {
+        Some(Credentials::Token(token)) => Some(token),
+        _ => None
+    }
+}
+
+
+/// Basic auth is only taken when `accept_basic` is set, as checking the password is expensive and
+/// would allow guessing passwords on every route
+fn credentials(req: &HttpRequest, accept_basic: bool) -> Option<Credentials> {
+    let authorization = req.headers().get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+
+    if let Some(authorization) = authorization {
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return Some(Credentials::Token(token.trim().to_string()));
+        }
+
+        // Basic auth is what WebDAV clients support
+        if let Some(encoded) = authorization.strip_prefix("Basic ").filter(|_| accept_basic) {
+            let decoded = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+            let decoded = String::from_utf8(decoded).ok()?;
+            let (username, password) = decoded.split_once(':')?;
+            return Some(Credentials::Password(username.to_string(), password.to_string()));
+        }
+    }
+
+    req.cookie(SESSION_COOKIE).map(|cookie| Credentials::Token(cookie.value().to_string()))
+}
+
+
+async fn authenticate(req: &HttpRequest, accept_basic: bool) -> Result<Option<AuthenticatedUser>, String> {
+    let state = match req.app_data::<Data<AppState>>() {
+        Some(state) => state,
+        None => return Err("Application state is missing".to_string())
+    };
+
+    match credentials(req, accept_basic) {
+        Some(Credentials::Token(token)) => state.auth_service.authenticate_token(&token).await,
+        Some(Credentials::Password(username, password)) => {
+            let user = state.auth_service.authenticate_password(&username, &password).await?;
+            Ok(user.map(|user| AuthenticatedUser { scopes: Scope::all_of(&user), user, api_token_id: None }))
+        },
+        None => Ok(None)
+    }
+}
+
+
+async fn authenticate_request<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+    challenge: &'static str,
+    accept_basic: bool
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    match authenticate(req.request(), accept_basic).await {
+        Ok(Some(authenticated_user)) => {
+            Span::current().record("user_id", field::display(authenticated_user.user.id));
+            req.extensions_mut().insert(authenticated_user);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        },
+        Ok(None) => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, HeaderValue::from_static(challenge)))
+                .json(ErrorDto { message: "Authentication required".to_string() });
+            Ok(req.into_response(response).map_into_right_body())
+        },
+        Err(msg) => {
+            let response = HttpResponse::InternalServerError().json(ErrorDto { message: msg });
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+
+/// Rejects requests without a valid session cookie or bearer token
+pub async fn require_user<B: MessageBody + 'static>(req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    trace!("middleware: require_user()");
+
+    authenticate_request(req, next, "Bearer", false).await
+}
+
+
+/// Same as `require_user`, but also accepts basic auth credentials and asks WebDAV clients for them
+pub async fn require_dav_user<B: MessageBody + 'static>(req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    trace!("middleware: require_dav_user()");
+
+    authenticate_request(req, next, "Basic realm=\"PdfStore\"", true).await
+}
+
+
+/// Has to run after `require_user`, rejects users that are not admins and API tokens without the admin scope
+pub async fn require_admin<B: MessageBody + 'static>(req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    trace!("middleware: require_admin()");
+
+    let is_admin = req.extensions().get::<AuthenticatedUser>().is_some_and(|authenticated_user| authenticated_user.has_scope(Scope::Admin));
+
+    if is_admin {
+        next.call(req).await.map(ServiceResponse::map_into_left_body)
+    } else {
+        let response = HttpResponse::Forbidden().json(ErrorDto { message: "Admin privileges required".to_string() });
+        Ok(req.into_response(response).map_into_right_body())
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error
+};
+
+use crate::metrics::METRICS;
+
+
+/// Records every request with the pattern of its route, so the number of label values stays bounded
+pub async fn record_metrics<B: MessageBody + 'static>(req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse, Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+
+    let res = next.call(req).await?;
+
+    let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+
+    METRICS.record_request(&method, &route, res.status().as_u16(), started.elapsed().as_secs_f64());
+
+    Ok(res.map_into_boxed_body())
+}
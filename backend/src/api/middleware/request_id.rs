@@ -0,0 +1,112 @@
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage
+};
+use serde_json::Value;
+use tracing::{field, info_span, Instrument};
+use uuid::Uuid;
+
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest ID that is taken over from a client or proxy
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+
+/// ID of the request, taken from the `X-Request-Id` header of the client or proxy or generated.
+/// Available to handlers as request extension.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+
+/// IDs with whitespace or control characters are replaced, so they can't break the log lines
+fn is_usable(request_id: &str) -> bool {
+    !request_id.is_empty() && request_id.len() <= MAX_REQUEST_ID_LENGTH && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+
+/// Runs the request in a span with its ID, returns the ID in the `X-Request-Id` header and adds it
+/// as `request_id` to JSON error bodies, so users can report it
+pub async fn request_id<B: MessageBody + 'static>(req: ServiceRequest, next: Next<B>) -> Result<ServiceResponse, Error> {
+    let request_id = req.headers().get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|request_id| is_usable(request_id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+        route = field::Empty,
+        status = field::Empty,
+        user_id = field::Empty
+    );
+
+    let res = match next.call(req).instrument(span.clone()).await {
+        Ok(res) => res.map_into_boxed_body(),
+        Err(err) => {
+            // Errors of extractors and handlers are only turned into a response by the server, so it gets the header here
+            let mut response = err.error_response();
+            span.record("status", response.status().as_u16());
+            insert_request_id(response.headers_mut(), &request_id);
+
+            return Err(InternalError::from_response(err, response).into());
+        }
+    };
+
+    if let Some(route) = res.request().match_pattern() {
+        span.record("route", route);
+    }
+    span.record("status", res.status().as_u16());
+
+    let mut res = with_request_id_in_error(res, &request_id).await?;
+    insert_request_id(res.headers_mut(), &request_id);
+
+    Ok(res)
+}
+
+
+fn insert_request_id(headers: &mut HeaderMap, request_id: &str) {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+
+async fn with_request_id_in_error(res: ServiceResponse, request_id: &str) -> Result<ServiceResponse, Error> {
+    let is_json = res.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with(mime::APPLICATION_JSON.essence_str()));
+
+    let is_error = res.status().is_client_error() || res.status().is_server_error();
+
+    if !is_error || !is_json {
+        return Ok(res);
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+
+    let bytes = match body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(err) => return Err(actix_web::error::ErrorInternalServerError(err))
+    };
+
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(Value::Object(mut error)) => {
+            error.entry("request_id").or_insert_with(|| Value::String(request_id.to_string()));
+            serde_json::to_vec(&error).map(Into::into).unwrap_or(bytes)
+        },
+        _ => bytes
+    };
+
+    Ok(ServiceResponse::new(http_req, res.set_body(BoxBody::new(bytes))))
+}
@@ -1,2 +1,5 @@
 pub mod controllers;
-pub mod dto;
\ No newline at end of file
+pub mod dto;
+pub mod middleware;
+pub mod openapi;
+pub mod webdav;
\ No newline at end of file
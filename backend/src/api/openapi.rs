@@ -0,0 +1,204 @@
+use tracing::info;
+use actix_web::{HttpResponse, Responder};
+use utoipa::{Modify, OpenApi};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
+
+use crate::api::controllers::{archive_handler, audit_handler, auth_handler, fsck_handler, group_handler, health_handler, metrics_handler, pdf_handler, share_handler, share_link_handler, token_handler, user_handler};
+use crate::api::middleware::auth::SESSION_COOKIE;
+use crate::api::dto::archive::{ArchiveConflict, ArchiveImportItemDto, ArchiveImportReportDto, ArchiveImportStatus, ArchiveManifestDto, ArchivePdfDto};
+use crate::api::dto::audit::{AuditEntryDto, AuditLogDto};
+use crate::api::dto::bulk::{BulkActionDto, BulkItemResultDto, BulkItemStatus, BulkOperationDto, BulkResultDto, BulkSearchDto, BulkSelectionDto};
+use crate::api::dto::error::ErrorDto;
+use crate::api::dto::fsck::{FsckIssueDto, FsckIssueKind, FsckRepairStatus, FsckReportDto};
+use crate::api::dto::group::{CreateGroupDto, GroupDto};
+use crate::api::dto::health::{HealthCheckDto, HealthStatus, LivenessDto, ReadinessChecksDto, ReadinessDto};
+use crate::api::dto::share::PdfShareDto;
+use crate::api::dto::share_link::{CreateShareLinkDto, CreatedShareLinkDto, ShareLinkAccessDto, ShareLinkDownloadDto, ShareLinkDto};
+use crate::api::dto::api_token::{ApiTokenDto, CreateApiTokenDto, CreatedApiTokenDto};
+use crate::api::dto::user::{CreateUserDto, LoginDto, PasswordChangeDto, SessionDto, UserDto};
+use crate::api::dto::pdf::{PdfDto, PdfMetadataDto, PdfOverviewDto, PdfPatchDto, PdfSearchDto, PdfUpdateDto, PdfUploadDto};
+use crate::domain::models::audit::AuditAction;
+use crate::domain::models::pdf::PdfOverview;
+use crate::domain::models::share::{Permission, ShareLinkOutcome};
+use crate::domain::models::user::Scope;
+
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "PdfStore",
+        description = "REST API of the PdfStore backend. The library is additionally available over WebDAV at `/dav`.\n\n\
+            The routes below `/pdfs` are deprecated aliases of the `/api/v1/documents` routes and answer with a `Deprecation` header.\n\n\
+            All routes except the health checks below `/health`, `/api/v1/auth/login` and the share links below `/s` require authentication, either with the session cookie set by \
+            the login, the session token or an API token as bearer token or, mainly for WebDAV clients, basic auth. \
+            API tokens are limited to their scopes: `read`, `write`, `upload` and `admin`.\n\n\
+            Every pdf belongs to the user that uploaded it. The owner can share it with other users and groups to `read`, \
+            `annotate` (change comments and tags) or `edit` it, all other users can't see it. \
+            To send a single pdf to someone without an account, the owner can create a share link below `/s`, which can be \
+            opened without logging in and may expire, be limited to a number of downloads or be password protected.\n\n\
+            Every response has an `X-Request-Id` header with the ID the request is logged with, taken from the request if it has one. \
+            JSON error responses additionally carry it as `request_id`, to be given along when reporting the error."
+    ),
+    paths(
+        health_handler::health,
+        health_handler::live,
+        health_handler::ready,
+        metrics_handler::metrics,
+        auth_handler::login,
+        auth_handler::logout,
+        auth_handler::me,
+        auth_handler::change_password,
+        token_handler::get_tokens,
+        token_handler::create_token,
+        token_handler::delete_token,
+        user_handler::get_users,
+        user_handler::create_user,
+        user_handler::delete_user,
+        group_handler::get_groups,
+        group_handler::create_group,
+        group_handler::delete_group,
+        group_handler::add_group_member,
+        group_handler::remove_group_member,
+        audit_handler::get_audit_log,
+        fsck_handler::fsck,
+        archive_handler::export_archive,
+        archive_handler::import_archive,
+        pdf_handler::get_all,
+        pdf_handler::search,
+        pdf_handler::get_metadata_by_id,
+        pdf_handler::get_content_by_id,
+        pdf_handler::update,
+        pdf_handler::patch,
+        pdf_handler::delete,
+        pdf_handler::bulk,
+        share_handler::get_shares,
+        share_handler::share,
+        share_handler::unshare,
+        share_link_handler::get_share_links,
+        share_link_handler::create_share_link,
+        share_link_handler::revoke_share_link,
+        share_link_handler::get_share_link_accesses,
+        share_link_handler::open_share_link,
+        share_link_handler::download_share_link,
+        pdf_handler::upload,
+        pdf_handler::get_by_id
+    ),
+    components(schemas(
+        ApiTokenDto,
+        ArchiveConflict,
+        ArchiveImportItemDto,
+        ArchiveImportReportDto,
+        ArchiveImportStatus,
+        ArchiveManifestDto,
+        ArchivePdfDto,
+        AuditAction,
+        AuditEntryDto,
+        AuditLogDto,
+        BulkActionDto,
+        BulkItemResultDto,
+        BulkItemStatus,
+        BulkOperationDto,
+        BulkResultDto,
+        BulkSearchDto,
+        BulkSelectionDto,
+        CreateApiTokenDto,
+        CreatedApiTokenDto,
+        CreateGroupDto,
+        CreateShareLinkDto,
+        CreatedShareLinkDto,
+        CreateUserDto,
+        ErrorDto,
+        FsckIssueDto,
+        FsckIssueKind,
+        FsckRepairStatus,
+        FsckReportDto,
+        GroupDto,
+        HealthCheckDto,
+        HealthStatus,
+        LivenessDto,
+        LoginDto,
+        PasswordChangeDto,
+        PdfDto,
+        PdfMetadataDto,
+        PdfOverview,
+        PdfOverviewDto,
+        PdfPatchDto,
+        PdfShareDto,
+        PdfSearchDto,
+        PdfUpdateDto,
+        PdfUploadDto,
+        Permission,
+        ReadinessChecksDto,
+        ReadinessDto,
+        Scope,
+        SessionDto,
+        ShareLinkAccessDto,
+        ShareLinkDownloadDto,
+        ShareLinkDto,
+        ShareLinkOutcome,
+        UserDto
+    )),
+    modifiers(&SecuritySchemes),
+    security(("session_cookie" = []), ("bearer_token" = [])),
+    tags(
+        (name = "documents", description = "Managing the stored pdfs"),
+        (name = "legacy", description = "Deprecated routes without a direct replacement"),
+        (name = "auth", description = "Logging in and out"),
+        (name = "tokens", description = "Managing the API tokens of the logged in user"),
+        (name = "sharing", description = "Sharing pdfs with other users and groups, owners only"),
+        (name = "share links", description = "Public links to single pdfs for people without an account, created by the owner"),
+        (name = "users", description = "Managing user accounts, admins only"),
+        (name = "groups", description = "Managing the groups pdfs can be shared with, admins only"),
+        (name = "audit", description = "Who changed which pdf and how, admins only"),
+        (name = "fsck", description = "Checking and repairing the stored files of the pdfs, admins only"),
+        (name = "archive", description = "Exporting the whole library as archive and restoring it, admins only"),
+        (name = "health", description = "Status of the backend"),
+        (name = "metrics", description = "Metrics of the backend for Prometheus")
+    )
+)]
+pub struct ApiDoc;
+
+
+/// Ways to authenticate that are accepted by the authentication middleware
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme("session_cookie", SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE))));
+        components.add_security_scheme("bearer_token", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+    }
+}
+
+
+pub async fn openapi_json() -> impl Responder {
+    info!("openapi_json()");
+
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::ApiDoc;
+    use utoipa::OpenApi;
+
+    const SNAPSHOT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    /// Fails whenever the API changes without the checked-in specification being updated.
+    /// Run with `UPDATE_OPENAPI_SNAPSHOT=1` to regenerate `openapi.json`.
+    #[test]
+    fn openapi_spec_matches_snapshot() {
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+
+        if std::env::var("UPDATE_OPENAPI_SNAPSHOT").is_ok() {
+            std::fs::write(SNAPSHOT_PATH, &generated).unwrap();
+            return;
+        }
+
+        let snapshot = std::fs::read_to_string(SNAPSHOT_PATH).unwrap_or_default();
+
+        assert!(snapshot == generated, "openapi.json is out of date, regenerate it with UPDATE_OPENAPI_SNAPSHOT=1 cargo test");
+    }
+}
@@ -0,0 +1,707 @@
+//! Read/write WebDAV view of the library.
+//!
+//! The stored pdfs are presented as a virtual filesystem:
+//!
+//! ```text
+//! /All/<file name>
+//! /Tags/<tag>/<file name>
+//! /Authors/<author>/<file name>
+//! ```
+//!
+//! Every user sees their own library, pdfs shared by other users are only available through the API.
+//! Files written into a folder are ingested like a regular upload. Writing into a tag folder also
+//! associates the tag, writing into an author folder sets the author.
+
+use std::fmt;
+use std::io::{SeekFrom, Write};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use actix_web::web;
+use bytes::{Buf, Bytes};
+use dav_server::davpath::DavPath;
+use dav_server::fs::{DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsStream, OpenOptions, ReadDirMeta};
+use futures_util::{stream, FutureExt};
+use tracing::trace;
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use uuid::Uuid;
+
+use crate::domain::models::pdf::PdfFile;
+use crate::domain::service::pdf::PdfService;
+use crate::util::map_pdf;
+use crate::engine::PdfEngine;
+use crate::config::{Config, StorageConfig};
+use crate::errors::PdfUpdateError;
+
+const ALL_FOLDER: &str = "All";
+const TAGS_FOLDER: &str = "Tags";
+const AUTHORS_FOLDER: &str = "Authors";
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Folder {
+    All,
+    Tag(String),
+    Author(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LibraryPath {
+    Root,
+    All,
+    Tags,
+    Authors,
+    Tag(String),
+    Author(String),
+    File(Folder, String)
+}
+
+impl LibraryPath {
+
+    pub fn parse(path: &DavPath) -> Option<LibraryPath> {
+        let segments: Vec<String> = path.as_bytes()
+            .split(|c| *c == b'/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| String::from_utf8_lossy(segment).to_string())
+            .collect();
+
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        match segments[..] {
+            [] => Some(LibraryPath::Root),
+            [ALL_FOLDER] => Some(LibraryPath::All),
+            [ALL_FOLDER, file_name] => Some(LibraryPath::File(Folder::All, file_name.to_string())),
+            [TAGS_FOLDER] => Some(LibraryPath::Tags),
+            [TAGS_FOLDER, tag] => Some(LibraryPath::Tag(tag.to_string())),
+            [TAGS_FOLDER, tag, file_name] => Some(LibraryPath::File(Folder::Tag(tag.to_string()), file_name.to_string())),
+            [AUTHORS_FOLDER] => Some(LibraryPath::Authors),
+            [AUTHORS_FOLDER, author] => Some(LibraryPath::Author(author.to_string())),
+            [AUTHORS_FOLDER, author, file_name] => Some(LibraryPath::File(Folder::Author(author.to_string()), file_name.to_string())),
+            _ => None
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, LibraryPath::File(_, _))
+    }
+}
+
+
+/// Tags and authors may contain a slash, which can't be part of a folder name.
+fn folder_name(name: &str) -> String {
+    name.replace('/', "_")
+}
+
+fn is_pdf_file_name(file_name: &str) -> bool {
+    file_name.to_lowercase().ends_with(".pdf") && !file_name.starts_with('.')
+}
+
+
+#[derive(Debug, Clone)]
+pub struct LibraryMetaData {
+    len: u64,
+    modified: SystemTime,
+    is_dir: bool
+}
+
+impl LibraryMetaData {
+
+    fn dir() -> LibraryMetaData {
+        LibraryMetaData { len: 0, modified: SystemTime::now(), is_dir: true }
+    }
+
+    fn file(pdf_file: &PdfFile, storage: &StorageConfig) -> Result<LibraryMetaData, FsError> {
+        let fs_metadata = match std::fs::metadata(storage.upload_path(&pdf_file.storage_key)) {
+            Ok(fs_metadata) => fs_metadata,
+            Err(_) => return Err(FsError::NotFound)
+        };
+
+        let modified = match pdf_file.time_added {
+            Some(time_added) => SystemTime::from(time_added),
+            None => fs_metadata.modified().unwrap_or_else(|_| SystemTime::now())
+        };
+
+        Ok(LibraryMetaData { len: fs_metadata.len(), modified, is_dir: false })
+    }
+}
+
+impl DavMetaData for LibraryMetaData {
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn modified(&self) -> Result<SystemTime, FsError> {
+        Ok(self.modified)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+
+struct LibraryDirEntry {
+    name: String,
+    metadata: LibraryMetaData
+}
+
+impl DavDirEntry for LibraryDirEntry {
+
+    fn name(&self) -> Vec<u8> {
+        self.name.as_bytes().to_vec()
+    }
+
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let metadata: Box<dyn DavMetaData> = Box::new(self.metadata.clone());
+        Box::pin(async move { Ok(metadata) })
+    }
+}
+
+
+/// A stored pdf that is opened for reading.
+#[derive(Debug)]
+struct StoredPdfFile {
+    file: tokio::fs::File,
+    metadata: LibraryMetaData
+}
+
+impl DavFile for StoredPdfFile {
+
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let metadata: Box<dyn DavMetaData> = Box::new(self.metadata.clone());
+        Box::pin(async move { Ok(metadata) })
+    }
+
+    fn write_buf(&mut self, _buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn write_bytes(&mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        async move {
+            let mut buf = vec![0; count];
+            let read = match self.file.read(&mut buf).await {
+                Ok(read) => read,
+                Err(_) => return Err(FsError::GeneralFailure)
+            };
+            buf.truncate(read);
+            Ok(Bytes::from(buf))
+        }
+        .boxed()
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        async move {
+            match self.file.seek(pos).await {
+                Ok(offset) => Ok(offset),
+                Err(_) => Err(FsError::GeneralFailure)
+            }
+        }
+        .boxed()
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+
+/// A pdf that is written into the library. It is buffered in a temporary file and ingested once the client
+/// is done writing.
+struct IncomingPdfFile {
+    service: Arc<dyn PdfService>,
+    config: Arc<Config>,
+    engine: Arc<dyn PdfEngine>,
+    owner_id: Uuid,
+    folder: Folder,
+    file_name: String,
+    file: Option<NamedTempFile>,
+    written: u64,
+    metadata: Option<LibraryMetaData>
+}
+
+impl fmt::Debug for IncomingPdfFile {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IncomingPdfFile")
+            .field("folder", &self.folder)
+            .field("file_name", &self.file_name)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+impl IncomingPdfFile {
+
+    async fn ingest(&mut self) -> Result<(), FsError> {
+        trace!("webdav: ingest()");
+
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(())
+        };
+
+        let mut pdf_uploaded = match map_pdf(self.engine.as_ref(), file, self.file_name.clone()).await {
+            Ok(pdf_uploaded) => pdf_uploaded,
+            Err(_) => return Err(FsError::GeneralFailure)
+        };
+
+        match &self.folder {
+            Folder::All => (),
+            Folder::Tag(tag) => pdf_uploaded.tags.push(tag.clone()),
+            Folder::Author(author) => pdf_uploaded.author = Some(author.clone())
+        }
+
+        let uploaded_ids = match self.service.upload(vec![pdf_uploaded], &self.owner_id).await {
+            Ok(uploaded_ids) => uploaded_ids,
+            Err(_) => return Err(FsError::GeneralFailure)
+        };
+
+        if uploaded_ids.is_empty() {
+            return Err(FsError::GeneralFailure);
+        }
+
+        match self.service.get_pdf_file_by_name(&self.file_name, &self.owner_id).await {
+            Ok(Some(pdf_file)) => {
+                self.metadata = Some(LibraryMetaData::file(&pdf_file, &self.config.storage)?);
+                Ok(())
+            },
+            _ => Err(FsError::GeneralFailure)
+        }
+    }
+}
+
+impl DavFile for IncomingPdfFile {
+
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let metadata: Box<dyn DavMetaData> = match &self.metadata {
+            Some(metadata) => Box::new(metadata.clone()),
+            None => Box::new(LibraryMetaData { len: self.written, modified: SystemTime::now(), is_dir: false })
+        };
+        Box::pin(async move { Ok(metadata) })
+    }
+
+    fn write_buf(&mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        let bytes = buf.copy_to_bytes(buf.remaining());
+        self.write_bytes(bytes)
+    }
+
+    fn write_bytes(&mut self, buf: Bytes) -> FsFuture<'_, ()> {
+        async move {
+            let Some(mut file) = self.file.take() else {
+                return Err(FsError::GeneralFailure);
+            };
+
+            let len = buf.len() as u64;
+
+            // Large uploads arrive in many chunks, they are written outside of the async workers
+            match web::block(move || file.write_all(&buf).map(|_| file)).await {
+                Ok(Ok(file)) => {
+                    self.file = Some(file);
+                    self.written += len;
+                    Ok(())
+                },
+                Ok(Err(_)) => Err(FsError::InsufficientStorage),
+                Err(_) => Err(FsError::GeneralFailure)
+            }
+        }
+        .boxed()
+    }
+
+    fn read_bytes(&mut self, _count: usize) -> FsFuture<'_, Bytes> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn seek(&mut self, _pos: SeekFrom) -> FsFuture<'_, u64> {
+        Box::pin(async { Err(FsError::NotImplemented) })
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        async move {
+            // Some clients create an empty file before writing the content, there is nothing to ingest yet
+            if self.written == 0 {
+                return Ok(());
+            }
+
+            self.ingest().await
+        }
+        .boxed()
+    }
+}
+
+
+/// Library of the user with the given ID
+#[derive(Clone)]
+pub struct LibraryFs {
+    pub service: Arc<dyn PdfService>,
+    pub config: Arc<Config>,
+    pub engine: Arc<dyn PdfEngine>,
+    pub user_id: Uuid
+}
+
+impl LibraryFs {
+
+    async fn find_tag(&self, name: &str) -> Result<String, FsError> {
+        let tags = match self.service.get_tags(&self.user_id).await {
+            Ok(tags) => tags,
+            Err(_) => return Err(FsError::GeneralFailure)
+        };
+
+        match tags.into_iter().find(|tag| folder_name(tag) == name) {
+            Some(tag) => Ok(tag),
+            None => Err(FsError::NotFound)
+        }
+    }
+
+    async fn find_author(&self, name: &str) -> Result<String, FsError> {
+        let authors = match self.service.get_authors(&self.user_id).await {
+            Ok(authors) => authors,
+            Err(_) => return Err(FsError::GeneralFailure)
+        };
+
+        match authors.into_iter().find(|author| folder_name(author) == name) {
+            Some(author) => Ok(author),
+            None => Err(FsError::NotFound)
+        }
+    }
+
+    /// Resolves a folder of the path to its tag or author, so that it can be used in queries.
+    async fn resolve_folder(&self, folder: &Folder) -> Result<Folder, FsError> {
+        match folder {
+            Folder::All => Ok(Folder::All),
+            Folder::Tag(name) => Ok(Folder::Tag(self.find_tag(name).await?)),
+            Folder::Author(name) => Ok(Folder::Author(self.find_author(name).await?))
+        }
+    }
+
+    async fn files_in_folder(&self, folder: &Folder) -> Result<Vec<PdfFile>, FsError> {
+        let pdf_files_res = match self.resolve_folder(folder).await? {
+            Folder::All => self.service.get_pdf_files(&self.user_id).await,
+            Folder::Tag(tag) => self.service.get_pdf_files_by_tag(&tag, &self.user_id).await,
+            Folder::Author(author) => self.service.get_pdf_files_by_author(&author, &self.user_id).await
+        };
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(_) => Err(FsError::GeneralFailure)
+        }
+    }
+
+    async fn find_file(&self, folder: &Folder, file_name: &str) -> Result<PdfFile, FsError> {
+        let pdf_files = self.files_in_folder(folder).await?;
+
+        match pdf_files.into_iter().find(|pdf_file| pdf_file.file_name == file_name) {
+            Some(pdf_file) => Ok(pdf_file),
+            None => Err(FsError::NotFound)
+        }
+    }
+
+    async fn list_dir(&self, path: &LibraryPath) -> Result<Vec<LibraryDirEntry>, FsError> {
+        let folder_entries = |names: Vec<String>| -> Vec<LibraryDirEntry> {
+            names.into_iter()
+                .map(|name| LibraryDirEntry { name: folder_name(&name), metadata: LibraryMetaData::dir() })
+                .collect()
+        };
+
+        let folder = match path {
+            LibraryPath::Root => {
+                let names = vec![ALL_FOLDER.to_string(), TAGS_FOLDER.to_string(), AUTHORS_FOLDER.to_string()];
+                return Ok(folder_entries(names));
+            },
+            LibraryPath::Tags => {
+                return match self.service.get_tags(&self.user_id).await {
+                    Ok(tags) => Ok(folder_entries(tags)),
+                    Err(_) => Err(FsError::GeneralFailure)
+                };
+            },
+            LibraryPath::Authors => {
+                return match self.service.get_authors(&self.user_id).await {
+                    Ok(authors) => Ok(folder_entries(authors)),
+                    Err(_) => Err(FsError::GeneralFailure)
+                };
+            },
+            LibraryPath::All => Folder::All,
+            LibraryPath::Tag(tag) => Folder::Tag(tag.clone()),
+            LibraryPath::Author(author) => Folder::Author(author.clone()),
+            LibraryPath::File(_, _) => return Err(FsError::Forbidden)
+        };
+
+        let pdf_files = self.files_in_folder(&folder).await?;
+
+        // Files whose content is missing on disk are left out of the listing
+        let entries = pdf_files.iter()
+            .filter_map(|pdf_file| {
+                LibraryMetaData::file(pdf_file, &self.config.storage)
+                    .ok()
+                    .map(|metadata| LibraryDirEntry { name: pdf_file.file_name.clone(), metadata })
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    async fn open_for_write(&self, path: &LibraryPath, options: &OpenOptions) -> Result<Box<dyn DavFile>, FsError> {
+        let (folder, file_name) = match path {
+            LibraryPath::File(folder, file_name) => (folder, file_name),
+            _ => return Err(FsError::Forbidden)
+        };
+
+        if !is_pdf_file_name(file_name) {
+            return Err(FsError::Forbidden);
+        }
+
+        let folder = self.resolve_folder(folder).await?;
+
+        // Stored pdfs are never overwritten, a pdf with the same name has to be deleted first
+        match self.service.get_pdf_file_by_name(file_name, &self.user_id).await {
+            Ok(None) => (),
+            Ok(Some(_)) => return Err(if options.create_new { FsError::Exists } else { FsError::Forbidden }),
+            Err(_) => return Err(FsError::GeneralFailure)
+        }
+
+        if !options.create {
+            return Err(FsError::NotFound);
+        }
+
+        let temp_file = match NamedTempFile::new_in(&self.config.storage.tmp_dir) {
+            Ok(temp_file) => temp_file,
+            Err(_) => return Err(FsError::GeneralFailure)
+        };
+
+        Ok(Box::new(IncomingPdfFile {
+            service: self.service.clone(),
+            config: self.config.clone(),
+            engine: self.engine.clone(),
+            owner_id: self.user_id,
+            folder,
+            file_name: file_name.clone(),
+            file: Some(temp_file),
+            written: 0,
+            metadata: None
+        }))
+    }
+
+    async fn open_for_read(&self, path: &LibraryPath) -> Result<Box<dyn DavFile>, FsError> {
+        let pdf_file = match path {
+            LibraryPath::File(folder, file_name) => self.find_file(folder, file_name).await?,
+            _ => return Err(FsError::Forbidden)
+        };
+
+        let metadata = LibraryMetaData::file(&pdf_file, &self.config.storage)?;
+
+        match tokio::fs::File::open(self.config.storage.upload_path(&pdf_file.storage_key)).await {
+            Ok(file) => Ok(Box::new(StoredPdfFile { file, metadata })),
+            Err(_) => Err(FsError::NotFound)
+        }
+    }
+
+    async fn path_metadata(&self, path: &LibraryPath) -> Result<LibraryMetaData, FsError> {
+        match path {
+            LibraryPath::Root | LibraryPath::All | LibraryPath::Tags | LibraryPath::Authors => Ok(LibraryMetaData::dir()),
+            LibraryPath::Tag(tag) => self.find_tag(tag).await.map(|_| LibraryMetaData::dir()),
+            LibraryPath::Author(author) => self.find_author(author).await.map(|_| LibraryMetaData::dir()),
+            LibraryPath::File(folder, file_name) => LibraryMetaData::file(&self.find_file(folder, file_name).await?, &self.config.storage)
+        }
+    }
+
+    async fn delete_file(&self, path: &LibraryPath) -> Result<(), FsError> {
+        let pdf_file = match path {
+            LibraryPath::File(folder, file_name) => self.find_file(folder, file_name).await?,
+            _ => return Err(FsError::Forbidden)
+        };
+
+        let pdf_id = match pdf_file.id {
+            Some(pdf_id) => pdf_id,
+            None => return Err(FsError::NotFound)
+        };
+
+        match self.service.delete(&pdf_id, &self.user_id).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(FsError::GeneralFailure)
+        }
+    }
+
+    async fn create_tag_folder(&self, path: &LibraryPath) -> Result<(), FsError> {
+        let tag = match path {
+            LibraryPath::Tag(tag) => tag,
+            _ => return Err(FsError::Forbidden)
+        };
+
+        if self.find_tag(tag).await.is_ok() {
+            return Err(FsError::Exists);
+        }
+
+        match self.service.create_tag(tag, &self.user_id).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(FsError::GeneralFailure)
+        }
+    }
+
+    /// Copying a pdf into a tag folder associates the tag with the pdf.
+    async fn copy_file(&self, from: &LibraryPath, to: &LibraryPath) -> Result<(), FsError> {
+        let (from_folder, from_name, to_folder, to_name) = match (from, to) {
+            (LibraryPath::File(from_folder, from_name), LibraryPath::File(to_folder, to_name)) => (from_folder, from_name, to_folder, to_name),
+            _ => return Err(FsError::Forbidden)
+        };
+
+        if from_name != to_name {
+            return Err(FsError::Forbidden);
+        }
+
+        let pdf_file = self.find_file(from_folder, from_name).await?;
+        let pdf_id = match pdf_file.id {
+            Some(pdf_id) => pdf_id,
+            None => return Err(FsError::NotFound)
+        };
+
+        match to_folder {
+            Folder::All => Ok(()),
+            Folder::Tag(tag) => {
+                let tag = self.find_tag(tag).await?;
+                match self.service.add_tag(&pdf_id, &tag, &self.user_id).await {
+                    Ok(_) => Ok(()),
+                    Err(PdfUpdateError::Forbidden(_)) => Err(FsError::Forbidden),
+                    Err(PdfUpdateError::NotFound(_)) => Err(FsError::NotFound),
+                    Err(_) => Err(FsError::GeneralFailure)
+                }
+            },
+            Folder::Author(_) => Err(FsError::Forbidden)
+        }
+    }
+
+    /// Moving a pdf from one tag folder into another replaces the tag.
+    async fn move_file(&self, from: &LibraryPath, to: &LibraryPath) -> Result<(), FsError> {
+        let from_tag = match from {
+            LibraryPath::File(Folder::Tag(from_tag), _) => self.find_tag(from_tag).await?,
+            _ => return Err(FsError::Forbidden)
+        };
+
+        if !matches!(to, LibraryPath::File(Folder::Tag(_), _)) {
+            return Err(FsError::Forbidden);
+        }
+
+        self.copy_file(from, to).await?;
+
+        let pdf_id = match from {
+            LibraryPath::File(folder, file_name) => self.find_file(folder, file_name).await?.id,
+            _ => None
+        };
+
+        match pdf_id {
+            Some(pdf_id) => match self.service.remove_tag(&pdf_id, &from_tag, &self.user_id).await {
+                Ok(_) => Ok(()),
+                Err(PdfUpdateError::Forbidden(_)) => Err(FsError::Forbidden),
+                Err(PdfUpdateError::NotFound(_)) => Err(FsError::NotFound),
+                Err(_) => Err(FsError::GeneralFailure)
+            },
+            None => Err(FsError::NotFound)
+        }
+    }
+}
+
+impl DavFileSystem for LibraryFs {
+
+    fn open<'a>(&'a self, path: &'a DavPath, options: OpenOptions) -> FsFuture<'a, Box<dyn DavFile>> {
+        trace!("webdav: open()");
+
+        async move {
+            let library_path = match LibraryPath::parse(path) {
+                Some(library_path) => library_path,
+                None => return Err(FsError::NotFound)
+            };
+
+            if options.write || options.append {
+                self.open_for_write(&library_path, &options).await
+            } else {
+                self.open_for_read(&library_path).await
+            }
+        }
+        .boxed()
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a DavPath, _meta: ReadDirMeta) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        trace!("webdav: read_dir()");
+
+        async move {
+            let library_path = match LibraryPath::parse(path) {
+                Some(library_path) => library_path,
+                None => return Err(FsError::NotFound)
+            };
+
+            let entries = self.list_dir(&library_path).await?;
+            let entries = entries.into_iter().map(|entry| Ok(Box::new(entry) as Box<dyn DavDirEntry>));
+
+            let entries_stream: FsStream<Box<dyn DavDirEntry>> = Box::pin(stream::iter(entries));
+            Ok(entries_stream)
+        }
+        .boxed()
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        trace!("webdav: metadata()");
+
+        async move {
+            let library_path = match LibraryPath::parse(path) {
+                Some(library_path) => library_path,
+                None => return Err(FsError::NotFound)
+            };
+
+            let metadata = self.path_metadata(&library_path).await?;
+            Ok(Box::new(metadata) as Box<dyn DavMetaData>)
+        }
+        .boxed()
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        trace!("webdav: create_dir()");
+
+        async move {
+            match LibraryPath::parse(path) {
+                Some(library_path) => self.create_tag_folder(&library_path).await,
+                None => Err(FsError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        trace!("webdav: remove_file()");
+
+        async move {
+            match LibraryPath::parse(path) {
+                Some(library_path) => self.delete_file(&library_path).await,
+                None => Err(FsError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        trace!("webdav: copy()");
+
+        async move {
+            match (LibraryPath::parse(from), LibraryPath::parse(to)) {
+                (Some(from), Some(to)) => self.copy_file(&from, &to).await,
+                _ => Err(FsError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        trace!("webdav: rename()");
+
+        async move {
+            match (LibraryPath::parse(from), LibraryPath::parse(to)) {
+                (Some(from), Some(to)) => self.move_file(&from, &to).await,
+                _ => Err(FsError::NotFound)
+            }
+        }
+        .boxed()
+    }
+}
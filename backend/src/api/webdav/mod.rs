@@ -0,0 +1 @@
+pub mod library_fs;
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use clap::Parser;
+use tracing::{error, info};
+
+use pdf_store::{engine, telemetry, AppState};
+use pdf_store::cli::{self, AdminArgs, Command};
+use pdf_store::repository::db::Database;
+use pdf_store::config::{Config, LoggingConfig};
+
+/// Maintenance commands for the library, run against the database and directories the backend is configured with
+#[actix_web::main]
+async fn main() {
+
+    dotenv::dotenv().ok();
+
+    let args = AdminArgs::parse();
+
+    let config = match Config::load(&args.settings) {
+        Ok(config) => Arc::new(config),
+        Err(msg) => {
+            let _ = telemetry::init(&LoggingConfig::default());
+            error!("{msg}");
+            std::process::exit(2);
+        }
+    };
+
+    let telemetry = match telemetry::init(&config.logging) {
+        Ok(telemetry) => telemetry,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(data_dir) = &config.storage.data_dir {
+        if let Err(err) = std::fs::create_dir_all(data_dir) {
+            error!("Error creating the data directory: {err}");
+            std::process::exit(2);
+        }
+    }
+
+    let database_connection = match Database::connect(&config.database).await {
+        Ok(database_connection) => database_connection,
+        Err(err) => {
+            error!("Error connecting to database: {err}");
+            std::process::exit(2);
+        }
+    };
+
+    let pending_check = cli::check_migrations(&database_connection).await;
+
+    // The other commands expect the schema of this version and don't migrate on their own
+    let exit_code = match args.command {
+        Command::Migrate { check: true } => pending_check,
+        Command::Migrate { check: false } => match database_connection.migrate().await {
+            Ok(_) => {
+                info!("The database schema is up to date");
+                0
+            },
+            Err(msg) => {
+                error!("{msg}");
+                2
+            }
+        },
+        _ if pending_check != 0 => {
+            error!("The database schema is not up to date, run `pdf-store-admin migrate` first");
+            2
+        },
+        command => run(command, config, &database_connection).await
+    };
+
+    database_connection.close().await;
+    telemetry.shutdown();
+
+    std::process::exit(exit_code);
+}
+
+
+/// Runs the command with the services of the backend and returns the exit code: `0` when it succeeded,
+/// `1` when it failed for some of the pdfs and `2` when it failed altogether
+async fn run(command: Command, config: Arc<Config>, database: &Database) -> i32 {
+    let engine = match engine::start(&config) {
+        Ok(engine) => engine,
+        Err(msg) => {
+            error!("{msg}");
+            return 2;
+        }
+    };
+
+    for dir in [&config.storage.upload_dir, &config.storage.tmp_dir] {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            error!("Error creating '{}': {}", dir.display(), err);
+            return 2;
+        }
+    }
+
+    let state = AppState::new(config, database, engine);
+
+    match cli::run(command, &state).await {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(msg) => {
+            error!("{msg}");
+            2
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use tracing::{error, info, warn};
+
+use crate::AppState;
+use crate::api::dto::archive::ArchiveConflict;
+use crate::api::dto::fsck::FsckRepairStatus;
+use crate::api::dto::user::CreateUserDto;
+use crate::config::SettingArgs;
+use crate::domain::service::archive::ArchiveService;
+use crate::domain::service::auth::AuthService;
+use crate::domain::service::fsck::FsckService;
+use crate::domain::service::pdf::PdfService;
+use crate::errors::{ArchiveError, AuthError};
+use crate::repository::db::Database;
+use crate::service::auth::generate_token;
+use crate::util::{folder_tags, import_pdf};
+
+#[derive(Parser)]
+#[command(version, about = "Maintenance of the PdfStore library, reads the same settings as the backend")]
+pub struct AdminArgs {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[command(flatten)]
+    pub settings: SettingArgs
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Applies the pending migrations
+    Migrate {
+        /// Only compare the database with the migrations, exits with 1 when migrations are pending
+        #[arg(long)]
+        check: bool
+    },
+    /// Imports the pdfs of a directory and its subdirectories
+    Import {
+        dir: PathBuf,
+        /// Name of the user who owns the imported pdfs, the first admin by default
+        #[arg(long)]
+        owner: Option<String>,
+        /// Tags the pdfs with the names of the folders they are in, below the imported directory
+        #[arg(long)]
+        tags_from_folders: bool
+    },
+    /// Copies the pdfs to a directory, into a folder per user unless the pdfs of one user are exported
+    Export {
+        dir: PathBuf,
+        /// Name of the user whose pdfs are exported
+        #[arg(long)]
+        owner: Option<String>
+    },
+    /// Rebuilds the indexes the search uses
+    Reindex,
+    /// Renders the previews of the pdfs again
+    RegenThumbnails {
+        /// Only render the previews of pdfs without one
+        #[arg(long)]
+        missing_only: bool
+    },
+    /// Compares the pdfs with the files in the upload directory, exits with 1 when issues are left
+    Fsck {
+        /// Repair the issues that can be repaired
+        #[arg(long)]
+        repair: bool
+    },
+    /// Writes or restores an archive of the pdfs of all users
+    #[command(subcommand)]
+    Archive(ArchiveCommand),
+    /// Manages the tags of all users
+    #[command(subcommand)]
+    Tags(TagsCommand),
+    /// Manages the user accounts
+    #[command(subcommand)]
+    User(UserCommand)
+}
+
+#[derive(Subcommand)]
+pub enum ArchiveCommand {
+    /// Writes the pdfs with their details to a tar archive
+    Export {
+        file: PathBuf,
+        /// Only archive the pdfs that were added or changed since then, as RFC 3339 timestamp
+        #[arg(long)]
+        since: Option<DateTime<Utc>>
+    },
+    /// Restores the pdfs of an archive, exits with 1 when pdfs couldn't be restored
+    Import {
+        file: PathBuf,
+        /// What happens to a pdf whose owner has a pdf with the same content already
+        #[arg(long, value_enum, default_value_t = ArchiveConflict::Skip)]
+        on_conflict: ArchiveConflict
+    }
+}
+
+#[derive(Subcommand)]
+pub enum TagsCommand {
+    /// Replaces the source tags by the target tag and deletes them
+    Merge {
+        #[arg(required = true)]
+        sources: Vec<String>,
+        #[arg(long)]
+        into: String
+    }
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Creates an account, a random password is generated and printed unless one is given
+    Create {
+        username: String,
+        #[arg(long)]
+        admin: bool,
+        #[arg(long, env = "PDF_STORE_USER_PASSWORD", hide_env_values = true)]
+        password: Option<String>
+    }
+}
+
+
+/// Compares the database with the migrations and returns the exit code: `0` when it is up to date,
+/// `1` when migrations are pending and `2` when the check failed
+pub async fn check_migrations(database: &Database) -> i32 {
+    match database.pending_migrations().await {
+        Ok(pending) if pending.is_empty() => {
+            info!("The database schema is up to date");
+            0
+        },
+        Ok(pending) => {
+            for migration in pending {
+                warn!("Migration {} ({}) has not been applied yet", migration.version, migration.description);
+            }
+            1
+        },
+        Err(msg) => {
+            error!("{msg}");
+            2
+        }
+    }
+}
+
+
+/// Runs a command other than `migrate`, which needs no state. Returns whether it succeeded completely,
+/// the errors of single pdfs are printed and don't stop the command.
+pub async fn run(command: Command, state: &AppState) -> Result<bool, String> {
+    match command {
+        Command::Migrate { .. } => Err("The migrations are applied without the services".to_string()),
+        Command::Import { dir, owner, tags_from_folders } => import(state, &dir, owner.as_deref(), tags_from_folders).await,
+        Command::Export { dir, owner } => export(state, &dir, owner.as_deref()).await,
+        Command::Reindex => reindex(state).await,
+        Command::RegenThumbnails { missing_only } => regen_thumbnails(state, missing_only).await,
+        Command::Fsck { repair } => fsck(state, !repair).await,
+        Command::Archive(ArchiveCommand::Export { file, since }) => export_archive(state, &file, since).await,
+        Command::Archive(ArchiveCommand::Import { file, on_conflict }) => import_archive(state, &file, on_conflict).await,
+        Command::Tags(TagsCommand::Merge { sources, into }) => merge_tags(state, &sources, &into).await,
+        Command::User(UserCommand::Create { username, admin, password }) => create_user(state, username, admin, password).await
+    }
+}
+
+
+/// Imports every pdf below the directory, the files are left in place
+pub async fn import(state: &AppState, dir: &Path, owner: Option<&str>, tags_from_folders: bool) -> Result<bool, String> {
+    let owner = state.auth_service.find_user_or_admin(owner).await?;
+
+    let mut files = Vec::new();
+    collect_pdfs(dir, &mut files)?;
+    files.sort();
+
+    let mut all_imported = true;
+
+    for path in files {
+        let tags = if tags_from_folders { folder_tags(dir, &path) } else { Vec::new() };
+
+        match import_pdf(state.engine.as_ref(), &state.service, &state.config.storage, &path, tags, &owner.id).await {
+            Ok(pdf_id) => println!("Imported {} as {}", path.display(), pdf_id),
+            Err(msg) => {
+                println!("Error importing {}: {}", path.display(), msg);
+                all_imported = false;
+            }
+        }
+    }
+
+    Ok(all_imported)
+}
+
+
+/// Copies the pdfs with their file names, existing files in the directory are not overwritten
+pub async fn export(state: &AppState, dir: &Path, owner: Option<&str>) -> Result<bool, String> {
+    let owners = match owner {
+        Some(username) => vec![(state.auth_service.find_user_or_admin(Some(username)).await?, dir.to_path_buf())],
+        None => state.auth_service.get_users().await?
+            .into_iter()
+            .map(|user| {
+                let user_dir = dir.join(&user.username);
+                (user, user_dir)
+            })
+            .collect()
+    };
+
+    let mut all_exported = true;
+
+    for (user, user_dir) in owners {
+        let pdf_files = state.service.get_pdf_files(&user.id).await?;
+
+        if pdf_files.is_empty() {
+            continue;
+        }
+
+        if let Err(err) = std::fs::create_dir_all(&user_dir) {
+            return Err(format!("Error creating '{}': {}", user_dir.display(), err));
+        }
+
+        for pdf_file in pdf_files {
+            let target = user_dir.join(&pdf_file.file_name);
+
+            if target.exists() {
+                println!("Skipped {}, the file exists already", target.display());
+                all_exported = false;
+                continue;
+            }
+
+            match std::fs::copy(state.config.storage.upload_path(&pdf_file.storage_key), &target) {
+                Ok(_) => println!("Exported {}", target.display()),
+                Err(err) => {
+                    println!("Error exporting {}: {}", target.display(), err);
+                    all_exported = false;
+                }
+            }
+        }
+    }
+
+    Ok(all_exported)
+}
+
+
+pub async fn reindex(state: &AppState) -> Result<bool, String> {
+    state.service.rebuild_search_index().await?;
+
+    println!("Rebuilt the search index");
+    Ok(true)
+}
+
+
+pub async fn regen_thumbnails(state: &AppState, missing_only: bool) -> Result<bool, String> {
+    let admin = state.auth_service.find_user_or_admin(None).await?;
+
+    let (rendered, errors) = state.fsck_service.regenerate_thumbnails(missing_only, &admin.id).await?;
+
+    for msg in &errors {
+        println!("Error rendering the preview of {msg}");
+    }
+
+    println!("Rendered previews: {rendered}");
+    Ok(errors.is_empty())
+}
+
+
+/// Prints the report of the storage check, it succeeds when no issues are left. Repairs are made on behalf of the first admin.
+pub async fn fsck(state: &AppState, dry_run: bool) -> Result<bool, String> {
+    let admin = state.auth_service.find_user_or_admin(None).await?;
+
+    let report = state.fsck_service.check(dry_run, &admin.id).await?;
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(err) => return Err(format!("Error printing the report of the storage check: {err}"))
+    }
+
+    let remaining = report.issues.iter().filter(|issue| issue.repair != FsckRepairStatus::Repaired).count();
+
+    if remaining == 0 {
+        info!("The storage check found no issues that are left to repair");
+    } else {
+        warn!("The storage check found issues that are left to repair: {remaining}");
+    }
+
+    Ok(remaining == 0)
+}
+
+
+pub async fn export_archive(state: &AppState, file: &Path, since: Option<DateTime<Utc>>) -> Result<bool, String> {
+    let export = state.archive_service.export(since).await?;
+    let archived = export.manifest.pdfs.len();
+
+    let path = file.to_path_buf();
+
+    let write_res = web::block(move || {
+        std::fs::File::create(path).and_then(|file| export.write_to(BufWriter::new(file)))
+    }).await;
+
+    match write_res {
+        Ok(Ok(_)) => {
+            println!("Archived pdfs: {archived}");
+            Ok(true)
+        },
+        Ok(Err(err)) => Err(format!("Error writing the archive '{}': {}", file.display(), err)),
+        Err(_) => Err(format!("Error writing the archive '{}'", file.display()))
+    }
+}
+
+
+/// Prints the report of restoring the archive, pdfs of owners without an account are restored for the first admin
+pub async fn import_archive(state: &AppState, file: &Path, on_conflict: ArchiveConflict) -> Result<bool, String> {
+    let admin = state.auth_service.find_user_or_admin(None).await?;
+
+    let report = match state.archive_service.import(file, on_conflict, &admin.id).await {
+        Ok(report) => report,
+        Err(ArchiveError::InvalidArchive(msg) | ArchiveError::DatabaseError(msg)) => return Err(msg)
+    };
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{json}"),
+        Err(err) => return Err(format!("Error printing the report of the import: {err}"))
+    }
+
+    Ok(report.failed == 0)
+}
+
+
+/// Merges the tags on behalf of the first admin
+pub async fn merge_tags(state: &AppState, sources: &[String], target: &str) -> Result<bool, String> {
+    let admin = state.auth_service.find_user_or_admin(None).await?;
+
+    let merged = state.service.merge_tags(sources, target, &admin.id).await?;
+
+    println!("Merged {} into '{}', changed pdfs: {}", sources.join(", "), target, merged);
+    Ok(true)
+}
+
+
+pub async fn create_user(state: &AppState, username: String, is_admin: bool, password: Option<String>) -> Result<bool, String> {
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(generate_token);
+
+    let user = match state.auth_service.create_user(CreateUserDto { username, password: password.clone(), is_admin }).await {
+        Ok(user) => user,
+        Err(AuthError::InvalidCredentials(msg) | AuthError::InvalidInput(msg) | AuthError::AlreadyExists(msg)
+            | AuthError::NotFound(msg) | AuthError::DatabaseError(msg)) => return Err(msg)
+    };
+
+    println!("Created the user '{}' with the ID {}", user.username, user.id);
+
+    if generated {
+        println!("Password: {password}");
+    }
+
+    Ok(true)
+}
+
+
+fn collect_pdfs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return Err(format!("Error reading '{}': {}", dir.display(), err))
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_pdfs(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf")) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
\ No newline at end of file
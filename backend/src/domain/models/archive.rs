@@ -0,0 +1,38 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::api::dto::archive::{ArchiveManifestDto, ARCHIVE_MANIFEST};
+
+
+/// The content of an archive of the library, which is written as tar by `write_to`
+#[derive(Debug)]
+pub struct ArchiveExport {
+    pub manifest: ArchiveManifestDto,
+    /// Paths of the pdfs in the archive and of their files in the upload directory
+    pub files: Vec<(String, PathBuf)>
+}
+
+
+impl ArchiveExport {
+
+    /// Writes the archive, the manifest comes first so it can be read before the files.
+    /// Nothing is buffered, so the archive can be streamed to a client while it is written.
+    pub fn write_to(&self, writer: impl Write) -> std::io::Result<()> {
+        let manifest = serde_json::to_vec_pretty(&self.manifest)?;
+
+        let mut builder = tar::Builder::new(writer);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(self.manifest.exported_at.timestamp().max(0) as u64);
+        builder.append_data(&mut header, ARCHIVE_MANIFEST, manifest.as_slice())?;
+
+        for (name, path) in &self.files {
+            builder.append_path_with_name(path, name)?;
+        }
+
+        builder.into_inner()?.flush()
+    }
+
+}
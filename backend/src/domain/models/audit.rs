@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+
+/// What was done to the target of an audit log entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Upload,
+    /// Covers changes of the details and the tags of a pdf
+    Update,
+    Delete,
+    Share,
+    Unshare,
+    /// A tag was created without being added to a pdf, which has no target
+    TagCreate,
+    ShareLinkCreate,
+    ShareLinkRevoke
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Upload => "upload",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Share => "share",
+            AuditAction::Unshare => "unshare",
+            AuditAction::TagCreate => "tag_create",
+            AuditAction::ShareLinkCreate => "share_link_create",
+            AuditAction::ShareLinkRevoke => "share_link_revoke"
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "upload" => Ok(AuditAction::Upload),
+            "update" => Ok(AuditAction::Update),
+            "delete" => Ok(AuditAction::Delete),
+            "share" => Ok(AuditAction::Share),
+            "unshare" => Ok(AuditAction::Unshare),
+            "tag_create" => Ok(AuditAction::TagCreate),
+            "share_link_create" => Ok(AuditAction::ShareLinkCreate),
+            "share_link_revoke" => Ok(AuditAction::ShareLinkRevoke),
+            _ => Err(format!("Unknown audit action '{}'", action))
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub time_created: DateTime<Utc>,
+    pub actor_id: Option<Uuid>,
+    /// Username of the actor at the time of the change
+    pub actor_name: Option<String>,
+    pub action: String,
+    pub target_id: Option<Uuid>,
+    pub changes: Value
+}
@@ -1,2 +1,6 @@
 pub mod pdf;
-pub mod errors;
\ No newline at end of file
+pub mod user;
+pub mod share;
+pub mod errors;
+pub mod audit;
+pub mod archive;
\ No newline at end of file
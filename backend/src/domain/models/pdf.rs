@@ -2,9 +2,10 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
 
 
-#[derive(Debug, FromRow, Deserialize, Serialize)]
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
 pub struct Pdf {
     pub id: Option<Uuid>,
     pub title: Option<String>,
@@ -14,7 +15,48 @@ pub struct Pdf {
     pub comments: Option<String>,
     pub time_added: Option<DateTime<Utc>>,
     pub last_accessed: Option<DateTime<Utc>>,
-    pub picture: Option<String>
+    pub picture: Option<String>,
+    pub version: i32,
+    pub owner_id: Uuid,
+    /// Name of the file in the upload directory, pdfs with the same content share it
+    pub storage_key: String,
+    /// Set by the storage check when the file is gone from the upload directory
+    pub file_missing: bool
+}
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct PdfFile {
+    pub id: Option<Uuid>,
+    pub file_name: String,
+    pub time_added: Option<DateTime<Utc>>,
+    pub storage_key: String
+}
+
+/// What the storage check needs to know about a pdf of any user
+#[derive(Debug, Clone, FromRow)]
+pub struct StoredPdf {
+    pub id: Uuid,
+    pub storage_key: String,
+    pub has_picture: bool,
+    pub file_missing: bool
+}
+
+/// A pdf of any user with what an archive of the library keeps of it
+#[derive(Debug, Clone)]
+pub struct ArchivedPdf {
+    pub pdf: Pdf,
+    /// Name of the owner, the pdf is restored for the user with the same name
+    pub owner: String,
+    pub tags: Vec<String>
+}
+
+/// Size of the library of all users
+#[derive(Debug, Clone)]
+pub struct LibraryStats {
+    pub documents: i64,
+    pub pages: i64,
+    /// Size of the stored files, pdfs with the same content share a file
+    pub bytes: u64
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,7 +79,7 @@ pub struct Search {
     pub page: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug, FromRow)]
+#[derive(Serialize, Deserialize, Debug, FromRow, ToSchema)]
 pub struct PdfOverview {
     pub id: Option<Uuid>,
     pub title: Option<String>,
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+
+/// What a pdf is shared for, every permission includes the ones before it.
+/// Annotating covers the comments and tags, editing also the title, author and preview picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Annotate,
+    Edit
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Annotate => "annotate",
+            Permission::Edit => "edit"
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(permission: &str) -> Result<Self, Self::Err> {
+        match permission {
+            "read" => Ok(Permission::Read),
+            "annotate" => Ok(Permission::Annotate),
+            "edit" => Ok(Permission::Edit),
+            _ => Err(format!("Unknown permission '{}'", permission))
+        }
+    }
+}
+
+/// What a user may do with a pdf, the levels match the ones returned by `pdf_access_level` in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    None = 0,
+    Read = 1,
+    Annotate = 2,
+    Edit = 3,
+    /// Only the owner may delete and share a pdf
+    Owner = 4
+}
+
+impl Access {
+    pub fn level(&self) -> i32 {
+        *self as i32
+    }
+
+    pub fn from_level(level: i32) -> Access {
+        match level {
+            4.. => Access::Owner,
+            3 => Access::Edit,
+            2 => Access::Annotate,
+            1 => Access::Read,
+            _ => Access::None
+        }
+    }
+}
+
+impl From<Permission> for Access {
+    fn from(permission: Permission) -> Self {
+        match permission {
+            Permission::Read => Access::Read,
+            Permission::Annotate => Access::Annotate,
+            Permission::Edit => Access::Edit
+        }
+    }
+}
+
+/// A share of a pdf, either with a user or with a group
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct PdfShare {
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub group_id: Option<Uuid>,
+    pub group_name: Option<String>,
+    pub permission: String
+}
+
+/// Public link to a pdf that can be opened without an account
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub pdf_id: Uuid,
+    pub created_by: Uuid,
+    pub password_hash: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_downloads: Option<i32>,
+    pub download_count: i32,
+    pub time_created: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>
+}
+
+impl ShareLink {
+    /// Why the link can't be used anymore, `None` if it still can
+    pub fn unusable_reason(&self) -> Option<ShareLinkOutcome> {
+        if self.revoked_at.is_some() {
+            return Some(ShareLinkOutcome::Revoked);
+        }
+
+        if self.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Some(ShareLinkOutcome::Expired);
+        }
+
+        if self.max_downloads.is_some_and(|max_downloads| self.download_count >= max_downloads) {
+            return Some(ShareLinkOutcome::LimitReached);
+        }
+
+        None
+    }
+}
+
+/// What happened when a share link was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareLinkOutcome {
+    /// The page with the details of the pdf was shown
+    Viewed,
+    Downloaded,
+    WrongPassword,
+    Expired,
+    Revoked,
+    LimitReached
+}
+
+impl ShareLinkOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareLinkOutcome::Viewed => "viewed",
+            ShareLinkOutcome::Downloaded => "downloaded",
+            ShareLinkOutcome::WrongPassword => "wrong_password",
+            ShareLinkOutcome::Expired => "expired",
+            ShareLinkOutcome::Revoked => "revoked",
+            ShareLinkOutcome::LimitReached => "limit_reached"
+        }
+    }
+}
+
+impl FromStr for ShareLinkOutcome {
+    type Err = String;
+
+    fn from_str(outcome: &str) -> Result<Self, Self::Err> {
+        match outcome {
+            "viewed" => Ok(ShareLinkOutcome::Viewed),
+            "downloaded" => Ok(ShareLinkOutcome::Downloaded),
+            "wrong_password" => Ok(ShareLinkOutcome::WrongPassword),
+            "expired" => Ok(ShareLinkOutcome::Expired),
+            "revoked" => Ok(ShareLinkOutcome::Revoked),
+            "limit_reached" => Ok(ShareLinkOutcome::LimitReached),
+            _ => Err(format!("Unknown share link outcome '{}'", outcome))
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct ShareLinkAccess {
+    pub time_accessed: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: String
+}
+
+/// Who opened a share link
+#[derive(Debug, Clone)]
+pub struct ShareLinkClient {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>
+}
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub password_hash: String,
+    pub is_admin: bool,
+    pub time_created: DateTime<Utc>
+}
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub time_created: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used: Option<DateTime<Utc>>
+}
+
+/// Users can share pdfs with all members of a group at once
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    /// Usernames of the members
+    pub members: Vec<String>,
+    pub time_created: DateTime<Utc>
+}
+
+/// What a request may do, API tokens are limited to the scopes they were created with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Upload,
+    Admin
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Upload => "upload",
+            Scope::Admin => "admin"
+        }
+    }
+
+    /// All scopes the user is allowed to have
+    pub fn all_of(user: &User) -> Vec<Scope> {
+        if user.is_admin {
+            vec![Scope::Read, Scope::Write, Scope::Upload, Scope::Admin]
+        } else {
+            vec![Scope::Read, Scope::Write, Scope::Upload]
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "upload" => Ok(Scope::Upload),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("Unknown scope '{}'", scope))
+        }
+    }
+}
+
+/// The user a request is made by, resolved by the authentication middleware
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user: User,
+    pub scopes: Vec<Scope>,
+    /// Set if the request was authenticated with an API token instead of a login
+    pub api_token_id: Option<Uuid>
+}
+
+impl AuthenticatedUser {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
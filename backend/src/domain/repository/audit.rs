@@ -0,0 +1,13 @@
+use async_trait::async_trait;
+
+use crate::api::dto::audit::AuditLogSearchDto;
+use crate::domain::models::audit::AuditEntry;
+
+
+/// Entries are written by the other repositories in the transaction of the change, so this only reads them
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    async fn count_entries(&self, search: &AuditLogSearchDto) -> Result<i64, String>;
+
+    async fn get_entries_paged(&self, search: &AuditLogSearchDto) -> Result<Vec<AuditEntry>, String>;
+}
@@ -0,0 +1,24 @@
+use async_trait::async_trait;
+
+
+/// Connections of the pool of the database
+#[derive(Debug, Clone, Copy)]
+pub struct PoolUsage {
+    pub open: u32,
+    pub idle: u32,
+    pub max: u32
+}
+
+
+/// What the readiness check and the metrics ask the database
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    /// Runs a query that touches no table
+    async fn ping(&self) -> Result<(), String>;
+
+    /// Number of embedded migrations the database is missing
+    async fn pending_migrations(&self) -> Result<usize, String>;
+
+    /// `None` when the database has no connection pool
+    fn pool_usage(&self) -> Option<PoolUsage>;
+}
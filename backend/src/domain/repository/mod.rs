@@ -1 +1,4 @@
-pub mod pdf;
\ No newline at end of file
+pub mod pdf;
+pub mod user;
+pub mod audit;
+pub mod health;
\ No newline at end of file
@@ -1,30 +1,127 @@
 use async_trait::async_trait;
 use uuid::Uuid;
-use sqlx::PgConnection;
 
-use crate::{domain::models::pdf::{PdfOverview, Pdf}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfMetadataDto}}};
-use crate::errors::PdfMetadataByIdError;
+use crate::{domain::models::pdf::{ArchivedPdf, PdfOverview, Pdf, PdfFile, StoredPdf}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfPatchDto, PdfMetadataDto}}};
+use crate::api::dto::bulk::{BulkOperationDto, BulkItemResultDto};
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::domain::models::share::{PdfShare, ShareLink, ShareLinkAccess, ShareLinkClient, ShareLinkOutcome};
+use chrono::{DateTime, Utc};
+use crate::util::PdfUploaded;
+use crate::errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError};
 
+/// Pdfs are only visible to their owner and the users they are shared with, every query that takes the
+/// `user_id` of the caller leaves out the pdfs the caller may not see.
 #[async_trait]
 pub trait PdfRepository: Send + Sync {
-    async fn total_number_of_pdfs(&self) -> Result<Option<i64>, String>;
+    async fn total_number_of_pdfs(&self, user_id: &Uuid) -> Result<Option<i64>, String>;
     
-    async fn get_pdfs_paged(&self, paging: &PagingDto) -> Result<Vec<PdfOverview>, String>;
+    async fn get_pdfs_paged(&self, paging: &PagingDto, user_id: &Uuid) -> Result<Vec<PdfOverview>, String>;
 
-    async fn get_pdf_metadata(&self, pdf_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError>;
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError>;
 
     async fn get_associated_tags_of_pdf(&self, pdf_id: &Uuid) -> Result<Vec<String>, PdfMetadataByIdError>;
 
-    async fn get_associated_tags_of_pdf_with_connection(&self, pdf_id: &Uuid, conn: &mut PgConnection) -> Result<Vec<String>, PdfMetadataByIdError>;
+    /// Returns the storage key of the pdf
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError>;
 
-    async fn get_by_id(&self, pdf_id: &Uuid) -> Result<String, String>;
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String>;
 
-    async fn search(&self, search: &PdfSearchDto) -> Result<PdfOverviewDto, String>;
+    /// Only applies the update if the pdf is at one of the expected versions, `None` accepts any version
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError>;
 
-    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid) -> Result<PdfMetadataDto, String>;
+    /// Only applies the patch if the pdf is at one of the expected versions, `None` accepts any version
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError>;
 
-    async fn delete(&self, id: &Uuid) -> Result<String, String>;
+    /// Returns the storage key of the pdf if no other pdf has the same content
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Option<String>, PdfAccessError>;
 
-    async fn upload(&self, title: String, filename: String, author: Option<String>, pages: Option<i32>, img: String) -> Result<Pdf, sqlx::Error>;
+    /// Applies the bulk operation in one transaction, which is rolled back for a dry run.
+    /// Returns the result per selected pdf and the storage keys that are no longer used by any pdf.
+    async fn bulk(&self, bulk: &BulkOperationDto, user_id: &Uuid) -> Result<(Vec<BulkItemResultDto>, Vec<String>), String>;
+
+    /// Fails if the owner already has a pdf with the same file name
+    async fn upload(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, String>;
+
+    async fn is_storage_key_used(&self, storage_key: &str) -> Result<bool, String>;
+
+    /// Pdfs of all users, which the storage check compares with the upload directory
+    async fn get_stored_pdfs(&self) -> Result<Vec<StoredPdf>, String>;
+
+    /// Marks the file of the pdf as missing or clears the mark, recorded in the audit log as done by the given user
+    async fn set_file_missing(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), String>;
+
+    /// Replaces the preview of the pdf, recorded in the audit log as done by the given user
+    async fn set_picture(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), String>;
+
+    /// Moves the pdfs of all users from the source tags to the target tag and deletes the source tags.
+    /// Recorded in the audit log as done by the given user, returns the number of changed pdfs.
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String>;
+
+    /// Pdfs of all users with their owner and tags, ordered by when they were added. With `since` only the pdfs
+    /// that were added or changed since then, according to the audit log.
+    async fn get_archived_pdfs(&self, since: Option<DateTime<Utc>>) -> Result<Vec<ArchivedPdf>, String>;
+
+    /// Rebuilds the indexes of the pdfs the search uses, e.g. after the database was restored or changed by hand
+    async fn rebuild_search_index(&self) -> Result<(), String>;
+
+    /// Number of pdfs and the sum of their pages over all users
+    async fn count_pdfs_and_pages(&self) -> Result<(i64, i64), String>;
+
+    /// Pdfs owned by the user, which make up the user's library on WebDAV
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String>;
+
+    /// Tags of the user's pdfs and tags that are not associated with any pdf yet
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String>;
+
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String>;
+
+    /// The tag operations are recorded in the audit log as done by the given user
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String>;
+
+    /// Tags are changed by annotators like the comments
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError>;
+
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError>;
+
+    /// Only the owner may see and change who a pdf is shared with
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShare>, PdfAccessError>;
+
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: &PdfShareDto) -> Result<(), PdfAccessError>;
+
+    /// Returns whether the pdf was shared with the user or group
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: &ShareTargetDto) -> Result<bool, PdfAccessError>;
+
+    /// Only the owner may create, list and revoke the share links of a pdf
+    async fn create_share_link(
+        &self,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        token_hash: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<i32>
+    ) -> Result<ShareLink, PdfAccessError>;
+
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLink>, PdfAccessError>;
+
+    /// Returns whether the pdf has a share link with the given ID that wasn't revoked yet
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<bool, PdfAccessError>;
+
+    /// Returns `None` if the pdf has no share link with the given ID
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Option<Vec<ShareLinkAccess>>, PdfAccessError>;
+
+    /// Looks the share link up by the hash of its token, regardless of whether it can still be used
+    async fn get_share_link(&self, token_hash: &str) -> Result<Option<(ShareLink, Pdf)>, String>;
+
+    async fn record_share_link_access(&self, share_link_id: &Uuid, client: &ShareLinkClient, outcome: ShareLinkOutcome) -> Result<(), String>;
+
+    /// Counts a download and records it in one go, fails with `false` if the link became unusable in the meantime
+    async fn record_share_link_download(&self, share_link_id: &Uuid, client: &ShareLinkClient) -> Result<bool, String>;
 
 }
\ No newline at end of file
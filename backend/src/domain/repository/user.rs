@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::domain::models::user::{ApiToken, Group, User};
+
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn count_users(&self) -> Result<i64, String>;
+
+    async fn get_users(&self) -> Result<Vec<User>, String>;
+
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, String>;
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String>;
+
+    /// Returns `None` if the username is already taken
+    async fn create_user(&self, username: &str, password_hash: &str, is_admin: bool) -> Result<Option<User>, String>;
+
+    async fn update_password(&self, user_id: &Uuid, password_hash: &str) -> Result<(), String>;
+
+    /// Deletes the user along with the user's pdfs, whose deletion is recorded in the audit log as done by the actor.
+    /// Returns the storage keys that are no longer used by any pdf, `None` if no user with the given ID existed.
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<Option<Vec<String>>, String>;
+
+    async fn create_session(&self, token_hash: &str, user_id: &Uuid, expires_at: DateTime<Utc>) -> Result<(), String>;
+
+    /// Resolves the user of a session that has not expired yet
+    async fn get_user_by_session(&self, token_hash: &str) -> Result<Option<User>, String>;
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), String>;
+
+    async fn delete_sessions_of_user(&self, user_id: &Uuid) -> Result<(), String>;
+
+    async fn create_api_token(&self, user_id: &Uuid, name: &str, token_hash: &str, scopes: &[String], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, String>;
+
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiToken>, String>;
+
+    /// Returns whether the user had a token with the given ID
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<bool, String>;
+
+    /// Resolves the user of an API token that has not expired yet and records the token as used
+    async fn get_user_by_api_token(&self, token_hash: &str) -> Result<Option<(User, ApiToken)>, String>;
+
+    async fn get_groups(&self) -> Result<Vec<Group>, String>;
+
+    /// Returns `None` if the name is already taken
+    async fn create_group(&self, name: &str) -> Result<Option<Group>, String>;
+
+    /// Returns whether a group with the given ID existed
+    async fn delete_group(&self, group_id: &Uuid) -> Result<bool, String>;
+
+    /// Returns whether the user is a member afterwards, which is not the case if the group or the user doesn't exist
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String>;
+
+    /// Returns whether the user was a member of the group
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String>;
+}
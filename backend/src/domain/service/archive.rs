@@ -0,0 +1,20 @@
+use std::path::Path;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::api::dto::archive::{ArchiveConflict, ArchiveImportReportDto};
+use crate::domain::models::archive::ArchiveExport;
+use crate::errors::ArchiveError;
+
+
+#[async_trait]
+pub trait ArchiveService: Send + Sync {
+    /// Collects the pdfs of all users for an archive, with `since` only those added or changed since then.
+    /// Pdfs whose file is missing are left out.
+    async fn export(&self, since: Option<DateTime<Utc>>) -> Result<ArchiveExport, String>;
+
+    /// Restores the pdfs of the archive for the users with the names of their owners, or for the given user when
+    /// there is no such user. A pdf whose owner has a pdf with the same content already is handled as `on_conflict` says.
+    async fn import(&self, archive: &Path, on_conflict: ArchiveConflict, user_id: &Uuid) -> Result<ArchiveImportReportDto, ArchiveError>;
+}
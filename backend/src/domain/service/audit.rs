@@ -0,0 +1,9 @@
+use async_trait::async_trait;
+
+use crate::api::dto::audit::{AuditLogDto, AuditLogSearchDto};
+
+
+#[async_trait]
+pub trait AuditService: Send + Sync {
+    async fn get_entries(&self, search: AuditLogSearchDto) -> Result<AuditLogDto, String>;
+}
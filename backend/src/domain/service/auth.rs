@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::user::{CreateUserDto, LoginDto, PasswordChangeDto, SessionDto, UserDto};
+use crate::api::dto::api_token::{ApiTokenDto, CreateApiTokenDto, CreatedApiTokenDto};
+use crate::api::dto::group::{CreateGroupDto, GroupDto};
+use crate::domain::models::user::{AuthenticatedUser, User};
+use crate::errors::AuthError;
+use crate::config::AdminConfig;
+
+#[async_trait]
+pub trait AuthService: Send + Sync {
+    async fn login(&self, login: LoginDto) -> Result<SessionDto, AuthError>;
+
+    async fn logout(&self, token: &str) -> Result<(), String>;
+
+    /// Resolves the user of a session or API token, `None` if the token is unknown or expired
+    async fn authenticate_token(&self, token: &str) -> Result<Option<AuthenticatedUser>, String>;
+
+    /// Resolves the user with the given credentials, `None` if they are wrong
+    async fn authenticate_password(&self, username: &str, password: &str) -> Result<Option<User>, String>;
+
+    async fn get_users(&self) -> Result<Vec<UserDto>, String>;
+
+    /// The user with the given name, or the first admin when no name is given. Background jobs and the
+    /// admin command line act on behalf of this user.
+    async fn find_user_or_admin(&self, username: Option<&str>) -> Result<UserDto, String>;
+
+    async fn create_user(&self, user: CreateUserDto) -> Result<UserDto, AuthError>;
+
+    /// Also deletes the pdfs of the user
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<(), AuthError>;
+
+    async fn change_password(&self, user_id: &Uuid, change: PasswordChangeDto) -> Result<(), AuthError>;
+
+    async fn create_api_token(&self, user: &User, api_token: CreateApiTokenDto) -> Result<CreatedApiTokenDto, AuthError>;
+
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiTokenDto>, String>;
+
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<(), AuthError>;
+
+    async fn get_groups(&self) -> Result<Vec<GroupDto>, String>;
+
+    async fn create_group(&self, group: CreateGroupDto) -> Result<GroupDto, AuthError>;
+
+    async fn delete_group(&self, group_id: &Uuid) -> Result<(), AuthError>;
+
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<(), AuthError>;
+
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<(), AuthError>;
+
+    /// Creates the first admin account if there are no users yet
+    async fn bootstrap_admin(&self, config: &AdminConfig) -> Result<(), String>;
+}
@@ -0,0 +1,16 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::fsck::FsckReportDto;
+
+
+#[async_trait]
+pub trait FsckService: Send + Sync {
+    /// Compares the pdfs of all users with the files in the upload directory. Unless it is a dry run, the issues
+    /// that can be repaired are repaired on behalf of the given user, who also owns the re-imported orphan files.
+    async fn check(&self, dry_run: bool, user_id: &Uuid) -> Result<FsckReportDto, String>;
+
+    /// Renders the previews of the pdfs of all users again, only of those without one when `missing_only` is set.
+    /// Returns the number of rendered previews and the errors of the pdfs that couldn't be rendered.
+    async fn regenerate_thumbnails(&self, missing_only: bool, user_id: &Uuid) -> Result<(usize, Vec<String>), String>;
+}
@@ -0,0 +1,10 @@
+use async_trait::async_trait;
+
+use crate::api::dto::health::ReadinessDto;
+
+
+#[async_trait]
+pub trait HealthService: Send + Sync {
+    /// Runs all checks, a check that fails doesn't keep the others from running
+    async fn readiness(&self) -> ReadinessDto;
+}
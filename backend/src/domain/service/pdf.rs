@@ -1,21 +1,82 @@
 use async_trait::async_trait;
 use uuid::Uuid;
 
-use crate::{api::dto::{paging::PagingDto, pdf::{PdfOverviewDto, PdfMetadataDto, PdfDto, PdfSearchDto, PdfUpdateDto}}, errors::PdfMetadataByIdError, util::PdfUploaded};
+use crate::api::dto::bulk::{BulkOperationDto, BulkResultDto};
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::api::dto::share_link::{CreateShareLinkDto, CreatedShareLinkDto, ShareLinkAccessDto, ShareLinkDto, SharedPdfDto};
+use crate::domain::models::share::ShareLinkClient;
+use crate::errors::ShareLinkError;
+use crate::{api::dto::{paging::PagingDto, pdf::{PdfOverviewDto, PdfMetadataDto, PdfDto, PdfSearchDto, PdfUpdateDto, PdfPatchDto}}, errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError}, util::PdfUploaded, domain::models::pdf::{LibraryStats, PdfFile}};
 
+/// All operations are done on behalf of the user with the given `user_id` and only see the pdfs the user may see
 #[async_trait]
 pub trait PdfService: Sync + Send {
-    async fn get_all(&self, paging: PagingDto) -> Result<PdfOverviewDto, String>;
+    async fn get_all(&self, paging: PagingDto, user_id: &Uuid) -> Result<PdfOverviewDto, String>;
 
-    async fn get_pdf_metadata(&self, pdf_id: &Uuid) -> Result<PdfMetadataDto, PdfMetadataByIdError>;
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<PdfMetadataDto, PdfMetadataByIdError>;
 
-    async fn get_by_id(&self, pdf_id: &Uuid) -> Result<PdfDto, String>;
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<PdfDto, PdfMetadataByIdError>;
 
-    async fn search(&self, search: &PdfSearchDto) -> Result<PdfOverviewDto, String>;
+    async fn get_file_path(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError>;
 
-    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid) -> Result<PdfMetadataDto, String>;
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String>;
 
-    async fn delete(&self, pdf_id: &Uuid) -> Result<(), String>;
+    /// Only applies the update if the pdf is at one of the expected versions, `None` accepts any version
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError>;
 
-    async fn upload(&self, to_upload: Vec<PdfUploaded>) -> Result<(), String>;
+    /// Only applies the patch if the pdf is at one of the expected versions, `None` accepts any version
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError>;
+
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<(), PdfAccessError>;
+
+    async fn bulk(&self, bulk: BulkOperationDto, user_id: &Uuid) -> Result<BulkResultDto, String>;
+
+    /// The uploaded pdfs are owned by the user with the given ID
+    async fn upload(&self, to_upload: Vec<PdfUploaded>, owner_id: &Uuid) -> Result<Vec<Uuid>, String>;
+
+    /// Size of the library of all users, regardless of who may see the pdfs
+    async fn get_library_stats(&self) -> Result<LibraryStats, String>;
+
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String>;
+
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String>;
+
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String>;
+
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String>;
+
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String>;
+
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError>;
+
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError>;
+
+    /// Replaces the source tags by the target tag on the pdfs of all users, returns the number of changed pdfs
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String>;
+
+    async fn rebuild_search_index(&self) -> Result<(), String>;
+
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShareDto>, PdfAccessError>;
+
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: PdfShareDto) -> Result<(), PdfAccessError>;
+
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: ShareTargetDto) -> Result<(), PdfAccessError>;
+
+    async fn create_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link: CreateShareLinkDto) -> Result<CreatedShareLinkDto, PdfAccessError>;
+
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLinkDto>, PdfAccessError>;
+
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<(), PdfAccessError>;
+
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Vec<ShareLinkAccessDto>, PdfAccessError>;
+
+    /// Details of the pdf behind a share link, opening the link is recorded
+    async fn open_share_link(&self, token: &str, client: ShareLinkClient) -> Result<SharedPdfDto, ShareLinkError>;
+
+    /// Counts and records the download and returns the path and the file name of the shared pdf
+    async fn download_share_link(&self, token: &str, password: Option<String>, client: ShareLinkClient) -> Result<(String, String), ShareLinkError>;
 }
\ No newline at end of file
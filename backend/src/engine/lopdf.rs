@@ -0,0 +1,62 @@
+use actix_web::web;
+use async_trait::async_trait;
+use tracing::instrument;
+use ::lopdf::Document;
+use crate::engine::{PdfEngine, PdfDocumentInfo};
+
+
+/// Engine written in Rust only, used when the pdfium library isn't available.
+/// It reads the metadata and the text of pdfs, but can't render their pages.
+#[derive(Clone)]
+pub struct LopdfEngine;
+
+
+#[async_trait]
+impl PdfEngine for LopdfEngine {
+
+    fn name(&self) -> &'static str {
+        "lopdf"
+    }
+
+    fn can_render(&self) -> bool {
+        false
+    }
+
+    #[instrument(skip_all)]
+    async fn load(&self, path: &str) -> Result<PdfDocumentInfo, String> {
+        let path = path.to_string();
+
+        match web::block(move || Document::load_metadata(path)).await {
+            Ok(Ok(metadata)) => Ok(PdfDocumentInfo {
+                title: metadata.title.map(|title| title.trim().to_string()).filter(|title| !title.is_empty()),
+                author: metadata.author,
+                pages: metadata.page_count as i32
+            }),
+            Ok(Err(err)) => Err(format!("Error reading pdf: {}", err)),
+            Err(_) => Err("Error reading pdf".to_string())
+        }
+    }
+
+    async fn render_page(&self, _path: &str, _page_index: u16, _scale: f32) -> Result<Vec<u8>, String> {
+        Err("The lopdf engine can't render pages".to_string())
+    }
+
+    #[instrument(skip_all)]
+    async fn extract_text(&self, path: &str) -> Result<String, String> {
+        let path = path.to_string();
+
+        let text_res = web::block(move || {
+            let document = Document::load(path)?;
+            let page_numbers: Vec<u32> = document.get_pages().keys().copied().collect();
+            document.extract_text(&page_numbers)
+        })
+        .await;
+
+        match text_res {
+            Ok(Ok(text)) => Ok(text),
+            Ok(Err(err)) => Err(format!("Error reading pdf: {}", err)),
+            Err(_) => Err("Error reading pdf".to_string())
+        }
+    }
+
+}
\ No newline at end of file
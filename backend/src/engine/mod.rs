@@ -0,0 +1,135 @@
+use std::sync::Arc;
+use std::time::Instant;
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine};
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage};
+use tracing::{info, warn, instrument};
+use crate::config::{Config, PdfEngineKind};
+use crate::engine::lopdf::LopdfEngine;
+use crate::engine::pdfium::PdfiumEngine;
+use crate::metrics::METRICS;
+
+pub mod lopdf;
+pub mod pdfium;
+
+/// Scale of the cover that is stored as preview of a pdf
+const PREVIEW_SCALE: f32 = 0.8;
+
+
+/// What every engine reads from a pdf
+#[derive(Debug)]
+pub struct PdfDocumentInfo {
+    /// Title from the metadata of the pdf, if it has a non-empty one
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: i32
+}
+
+/// What is read from a pdf when it is uploaded
+#[derive(Debug)]
+pub struct PdfAnalysis {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: i32,
+    /// Cover of the pdf as base64 encoded jpeg
+    pub preview: String
+}
+
+
+/// Reads and renders pdfs. All pdf processing of the backend goes through an engine,
+/// the pdfs are given by the path of their file.
+#[async_trait]
+pub trait PdfEngine: Send + Sync {
+
+    fn name(&self) -> &'static str;
+
+    /// Whether the engine can render pages, the others fail in `render_page`
+    fn can_render(&self) -> bool;
+
+    /// Opens the pdf and reads its metadata and number of pages
+    async fn load(&self, path: &str) -> Result<PdfDocumentInfo, String>;
+
+    /// Renders the page with the given index as jpeg
+    async fn render_page(&self, path: &str, page_index: u16, scale: f32) -> Result<Vec<u8>, String>;
+
+    async fn extract_text(&self, path: &str) -> Result<String, String>;
+
+    /// Fails when the engine can't take jobs anymore
+    async fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Number of jobs waiting for the engine, engines that process the pdfs on the calling task have none
+    fn queued_jobs(&self) -> usize {
+        0
+    }
+
+}
+
+
+/// Starts the engine that is selected in the configuration. With `auto` the lopdf engine is used
+/// when the pdfium library can't be loaded, with `pdfium` the start fails instead.
+pub fn start(config: &Config) -> Result<Arc<dyn PdfEngine>, String> {
+    let engine: Arc<dyn PdfEngine> = match config.pdf.engine {
+        PdfEngineKind::Pdfium => Arc::new(PdfiumEngine::start(&config.pdfium)?),
+        PdfEngineKind::Lopdf => Arc::new(LopdfEngine),
+        PdfEngineKind::Auto => match PdfiumEngine::start(&config.pdfium) {
+            Ok(engine) => Arc::new(engine),
+            Err(msg) => {
+                warn!("{msg}");
+                warn!("Falling back to the lopdf engine, uploaded pdfs get a placeholder instead of a preview of their cover");
+                Arc::new(LopdfEngine)
+            }
+        }
+    };
+
+    info!("Using the {} engine for pdfs", engine.name());
+
+    Ok(engine)
+}
+
+
+/// Reads what is stored about an uploaded pdf. Engines that can't render get a blank page as preview.
+#[instrument(skip_all)]
+pub async fn analyze(engine: &dyn PdfEngine, path: &str) -> Result<PdfAnalysis, String> {
+    let started = Instant::now();
+    let info = engine.load(path).await?;
+    METRICS.record_pdf_engine(engine.name(), "load", started.elapsed().as_secs_f64());
+
+    let preview = if engine.can_render() {
+        let started = Instant::now();
+        let preview = engine.render_page(path, 0, PREVIEW_SCALE).await?;
+        METRICS.record_pdf_engine(engine.name(), "render", started.elapsed().as_secs_f64());
+
+        preview
+    } else {
+        placeholder_preview()?
+    };
+
+    Ok(PdfAnalysis {
+        title: info.title,
+        author: info.author,
+        pages: info.pages,
+        preview: general_purpose::STANDARD.encode(preview)
+    })
+}
+
+
+fn placeholder_preview() -> Result<Vec<u8>, String> {
+    let (width, height) = (420, 595);
+
+    let page = RgbImage::from_fn(width, height, |x, y| {
+        if x < 2 || y < 2 || x >= width - 2 || y >= height - 2 {
+            Rgb([200, 200, 200])
+        } else {
+            Rgb([255, 255, 255])
+        }
+    });
+
+    let mut bytes = Vec::new();
+
+    match DynamicImage::ImageRgb8(page).write_to(&mut std::io::Cursor::new(&mut bytes), ImageOutputFormat::Jpeg(80)) {
+        Ok(_) => Ok(bytes),
+        Err(_) => Err("Error creating the preview".to_string())
+    }
+}
\ No newline at end of file
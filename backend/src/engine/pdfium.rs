@@ -0,0 +1,201 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use async_trait::async_trait;
+use image::ImageOutputFormat;
+use tracing::{info, instrument};
+use pdfium_render::{prelude::{Pdfium, PdfiumError, PdfDocument, PdfDocumentMetadataTagType}, render_config::PdfRenderConfig};
+use tokio::sync::oneshot;
+use crate::config::PdfiumConfig;
+use crate::engine::{PdfEngine, PdfDocumentInfo};
+
+type Job = Box<dyn FnOnce(&Pdfium) + Send>;
+
+
+/// Engine backed by the pdfium library. The library is bound once and only used from a dedicated thread,
+/// which runs the jobs one after another, since pdfium must not be called concurrently.
+#[derive(Clone)]
+pub struct PdfiumEngine {
+    jobs: mpsc::Sender<Job>,
+    /// Jobs that were sent to the thread but haven't started yet
+    queued: Arc<AtomicUsize>
+}
+
+
+impl PdfiumEngine {
+
+    /// Binds the pdfium library and starts the thread of the engine. Fails if the library can't be loaded,
+    /// so a missing library is noticed when the backend starts and not on the first upload.
+    pub fn start(config: &PdfiumConfig) -> Result<PdfiumEngine, String> {
+        info!("Loading the pdfium library from '{}'", config.library_dir.display());
+
+        let library_path = config.library_dir.join(Pdfium::pdfium_platform_library_name()).to_string_lossy().into_owned();
+        let (jobs, received_jobs) = mpsc::channel::<Job>();
+        let (bound, bind_res) = mpsc::channel::<Result<(), String>>();
+
+        let spawn_res = thread::Builder::new()
+            .name("pdf-engine".to_string())
+            .spawn(move || {
+                let pdfium = match Pdfium::bind_to_library(&library_path) {
+                    Ok(bindings) => Pdfium::new(bindings),
+                    Err(PdfiumError::LoadLibraryError(err)) => {
+                        let _ = bound.send(Err(format!("Error loading the pdfium library: {}", err)));
+                        return;
+                    },
+                    Err(err) => {
+                        let _ = bound.send(Err(format!("Error loading the pdfium library '{}': {}", library_path, err)));
+                        return;
+                    }
+                };
+
+                let _ = bound.send(Ok(()));
+
+                // Runs until every handle of the engine is dropped
+                for job in received_jobs {
+                    job(&pdfium);
+                }
+            });
+
+        if spawn_res.is_err() {
+            return Err("Error starting the thread of the pdf engine".to_string());
+        }
+
+        match bind_res.recv() {
+            Ok(Ok(())) => Ok(PdfiumEngine { jobs, queued: Arc::new(AtomicUsize::new(0)) }),
+            Ok(Err(msg)) => Err(msg),
+            Err(_) => Err("The pdf engine stopped while loading the pdfium library".to_string())
+        }
+    }
+
+    /// Runs the job on the thread of the engine and waits for its result.
+    /// A panic inside pdfium only fails this job, the engine keeps running.
+    async fn run<T, F>(&self, job: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Pdfium) -> Result<T, String> + Send + 'static
+    {
+        let (result, received_result) = oneshot::channel();
+        let queued = self.queued.clone();
+
+        let job: Job = Box::new(move |pdfium| {
+            queued.fetch_sub(1, Ordering::Relaxed);
+
+            let job_res = match catch_unwind(AssertUnwindSafe(|| job(pdfium))) {
+                Ok(job_res) => job_res,
+                Err(_) => Err("The pdf engine failed on the pdf".to_string())
+            };
+            let _ = result.send(job_res);
+        });
+
+        self.queued.fetch_add(1, Ordering::Relaxed);
+
+        if self.jobs.send(job).is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            return Err("The pdf engine is not running".to_string());
+        }
+
+        match received_result.await {
+            Ok(job_res) => job_res,
+            Err(_) => Err("The pdf engine is not running".to_string())
+        }
+    }
+
+    /// Opens the pdf at the path and runs the job with it
+    async fn run_with_document<T, F>(&self, path: &str, job: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PdfDocument) -> Result<T, PdfiumError> + Send + 'static
+    {
+        let path = path.to_string();
+
+        self.run(move |pdfium| {
+            let document = match pdfium.load_pdf_from_file(&path, None) {
+                Ok(document) => document,
+                Err(err) => return Err(format!("Error reading pdf: {}", err))
+            };
+
+            match job(&document) {
+                Ok(result) => Ok(result),
+                Err(err) => Err(format!("Error reading pdf: {}", err))
+            }
+        })
+        .await
+    }
+
+}
+
+
+#[async_trait]
+impl PdfEngine for PdfiumEngine {
+
+    fn name(&self) -> &'static str {
+        "pdfium"
+    }
+
+    fn can_render(&self) -> bool {
+        true
+    }
+
+    #[instrument(skip_all)]
+    async fn load(&self, path: &str) -> Result<PdfDocumentInfo, String> {
+        self.run_with_document(path, |document| {
+            let title = document.metadata()
+                .get(PdfDocumentMetadataTagType::Title)
+                .map(|title| title.value().trim().to_string())
+                .filter(|title| !title.is_empty());
+
+            let author = document.metadata()
+                .get(PdfDocumentMetadataTagType::Author)
+                .map(|author| author.value().to_string());
+
+            Ok(PdfDocumentInfo { title, author, pages: document.pages().len() as i32 })
+        })
+        .await
+    }
+
+    #[instrument(skip_all)]
+    async fn render_page(&self, path: &str, page_index: u16, scale: f32) -> Result<Vec<u8>, String> {
+        self.run_with_document(path, move |document| {
+            let render_config = PdfRenderConfig::new()
+                .scale_page_by_factor(scale);
+
+            let rendered_image = document.pages().get(page_index)?.render_with_config(&render_config)?.as_image();
+
+            let mut bytes = Vec::new();
+            let _ = rendered_image.write_to(&mut std::io::Cursor::new(&mut bytes), ImageOutputFormat::Jpeg(80));
+
+            Ok(bytes)
+        })
+        .await
+    }
+
+    #[instrument(skip_all)]
+    async fn extract_text(&self, path: &str) -> Result<String, String> {
+        self.run_with_document(path, |document| {
+            let mut text = String::new();
+
+            for page in document.pages().iter() {
+                text.push_str(&page.text()?.all());
+                text.push('\n');
+            }
+
+            Ok(text)
+        })
+        .await
+    }
+
+    #[instrument(skip_all)]
+    async fn check(&self) -> Result<(), String> {
+        // Doesn't wait for the jobs in front of it, sending only fails once the thread has stopped
+        match self.jobs.send(Box::new(|_| ())) {
+            Ok(_) => Ok(()),
+            Err(_) => Err("The pdf engine is not running".to_string())
+        }
+    }
+
+    fn queued_jobs(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+}
\ No newline at end of file
@@ -1,5 +1,54 @@
+use crate::api::dto::pdf::PdfMetadataDto;
+
 #[derive(Debug)]
 pub enum PdfMetadataByIdError {
     NotFound(String),
     DatabaseError(String)
+}
+
+#[derive(Debug)]
+pub enum PdfUpdateError {
+    NotFound(String),
+    /// The user may see the pdf, but not make this change
+    Forbidden(String),
+    /// The pdf was modified since the client read it, holds its current details
+    VersionMismatch(Box<PdfMetadataDto>),
+    DatabaseError(String)
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidCredentials(String),
+    InvalidInput(String),
+    AlreadyExists(String),
+    NotFound(String),
+    DatabaseError(String)
+}
+
+/// Errors of operations that only some of the users that can see a pdf may do
+#[derive(Debug)]
+pub enum PdfAccessError {
+    NotFound(String),
+    Forbidden(String),
+    InvalidInput(String),
+    DatabaseError(String)
+}
+
+/// Errors of opening a public share link
+#[derive(Debug)]
+pub enum ShareLinkError {
+    NotFound(String),
+    /// The link was revoked, has expired or all of its downloads are used up
+    Gone(String),
+    /// The link is password protected and no or a wrong password was given
+    PasswordRequired(String),
+    DatabaseError(String)
+}
+
+/// Errors of restoring an archive of the library
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// The archive can't be read or its manifest is missing or of an unsupported version
+    InvalidArchive(String),
+    DatabaseError(String)
 }
\ No newline at end of file
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use actix_web::web::{self, Data};
+use chrono::Utc;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, instrument, warn};
+
+use crate::AppState;
+use crate::domain::service::auth::AuthService;
+use crate::util::{folder_tags, import_pdf};
+
+/// Folder in the inbox the imported files are moved to
+pub const PROCESSED_DIR: &str = "processed";
+
+/// Folder in the inbox the files that couldn't be imported are moved to, each with a report of the error
+pub const FAILED_DIR: &str = "failed";
+
+/// Appended to the name of a failed file to get the name of its error report
+pub const ERROR_REPORT_SUFFIX: &str = ".error.txt";
+
+
+/// What a scan of the inbox did
+#[derive(Debug, Default, PartialEq)]
+pub struct InboxScan {
+    pub imported: usize,
+    pub failed: usize,
+    /// Files that are still being written, they are imported by a later scan
+    pub pending: usize
+}
+
+/// The inbox is watched until it is stopped or dropped
+pub struct InboxWatcher {
+    watcher: RecommendedWatcher,
+    wake: mpsc::UnboundedSender<()>,
+    stopping: Arc<AtomicBool>,
+    task: JoinHandle<()>
+}
+
+
+impl InboxWatcher {
+
+    /// Stops watching the inbox and waits for the scan in progress to finish the file it is importing.
+    /// The files it didn't get to are left in the inbox for the next start.
+    pub async fn stop(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let _ = self.wake.send(());
+        drop(self.watcher);
+
+        if self.task.await.is_err() {
+            error!("The inbox scan ended with a panic");
+        }
+    }
+
+}
+
+
+/// Starts watching the inbox, if one is configured. The inbox is scanned right away, whenever the file system
+/// reports a change in it and every `inbox.scan_interval` seconds.
+pub async fn start(state: Data<AppState>) -> Result<Option<InboxWatcher>, String> {
+    let Some(dir) = state.config.inbox.dir.clone() else {
+        return Ok(None);
+    };
+
+    for folder in [dir.join(PROCESSED_DIR), dir.join(FAILED_DIR)] {
+        if let Err(err) = tokio::fs::create_dir_all(&folder).await {
+            return Err(format!("Error creating the inbox folder '{}': {}", folder.display(), err));
+        }
+    }
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let wake = sender.clone();
+
+    let watcher_res = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if event.is_ok() {
+            let _ = sender.send(());
+        }
+    });
+
+    let mut watcher = match watcher_res {
+        Ok(watcher) => watcher,
+        Err(err) => return Err(format!("Error watching the inbox: {err}"))
+    };
+
+    if let Err(err) = watcher.watch(&dir, RecursiveMode::Recursive) {
+        return Err(format!("Error watching the inbox '{}': {}", dir.display(), err));
+    }
+
+    info!("Watching the inbox {}", dir.display());
+
+    let stopping = Arc::new(AtomicBool::new(false));
+    let scan_stopping = stopping.clone();
+
+    let task = actix_web::rt::spawn(async move {
+        let settle_time = Duration::from_secs(state.config.inbox.settle_time.max(1));
+        let scan_interval = Duration::from_secs(state.config.inbox.scan_interval);
+
+        let mut wait = Duration::ZERO;
+
+        loop {
+            // Without a sender the watcher was dropped and the inbox isn't watched anymore
+            if let Ok(None) = tokio::time::timeout(wait, receiver.recv()).await {
+                break;
+            }
+
+            if scan_stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            // The events that arrived in the meantime are handled by the same scan
+            while receiver.try_recv().is_ok() {}
+
+            wait = match scan(&state, &dir, &scan_stopping).await {
+                Ok(scan) if scan.pending > 0 => settle_time,
+                Ok(_) => scan_interval,
+                Err(msg) => {
+                    error!("Error scanning the inbox: {msg}");
+                    scan_interval
+                }
+            };
+        }
+    });
+
+    Ok(Some(InboxWatcher { watcher, wake, stopping, task }))
+}
+
+
+/// Imports the pdfs in the inbox that weren't modified for `inbox.settle_time` seconds and moves them to the
+/// processed folder, or to the failed folder with an error report. Other files are left in place, as are the
+/// remaining pdfs once `stopping` is set.
+#[instrument(skip_all)]
+pub async fn scan(state: &AppState, dir: &Path, stopping: &AtomicBool) -> Result<InboxScan, String> {
+    let inbox = &state.config.inbox;
+
+    // The inbox may be a slow network share, it is read outside of the async workers
+    let inbox_dir = dir.to_path_buf();
+    let files_res = web::block(move || {
+        let mut files = Vec::new();
+        collect_files(&inbox_dir, &inbox_dir, &mut files)?;
+        files.sort();
+        Ok::<Vec<PathBuf>, String>(files)
+    }).await;
+
+    let files = match files_res {
+        Ok(files) => files?,
+        Err(_) => return Err("Error reading the inbox".to_string())
+    };
+
+    let mut result = InboxScan::default();
+
+    if files.is_empty() {
+        return Ok(result);
+    }
+
+    // Without the owner nothing can be imported, the files are left for when the configuration is fixed
+    let owner = state.auth_service.find_user_or_admin(inbox.owner.as_deref()).await?;
+
+    let settle_time = Duration::from_secs(inbox.settle_time);
+
+    for path in files {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let is_settled = tokio::fs::metadata(&path).await
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() >= settle_time);
+
+        if !is_settled {
+            result.pending += 1;
+            continue;
+        }
+
+        let tags = if inbox.tags_from_folders { folder_tags(dir, &path) } else { Vec::new() };
+
+        match import_pdf(state.engine.as_ref(), &state.service, &state.config.storage, &path, tags, &owner.id).await {
+            Ok(pdf_id) => {
+                info!("Imported '{}' from the inbox as {}", path.display(), pdf_id);
+                result.imported += 1;
+
+                if let Err(msg) = move_to(dir, PROCESSED_DIR, &path).await {
+                    error!("{msg}");
+                }
+            },
+            Err(msg) => {
+                warn!("Error importing '{}' from the inbox: {}", path.display(), msg);
+                result.failed += 1;
+
+                match move_to(dir, FAILED_DIR, &path).await {
+                    Ok(failed_path) => write_error_report(dir, &path, &failed_path, &msg).await,
+                    Err(msg) => error!("{msg}")
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+
+/// The pdfs below the directory, without the processed and failed folders and hidden files like the
+/// partial downloads of sync clients
+fn collect_files(inbox: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return Err(format!("Error reading '{}': {}", dir.display(), err))
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if name.starts_with('.') || (dir == inbox && (name == PROCESSED_DIR || name == FAILED_DIR)) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(inbox, &path, files)?;
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pdf")) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Moves the file to the same subfolder of the given folder of the inbox. A number is added to its name
+/// when the folder has a file with the same name already. Returns the new path of the file.
+async fn move_to(inbox: &Path, folder: &'static str, path: &Path) -> Result<PathBuf, String> {
+    let (inbox, source) = (inbox.to_path_buf(), path.to_path_buf());
+
+    match web::block(move || move_file(&inbox, folder, &source)).await {
+        Ok(move_res) => move_res,
+        Err(_) => Err(format!("Error moving '{}'", path.display()))
+    }
+}
+
+
+fn move_file(inbox: &Path, folder: &str, path: &Path) -> Result<PathBuf, String> {
+    let relative = path.strip_prefix(inbox).unwrap_or(path);
+    let mut target = inbox.join(folder).join(relative);
+
+    if let Some(parent) = target.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            return Err(format!("Error creating '{}': {}", parent.display(), err));
+        }
+    }
+
+    let stem = target.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = target.extension().unwrap_or_default().to_string_lossy().to_string();
+
+    let mut number = 1;
+    while target.exists() {
+        target.set_file_name(format!("{stem} ({number}).{extension}"));
+        number += 1;
+    }
+
+    match std::fs::rename(path, &target) {
+        Ok(_) => Ok(target),
+        Err(err) => Err(format!("Error moving '{}' to '{}': {}", path.display(), target.display(), err))
+    }
+}
+
+
+async fn write_error_report(inbox: &Path, path: &Path, failed_path: &Path, msg: &str) {
+    let mut report_path = failed_path.as_os_str().to_owned();
+    report_path.push(ERROR_REPORT_SUFFIX);
+
+    let report = format!(
+        "File: {}\nTime: {}\nError: {}\n",
+        path.strip_prefix(inbox).unwrap_or(path).display(),
+        Utc::now().to_rfc3339(),
+        msg
+    );
+
+    if let Err(err) = tokio::fs::write(&report_path, report).await {
+        error!("Error writing the error report '{}': {}", PathBuf::from(report_path).display(), err);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+use actix_multipart::form::MultipartFormConfig;
+use actix_multipart::form::tempfile::TempFileConfig;
+use actix_web::{web::Data, middleware, middleware::from_fn, web};
+use dav_server::{DavHandler, fakels::FakeLs};
+use utoipa::OpenApi;
+use utoipa_redoc::{Redoc, Servable};
+
+use crate::api::controllers::audit_handler::get_audit_log;
+use crate::api::controllers::auth_handler::{login, logout, me, change_password};
+use crate::api::controllers::fsck_handler::fsck;
+use crate::api::controllers::archive_handler::{export_archive, import_archive};
+use crate::api::controllers::health_handler::{health, live, ready};
+use crate::api::controllers::metrics_handler::metrics;
+use crate::api::controllers::token_handler::{get_tokens, create_token, delete_token};
+use crate::api::controllers::user_handler::{get_users, create_user, delete_user};
+use crate::api::controllers::group_handler::{get_groups, create_group, delete_group, add_group_member, remove_group_member};
+use crate::api::controllers::share_handler::{get_shares, share, unshare};
+use crate::api::controllers::share_link_handler::{get_share_links, create_share_link, revoke_share_link, get_share_link_accesses, open_share_link, download_share_link};
+use crate::api::middleware::auth::{require_user, require_dav_user, require_admin};
+use crate::api::controllers::webdav_handler::{dav, WEBDAV_PREFIX};
+use crate::api::openapi::{ApiDoc, openapi_json};
+use crate::api::controllers::pdf_handler::{get_all, get_by_id, get_content_by_id, get_metadata_by_id, search, update, patch, delete, bulk, upload};
+use crate::service::pdf::PdfServiceImpl;
+use crate::service::auth::AuthServiceImpl;
+use crate::service::audit::AuditServiceImpl;
+use crate::service::health::HealthServiceImpl;
+use crate::service::fsck::FsckServiceImpl;
+use crate::service::archive::ArchiveServiceImpl;
+use crate::config::Config;
+use crate::engine::PdfEngine;
+use crate::repository::db::Database;
+
+pub mod api;
+pub mod cli;
+pub mod config;
+pub mod engine;
+pub mod inbox;
+pub mod util;
+pub mod repository;
+pub mod domain;
+pub mod service;
+pub mod errors;
+pub mod metrics;
+pub mod telemetry;
+
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub engine: Arc<dyn PdfEngine>,
+    pub service: PdfServiceImpl,
+    pub auth_service: AuthServiceImpl,
+    pub audit_service: AuditServiceImpl,
+    pub health_service: HealthServiceImpl,
+    pub fsck_service: FsckServiceImpl,
+    pub archive_service: ArchiveServiceImpl
+}
+
+
+impl AppState {
+
+    /// Services backed by the repositories of the database, shared by the server and the admin command line
+    pub fn new(config: Arc<Config>, database: &Database, engine: Arc<dyn PdfEngine>) -> AppState {
+        let storage = Arc::new(config.storage.clone());
+        let pdf_repository = database.pdf_repository();
+
+        AppState {
+            service: PdfServiceImpl { repository: pdf_repository.clone(), storage: storage.clone() },
+            auth_service: AuthServiceImpl { repository: database.user_repository(), storage: storage.clone() },
+            audit_service: AuditServiceImpl { repository: database.audit_repository() },
+            health_service: HealthServiceImpl { repository: database.health_repository(), engine: engine.clone(), config: config.clone() },
+            fsck_service: FsckServiceImpl { repository: pdf_repository.clone(), engine: engine.clone(), storage: storage.clone() },
+            archive_service: ArchiveServiceImpl {
+                repository: pdf_repository,
+                user_repository: database.user_repository(),
+                engine: engine.clone(),
+                storage
+            },
+            config,
+            engine
+        }
+    }
+
+}
+
+
+/// Registers the state and all routes of the backend, used by the server and by the tests alike
+pub fn configure(state: Data<AppState>) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        // The filesystem depends on the user and is set per request
+        let dav_handler: DavHandler = DavHandler::builder()
+            .locksystem(FakeLs::new())
+            .strip_prefix(WEBDAV_PREFIX)
+            .build_handler();
+
+        let multipart_config = MultipartFormConfig::default()
+        .memory_limit(state.config.storage.max_upload_size)
+        .total_limit(state.config.storage.max_upload_size);
+
+        let temp_file_config = TempFileConfig::default().directory(&state.config.storage.tmp_dir);
+
+        cfg
+            .app_data(multipart_config)
+            .app_data(temp_file_config)
+            .app_data(state)
+            .app_data(Data::new(dav_handler))
+            .route("/openapi.json", web::get().to(openapi_json))
+            .service(Redoc::with_url("/docs", ApiDoc::openapi()))
+            .service(
+                web::scope("/metrics")
+                    .wrap(from_fn(require_admin))
+                    .wrap(from_fn(require_user))
+                    .route("", web::get().to(metrics))
+            )
+            .service(
+                web::scope("/health")
+                    .route("", web::get().to(health))
+                    .route("/live", web::get().to(live))
+                    .route("/ready", web::get().to(ready))
+            )
+            .service(
+                web::scope("/api/v1")
+                    .route("/auth/login", web::post().to(login))
+                    .service(
+                        web::scope("/auth")
+                            .wrap(from_fn(require_user))
+                            .route("/logout", web::post().to(logout))
+                            .route("/me", web::get().to(me))
+                            .route("/password", web::put().to(change_password))
+                    )
+                    .service(
+                        web::scope("/tokens")
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(get_tokens))
+                            .route("", web::post().to(create_token))
+                            .route("/{token_id}", web::delete().to(delete_token))
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(require_admin))
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(get_users))
+                            .route("", web::post().to(create_user))
+                            .route("/{user_id}", web::delete().to(delete_user))
+                    )
+                    .service(
+                        web::scope("/groups")
+                            .wrap(from_fn(require_admin))
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(get_groups))
+                            .route("", web::post().to(create_group))
+                            .route("/{group_id}", web::delete().to(delete_group))
+                            .route("/{group_id}/members/{user_id}", web::put().to(add_group_member))
+                            .route("/{group_id}/members/{user_id}", web::delete().to(remove_group_member))
+                    )
+                    .service(
+                        web::scope("/audit")
+                            .wrap(from_fn(require_admin))
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(get_audit_log))
+                    )
+                    .service(
+                        web::scope("/fsck")
+                            .wrap(from_fn(require_admin))
+                            .wrap(from_fn(require_user))
+                            .route("", web::post().to(fsck))
+                    )
+                    .service(
+                        web::scope("/archive")
+                            .wrap(from_fn(require_admin))
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(export_archive))
+                            .route("", web::post().to(import_archive))
+                    )
+                    .service(
+                        web::scope("/documents")
+                            .wrap(from_fn(require_user))
+                            .route("", web::get().to(get_all))
+                            .route("", web::post().to(upload))
+                            .route("/search", web::get().to(search))
+                            .route("/bulk", web::post().to(bulk))
+                            .route("/{pdf_id}", web::get().to(get_metadata_by_id))
+                            .route("/{pdf_id}", web::put().to(update))
+                            .route("/{pdf_id}", web::patch().to(patch))
+                            .route("/{pdf_id}", web::delete().to(delete))
+                            .route("/{pdf_id}/content", web::get().to(get_content_by_id))
+                            .route("/{pdf_id}/permissions", web::get().to(get_shares))
+                            .route("/{pdf_id}/permissions", web::put().to(share))
+                            .route("/{pdf_id}/permissions", web::delete().to(unshare))
+                            .route("/{pdf_id}/shares", web::get().to(get_share_links))
+                            .route("/{pdf_id}/shares", web::post().to(create_share_link))
+                            .route("/{pdf_id}/shares/{share_link_id}", web::delete().to(revoke_share_link))
+                            .route("/{pdf_id}/shares/{share_link_id}/accesses", web::get().to(get_share_link_accesses))
+                    )
+            )
+            // Deprecated aliases of the /api/v1 routes, kept for existing clients
+            .service(
+                web::scope("/pdfs")
+                    .wrap(from_fn(require_user))
+                    .wrap(
+                        middleware::DefaultHeaders::new()
+                            .add(("Deprecation", "true"))
+                            .add(("Link", "</api/v1/documents>; rel=\"successor-version\""))
+                    )
+                    .route("", web::get().to(get_all))
+                    .route("/search", web::get().to(search))
+                    .route("/bulk", web::post().to(bulk))
+                    .route("/{pdf_id}", web::get().to(get_by_id))
+                    .route("/metadata/{pdf_id}", web::get().to(get_metadata_by_id))
+                    .route("/{pdf_id}", web::put().to(update))
+                    .route("/{pdf_id}", web::delete().to(delete))
+                    .route("/upload", web::post().to(upload))
+                    .route("/{pdf_id}/shares", web::get().to(get_share_links))
+                    .route("/{pdf_id}/shares", web::post().to(create_share_link))
+                    .route("/{pdf_id}/shares/{share_link_id}", web::delete().to(revoke_share_link))
+            )
+            // Public share links, opened without logging in
+            .service(
+                web::scope("/s")
+                    .route("/{token}", web::get().to(open_share_link))
+                    .route("/{token}", web::post().to(download_share_link))
+            )
+            .service(
+                web::resource([WEBDAV_PREFIX, "/dav/{tail:.*}"])
+                    .wrap(from_fn(require_dav_user))
+                    .to(dav)
+            );
+    }
+}
@@ -1,85 +1,155 @@
 use std::io::Result;
 use std::sync::Arc;
 use actix_cors::Cors;
-use actix_multipart::form::MultipartFormConfig;
-use actix_multipart::form::tempfile::TempFileConfig;
-use actix_web::{HttpServer, App, web::Data, middleware, web};
-use env_logger::{init_from_env, Env};
-use service::pdf::PdfServiceImpl;
-use std::env;
-use log::info;
-
-use crate::api::controllers::health_handler::health;
-use crate::api::controllers::pdf_handler::{get_all, get_by_id, get_metadata_by_id, search, update, delete, upload};
-use crate::repository::pdfs::PdfRepositoryImpl;
-
-pub mod api;
-pub mod util;
-pub mod repository;
-pub mod domain;
-pub mod service;
-pub mod errors;
-
-pub struct AppState {
-    service: PdfServiceImpl
-}
+use actix_web::{HttpServer, App, web::Data, middleware, middleware::from_fn};
+use clap::Parser;
+use tracing::{info, error};
+
+use pdf_store::{cli, configure, engine, inbox, telemetry, util, AppState};
+use pdf_store::api::middleware::metrics::record_metrics;
+use pdf_store::api::middleware::request_id::request_id;
+use pdf_store::repository::db::Database;
+use pdf_store::domain::service::auth::AuthService;
+use pdf_store::config::{Args, Config, LoggingConfig};
 
 #[actix_web::main]
 async fn main() -> Result<()> {
 
     dotenv::dotenv().ok();
 
-    init_from_env(Env::new().default_filter_or("info"));
+    let args = Args::parse();
 
-    let database_connection = repository::db::connect()
-        .await
-        .expect("Error connecting to database");
+    let config = match Config::load(&args.settings) {
+        Ok(config) => Arc::new(config),
+        Err(msg) => {
+            // Without a valid configuration the errors are logged as text
+            let _ = telemetry::init(&LoggingConfig::default());
+            error!("{msg}");
+            std::process::exit(2);
+        }
+    };
 
-    let backend_url = env::var("BACKEND_URL").unwrap_or("127.0.0.1".to_string());
-    let backend_port = env::var("BACKEND_PORT").unwrap_or("8080".to_string());
+    let telemetry = match telemetry::init(&config.logging) {
+        Ok(telemetry) => telemetry,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
 
-    std::fs::create_dir_all("./tmp")?;
+    if args.print_config {
+        match config.to_redacted_toml() {
+            Ok(toml) => print!("{toml}"),
+            Err(msg) => {
+                error!("{msg}");
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
 
-    info!("Starting HTTP Server at http://{backend_url}:{backend_port}");
-    
-    HttpServer::new(move || {
-        let cors = Cors::permissive();
+    if let Some(data_dir) = &config.storage.data_dir {
+        std::fs::create_dir_all(data_dir)?;
+    }
+
+    let database_connection = match Database::connect(&config.database).await {
+        Ok(database_connection) => database_connection,
+        Err(err) => {
+            error!("Error connecting to the database: {err}");
+            // `--check-migrations` reports that the check itself failed
+            std::process::exit(if args.check_migrations { 2 } else { 1 });
+        }
+    };
+
+    // `--check-migrations` and `--migrate-only` are meant for deployment pipelines and exit afterwards
+    if args.check_migrations {
+        std::process::exit(cli::check_migrations(&database_connection).await);
+    }
+
+    if let Err(msg) = database_connection.migrate().await {
+        error!("{msg}");
+        std::process::exit(1);
+    }
+
+    if args.migrate_only {
+        info!("The database schema is up to date");
+        return Ok(());
+    }
+
+    let engine = match engine::start(&config) {
+        Ok(engine) => engine,
+        Err(msg) => {
+            error!("{msg}");
+            error!("The pdfium library can be downloaded from https://github.com/bblanchon/pdfium-binaries/releases, its directory is set with pdfium.library_dir (PDFIUM_LIBRARY_DIR)");
+            std::process::exit(1);
+        }
+    };
+
+    std::fs::create_dir_all(&config.storage.upload_dir)?;
+    std::fs::create_dir_all(&config.storage.tmp_dir)?;
 
-        let pdf_repository = PdfRepositoryImpl {
-            pool: Arc::new(database_connection.clone())
-        };
+    let state = Data::new(AppState::new(config.clone(), &database_connection, engine));
 
-        let pdf_service = PdfServiceImpl {
-            repository: Arc::new(pdf_repository)
-        };
+    let swept = util::sweep_stale_files(&config.storage, state.service.repository.as_ref()).await;
+    if swept > 0 {
+        info!("Removed stale files left over from interrupted uploads: {swept}");
+    }
 
-        let multipart_config = MultipartFormConfig::default()
-        .memory_limit(1073741824 * 5)
-        .total_limit(1073741824 * 5);
+    if let Err(msg) = state.auth_service.bootstrap_admin(&config.admin).await {
+        error!("Error creating the admin account: {msg}");
+        std::process::exit(1);
+    }
+
+    let inbox_watcher = match inbox::start(state.clone()).await {
+        Ok(inbox_watcher) => inbox_watcher,
+        Err(msg) => {
+            error!("{msg}");
+            std::process::exit(1);
+        }
+    };
+
+    let backend_url = config.server.host.clone();
+    let backend_port = config.server.port;
+    let workers = config.server.workers;
+    let shutdown_timeout = config.server.shutdown_timeout;
+
+    info!("Starting HTTP Server at http://{backend_url}:{backend_port}");
+    
+    let server_res = HttpServer::new(move || {
+        // The session cookie is only sent along by the frontends listed here
+        let cors = config.server.cors_allowed_origins.iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allow_any_method()
+            .allow_any_header()
+            .expose_any_header()
+            .supports_credentials()
+            .max_age(3600);
 
         App::new()
-            .wrap(middleware::Logger::new("%a \"%r\" Status: %s (Req size: %{Content-Length}i) (Time: %T) \"%{Referer}i\""))
+            .wrap(from_fn(request_id))
+            .wrap(from_fn(record_metrics))
+            .wrap(middleware::Logger::new("%a \"%r\" Status: %s (Req size: %{Content-Length}i) (Time: %T) \"%{Referer}i\" (Request ID: %{X-Request-Id}o)"))
             .wrap(cors)
-            .app_data(multipart_config)
-            .app_data(Data::new(AppState {service: pdf_service.clone()}))
-            .app_data(TempFileConfig::default().directory("./tmp"))
-            .service(
-                web::scope("/health")
-                    .route("", web::get().to(health))
-            )
-            .service(
-                web::scope("/pdfs")
-                    .route("", web::get().to(get_all))
-                    .route("/search", web::get().to(search))
-                    .route("/{pdf_id}", web::get().to(get_by_id))
-                    .route("/metadata/{pdf_id}", web::get().to(get_metadata_by_id))
-                    .route("/{pdf_id}", web::put().to(update))       
-                    .route("/{pdf_id}", web::delete().to(delete))
-                    .route("/upload", web::post().to(upload))
-            )
+            .configure(configure(state.clone()))
     })
-    .bind(("127.0.0.1", 8080))?
-    .workers(3)
+    .bind((backend_url.as_str(), backend_port))?
+    .workers(workers)
+    // On SIGINT or SIGTERM no new connections are accepted and the requests in progress, uploads included,
+    // get this long to finish before the workers are stopped
+    .shutdown_timeout(shutdown_timeout)
     .run()
-    .await
+    .await;
+
+    info!("Stopped the HTTP server, closing the database connections");
+
+    // The pdf being imported from the inbox still needs the database
+    if let Some(inbox_watcher) = inbox_watcher {
+        inbox_watcher.stop().await;
+    }
+
+    database_connection.close().await;
+    telemetry.shutdown();
+
+    server_res
 }
+
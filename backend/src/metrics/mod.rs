@@ -0,0 +1,174 @@
+use std::sync::LazyLock;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+
+/// Every metric of the backend starts with it
+const NAMESPACE: &str = "pdfstore";
+
+/// Buckets of the request latency in seconds, most requests take milliseconds while uploads may take minutes
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Buckets of the durations of the pdf engine in seconds
+const ENGINE_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+
+/// The metrics of the process, exported at `/metrics`. The counters are shared by everything that runs in the
+/// process, the gauges describe the backend that was scraped last and are set right before they are exported.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+
+/// Why a received pdf wasn't stored
+#[derive(Debug, Clone, Copy)]
+pub enum UploadRejection {
+    /// Sent without the content type of a pdf
+    NotPdf,
+    /// The file couldn't be read or moved to the upload directory
+    Storage,
+    /// The pdf engine couldn't read the pdf
+    Unreadable,
+    /// The database didn't accept the pdf, e.g. because the user has a pdf with the same file name
+    Database
+}
+
+impl UploadRejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadRejection::NotPdf => "not_pdf",
+            UploadRejection::Storage => "storage",
+            UploadRejection::Unreadable => "unreadable",
+            UploadRejection::Database => "database"
+        }
+    }
+}
+
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    uploads_accepted: IntCounter,
+    uploads_rejected: IntCounterVec,
+    uploads_duplicate: IntCounter,
+    pdf_engine_duration: HistogramVec,
+    pub pdf_engine_queued_jobs: IntGauge,
+    pub library_documents: IntGauge,
+    pub library_pages: IntGauge,
+    pub library_bytes: IntGauge,
+    /// Open connections of the pool by `state`, `idle` or `in_use`
+    pub db_pool_connections: IntGaugeVec,
+    pub db_pool_max_connections: IntGauge
+}
+
+
+impl Metrics {
+
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None).expect("The metrics namespace is valid");
+
+        let metrics = Metrics {
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "Handled HTTP requests by method, route and status"),
+                &["method", "route", "status"]
+            ).expect("The metric is valid"),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "Time until the response of HTTP requests by method, route and status")
+                    .buckets(LATENCY_BUCKETS.to_vec()),
+                &["method", "route", "status"]
+            ).expect("The metric is valid"),
+            uploads_accepted: IntCounter::new("uploads_accepted_total", "Uploaded pdfs that were stored").expect("The metric is valid"),
+            uploads_rejected: IntCounterVec::new(
+                Opts::new("uploads_rejected_total", "Uploaded files that weren't stored by the reason"),
+                &["reason"]
+            ).expect("The metric is valid"),
+            uploads_duplicate: IntCounter::new(
+                "uploads_duplicate_total",
+                "Uploaded pdfs whose content was stored already, so no new file was written"
+            ).expect("The metric is valid"),
+            pdf_engine_duration: HistogramVec::new(
+                HistogramOpts::new("pdf_engine_duration_seconds", "Time the pdf engine took to load or render an uploaded pdf")
+                    .buckets(ENGINE_BUCKETS.to_vec()),
+                &["engine", "operation"]
+            ).expect("The metric is valid"),
+            pdf_engine_queued_jobs: IntGauge::new("pdf_engine_queued_jobs", "Jobs waiting for the pdf engine").expect("The metric is valid"),
+            library_documents: IntGauge::new("library_documents", "Stored pdfs of all users").expect("The metric is valid"),
+            library_pages: IntGauge::new("library_pages", "Pages of all stored pdfs").expect("The metric is valid"),
+            library_bytes: IntGauge::new(
+                "library_bytes",
+                "Size of the files in the upload directory, pdfs with the same content are stored once"
+            ).expect("The metric is valid"),
+            db_pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Open connections of the database pool by state"),
+                &["state"]
+            ).expect("The metric is valid"),
+            db_pool_max_connections: IntGauge::new("db_pool_max_connections", "Connections the database pool may open")
+                .expect("The metric is valid"),
+            registry
+        };
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_request_duration.clone()),
+            Box::new(metrics.uploads_accepted.clone()),
+            Box::new(metrics.uploads_rejected.clone()),
+            Box::new(metrics.uploads_duplicate.clone()),
+            Box::new(metrics.pdf_engine_duration.clone()),
+            Box::new(metrics.pdf_engine_queued_jobs.clone()),
+            Box::new(metrics.library_documents.clone()),
+            Box::new(metrics.library_pages.clone()),
+            Box::new(metrics.library_bytes.clone()),
+            Box::new(metrics.db_pool_connections.clone()),
+            Box::new(metrics.db_pool_max_connections.clone())
+        ];
+
+        for collector in collectors {
+            metrics.registry.register(collector).expect("Every metric is registered once");
+        }
+
+        metrics
+    }
+
+
+    pub fn record_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration.with_label_values(&labels).observe(seconds);
+    }
+
+
+    pub fn record_upload_accepted(&self) {
+        self.uploads_accepted.inc();
+    }
+
+
+    pub fn record_upload_rejected(&self, rejection: UploadRejection) {
+        self.uploads_rejected.with_label_values(&[rejection.as_str()]).inc();
+    }
+
+
+    pub fn record_upload_duplicate(&self) {
+        self.uploads_duplicate.inc();
+    }
+
+
+    /// `operation` is `load` or `render`
+    pub fn record_pdf_engine(&self, engine: &str, operation: &str, seconds: f64) {
+        self.pdf_engine_duration.with_label_values(&[engine, operation]).observe(seconds);
+    }
+
+
+    /// All metrics in the text format of Prometheus
+    pub fn export(&self) -> Result<String, String> {
+        let mut buffer = Vec::new();
+
+        if TextEncoder::new().encode(&self.registry.gather(), &mut buffer).is_err() {
+            return Err("Error encoding the metrics".to_string());
+        }
+
+        match String::from_utf8(buffer) {
+            Ok(text) => Ok(text),
+            Err(_) => Err("Error encoding the metrics".to_string())
+        }
+    }
+
+}
\ No newline at end of file
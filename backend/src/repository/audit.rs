@@ -0,0 +1,193 @@
+use std::{sync::Arc, collections::{BTreeSet, HashMap}};
+
+use tracing::instrument;
+use serde_json::{json, Map, Value};
+use sqlx::{Pool, Postgres, PgConnection};
+use uuid::Uuid;
+
+use crate::api::dto::audit::AuditLogSearchDto;
+use crate::domain::models::audit::{AuditAction, AuditEntry};
+use crate::domain::repository::audit::AuditRepository;
+use crate::repository::logged;
+
+use async_trait::async_trait;
+
+
+pub struct AuditRepositoryImpl {
+    pub pool: Arc<Pool<Postgres>>
+}
+
+impl AuditRepositoryImpl {
+
+    /// Details of the pdfs as they are recorded in the audit log, the preview picture is only recorded as hash
+    #[instrument(skip_all)]
+    pub async fn pdf_snapshots_with_connection(pdf_ids: &[Uuid], conn: &mut PgConnection) -> Result<HashMap<Uuid, Value>, sqlx::Error> {
+        let snapshots = sqlx::query!(
+            "SELECT id, jsonb_build_object(
+                'title', title,
+                'file_name', file_name,
+                'author', author,
+                'pages', pages,
+                'comments', comments,
+                'picture', md5(picture),
+                'owner_id', owner_id,
+                'storage_key', storage_key,
+                'file_missing', file_missing,
+                'tags', COALESCE((SELECT jsonb_agg(name ORDER BY name) FROM tags_to_pdfs WHERE tags_to_pdfs.id = pdfs.id), '[]'::jsonb),
+                'shares', COALESCE((
+                    SELECT jsonb_object_agg(COALESCE('user:' || users.username, 'group:' || groups.name), pdf_shares.permission)
+                    FROM pdf_shares LEFT JOIN users ON users.id = pdf_shares.user_id LEFT JOIN groups ON groups.id = pdf_shares.group_id
+                    WHERE pdf_shares.pdf_id = pdfs.id
+                ), '{}'::jsonb)
+            ) AS \"snapshot!\"
+            FROM pdfs WHERE id = ANY($1)",
+            pdf_ids
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(snapshots.into_iter().map(|record| (record.id, record.snapshot)).collect())
+    }
+
+
+    /// Records one entry per pdf whose snapshot changed, pdfs without a snapshot before were created and pdfs
+    /// without a snapshot afterwards were deleted
+    #[instrument(skip_all)]
+    pub async fn record_changes_with_connection(
+        actor_id: &Uuid,
+        action: AuditAction,
+        before: &HashMap<Uuid, Value>,
+        after: &HashMap<Uuid, Value>,
+        conn: &mut PgConnection
+    ) -> Result<(), sqlx::Error> {
+        let pdf_ids: BTreeSet<&Uuid> = before.keys().chain(after.keys()).collect();
+
+        let (target_ids, changes): (Vec<Uuid>, Vec<Value>) = pdf_ids.into_iter()
+            .map(|pdf_id| (*pdf_id, diff(before.get(pdf_id), after.get(pdf_id))))
+            .filter(|(_, changes)| !changes.is_empty())
+            .map(|(pdf_id, changes)| (pdf_id, Value::Object(changes)))
+            .unzip();
+
+        if target_ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "INSERT INTO audit_log (actor_id, actor_name, action, target_id, changes)
+            SELECT $1, (SELECT username FROM users WHERE id = $1), $2, changed.target_id, changed.changes
+            FROM UNNEST($3::UUID[], $4::JSONB[]) AS changed(target_id, changes)",
+            actor_id,
+            action.as_str(),
+            &target_ids[..],
+            &changes[..]
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    pub async fn record_with_connection(
+        actor_id: &Uuid,
+        action: AuditAction,
+        target_id: Option<&Uuid>,
+        changes: Value,
+        conn: &mut PgConnection
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO audit_log (actor_id, actor_name, action, target_id, changes)
+            VALUES ($1, (SELECT username FROM users WHERE id = $1), $2, $3, $4)",
+            actor_id,
+            action.as_str(),
+            target_id,
+            changes
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+
+/// The fields that differ between both snapshots as `{"field": {"old": ..., "new": ...}}`,
+/// a missing snapshot counts as all of its fields being null
+pub fn diff(old: Option<&Value>, new: Option<&Value>) -> Map<String, Value> {
+    let empty = Map::new();
+    let old = old.and_then(Value::as_object).unwrap_or(&empty);
+    let new = new.and_then(Value::as_object).unwrap_or(&empty);
+
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    fields.into_iter()
+        .filter_map(|field| {
+            let old_value = old.get(field).unwrap_or(&Value::Null);
+            let new_value = new.get(field).unwrap_or(&Value::Null);
+
+            (old_value != new_value).then(|| (field.clone(), json!({ "old": old_value, "new": new_value })))
+        })
+        .collect()
+}
+
+
+#[async_trait]
+impl AuditRepository for AuditRepositoryImpl {
+
+    #[instrument(skip_all)]
+    async fn count_entries(&self, search: &AuditLogSearchDto) -> Result<i64, String> {
+        let count_res = sqlx::query_scalar!(
+            "SELECT count(*) FROM audit_log
+            WHERE ($1::TEXT IS NULL OR actor_name = $1)
+            AND ($2::TEXT IS NULL OR action = $2)
+            AND ($3::UUID IS NULL OR target_id = $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR time_created >= $4)
+            AND ($5::TIMESTAMPTZ IS NULL OR time_created < $5)",
+            search.actor,
+            search.action.map(|action| action.as_str()),
+            search.target_id,
+            search.from,
+            search.to
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match count_res {
+            Ok(count) => Ok(count.unwrap_or(0)),
+            Err(err) => Err(logged(err, "Error getting number of audit log entries".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_entries_paged(&self, search: &AuditLogSearchDto) -> Result<Vec<AuditEntry>, String> {
+        let size: i64 = search.size.unwrap() as i64;
+        let offset: i64 = size * (search.page.unwrap() - 1) as i64;
+
+        let entries_res = sqlx::query_as!(
+            AuditEntry,
+            "SELECT id, time_created, actor_id, actor_name, action, target_id, changes FROM audit_log
+            WHERE ($1::TEXT IS NULL OR actor_name = $1)
+            AND ($2::TEXT IS NULL OR action = $2)
+            AND ($3::UUID IS NULL OR target_id = $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR time_created >= $4)
+            AND ($5::TIMESTAMPTZ IS NULL OR time_created < $5)
+            ORDER BY id DESC LIMIT $6 OFFSET $7",
+            search.actor,
+            search.action.map(|action| action.as_str()),
+            search.target_id,
+            search.from,
+            search.to,
+            size,
+            offset
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match entries_res {
+            Ok(entries) => Ok(entries),
+            Err(err) => Err(logged(err, "Error retrieving audit log entries".to_string()))
+        }
+    }
+}
@@ -1,18 +1,235 @@
-use sqlx::{Pool, Postgres, Error, postgres::PgPoolOptions};
-use log::info;
-use std::env;
+use std::sync::Arc;
+use sqlx::{Pool, Postgres, Sqlite, Error, PgConnection, postgres::PgPoolOptions};
+use sqlx::migrate::{Migrate, Migration, Migrator};
+use tracing::{info, warn};
+use crate::config::DatabaseConfig;
+use crate::domain::repository::{audit::AuditRepository, health::HealthRepository, pdf::PdfRepository, user::UserRepository};
+use crate::repository::{audit::AuditRepositoryImpl, health::HealthRepositoryImpl, pdfs::PdfRepositoryImpl, users::UserRepositoryImpl, sqlite};
+use crate::repository::sqlite::{audit::SqliteAuditRepository, health::SqliteHealthRepository, pdfs::SqlitePdfRepository, users::SqliteUserRepository};
+use crate::repository::logged;
 
 
+/// The migrations below `backend/migrations`, embedded into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
 
-pub async fn connect() -> Result<Pool<Postgres>, Error> {
+
+/// The database the repositories are backed by, chosen by the scheme of `database.url`
+#[derive(Clone)]
+pub enum Database {
+    Postgres(Pool<Postgres>),
+    Sqlite(Pool<Sqlite>)
+}
+
+impl Database {
+
+    pub async fn connect(config: &DatabaseConfig) -> Result<Database, Error> {
+        if is_sqlite_url(&config.url) {
+            sqlite::connect(config).await.map(Database::Sqlite)
+        } else {
+            connect(config).await.map(Database::Postgres)
+        }
+    }
+
+
+    pub async fn migrate(&self) -> Result<(), String> {
+        match self {
+            Database::Postgres(pool) => migrate(pool).await,
+            Database::Sqlite(pool) => sqlite::migrate(pool).await
+        }
+    }
+
+
+    pub async fn pending_migrations(&self) -> Result<Vec<&'static Migration>, String> {
+        match self {
+            Database::Postgres(pool) => pending_migrations(pool).await,
+            Database::Sqlite(pool) => sqlite::pending_migrations(pool).await
+        }
+    }
+
+
+    /// Waits for the connections in use to be returned and closes all of them
+    pub async fn close(&self) {
+        match self {
+            Database::Postgres(pool) => pool.close().await,
+            Database::Sqlite(pool) => pool.close().await
+        }
+    }
+
+
+    pub fn pdf_repository(&self) -> Arc<dyn PdfRepository> {
+        match self {
+            Database::Postgres(pool) => Arc::new(PdfRepositoryImpl { pool: Arc::new(pool.clone()) }),
+            Database::Sqlite(pool) => Arc::new(SqlitePdfRepository { pool: Arc::new(pool.clone()) })
+        }
+    }
+
+
+    pub fn user_repository(&self) -> Arc<dyn UserRepository> {
+        match self {
+            Database::Postgres(pool) => Arc::new(UserRepositoryImpl { pool: Arc::new(pool.clone()) }),
+            Database::Sqlite(pool) => Arc::new(SqliteUserRepository { pool: Arc::new(pool.clone()) })
+        }
+    }
+
+
+    pub fn audit_repository(&self) -> Arc<dyn AuditRepository> {
+        match self {
+            Database::Postgres(pool) => Arc::new(AuditRepositoryImpl { pool: Arc::new(pool.clone()) }),
+            Database::Sqlite(pool) => Arc::new(SqliteAuditRepository { pool: Arc::new(pool.clone()) })
+        }
+    }
+
+
+    pub fn health_repository(&self) -> Arc<dyn HealthRepository> {
+        match self {
+            Database::Postgres(pool) => Arc::new(HealthRepositoryImpl { pool: Arc::new(pool.clone()) }),
+            Database::Sqlite(pool) => Arc::new(SqliteHealthRepository { pool: Arc::new(pool.clone()) })
+        }
+    }
+}
+
+
+pub fn is_sqlite_url(url: &str) -> bool {
+    url.starts_with("sqlite:")
+}
+
+
+pub async fn connect(config: &DatabaseConfig) -> Result<Pool<Postgres>, Error> {
     info!("Creating database connection pool");
 
-    let database_url = env::var("DATABASE_URL").expect("Environment variable DATABASE_URL must be set");
-    
     let pool = PgPoolOptions::new()
-    .max_connections(10)
-    .connect(&database_url)
+    .max_connections(config.max_connections)
+    .connect(&config.url)
     .await;
 
     return pool;
+}
+
+
+/// Applies the migrations the database is missing. The migrator holds an advisory lock while doing so,
+/// so instances that are started at the same time apply them one after another.
+pub async fn migrate(pool: &Pool<Postgres>) -> Result<(), String> {
+    info!("Applying database migrations");
+
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(err) => return Err(logged(err, "Error connecting to the database".to_string()))
+    };
+
+    if conn.lock().await.is_err() {
+        return Err("Error locking the database for the migrations".to_string());
+    }
+
+    let adopt_res = adopt_unversioned_schema(&mut conn).await;
+
+    if conn.unlock().await.is_err() {
+        return Err("Error unlocking the database after the migrations".to_string());
+    }
+
+    adopt_res?;
+
+    match MIGRATOR.run(pool).await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("Error applying the database migrations: {}", err))
+    }
+}
+
+
+/// Databases that were set up by hand from the former `schema.sql` have the schema of the first migration,
+/// but no record of it. The first migration is recorded as applied for them, the following ones upgrade them.
+async fn adopt_unversioned_schema(conn: &mut PgConnection) -> Result<(), String> {
+    let schema_res = sqlx::query_as::<_, (bool, bool, bool, bool, bool)>(
+        "SELECT to_regclass('_sqlx_migrations') IS NOT NULL, to_regclass('pdfs') IS NOT NULL, to_regclass('tags') IS NOT NULL,
+        to_regclass('tags_to_pdfs') IS NOT NULL, to_regclass('users') IS NOT NULL"
+    )
+    .fetch_one(&mut *conn)
+    .await;
+
+    let (versioned, has_pdfs, has_tags, has_tags_to_pdfs, has_users) = match schema_res {
+        Ok(schema) => schema,
+        Err(err) => return Err(logged(err, "Error inspecting the database schema".to_string()))
+    };
+
+    if versioned || !has_pdfs {
+        return Ok(());
+    }
+
+    if !has_tags || !has_tags_to_pdfs || has_users {
+        return Err("The database has neither the schema of the former schema.sql nor a record of its migrations".to_string());
+    }
+
+    let Some(initial) = MIGRATOR.iter().next() else {
+        return Ok(());
+    };
+
+    warn!("Recording migration {} as applied for the database that was set up without migrations", initial.version);
+
+    if conn.ensure_migrations_table().await.is_err() {
+        return Err("Error creating the migrations table".to_string());
+    }
+
+    let record_res = sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES ($1, $2, TRUE, $3, 0)"
+    )
+    .bind(initial.version)
+    .bind(initial.description.as_ref())
+    .bind(initial.checksum.as_ref())
+    .execute(&mut *conn)
+    .await;
+
+    match record_res {
+        Ok(_) => Ok(()),
+        Err(err) => Err(logged(err, "Error recording the initial migration".to_string()))
+    }
+}
+
+
+/// Compares the applied migrations with the embedded ones without changing the database.
+/// Returns the migrations that still have to be applied, or why the database can't be migrated.
+pub async fn pending_migrations(pool: &Pool<Postgres>) -> Result<Vec<&'static Migration>, String> {
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(err) => return Err(logged(err, "Error connecting to the database".to_string()))
+    };
+
+    let versioned = match sqlx::query_scalar::<_, bool>("SELECT to_regclass('_sqlx_migrations') IS NOT NULL").fetch_one(&mut *conn).await {
+        Ok(versioned) => versioned,
+        Err(err) => return Err(logged(err, "Error inspecting the database schema".to_string()))
+    };
+
+    if !versioned {
+        return Ok(MIGRATOR.iter().collect());
+    }
+
+    unapplied_migrations(&MIGRATOR, &mut *conn).await
+}
+
+
+/// Checks the migrations recorded in a database that is already versioned against the embedded ones of the migrator
+/// and returns the ones that haven't been applied yet
+pub async fn unapplied_migrations<C: Migrate>(migrator: &'static Migrator, conn: &mut C) -> Result<Vec<&'static Migration>, String> {
+    match conn.dirty_version().await {
+        Ok(None) => (),
+        Ok(Some(version)) => return Err(format!("Migration {} failed partway and has to be fixed by hand", version)),
+        Err(err) => return Err(format!("Error reading the applied migrations: {}", err))
+    }
+
+    let applied = match conn.list_applied_migrations().await {
+        Ok(applied) => applied,
+        Err(err) => return Err(format!("Error reading the applied migrations: {}", err))
+    };
+
+    for applied_migration in &applied {
+        match migrator.iter().find(|migration| migration.version == applied_migration.version) {
+            None => return Err(format!("Migration {} was applied by a newer version of the backend", applied_migration.version)),
+            Some(migration) if migration.checksum != applied_migration.checksum => {
+                return Err(format!("Migration {} was changed after it was applied", applied_migration.version));
+            },
+            Some(_) => ()
+        }
+    }
+
+    Ok(migrator.iter()
+        .filter(|migration| !applied.iter().any(|applied_migration| applied_migration.version == migration.version))
+        .collect())
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use tracing::instrument;
+use sqlx::{Pool, Postgres};
+
+use crate::domain::repository::health::{HealthRepository, PoolUsage};
+use crate::repository::db;
+
+use async_trait::async_trait;
+
+
+pub struct HealthRepositoryImpl {
+    pub pool: Arc<Pool<Postgres>>
+}
+
+
+#[async_trait]
+impl HealthRepository for HealthRepositoryImpl {
+
+    #[instrument(skip_all)]
+    async fn ping(&self) -> Result<(), String> {
+        match sqlx::query("SELECT 1").execute(self.pool.as_ref()).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("Error querying the database: {}", err))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn pending_migrations(&self) -> Result<usize, String> {
+        db::pending_migrations(&self.pool).await.map(|pending| pending.len())
+    }
+
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        Some(PoolUsage {
+            open: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections()
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use tracing::instrument;
+
+use crate::api::dto::audit::AuditLogSearchDto;
+use crate::domain::models::audit::AuditEntry;
+use crate::domain::repository::audit::AuditRepository;
+use crate::repository::memory::MemoryDatabase;
+
+use async_trait::async_trait;
+
+
+/// Reads the audit log that the other in-memory repositories write
+pub struct MemoryAuditRepository {
+    pub database: Arc<MemoryDatabase>
+}
+
+
+fn matches(entry: &AuditEntry, search: &AuditLogSearchDto) -> bool {
+    search.actor.as_ref().is_none_or(|actor| entry.actor_name.as_ref() == Some(actor))
+        && search.action.is_none_or(|action| entry.action == action.as_str())
+        && search.target_id.is_none_or(|target_id| entry.target_id == Some(target_id))
+        && search.from.is_none_or(|from| entry.time_created >= from)
+        && search.to.is_none_or(|to| entry.time_created < to)
+}
+
+
+#[async_trait]
+impl AuditRepository for MemoryAuditRepository {
+
+    #[instrument(skip_all)]
+    async fn count_entries(&self, search: &AuditLogSearchDto) -> Result<i64, String> {
+        let tables = self.database.lock()?;
+
+        Ok(tables.audit_log.iter().filter(|entry| matches(entry, search)).count() as i64)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_entries_paged(&self, search: &AuditLogSearchDto) -> Result<Vec<AuditEntry>, String> {
+        let size = search.size.unwrap().max(0) as usize;
+        let offset = size * (search.page.unwrap() - 1).max(0) as usize;
+
+        let tables = self.database.lock()?;
+
+        // The newest entries come first
+        Ok(tables.audit_log.iter()
+            .rev()
+            .filter(|entry| matches(entry, search))
+            .skip(offset)
+            .take(size)
+            .cloned()
+            .collect())
+    }
+
+}
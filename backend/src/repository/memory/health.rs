@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use tracing::instrument;
+
+use crate::domain::repository::health::{HealthRepository, PoolUsage};
+use crate::repository::memory::MemoryDatabase;
+
+use async_trait::async_trait;
+
+
+/// The in-memory database has no migrations, it is only unusable after a failed operation
+pub struct MemoryHealthRepository {
+    pub database: Arc<MemoryDatabase>
+}
+
+
+#[async_trait]
+impl HealthRepository for MemoryHealthRepository {
+
+    #[instrument(skip_all)]
+    async fn ping(&self) -> Result<(), String> {
+        self.database.lock().map(|_| ())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn pending_migrations(&self) -> Result<usize, String> {
+        Ok(0)
+    }
+
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        None
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::domain::models::audit::{AuditAction, AuditEntry};
+use crate::domain::models::pdf::Pdf;
+use crate::domain::models::share::{Access, Permission, ShareLink, ShareLinkAccess};
+use crate::domain::models::user::{ApiToken, User};
+use crate::repository::audit::diff;
+
+pub mod pdfs;
+pub mod users;
+pub mod audit;
+pub mod health;
+
+
+/// Keeps the tables of the database in memory, so the handlers can be tested without Postgres.
+/// Every repository operation holds the lock for its whole duration, which makes it a transaction of its own.
+#[derive(Default)]
+pub struct MemoryDatabase {
+    tables: Mutex<Tables>
+}
+
+impl MemoryDatabase {
+
+    pub fn new() -> Self {
+        MemoryDatabase::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Tables>, String> {
+        match self.tables.lock() {
+            Ok(tables) => Ok(tables),
+            Err(_) => Err("The in-memory database is unusable after a failed operation".to_string())
+        }
+    }
+
+}
+
+
+/// Rows of the tables, the vectors are in the order the rows were inserted in
+#[derive(Clone, Default)]
+struct Tables {
+    users: Vec<User>,
+    pdfs: Vec<Pdf>,
+    tags: BTreeSet<String>,
+    /// Pairs of tag name and pdf ID
+    tags_to_pdfs: Vec<(String, Uuid)>,
+    sessions: Vec<Session>,
+    api_tokens: Vec<ApiToken>,
+    groups: Vec<GroupRow>,
+    /// Pairs of group ID and user ID
+    group_members: Vec<(Uuid, Uuid)>,
+    pdf_shares: Vec<PdfShareRow>,
+    share_links: Vec<ShareLinkRow>,
+    share_link_accesses: Vec<(Uuid, ShareLinkAccess)>,
+    audit_log: Vec<AuditEntry>
+}
+
+#[derive(Clone)]
+struct Session {
+    token_hash: String,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>
+}
+
+#[derive(Clone)]
+struct GroupRow {
+    id: Uuid,
+    name: String,
+    time_created: DateTime<Utc>
+}
+
+/// Either the user or the group is set
+#[derive(Clone)]
+struct PdfShareRow {
+    pdf_id: Uuid,
+    user_id: Option<Uuid>,
+    group_id: Option<Uuid>,
+    permission: String
+}
+
+#[derive(Clone)]
+struct ShareLinkRow {
+    token_hash: String,
+    share_link: ShareLink
+}
+
+
+impl Tables {
+
+    fn user(&self, user_id: &Uuid) -> Option<&User> {
+        self.users.iter().find(|user| user.id == *user_id)
+    }
+
+    fn pdf(&self, pdf_id: &Uuid) -> Option<&Pdf> {
+        self.pdfs.iter().find(|pdf| pdf.id == Some(*pdf_id))
+    }
+
+    fn pdf_mut(&mut self, pdf_id: &Uuid) -> Option<&mut Pdf> {
+        self.pdfs.iter_mut().find(|pdf| pdf.id == Some(*pdf_id))
+    }
+
+    fn group_by_name(&self, name: &str) -> Option<&GroupRow> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Same as `pdf_access_level` in the database
+    fn access(&self, pdf_id: &Uuid, viewer: &Uuid) -> Access {
+        let Some(pdf) = self.pdf(pdf_id) else {
+            return Access::None;
+        };
+
+        if pdf.owner_id == *viewer {
+            return Access::Owner;
+        }
+
+        let groups: HashSet<Uuid> = self.group_members.iter()
+            .filter(|(_, user_id)| user_id == viewer)
+            .map(|(group_id, _)| *group_id)
+            .collect();
+
+        self.pdf_shares.iter()
+            .filter(|share| share.pdf_id == *pdf_id)
+            .filter(|share| share.user_id == Some(*viewer) || share.group_id.is_some_and(|group_id| groups.contains(&group_id)))
+            .filter_map(|share| share.permission.parse::<Permission>().ok())
+            .map(Access::from)
+            .max()
+            .unwrap_or(Access::None)
+    }
+
+    fn tags_of(&self, pdf_id: &Uuid) -> Vec<String> {
+        self.tags_to_pdfs.iter()
+            .filter(|(_, id)| id == pdf_id)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Associates the tags with the pdf, tags that don't exist yet are created.
+    /// Returns whether the pdf got a tag it didn't have before.
+    fn add_tags(&mut self, pdf_id: &Uuid, tags: &[String]) -> bool {
+        let mut added = false;
+
+        for tag in tags {
+            self.tags.insert(tag.clone());
+
+            if !self.tags_to_pdfs.iter().any(|(name, id)| name == tag && id == pdf_id) {
+                self.tags_to_pdfs.push((tag.clone(), *pdf_id));
+                added = true;
+            }
+        }
+
+        added
+    }
+
+    /// Returns whether the pdf had one of the tags
+    fn remove_tags(&mut self, pdf_id: &Uuid, tags: &[String]) -> bool {
+        let count = self.tags_to_pdfs.len();
+
+        self.tags_to_pdfs.retain(|(name, id)| id != pdf_id || !tags.contains(name));
+
+        self.tags_to_pdfs.len() < count
+    }
+
+    fn is_storage_key_used(&self, storage_key: &str) -> bool {
+        self.pdfs.iter().any(|pdf| pdf.storage_key == storage_key)
+    }
+
+    /// Deletes the pdfs along with everything that references them, returns the storage keys of the deleted pdfs
+    fn delete_pdfs(&mut self, pdf_ids: &HashSet<Uuid>) -> Vec<String> {
+        let deleted_share_links: HashSet<Uuid> = self.share_links.iter()
+            .filter(|row| pdf_ids.contains(&row.share_link.pdf_id))
+            .map(|row| row.share_link.id)
+            .collect();
+
+        self.share_link_accesses.retain(|(share_link_id, _)| !deleted_share_links.contains(share_link_id));
+        self.share_links.retain(|row| !pdf_ids.contains(&row.share_link.pdf_id));
+        self.pdf_shares.retain(|share| !pdf_ids.contains(&share.pdf_id));
+        self.tags_to_pdfs.retain(|(_, id)| !pdf_ids.contains(id));
+
+        let mut storage_keys = Vec::new();
+
+        self.pdfs.retain(|pdf| match pdf.id {
+            Some(id) if pdf_ids.contains(&id) => {
+                storage_keys.push(pdf.storage_key.clone());
+                false
+            },
+            _ => true
+        });
+
+        storage_keys
+    }
+
+    /// The storage keys that no pdf uses anymore, each only once
+    fn unused_storage_keys(&self, storage_keys: Vec<String>) -> Vec<String> {
+        let mut seen = HashSet::new();
+
+        storage_keys.into_iter()
+            .filter(|storage_key| !self.is_storage_key_used(storage_key) && seen.insert(storage_key.clone()))
+            .collect()
+    }
+
+    /// Same as `AuditRepositoryImpl::pdf_snapshots_with_connection`
+    fn pdf_snapshots(&self, pdf_ids: &[Uuid]) -> HashMap<Uuid, Value> {
+        self.pdfs.iter()
+            .filter_map(|pdf| pdf.id.filter(|id| pdf_ids.contains(id)).map(|id| (id, pdf)))
+            .map(|(id, pdf)| {
+                let mut tags = self.tags_of(&id);
+                tags.sort();
+
+                let shares: BTreeMap<String, String> = self.pdf_shares.iter()
+                    .filter(|share| share.pdf_id == id)
+                    .filter_map(|share| {
+                        let target = match (share.user_id, share.group_id) {
+                            (Some(user_id), _) => self.user(&user_id).map(|user| format!("user:{}", user.username)),
+                            (None, Some(group_id)) => self.groups.iter().find(|group| group.id == group_id).map(|group| format!("group:{}", group.name)),
+                            (None, None) => None
+                        };
+
+                        target.map(|target| (target, share.permission.clone()))
+                    })
+                    .collect();
+
+                let snapshot = json!({
+                    "title": pdf.title,
+                    "file_name": pdf.file_name,
+                    "author": pdf.author,
+                    "pages": pdf.pages,
+                    "comments": pdf.comments,
+                    "picture": pdf.picture.as_ref().map(|picture| format!("{:x}", Md5::digest(picture.as_bytes()))),
+                    "owner_id": pdf.owner_id,
+                    "storage_key": pdf.storage_key,
+                    "file_missing": pdf.file_missing,
+                    "tags": tags,
+                    "shares": shares
+                });
+
+                (id, snapshot)
+            })
+            .collect()
+    }
+
+    /// Same as `AuditRepositoryImpl::record_changes_with_connection`
+    fn record_changes(&mut self, actor_id: &Uuid, action: AuditAction, before: &HashMap<Uuid, Value>, after: &HashMap<Uuid, Value>) {
+        let pdf_ids: BTreeSet<&Uuid> = before.keys().chain(after.keys()).collect();
+
+        for pdf_id in pdf_ids {
+            let changes = diff(before.get(pdf_id), after.get(pdf_id));
+
+            if !changes.is_empty() {
+                self.record(actor_id, action, Some(pdf_id), Value::Object(changes));
+            }
+        }
+    }
+
+    /// Records the changes made to the pdfs since the `before` snapshots were taken
+    fn audit_changes(&mut self, actor_id: &Uuid, action: AuditAction, before: &HashMap<Uuid, Value>, pdf_ids: &[Uuid]) {
+        let after = self.pdf_snapshots(pdf_ids);
+
+        self.record_changes(actor_id, action, before, &after);
+    }
+
+    fn record(&mut self, actor_id: &Uuid, action: AuditAction, target_id: Option<&Uuid>, changes: Value) {
+        let entry = AuditEntry {
+            id: self.audit_log.last().map(|entry| entry.id + 1).unwrap_or(1),
+            time_created: Utc::now(),
+            actor_id: Some(*actor_id),
+            actor_name: self.user(actor_id).map(|user| user.username.clone()),
+            action: action.as_str().to_string(),
+            target_id: target_id.copied(),
+            changes
+        };
+
+        self.audit_log.push(entry);
+    }
+
+}
+
+
+/// Same as `value ILIKE CONCAT('%', pattern, '%')`, so `%` and `_` in the pattern are wildcards and `\` escapes them
+fn ilike_contains(value: &str, pattern: &str) -> bool {
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+
+    let mut tokens = Vec::new();
+    let mut chars = format!("%{}%", pattern.to_lowercase()).chars().collect::<Vec<char>>().into_iter();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '%' => tokens.push(LikeToken::AnyText),
+            '_' => tokens.push(LikeToken::AnyChar),
+            '\\' => tokens.push(LikeToken::Char(chars.next().unwrap_or('\\'))),
+            char => tokens.push(LikeToken::Char(char))
+        }
+    }
+
+    like_matches(&tokens, &value)
+}
+
+enum LikeToken {
+    AnyText,
+    AnyChar,
+    Char(char)
+}
+
+fn like_matches(tokens: &[LikeToken], value: &[char]) -> bool {
+    match tokens.split_first() {
+        None => value.is_empty(),
+        Some((LikeToken::AnyText, rest)) => (0..=value.len()).any(|skipped| like_matches(rest, &value[skipped..])),
+        Some((LikeToken::AnyChar, rest)) => !value.is_empty() && like_matches(rest, &value[1..]),
+        Some((LikeToken::Char(char), rest)) => value.first() == Some(char) && like_matches(rest, &value[1..])
+    }
+}
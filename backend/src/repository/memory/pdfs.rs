@@ -0,0 +1,1064 @@
+use std::{sync::Arc, cmp::Reverse, collections::{HashMap, HashSet}};
+
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{domain::models::pdf::{ArchivedPdf, PdfOverview, Pdf, PdfFile, StoredPdf}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfPatchDto, PdfMetadataDto}}};
+use crate::api::dto::bulk::{BulkOperationDto, BulkSelectionDto, BulkActionDto, BulkItemResultDto, BulkItemStatus};
+use crate::domain::repository::pdf::PdfRepository;
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::domain::models::share::{Access, PdfShare, ShareLink, ShareLinkAccess, ShareLinkClient, ShareLinkOutcome};
+use crate::domain::models::audit::AuditAction;
+use crate::repository::memory::{ilike_contains, MemoryDatabase, PdfShareRow, ShareLinkRow, Tables};
+use crate::repository::pdfs::share_link_audit_details;
+use crate::util::PdfUploaded;
+use crate::errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError};
+
+use async_trait::async_trait;
+
+
+/// Keeps the pdfs in memory with the same behaviour as `PdfRepositoryImpl`
+pub struct MemoryPdfRepository {
+    pub database: Arc<MemoryDatabase>
+}
+
+impl MemoryPdfRepository {
+
+    /// The pdfs the user may see, in the order they are listed in
+    fn visible_pdfs<'a>(tables: &'a Tables, user_id: &Uuid, filter: impl Fn(&Pdf) -> bool) -> Vec<&'a Pdf> {
+        let mut pdfs: Vec<&Pdf> = tables.pdfs.iter()
+            .filter(|pdf| pdf.id.is_some_and(|id| tables.access(&id, user_id) > Access::None))
+            .filter(|pdf| filter(pdf))
+            .collect();
+
+        pdfs.sort_by_key(|pdf| (pdf.time_added, pdf.id));
+
+        pdfs
+    }
+
+
+    /// Same as `title ILIKE ... AND author ILIKE ... AND <some tag> ILIKE ...` of the search queries
+    fn matches_search(tables: &Tables, pdf: &Pdf, title: &Option<String>, author: &Option<String>, tag: &Option<String>) -> bool {
+        let matches = |value: &Option<String>, pattern: &Option<String>| match pattern {
+            Some(pattern) => value.as_ref().is_some_and(|value| ilike_contains(value, pattern)),
+            None => true
+        };
+
+        let matches_tag = match (tag, pdf.id) {
+            (Some(pattern), Some(id)) => tables.tags_of(&id).iter().any(|name| ilike_contains(name, pattern)),
+            (Some(_), None) => false,
+            (None, _) => true
+        };
+
+        matches(&pdf.title, title) && matches(&pdf.author, author) && matches_tag
+    }
+
+
+    fn require_owner(tables: &Tables, pdf_id: &Uuid, user_id: &Uuid) -> Result<(), PdfAccessError> {
+        match tables.access(pdf_id, user_id) {
+            Access::Owner => Ok(()),
+            Access::None => Err(PdfAccessError::NotFound("No pdf with given ID exists".to_string())),
+            _ => Err(PdfAccessError::Forbidden("Only the owner of the pdf may do this".to_string()))
+        }
+    }
+
+
+    /// Checks that the user may make the change and that the pdf is still at one of the expected versions
+    fn check_version(
+        tables: &Tables,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        expected_versions: &Option<Vec<i32>>,
+        required_access: impl FnOnce(&Pdf) -> Access
+    ) -> Result<(), PdfUpdateError> {
+        let Some(current_pdf) = tables.pdf(pdf_id) else {
+            return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string()));
+        };
+
+        let access = tables.access(pdf_id, user_id);
+
+        if access == Access::None {
+            return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string()));
+        }
+
+        if access < required_access(current_pdf) {
+            return Err(PdfUpdateError::Forbidden("The pdf is not shared with you with a permission that allows this change".to_string()));
+        }
+
+        match expected_versions {
+            Some(versions) if !versions.contains(&current_pdf.version) => {
+                Err(PdfUpdateError::VersionMismatch(Box::new(to_metadata_dto(current_pdf.clone(), tables.tags_of(pdf_id)))))
+            },
+            _ => Ok(())
+        }
+    }
+
+}
+
+
+fn to_metadata_dto(pdf: Pdf, tags: Vec<String>) -> PdfMetadataDto {
+    PdfMetadataDto {
+        id: pdf.id,
+        title: pdf.title,
+        file_name: pdf.file_name,
+        author: pdf.author,
+        pages: pdf.pages,
+        comments: pdf.comments,
+        uploaded: pdf.time_added,
+        last_accessed: pdf.last_accessed,
+        picture: pdf.picture,
+        tags: Some(tags),
+        version: pdf.version,
+        owner_id: pdf.owner_id,
+        file_missing: pdf.file_missing
+    }
+}
+
+
+#[async_trait]
+impl PdfRepository for MemoryPdfRepository {
+
+    #[instrument(skip_all)]
+    async fn total_number_of_pdfs(&self, user_id: &Uuid) -> Result<Option<i64>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(Some(Self::visible_pdfs(&tables, user_id, |_| true).len() as i64))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdfs_paged(&self, paging: &PagingDto, user_id: &Uuid) -> Result<Vec<PdfOverview>, String> {
+        let size = paging.size.unwrap().max(0) as usize;
+        let offset = size * (paging.page.unwrap() - 1).max(0) as usize;
+
+        let tables = self.database.lock()?;
+
+        Ok(Self::visible_pdfs(&tables, user_id, |_| true).into_iter()
+            .skip(offset)
+            .take(size)
+            .map(|pdf| PdfOverview { id: pdf.id, title: pdf.title.clone(), picture: pdf.picture.clone() })
+            .collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError> {
+        let tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfMetadataByIdError::DatabaseError(msg))
+        };
+
+        match tables.pdf(pdf_id) {
+            Some(pdf) if tables.access(pdf_id, user_id) > Access::None => Ok(pdf.clone()),
+            _ => Err(PdfMetadataByIdError::NotFound("No pdf with given ID exists".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_associated_tags_of_pdf(&self, pdf_id: &Uuid) -> Result<Vec<String>, PdfMetadataByIdError> {
+        match self.database.lock() {
+            Ok(tables) => Ok(tables.tags_of(pdf_id)),
+            Err(msg) => Err(PdfMetadataByIdError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError> {
+        let tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfMetadataByIdError::DatabaseError(msg))
+        };
+
+        match tables.pdf(pdf_id) {
+            Some(pdf) if tables.access(pdf_id, user_id) > Access::None => Ok(pdf.storage_key.clone()),
+            _ => Err(PdfMetadataByIdError::NotFound("No pdf with given ID exists".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String> {
+        let size = search.size.unwrap().max(0) as usize;
+        let offset = size * (search.page.unwrap() - 1).max(0) as usize;
+
+        let tables = self.database.lock()?;
+
+        let found = Self::visible_pdfs(&tables, user_id, |pdf| Self::matches_search(&tables, pdf, &search.title, &search.author, &search.tag));
+
+        let count = found.len() as i64;
+
+        let pdfs_previews = found.into_iter()
+            .skip(offset)
+            .take(size)
+            .map(|pdf| PdfOverview { id: pdf.id, title: pdf.title.clone(), picture: pdf.picture.clone() })
+            .collect();
+
+        Ok(PdfOverviewDto { pdfs_previews, count: Some(count) })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfUpdateError::DatabaseError(msg))
+        };
+
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            if update.title != current_pdf.title || update.author != current_pdf.author || update.picture != current_pdf.picture {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        Self::check_version(&tables, pdf_id, user_id, &expected_versions, required_access)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        // The tags of the request replace all tags of the pdf
+        let mut seen_tags = HashSet::new();
+        let tags: Vec<String> = update.tags.unwrap_or_default().into_iter().filter(|tag| seen_tags.insert(tag.clone())).collect();
+
+        tables.tags_to_pdfs.retain(|(_, id)| id != pdf_id);
+        tables.add_tags(pdf_id, &tags);
+
+        let Some(pdf) = tables.pdf_mut(pdf_id) else {
+            return Err(PdfUpdateError::DatabaseError("Error updating pdf".to_string()));
+        };
+
+        pdf.title = update.title;
+        pdf.author = update.author;
+        pdf.comments = update.comments;
+        pdf.picture = update.picture;
+        pdf.version += 1;
+
+        let updated_pdf = pdf.clone();
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(to_metadata_dto(updated_pdf, tables.tags_of(pdf_id)))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfUpdateError::DatabaseError(msg))
+        };
+
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            let changes = |field: &Option<Option<String>>, current: &Option<String>| field.as_ref().is_some_and(|value| value != current);
+
+            if changes(&patch.title, &current_pdf.title) || changes(&patch.author, &current_pdf.author) || changes(&patch.picture, &current_pdf.picture) {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        Self::check_version(&tables, pdf_id, user_id, &expected_versions, required_access)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        let Some(pdf) = tables.pdf_mut(pdf_id) else {
+            return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string()));
+        };
+
+        // Each field is only overwritten if it is part of the patch, a field set to null is cleared
+        if let Some(title) = patch.title { pdf.title = title; }
+        if let Some(author) = patch.author { pdf.author = author; }
+        if let Some(comments) = patch.comments { pdf.comments = comments; }
+        if let Some(picture) = patch.picture { pdf.picture = picture; }
+        pdf.version += 1;
+
+        let patched_pdf = pdf.clone();
+
+        if let Some(tags) = &patch.tags {
+            tables.tags_to_pdfs.retain(|(_, id)| id != pdf_id);
+
+            if let Some(tags) = tags {
+                tables.add_tags(pdf_id, tags);
+            }
+        }
+
+        if let Some(add_tags) = &patch.add_tags {
+            tables.add_tags(pdf_id, add_tags);
+        }
+
+        if let Some(remove_tags) = &patch.remove_tags {
+            tables.remove_tags(pdf_id, remove_tags);
+        }
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(to_metadata_dto(patched_pdf, tables.tags_of(pdf_id)))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Option<String>, PdfAccessError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        let storage_keys = tables.delete_pdfs(&HashSet::from([*pdf_id]));
+
+        tables.audit_changes(user_id, AuditAction::Delete, &before, &[*pdf_id]);
+
+        // The file is shared by all pdfs with the same content
+        Ok(tables.unused_storage_keys(storage_keys).into_iter().next())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn bulk(&self, bulk: &BulkOperationDto, user_id: &Uuid) -> Result<(Vec<BulkItemResultDto>, Vec<String>), String> {
+        let mut database_tables = self.database.lock()?;
+
+        // The operation is applied to a copy, which only replaces the tables if it isn't a dry run
+        let mut tables = database_tables.clone();
+
+        let (requested_ids, selected) = match &bulk.selection {
+            BulkSelectionDto::Ids(ids) => {
+                let mut seen_ids = HashSet::new();
+                let requested_ids: Vec<Uuid> = ids.iter().filter(|id| seen_ids.insert(**id)).copied().collect();
+
+                let mut selected: Vec<(Uuid, Access)> = requested_ids.iter()
+                    .map(|id| (*id, tables.access(id, user_id)))
+                    .filter(|(_, access)| *access > Access::None)
+                    .collect();
+
+                selected.sort_by_key(|(id, _)| *id);
+
+                (Some(requested_ids), selected)
+            },
+            BulkSelectionDto::Search(search) => {
+                let selected = Self::visible_pdfs(&tables, user_id, |pdf| Self::matches_search(&tables, pdf, &search.title, &search.author, &search.tag))
+                    .into_iter()
+                    .filter_map(|pdf| pdf.id)
+                    .map(|id| (id, tables.access(&id, user_id)))
+                    .collect();
+
+                (None, selected)
+            }
+        };
+
+        let required_access = match &bulk.action {
+            BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. } => Access::Annotate,
+            BulkActionDto::SetAuthor { .. } => Access::Edit,
+            BulkActionDto::Delete => Access::Owner
+        };
+
+        let selected_in_order: Vec<Uuid> = selected.iter().map(|(id, _)| *id).collect();
+
+        // The action is only applied to the selected pdfs the user has the permission for
+        let (permitted, forbidden): (Vec<_>, Vec<_>) = selected.into_iter()
+            .partition(|(_, access)| *access >= required_access);
+
+        let selected_ids: Vec<Uuid> = permitted.into_iter().map(|(id, _)| id).collect();
+        let forbidden_ids: HashSet<Uuid> = forbidden.into_iter().map(|(id, _)| id).collect();
+
+        let before = tables.pdf_snapshots(&selected_ids);
+
+        let mut deleted_storage_keys = Vec::new();
+
+        let changed_ids: HashSet<Uuid> = match &bulk.action {
+            BulkActionDto::AddTags { tags } => {
+                selected_ids.iter().filter(|id| tables.add_tags(id, tags)).copied().collect()
+            },
+            BulkActionDto::RemoveTags { tags } => {
+                selected_ids.iter().filter(|id| tables.remove_tags(id, tags)).copied().collect()
+            },
+            BulkActionDto::SetAuthor { author } => {
+                let mut changed_ids = HashSet::new();
+
+                for id in &selected_ids {
+                    if let Some(pdf) = tables.pdf_mut(id) {
+                        if pdf.author != *author {
+                            pdf.author = author.clone();
+                            pdf.version += 1;
+                            changed_ids.insert(*id);
+                        }
+                    }
+                }
+
+                changed_ids
+            },
+            BulkActionDto::Delete => {
+                let deleted_ids: HashSet<Uuid> = selected_ids.iter().copied().collect();
+                deleted_storage_keys = tables.delete_pdfs(&deleted_ids);
+                deleted_ids
+            }
+        };
+
+        // Tags are part of the pdf details, changing them creates a new version
+        if matches!(bulk.action, BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. }) {
+            for id in &changed_ids {
+                if let Some(pdf) = tables.pdf_mut(id) {
+                    pdf.version += 1;
+                }
+            }
+        }
+
+        let audit_action = match bulk.action {
+            BulkActionDto::Delete => AuditAction::Delete,
+            _ => AuditAction::Update
+        };
+
+        tables.audit_changes(user_id, audit_action, &before, &selected_ids);
+
+        // Files are shared by all pdfs with the same content, only the ones no pdf uses anymore can be removed
+        let unused_storage_keys = tables.unused_storage_keys(deleted_storage_keys);
+
+        if !bulk.dry_run {
+            *database_tables = tables;
+        }
+
+        let status_of = |id: &Uuid| {
+            if forbidden_ids.contains(id) {
+                BulkItemStatus::Forbidden
+            } else if changed_ids.contains(id) {
+                BulkItemStatus::Changed
+            } else {
+                BulkItemStatus::Unchanged
+            }
+        };
+
+        let results = match requested_ids {
+            Some(requested_ids) => {
+                let selected_ids: HashSet<Uuid> = selected_in_order.into_iter().collect();
+
+                requested_ids.into_iter().map(|id| {
+                    let status = if selected_ids.contains(&id) { status_of(&id) } else { BulkItemStatus::NotFound };
+                    BulkItemResultDto { id, status }
+                }).collect()
+            },
+            None => selected_in_order.into_iter().map(|id| BulkItemResultDto { id, status: status_of(&id) }).collect()
+        };
+
+        Ok((results, unused_storage_keys))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn upload(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.user(owner_id).is_none() {
+            return Err("Error storing the uploaded pdf".to_string());
+        }
+
+        // The file names are unique per owner
+        if tables.pdfs.iter().any(|pdf| pdf.owner_id == *owner_id && pdf.file_name == upload.filename) {
+            return Err("Error storing the uploaded pdf".to_string());
+        }
+
+        let pdf_id = Uuid::new_v4();
+
+        let uploaded_pdf = Pdf {
+            id: Some(pdf_id),
+            title: Some(upload.title.clone()),
+            file_name: upload.filename.clone(),
+            author: upload.author.clone(),
+            pages: upload.pages,
+            comments: upload.comments.clone(),
+            time_added: Some(upload.time_added.unwrap_or_else(Utc::now)),
+            last_accessed: None,
+            picture: Some(upload.img.clone()),
+            version: 1,
+            owner_id: *owner_id,
+            storage_key: upload.storage_key.clone(),
+            file_missing: false
+        };
+
+        tables.pdfs.push(uploaded_pdf.clone());
+        tables.add_tags(&pdf_id, &upload.tags);
+
+        tables.audit_changes(owner_id, AuditAction::Upload, &HashMap::new(), &[pdf_id]);
+
+        Ok(uploaded_pdf)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn is_storage_key_used(&self, storage_key: &str) -> Result<bool, String> {
+        let tables = self.database.lock()?;
+
+        Ok(tables.is_storage_key_used(storage_key))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_stored_pdfs(&self) -> Result<Vec<StoredPdf>, String> {
+        let tables = self.database.lock()?;
+
+        let mut stored_pdfs: Vec<StoredPdf> = tables.pdfs.iter()
+            .filter_map(|pdf| pdf.id.map(|id| StoredPdf {
+                id,
+                storage_key: pdf.storage_key.clone(),
+                has_picture: pdf.picture.as_ref().is_some_and(|picture| !picture.is_empty()),
+                file_missing: pdf.file_missing
+            }))
+            .collect();
+
+        stored_pdfs.sort_by(|a, b| (&a.storage_key, a.id).cmp(&(&b.storage_key, b.id)));
+
+        Ok(stored_pdfs)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_file_missing(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        match tables.pdf_mut(pdf_id) {
+            Some(pdf) if pdf.file_missing != file_missing => {
+                pdf.file_missing = file_missing;
+                pdf.version += 1;
+            },
+            _ => return Ok(())
+        }
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_picture(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        let Some(pdf) = tables.pdf_mut(pdf_id) else {
+            return Ok(());
+        };
+
+        pdf.picture = Some(picture.to_string());
+        pdf.version += 1;
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String> {
+        let mut tables = self.database.lock()?;
+
+        let sources: Vec<&String> = sources.iter().filter(|source| *source != target).collect();
+
+        let mut pdf_ids: Vec<Uuid> = Vec::new();
+        for (name, id) in &tables.tags_to_pdfs {
+            if sources.contains(&name) && !pdf_ids.contains(id) {
+                pdf_ids.push(*id);
+            }
+        }
+
+        let before = tables.pdf_snapshots(&pdf_ids);
+
+        tables.tags.insert(target.to_string());
+
+        for pdf_id in &pdf_ids {
+            tables.add_tags(pdf_id, &[target.to_string()]);
+        }
+
+        tables.tags.retain(|name| !sources.contains(&name));
+        tables.tags_to_pdfs.retain(|(name, _)| !sources.contains(&name));
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &pdf_ids);
+
+        Ok(pdf_ids.len())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_archived_pdfs(&self, since: Option<DateTime<Utc>>) -> Result<Vec<ArchivedPdf>, String> {
+        let tables = self.database.lock()?;
+
+        let is_changed = |pdf: &Pdf| match since {
+            Some(since) => pdf.time_added.is_some_and(|time_added| time_added >= since)
+                || tables.audit_log.iter().any(|entry| entry.target_id == pdf.id && entry.time_created >= since),
+            None => true
+        };
+
+        let mut pdfs: Vec<&Pdf> = tables.pdfs.iter().filter(|pdf| is_changed(pdf)).collect();
+        pdfs.sort_by_key(|pdf| (pdf.time_added, pdf.id));
+
+        Ok(pdfs.into_iter()
+            .map(|pdf| {
+                let mut tags: Vec<String> = tables.tags_to_pdfs.iter()
+                    .filter(|(_, pdf_id)| Some(*pdf_id) == pdf.id)
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                tags.sort();
+
+                ArchivedPdf {
+                    pdf: pdf.clone(),
+                    owner: tables.user(&pdf.owner_id).map(|owner| owner.username.clone()).unwrap_or_default(),
+                    tags
+                }
+            })
+            .collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn rebuild_search_index(&self) -> Result<(), String> {
+        // The pdfs in memory are searched without an index
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn count_pdfs_and_pages(&self) -> Result<(i64, i64), String> {
+        let tables = self.database.lock()?;
+
+        let pages = tables.pdfs.iter().map(|pdf| pdf.pages.unwrap_or(0) as i64).sum();
+
+        Ok((tables.pdfs.len() as i64, pages))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(pdf_files(&tables, |pdf| pdf.owner_id == *owner_id))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(pdf_files(&tables, |pdf| {
+            pdf.owner_id == *owner_id && pdf.id.is_some_and(|id| tables.tags_to_pdfs.iter().any(|(name, tagged)| name == tag && *tagged == id))
+        }))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(pdf_files(&tables, |pdf| pdf.owner_id == *owner_id && pdf.author.as_deref() == Some(author)))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(pdf_files(&tables, |pdf| pdf.owner_id == *owner_id && pdf.file_name == file_name).into_iter().next())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let tables = self.database.lock()?;
+
+        let owned_pdfs: HashSet<Uuid> = tables.pdfs.iter()
+            .filter(|pdf| pdf.owner_id == *owner_id)
+            .filter_map(|pdf| pdf.id)
+            .collect();
+
+        // The tags are kept sorted
+        Ok(tables.tags.iter()
+            .filter(|tag| {
+                let mut tagged = tables.tags_to_pdfs.iter().filter(|(name, _)| name == *tag).peekable();
+                tagged.peek().is_none() || tagged.any(|(_, id)| owned_pdfs.contains(id))
+            })
+            .cloned()
+            .collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let tables = self.database.lock()?;
+
+        let mut authors: Vec<String> = tables.pdfs.iter()
+            .filter(|pdf| pdf.owner_id == *owner_id)
+            .filter_map(|pdf| pdf.author.clone())
+            .filter(|author| !author.is_empty())
+            .collect();
+
+        authors.sort();
+        authors.dedup();
+
+        Ok(authors)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.tags.insert(tag.to_string()) {
+            let changes = json!({ "tag": { "old": null, "new": tag } });
+
+            tables.record(user_id, AuditAction::TagCreate, None, changes);
+        }
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfUpdateError::DatabaseError(msg))
+        };
+
+        Self::check_version(&tables, pdf_id, user_id, &None, |_| Access::Annotate)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        if tables.add_tags(pdf_id, &[tag.to_string()]) {
+            if let Some(pdf) = tables.pdf_mut(pdf_id) {
+                pdf.version += 1;
+            }
+        }
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfUpdateError::DatabaseError(msg))
+        };
+
+        Self::check_version(&tables, pdf_id, user_id, &None, |_| Access::Annotate)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        if tables.remove_tags(pdf_id, &[tag.to_string()]) {
+            if let Some(pdf) = tables.pdf_mut(pdf_id) {
+                pdf.version += 1;
+            }
+        }
+
+        tables.audit_changes(user_id, AuditAction::Update, &before, &[*pdf_id]);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShare>, PdfAccessError> {
+        let tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let mut shares: Vec<PdfShare> = tables.pdf_shares.iter()
+            .filter(|share| share.pdf_id == *pdf_id)
+            .map(|share| PdfShare {
+                user_id: share.user_id,
+                username: share.user_id.and_then(|id| tables.user(&id)).map(|user| user.username.clone()),
+                group_id: share.group_id,
+                group_name: share.group_id.and_then(|id| tables.groups.iter().find(|group| group.id == id)).map(|group| group.name.clone()),
+                permission: share.permission.clone()
+            })
+            .collect();
+
+        // Ordered by username and group name like in Postgres, where missing names come last
+        shares.sort_by(|a, b| {
+            (a.username.is_none(), &a.username, a.group_name.is_none(), &a.group_name)
+                .cmp(&(b.username.is_none(), &b.username, b.group_name.is_none(), &b.group_name))
+        });
+
+        Ok(shares)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: &PdfShareDto) -> Result<(), PdfAccessError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        let (share_user_id, share_group_id) = match (&share.user, &share.group) {
+            (Some(username), None) => {
+                let share_user_id = match tables.users.iter().find(|user| user.username == *username) {
+                    Some(share_user) => share_user.id,
+                    None => return Err(PdfAccessError::InvalidInput(format!("No user named '{}' exists", username)))
+                };
+
+                if share_user_id == *user_id {
+                    return Err(PdfAccessError::InvalidInput("A pdf can't be shared with its owner".to_string()));
+                }
+
+                (Some(share_user_id), None)
+            },
+            (None, Some(group_name)) => match tables.group_by_name(group_name) {
+                Some(group) => (None, Some(group.id)),
+                None => return Err(PdfAccessError::InvalidInput(format!("No group named '{}' exists", group_name)))
+            },
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        let permission = share.permission.as_str().to_string();
+
+        match tables.pdf_shares.iter_mut().find(|row| row.pdf_id == *pdf_id && row.user_id == share_user_id && row.group_id == share_group_id) {
+            Some(row) => row.permission = permission,
+            None => tables.pdf_shares.push(PdfShareRow { pdf_id: *pdf_id, user_id: share_user_id, group_id: share_group_id, permission })
+        }
+
+        tables.audit_changes(user_id, AuditAction::Share, &before, &[*pdf_id]);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: &ShareTargetDto) -> Result<bool, PdfAccessError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let before = tables.pdf_snapshots(&[*pdf_id]);
+
+        let (target_user_id, target_group_id) = match (&target.user, &target.group) {
+            (Some(username), None) => (tables.users.iter().find(|user| user.username == *username).map(|user| user.id), None),
+            (None, Some(group_name)) => (None, tables.group_by_name(group_name).map(|group| group.id)),
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        // Users and groups that don't exist have no shares
+        let is_target = |share: &PdfShareRow| share.pdf_id == *pdf_id && match (target_user_id, target_group_id) {
+            (Some(target_user_id), _) => share.user_id == Some(target_user_id),
+            (None, Some(target_group_id)) => share.group_id == Some(target_group_id),
+            (None, None) => false
+        };
+
+        let count = tables.pdf_shares.len();
+
+        tables.pdf_shares.retain(|share| !is_target(share));
+
+        let unshared = tables.pdf_shares.len() < count;
+
+        tables.audit_changes(user_id, AuditAction::Unshare, &before, &[*pdf_id]);
+
+        Ok(unshared)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_share_link(
+        &self,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        token_hash: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<i32>
+    ) -> Result<ShareLink, PdfAccessError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        if tables.share_links.iter().any(|row| row.token_hash == token_hash) {
+            return Err(PdfAccessError::DatabaseError("Error creating the share link".to_string()));
+        }
+
+        let share_link = ShareLink {
+            id: Uuid::new_v4(),
+            pdf_id: *pdf_id,
+            created_by: *user_id,
+            password_hash: password_hash.map(|password_hash| password_hash.to_string()),
+            expires_at,
+            max_downloads,
+            download_count: 0,
+            time_created: Utc::now(),
+            revoked_at: None
+        };
+
+        tables.share_links.push(ShareLinkRow { token_hash: token_hash.to_string(), share_link: share_link.clone() });
+
+        let changes = json!({ "share_link": { "old": null, "new": share_link_audit_details(&share_link) } });
+
+        tables.record(user_id, AuditAction::ShareLinkCreate, Some(pdf_id), changes);
+
+        Ok(share_link)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLink>, PdfAccessError> {
+        let tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let mut share_links: Vec<ShareLink> = tables.share_links.iter()
+            .filter(|row| row.share_link.pdf_id == *pdf_id)
+            .map(|row| row.share_link.clone())
+            .collect();
+
+        share_links.sort_by_key(|share_link| Reverse(share_link.time_created));
+
+        Ok(share_links)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<bool, PdfAccessError> {
+        let mut tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        let revoked = match tables.share_links.iter_mut().find(|row| row.share_link.id == *share_link_id && row.share_link.pdf_id == *pdf_id && row.share_link.revoked_at.is_none()) {
+            Some(row) => {
+                row.share_link.revoked_at = Some(Utc::now());
+                row.share_link.clone()
+            },
+            None => return Ok(false)
+        };
+
+        let changes = json!({ "share_link": { "old": share_link_audit_details(&revoked), "new": null } });
+
+        tables.record(user_id, AuditAction::ShareLinkRevoke, Some(pdf_id), changes);
+
+        Ok(true)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Option<Vec<ShareLinkAccess>>, PdfAccessError> {
+        let tables = match self.database.lock() {
+            Ok(tables) => tables,
+            Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+        };
+
+        Self::require_owner(&tables, pdf_id, user_id)?;
+
+        if !tables.share_links.iter().any(|row| row.share_link.id == *share_link_id && row.share_link.pdf_id == *pdf_id) {
+            return Ok(None);
+        }
+
+        let mut accesses: Vec<ShareLinkAccess> = tables.share_link_accesses.iter()
+            .filter(|(id, _)| id == share_link_id)
+            .map(|(_, access)| access.clone())
+            .collect();
+
+        accesses.sort_by_key(|access| Reverse(access.time_accessed));
+
+        Ok(Some(accesses))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link(&self, token_hash: &str) -> Result<Option<(ShareLink, Pdf)>, String> {
+        let tables = self.database.lock()?;
+
+        let Some(row) = tables.share_links.iter().find(|row| row.token_hash == token_hash) else {
+            return Ok(None);
+        };
+
+        // The link grants access on its own, so the pdf isn't filtered by who may see it
+        match tables.pdf(&row.share_link.pdf_id) {
+            Some(pdf) => Ok(Some((row.share_link.clone(), pdf.clone()))),
+            None => Err("Error retrieving the shared pdf".to_string())
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_access(&self, share_link_id: &Uuid, client: &ShareLinkClient, outcome: ShareLinkOutcome) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        if !tables.share_links.iter().any(|row| row.share_link.id == *share_link_id) {
+            return Err("Error recording the access of the share link".to_string());
+        }
+
+        let access = ShareLinkAccess {
+            time_accessed: Utc::now(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            outcome: outcome.as_str().to_string()
+        };
+
+        tables.share_link_accesses.push((*share_link_id, access));
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_download(&self, share_link_id: &Uuid, client: &ShareLinkClient) -> Result<bool, String> {
+        let mut tables = self.database.lock()?;
+
+        // Holding the lock keeps concurrent downloads from exceeding the limit
+        match tables.share_links.iter_mut().find(|row| row.share_link.id == *share_link_id) {
+            Some(row) if row.share_link.unusable_reason().is_none() => row.share_link.download_count += 1,
+            _ => return Ok(false)
+        }
+
+        let access = ShareLinkAccess {
+            time_accessed: Utc::now(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            outcome: ShareLinkOutcome::Downloaded.as_str().to_string()
+        };
+
+        tables.share_link_accesses.push((*share_link_id, access));
+
+        Ok(true)
+    }
+
+}
+
+
+/// The owner's pdfs as files, ordered by file name
+fn pdf_files(tables: &Tables, filter: impl Fn(&Pdf) -> bool) -> Vec<PdfFile> {
+    let mut pdf_files: Vec<PdfFile> = tables.pdfs.iter()
+        .filter(|pdf| filter(pdf))
+        .map(|pdf| PdfFile { id: pdf.id, file_name: pdf.file_name.clone(), time_added: pdf.time_added, storage_key: pdf.storage_key.clone() })
+        .collect();
+
+    pdf_files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    pdf_files
+}
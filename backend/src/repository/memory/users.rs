@@ -0,0 +1,338 @@
+use std::{sync::Arc, collections::{HashMap, HashSet}};
+
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::domain::models::audit::AuditAction;
+use crate::domain::models::user::{ApiToken, Group, User};
+use crate::domain::repository::user::UserRepository;
+use crate::repository::memory::{GroupRow, MemoryDatabase, Session};
+
+use async_trait::async_trait;
+
+
+/// Keeps the users, groups, sessions and API tokens in memory with the same behaviour as `UserRepositoryImpl`
+pub struct MemoryUserRepository {
+    pub database: Arc<MemoryDatabase>
+}
+
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+
+    #[instrument(skip_all)]
+    async fn count_users(&self) -> Result<i64, String> {
+        let tables = self.database.lock()?;
+
+        Ok(tables.users.len() as i64)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_users(&self) -> Result<Vec<User>, String> {
+        let tables = self.database.lock()?;
+
+        let mut users = tables.users.clone();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+
+        Ok(users)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(tables.user(user_id).cloned())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        let tables = self.database.lock()?;
+
+        Ok(tables.users.iter().find(|user| user.username == username).cloned())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_user(&self, username: &str, password_hash: &str, is_admin: bool) -> Result<Option<User>, String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.users.iter().any(|user| user.username == username) {
+            return Ok(None);
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            password_hash: password_hash.to_string(),
+            is_admin,
+            time_created: Utc::now()
+        };
+
+        tables.users.push(user.clone());
+
+        Ok(Some(user))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn update_password(&self, user_id: &Uuid, password_hash: &str) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        if let Some(user) = tables.users.iter_mut().find(|user| user.id == *user_id) {
+            user.password_hash = password_hash.to_string();
+        }
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<Option<Vec<String>>, String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.user(user_id).is_none() {
+            return Ok(None);
+        }
+
+        let pdf_ids: HashSet<Uuid> = tables.pdfs.iter()
+            .filter(|pdf| pdf.owner_id == *user_id)
+            .filter_map(|pdf| pdf.id)
+            .collect();
+
+        let before = tables.pdf_snapshots(&pdf_ids.iter().copied().collect::<Vec<Uuid>>());
+
+        // Everything that references the user is deleted along with the user
+        let deleted_share_links: HashSet<Uuid> = tables.share_links.iter()
+            .filter(|row| row.share_link.created_by == *user_id)
+            .map(|row| row.share_link.id)
+            .collect();
+
+        tables.share_link_accesses.retain(|(share_link_id, _)| !deleted_share_links.contains(share_link_id));
+        tables.share_links.retain(|row| row.share_link.created_by != *user_id);
+        tables.pdf_shares.retain(|share| share.user_id != Some(*user_id));
+        tables.group_members.retain(|(_, member_id)| member_id != user_id);
+        tables.api_tokens.retain(|api_token| api_token.user_id != *user_id);
+        tables.sessions.retain(|session| session.user_id != *user_id);
+
+        let storage_keys = tables.delete_pdfs(&pdf_ids);
+
+        tables.users.retain(|user| user.id != *user_id);
+
+        tables.record_changes(actor_id, AuditAction::Delete, &before, &HashMap::new());
+
+        Ok(Some(tables.unused_storage_keys(storage_keys)))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_session(&self, token_hash: &str, user_id: &Uuid, expires_at: DateTime<Utc>) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.user(user_id).is_none() || tables.sessions.iter().any(|session| session.token_hash == token_hash) {
+            return Err("Error creating session".to_string());
+        }
+
+        tables.sessions.push(Session { token_hash: token_hash.to_string(), user_id: *user_id, expires_at });
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_session(&self, token_hash: &str) -> Result<Option<User>, String> {
+        let tables = self.database.lock()?;
+
+        let now = Utc::now();
+
+        Ok(tables.sessions.iter()
+            .find(|session| session.token_hash == token_hash && session.expires_at > now)
+            .and_then(|session| tables.user(&session.user_id))
+            .cloned())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_session(&self, token_hash: &str) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        // Expired sessions are cleaned up along the way
+        let now = Utc::now();
+
+        tables.sessions.retain(|session| session.token_hash != token_hash && session.expires_at > now);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_sessions_of_user(&self, user_id: &Uuid) -> Result<(), String> {
+        let mut tables = self.database.lock()?;
+
+        tables.sessions.retain(|session| session.user_id != *user_id);
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_api_token(&self, user_id: &Uuid, name: &str, token_hash: &str, scopes: &[String], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.user(user_id).is_none() || tables.api_tokens.iter().any(|api_token| api_token.token_hash == token_hash) {
+            return Err("Error creating API token".to_string());
+        }
+
+        let api_token = ApiToken {
+            id: Uuid::new_v4(),
+            user_id: *user_id,
+            name: name.to_string(),
+            token_hash: token_hash.to_string(),
+            scopes: scopes.to_vec(),
+            time_created: Utc::now(),
+            expires_at,
+            last_used: None
+        };
+
+        tables.api_tokens.push(api_token.clone());
+
+        Ok(api_token)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiToken>, String> {
+        let tables = self.database.lock()?;
+
+        let mut api_tokens: Vec<ApiToken> = tables.api_tokens.iter()
+            .filter(|api_token| api_token.user_id == *user_id)
+            .cloned()
+            .collect();
+
+        api_tokens.sort_by_key(|api_token| api_token.time_created);
+
+        Ok(api_tokens)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<bool, String> {
+        let mut tables = self.database.lock()?;
+
+        let count = tables.api_tokens.len();
+
+        tables.api_tokens.retain(|api_token| api_token.id != *token_id || api_token.user_id != *user_id);
+
+        Ok(tables.api_tokens.len() < count)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_api_token(&self, token_hash: &str) -> Result<Option<(User, ApiToken)>, String> {
+        let mut tables = self.database.lock()?;
+
+        let now = Utc::now();
+
+        let api_token = match tables.api_tokens.iter_mut().find(|api_token| api_token.token_hash == token_hash && api_token.expires_at.is_none_or(|expires_at| expires_at > now)) {
+            Some(api_token) => {
+                api_token.last_used = Some(now);
+                api_token.clone()
+            },
+            None => return Ok(None)
+        };
+
+        match tables.user(&api_token.user_id) {
+            Some(user) => Ok(Some((user.clone(), api_token))),
+            None => Ok(None)
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_groups(&self) -> Result<Vec<Group>, String> {
+        let tables = self.database.lock()?;
+
+        let mut groups: Vec<Group> = tables.groups.iter()
+            .map(|group| {
+                let mut members: Vec<String> = tables.group_members.iter()
+                    .filter(|(group_id, _)| *group_id == group.id)
+                    .filter_map(|(_, user_id)| tables.user(user_id))
+                    .map(|user| user.username.clone())
+                    .collect();
+
+                members.sort();
+
+                Group { id: group.id, name: group.name.clone(), members, time_created: group.time_created }
+            })
+            .collect();
+
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(groups)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_group(&self, name: &str) -> Result<Option<Group>, String> {
+        let mut tables = self.database.lock()?;
+
+        if tables.group_by_name(name).is_some() {
+            return Ok(None);
+        }
+
+        let group = GroupRow { id: Uuid::new_v4(), name: name.to_string(), time_created: Utc::now() };
+
+        tables.groups.push(group.clone());
+
+        Ok(Some(Group { id: group.id, name: group.name, members: Vec::new(), time_created: group.time_created }))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_group(&self, group_id: &Uuid) -> Result<bool, String> {
+        let mut tables = self.database.lock()?;
+
+        if !tables.groups.iter().any(|group| group.id == *group_id) {
+            return Ok(false);
+        }
+
+        tables.pdf_shares.retain(|share| share.group_id != Some(*group_id));
+        tables.group_members.retain(|(member_group_id, _)| member_group_id != group_id);
+        tables.groups.retain(|group| group.id != *group_id);
+
+        Ok(true)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        let mut tables = self.database.lock()?;
+
+        if !tables.groups.iter().any(|group| group.id == *group_id) || tables.user(user_id).is_none() {
+            return Ok(false);
+        }
+
+        // Adding a user that is already a member inserts nothing, but still counts as success
+        if !tables.group_members.contains(&(*group_id, *user_id)) {
+            tables.group_members.push((*group_id, *user_id));
+        }
+
+        Ok(true)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        let mut tables = self.database.lock()?;
+
+        let count = tables.group_members.len();
+
+        tables.group_members.retain(|member| *member != (*group_id, *user_id));
+
+        Ok(tables.group_members.len() < count)
+    }
+
+}
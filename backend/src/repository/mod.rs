@@ -1,2 +1,17 @@
+use std::fmt::Display;
+use tracing::error;
+
 pub mod db;
-pub mod pdfs;
\ No newline at end of file
+pub mod pdfs;
+pub mod users;
+pub mod audit;
+pub mod health;
+pub mod memory;
+pub mod sqlite;
+
+
+/// Logs the error of the database and returns the error the caller gets instead, whose message is shown to clients
+pub fn logged<T>(err: impl Display, mapped: T) -> T {
+    error!("{err}");
+    mapped
+}
\ No newline at end of file
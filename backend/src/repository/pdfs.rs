@@ -1,13 +1,21 @@
-use std::{sync::Arc, collections::HashSet};
+use std::{sync::Arc, collections::{HashMap, HashSet}};
 
-use chrono::Local;
-use log::trace;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use serde_json::{json, Value};
 use sqlx::{Pool, Postgres, QueryBuilder, PgConnection};
 use uuid::Uuid;
 
-use crate::{domain::models::pdf::{PdfOverview, Pdf, Tag, TotalPageNumber}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfMetadataDto}}};
+use crate::{domain::models::pdf::{ArchivedPdf, PdfOverview, Pdf, PdfFile, StoredPdf, Tag, TotalPageNumber}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfPatchDto, PdfMetadataDto}}};
+use crate::api::dto::bulk::{BulkOperationDto, BulkSelectionDto, BulkActionDto, BulkItemResultDto, BulkItemStatus};
 use crate::domain::repository::pdf::PdfRepository;
-use crate::errors::PdfMetadataByIdError;
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::domain::models::share::{Access, PdfShare, ShareLink, ShareLinkAccess, ShareLinkClient, ShareLinkOutcome};
+use crate::domain::models::audit::AuditAction;
+use crate::repository::audit::AuditRepositoryImpl;
+use crate::util::PdfUploaded;
+use crate::errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError};
+use crate::repository::logged;
 
 use async_trait::async_trait;
 
@@ -16,14 +24,297 @@ pub struct PdfRepositoryImpl {
     pub pool: Arc<Pool<Postgres>>
 }
 
+impl PdfRepositoryImpl {
+
+    /// Associates the tags with the pdf, tags that don't exist yet are created.
+    #[instrument(skip_all)]
+    async fn add_tags_with_connection(pdf_id: &Uuid, tags: &[String], conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO tags(name) SELECT UNNEST($1::TEXT[]) ON CONFLICT DO NOTHING",
+            tags
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO tags_to_pdfs(name, id) SELECT UNNEST($1::TEXT[]), $2 ON CONFLICT DO NOTHING",
+            tags,
+            pdf_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn access_with_connection(pdf_id: &Uuid, user_id: &Uuid, conn: &mut PgConnection) -> Result<Access, sqlx::Error> {
+        let access_level = sqlx::query_scalar!(
+            "SELECT pdf_access_level($1, $2)",
+            pdf_id,
+            user_id
+        )
+        .fetch_one(conn)
+        .await?;
+
+        Ok(Access::from_level(access_level.unwrap_or(0)))
+    }
+
+
+    /// Fails unless the user owns the pdf, pdfs the user can't see are reported as not found
+    #[instrument(skip_all)]
+    async fn require_owner_with_connection(pdf_id: &Uuid, user_id: &Uuid, conn: &mut PgConnection) -> Result<(), PdfAccessError> {
+        match Self::access_with_connection(pdf_id, user_id, conn).await {
+            Ok(Access::Owner) => Ok(()),
+            Ok(Access::None) => Err(PdfAccessError::NotFound("No pdf with given ID exists".to_string())),
+            Ok(_) => Err(PdfAccessError::Forbidden("Only the owner of the pdf may do this".to_string())),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error checking the permissions of the pdf".to_string())))
+        }
+    }
+
+
+    /// Locks the pdf until the end of the transaction and checks that the user may make the change and that the
+    /// pdf is still at one of the expected versions. Without expected versions any version is accepted.
+    #[instrument(skip_all)]
+    async fn lock_version_with_connection(
+        &self,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        expected_versions: &Option<Vec<i32>>,
+        required_access: impl FnOnce(&Pdf) -> Access + Send,
+        conn: &mut PgConnection
+    ) -> Result<(), PdfUpdateError> {
+        let current_pdf_res = sqlx::query_as!(
+            Pdf,
+            "SELECT * FROM pdfs WHERE id = $1 FOR UPDATE",
+            pdf_id
+        )
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let current_pdf = match current_pdf_res {
+            Ok(Some(pdf)) => pdf,
+            Ok(None) => return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+        };
+
+        let access = match Self::access_with_connection(pdf_id, user_id, conn).await {
+            Ok(access) => access,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+        };
+
+        if access == Access::None {
+            return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string()));
+        }
+
+        if access < required_access(&current_pdf) {
+            return Err(PdfUpdateError::Forbidden("The pdf is not shared with you with a permission that allows this change".to_string()));
+        }
+
+        match expected_versions {
+            Some(versions) if !versions.contains(&current_pdf.version) => {
+                let associated_tags = match self.get_associated_tags_of_pdf_with_connection(pdf_id, conn).await {
+                    Ok(tags) => tags,
+                    Err(_) => return Err(PdfUpdateError::DatabaseError("Error updating pdf".to_string()))
+                };
+
+                Err(PdfUpdateError::VersionMismatch(Box::new(Self::to_metadata_dto(current_pdf, associated_tags))))
+            },
+            _ => Ok(())
+        }
+    }
+
+
+    /// Records the changes made to the pdfs since the `before` snapshots were taken in the audit log
+    #[instrument(skip_all)]
+    async fn audit_changes_with_connection(
+        user_id: &Uuid,
+        action: AuditAction,
+        before: &HashMap<Uuid, Value>,
+        pdf_ids: &[Uuid],
+        conn: &mut PgConnection
+    ) -> Result<(), sqlx::Error> {
+        let after = AuditRepositoryImpl::pdf_snapshots_with_connection(pdf_ids, conn).await?;
+
+        AuditRepositoryImpl::record_changes_with_connection(user_id, action, before, &after, conn).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_associated_tags_of_pdf_with_connection(&self, pdf_id: &Uuid, conn: &mut PgConnection) -> Result<Vec<String>, PdfMetadataByIdError> {
+        let tags_query_res = sqlx::query_scalar!(
+            "SELECT name AS \"name!\" FROM tags_to_pdfs WHERE id = $1",
+            pdf_id
+        )
+        .fetch_all(conn)
+        .await;
+
+        match tags_query_res {
+            Ok(tags) => Ok(tags),
+            Err(err) => Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving the tags associated with pdf".to_string())))
+        }
+    }
+
+
+    /// Inserts the pdf along with its tags, which fails if the owner already has a pdf with the same file name
+    #[instrument(skip_all)]
+    async fn upload_with_transaction(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, sqlx::Error> {
+        let time_added = upload.time_added.unwrap_or_else(Utc::now);
+
+        let mut conn = self.pool.begin().await?;
+
+        let uploaded_pdf = sqlx::query_as!(
+            Pdf,
+            "INSERT INTO pdfs (title, file_name, author, pages, comments, time_added, picture, owner_id, storage_key) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+            upload.title,
+            upload.filename,
+            upload.author,
+            upload.pages,
+            upload.comments,
+            time_added,
+            upload.img,
+            owner_id,
+            upload.storage_key
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if let Some(pdf_id) = &uploaded_pdf.id {
+            Self::add_tags_with_connection(pdf_id, &upload.tags, &mut conn).await?;
+
+            Self::audit_changes_with_connection(owner_id, AuditAction::Upload, &HashMap::new(), &[*pdf_id], &mut conn).await?;
+        }
+
+        conn.commit().await?;
+
+        Ok(uploaded_pdf)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_file_missing_with_transaction(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let before = AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await?;
+
+        sqlx::query!(
+            "UPDATE pdfs SET file_missing = $2, version = version + 1 WHERE id = $1 AND file_missing <> $2",
+            pdf_id,
+            file_missing
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await?;
+
+        conn.commit().await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_picture_with_transaction(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let before = AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await?;
+
+        sqlx::query!(
+            "UPDATE pdfs SET picture = $2, version = version + 1 WHERE id = $1",
+            pdf_id,
+            picture
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await?;
+
+        conn.commit().await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags_with_transaction(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let pdf_ids = sqlx::query_scalar!(
+            "SELECT DISTINCT id AS \"id!\" FROM tags_to_pdfs WHERE name = ANY($1)",
+            sources
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let before = AuditRepositoryImpl::pdf_snapshots_with_connection(&pdf_ids, &mut conn).await?;
+
+        sqlx::query!(
+            "INSERT INTO tags(name) VALUES ($1) ON CONFLICT DO NOTHING",
+            target
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO tags_to_pdfs(name, id) SELECT $1, id FROM tags_to_pdfs WHERE name = ANY($2) ON CONFLICT DO NOTHING",
+            target,
+            sources
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // The associations with the source tags are deleted along with them
+        sqlx::query!(
+            "DELETE FROM tags WHERE name = ANY($1)",
+            sources
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &pdf_ids, &mut conn).await?;
+
+        conn.commit().await?;
+
+        Ok(pdf_ids.len())
+    }
+
+
+    fn to_metadata_dto(pdf: Pdf, tags: Vec<String>) -> PdfMetadataDto {
+        PdfMetadataDto {
+            id: pdf.id,
+            title: pdf.title,
+            file_name: pdf.file_name,
+            author: pdf.author,
+            pages: pdf.pages,
+            comments: pdf.comments,
+            uploaded: pdf.time_added,
+            last_accessed: pdf.last_accessed,
+            picture: pdf.picture,
+            tags: Some(tags),
+            version: pdf.version,
+            owner_id: pdf.owner_id,
+            file_missing: pdf.file_missing
+        }
+    }
+}
+
+
+/// How a share link is recorded in the audit log, without its token and password
+pub fn share_link_audit_details(share_link: &ShareLink) -> Value {
+    json!({
+        "id": share_link.id,
+        "expires_at": share_link.expires_at,
+        "max_downloads": share_link.max_downloads,
+        "password_protected": share_link.password_hash.is_some()
+    })
+}
+
+
 #[async_trait]
 impl PdfRepository for PdfRepositoryImpl {
 
-    async fn total_number_of_pdfs(&self) -> Result<Option<i64>, String> {
-        trace!("repository: total_number_of_pdfs()");
-    
+    #[instrument(skip_all)]
+    async fn total_number_of_pdfs(&self, user_id: &Uuid) -> Result<Option<i64>, String> {
         let pdf_count_result = sqlx::query!(
-            "SELECT count(*) FROM pdfs"
+            "SELECT count(*) FROM pdfs WHERE pdf_access_level(id, $1) > 0",
+            user_id
         )
         .fetch_one(self.pool.as_ref())
         .await;
@@ -36,35 +327,35 @@ impl PdfRepository for PdfRepositoryImpl {
     }
 
 
-    async fn get_pdfs_paged(&self, paging: &PagingDto) -> Result<Vec<PdfOverview>, String> {
-        trace!("repository: get_pdfs_paged()");
-
+    #[instrument(skip_all)]
+    async fn get_pdfs_paged(&self, paging: &PagingDto, user_id: &Uuid) -> Result<Vec<PdfOverview>, String> {
         let size: i64 = paging.size.unwrap() as i64;
         let page: i64 = size * (paging.page.unwrap() - 1) as i64;
 
         let paged_pdfs_result = sqlx::query_as!(
             PdfOverview,
-            "SELECT id, title, picture FROM pdfs ORDER BY time_added, id LIMIT $1 OFFSET $2",
+            "SELECT id, title, picture FROM pdfs WHERE pdf_access_level(id, $3) > 0 ORDER BY time_added, id LIMIT $1 OFFSET $2",
             size,
-            page
+            page,
+            user_id
         )
         .fetch_all(self.pool.as_ref())
         .await;
 
         match paged_pdfs_result {
             Ok(pdfs) => Ok(pdfs),
-            Err(_) => Err("Error retrieving paginated data".to_string())
+            Err(err) => Err(logged(err, "Error retrieving paginated data".to_string()))
         }
     }
 
 
-    async fn get_pdf_metadata(&self, pdf_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError> {
-        trace!("repository: get_pdf_metadata()");
-
+    #[instrument(skip_all)]
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError> {
         let pdf_metadata_res = sqlx::query_as!(
             Pdf,
-            "SELECT * FROM pdfs WHERE id = $1",
-            pdf_id
+            "SELECT * FROM pdfs WHERE id = $1 AND pdf_access_level(id, $2) > 0",
+            pdf_id,
+            user_id
         )
         .fetch_one(self.pool.as_ref())
         .await;
@@ -83,9 +374,8 @@ impl PdfRepository for PdfRepositoryImpl {
     }
 
 
+    #[instrument(skip_all)]
     async fn get_associated_tags_of_pdf(&self, pdf_id: &Uuid) -> Result<Vec<String>, PdfMetadataByIdError> {
-        trace!("repository: get_associated_tags_of_pdf()");
-
         let tags_query_res = sqlx::query_as!(
             Tag,
             "SELECT name FROM tags_to_pdfs WHERE id = $1",
@@ -109,52 +399,26 @@ impl PdfRepository for PdfRepositoryImpl {
     }
 
 
-    async fn get_associated_tags_of_pdf_with_connection(&self, pdf_id: &Uuid, conn: &mut PgConnection) -> Result<Vec<String>, PdfMetadataByIdError> {
-        trace!("repository: get_associated_tags_of_pdf()");
-
-        let tags_query_res = sqlx::query_as!(
-            Tag,
-            "SELECT name FROM tags_to_pdfs WHERE id = $1",
-            pdf_id
-        )
-        .fetch_all(conn)
-        .await;
-
-        let mut tags_as_vec: Vec<String> = Vec::new();
-
-        if tags_query_res.is_ok() {
-
-            for item in tags_query_res.unwrap().into_iter() {
-                tags_as_vec.push(item.name.unwrap());
-            }
-
-            return Ok(tags_as_vec);
-        }
-
-        return Err(PdfMetadataByIdError::DatabaseError("Error retrieving the tags associated with pdf".to_string()));
-    }
-
-
-    async fn get_by_id(&self, pdf_id: &Uuid) -> Result<String, String> {
-        trace!("repository: get_by_id()");
-
-        let query_res = sqlx::query!(
-            "SELECT * FROM pdfs WHERE id = $1",
-            pdf_id
+    #[instrument(skip_all)]
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError> {
+        let query_res = sqlx::query_scalar!(
+            "SELECT storage_key FROM pdfs WHERE id = $1 AND pdf_access_level(id, $2) > 0",
+            pdf_id,
+            user_id
         )
-        .fetch_one(self.pool.as_ref())
+        .fetch_optional(self.pool.as_ref())
         .await;
         
         match query_res {
-            Ok(record) => return Ok(record.file_name),
-            Err(_) => return Err("Error retrieving pdf information".to_string())
+            Ok(Some(storage_key)) => Ok(storage_key),
+            Ok(None) => Err(PdfMetadataByIdError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving pdf information".to_string())))
         }
     }
 
 
-    async fn search(&self, search: &PdfSearchDto) -> Result<PdfOverviewDto, String> {
-        trace!("repository: search()");
-
+    #[instrument(skip_all)]
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String> {
         let size: i64 = search.size.unwrap() as i64;
         let page: i64 = size * (search.page.unwrap() - 1) as i64; 
 
@@ -165,6 +429,7 @@ impl PdfRepository for PdfRepositoryImpl {
             ($1 IS NULL OR pdfs.title ILIKE CONCAT('%', $1, '%'))
             AND ($2 IS NULL OR pdfs.author ILIKE CONCAT('%', $2, '%'))
             AND ($3 IS NULL OR tags_to_pdfs.name ILIKE CONCAT('%', $3, '%'))
+            AND pdf_access_level(pdfs.id, $6) > 0
             ORDER BY pdfs.time_added, pdfs.id LIMIT $4 OFFSET $5
             "
         );
@@ -176,6 +441,7 @@ impl PdfRepository for PdfRepositoryImpl {
             ($1 IS NULL OR pdfs.title ILIKE CONCAT('%', $1, '%'))
             AND ($2 IS NULL OR pdfs.author ILIKE CONCAT('%', $2, '%'))
             AND ($3 IS NULL OR tags_to_pdfs.name ILIKE CONCAT('%', $3, '%'))
+            AND pdf_access_level(pdfs.id, $6) > 0
             "
         );
 
@@ -185,11 +451,12 @@ impl PdfRepository for PdfRepositoryImpl {
             .bind(search.tag.to_owned())
             .bind(size)
             .bind(page)
+            .bind(user_id)
             .fetch_all(self.pool.as_ref())
             .await;
 
         match search_pfd_res {
-            Err(_) => return Err("An error occured searching the pdfs".to_string()),
+            Err(err) => return Err(logged(err, "An error occured searching the pdfs".to_string())),
             _ => ()
         }
 
@@ -199,11 +466,12 @@ impl PdfRepository for PdfRepositoryImpl {
             .bind(search.tag.to_owned())
             .bind(size)
             .bind(page)
+            .bind(user_id)
             .fetch_one(self.pool.as_ref())
             .await;
 
         match search_count_res {
-            Err(_) => return Err("An error occured searching the pdfs".to_string()),
+            Err(err) => return Err(logged(err, "An error occured searching the pdfs".to_string())),
             _ => ()   
         }
 
@@ -212,17 +480,32 @@ impl PdfRepository for PdfRepositoryImpl {
     }
 
 
-    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid) -> Result<PdfMetadataDto, String> {
-        trace!("repository: update()");
-
+    #[instrument(skip_all)]
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
         const BIND_LIMIT: usize = 65535;
 
-        let request_tags_cloned = update.tags.unwrap_or_else(Vec::new).clone();
+        let request_tags_cloned = update.tags.clone().unwrap_or_default();
         let number_of_tags_in_request = request_tags_cloned.len();
 
         //Start transaction
         let mut conn = self.pool.begin().await.unwrap();
 
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            if update.title != current_pdf.title || update.author != current_pdf.author || update.picture != current_pdf.picture {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        self.lock_version_with_connection(pdf_id, user_id, &expected_versions, required_access, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+        };
+
         //Get all tags that we want to add and already have persisted in the database
         let persisted_tags = sqlx::query_as!(
             Tag,
@@ -233,7 +516,7 @@ impl PdfRepository for PdfRepositoryImpl {
         .await;
 
         match persisted_tags {
-            Err(_) => return Err("Error updating pdf".to_string()),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string()))),
             _ => ()
         }
 
@@ -259,7 +542,7 @@ impl PdfRepository for PdfRepositoryImpl {
         .await;
 
         match delete_existing_tag_relations {
-            Err(_) => return Err("Error updating pdf".to_string()),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string()))),
             _ => ()
         }
 
@@ -279,7 +562,7 @@ impl PdfRepository for PdfRepositoryImpl {
                 let insert_tags_res = insert_tags_query.execute(&mut *conn).await;
     
                 match insert_tags_res { 
-                    Err(_) => return Err("Error updating the pdf details".to_string()),
+                    Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating the pdf details".to_string()))),
                     _ => ()
                 }  
             }
@@ -297,14 +580,14 @@ impl PdfRepository for PdfRepositoryImpl {
             let insert_tag_pdf_relation_result = insert_tag_pdf_relation_query.execute(&mut *conn).await;
     
             match insert_tag_pdf_relation_result {
-                Err(_) => return Err("Error updating the pdf details".to_string()),
+                Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating the pdf details".to_string()))),
                     _ => ()
             }
         }
 
         let update_pdf_info_result = sqlx::query_as!(
             Pdf,
-            "UPDATE pdfs SET title = $2, author = $3, comments = $4, picture = $5 WHERE id = $1 RETURNING *",
+            "UPDATE pdfs SET title = $2, author = $3, comments = $4, picture = $5, version = version + 1 WHERE id = $1 RETURNING *",
             pdf_id,
             update.title,
             update.author,
@@ -315,95 +598,1188 @@ impl PdfRepository for PdfRepositoryImpl {
         .await;
 
         match update_pdf_info_result {
-            Err(_) => return Err("Error updating pdf".to_string()),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string()))),
             _ => ()
         }
 
         let associated_tags_res = self.get_associated_tags_of_pdf_with_connection(pdf_id, &mut *conn).await;
 
         match associated_tags_res {
-            Err(_) => return Err("Error updating pdf".to_string()),
+            Err(_) => return Err(PdfUpdateError::DatabaseError("Error updating pdf".to_string())),
             _ => ()
         }
 
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error updating pdf".to_string()));
+        }
+
         let transaction_result = conn.commit().await;
 
         match transaction_result {
-            Err(_) => return Err("Error updating pdf".to_string()),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string()))),
             _ => ()
         }
 
         let pdf_metadata = update_pdf_info_result.unwrap();
 
-        let return_dto = PdfMetadataDto {
-            id: pdf_metadata.id,
-            title: pdf_metadata.title,
-            file_name: pdf_metadata.file_name,
-            author: pdf_metadata.author,
-            pages: pdf_metadata.pages,
-            comments: pdf_metadata.comments,
-            uploaded: pdf_metadata.time_added,
-            last_accessed: pdf_metadata.last_accessed,
-            picture: pdf_metadata.picture,
-            tags: Some(associated_tags_res.unwrap())
-        };
+        let return_dto = Self::to_metadata_dto(pdf_metadata, associated_tags_res.unwrap());
 
 
         Ok(return_dto)
     }
 
-    async fn delete(&self, pdf_id: &Uuid) -> Result<String, String> {
-        trace!("repository: delete()");
+    #[instrument(skip_all)]
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let patch_error = || PdfUpdateError::DatabaseError("Error updating pdf".to_string());
 
-        let file_name_to_delete_res = sqlx::query!(
-            "SELECT file_name FROM pdfs where id=$1",
-            pdf_id
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            let changes = |field: &Option<Option<String>>, current: &Option<String>| field.as_ref().is_some_and(|value| value != current);
+
+            if changes(&patch.title, &current_pdf.title) || changes(&patch.author, &current_pdf.author) || changes(&patch.picture, &current_pdf.picture) {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        self.lock_version_with_connection(pdf_id, user_id, &expected_versions, required_access, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        // Each field is only overwritten if it is part of the patch, a field set to null is cleared
+        let patch_pdf_info_result = sqlx::query_as!(
+            Pdf,
+            "UPDATE pdfs SET
+                title = CASE WHEN $2 THEN $3 ELSE title END,
+                author = CASE WHEN $4 THEN $5 ELSE author END,
+                comments = CASE WHEN $6 THEN $7 ELSE comments END,
+                picture = CASE WHEN $8 THEN $9 ELSE picture END,
+                version = version + 1
+            WHERE id = $1 RETURNING *",
+            pdf_id,
+            patch.title.is_some(),
+            patch.title.clone().flatten(),
+            patch.author.is_some(),
+            patch.author.clone().flatten(),
+            patch.comments.is_some(),
+            patch.comments.clone().flatten(),
+            patch.picture.is_some(),
+            patch.picture.clone().flatten()
         )
-        .fetch_one(self.pool.as_ref())
+        .fetch_optional(&mut *conn)
         .await;
 
-        match file_name_to_delete_res {
-            Err(_) => return Err("Failed to delete pdf".to_string()),
-            _ => ()
+        let pdf_metadata = match patch_pdf_info_result {
+            Ok(Some(pdf)) => pdf,
+            Ok(None) => return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        if let Some(tags) = &patch.tags {
+            let delete_existing_tag_relations = sqlx::query!(
+                "DELETE FROM tags_to_pdfs WHERE id = $1",
+                pdf_id
+            )
+            .execute(&mut *conn)
+            .await;
+
+            if delete_existing_tag_relations.is_err() {
+                return Err(patch_error());
+            }
+
+            if let Some(tags) = tags {
+                if Self::add_tags_with_connection(pdf_id, tags, &mut conn).await.is_err() {
+                    return Err(patch_error());
+                }
+            }
+        }
+
+        if let Some(add_tags) = &patch.add_tags {
+            if Self::add_tags_with_connection(pdf_id, add_tags, &mut conn).await.is_err() {
+                return Err(patch_error());
+            }
+        }
+
+        if let Some(remove_tags) = &patch.remove_tags {
+            let remove_tag_relations = sqlx::query!(
+                "DELETE FROM tags_to_pdfs WHERE id = $1 AND name = ANY($2)",
+                pdf_id,
+                &remove_tags[..]
+            )
+            .execute(&mut *conn)
+            .await;
+
+            if remove_tag_relations.is_err() {
+                return Err(patch_error());
+            }
+        }
+
+        let associated_tags = match self.get_associated_tags_of_pdf_with_connection(pdf_id, &mut conn).await {
+            Ok(tags) => tags,
+            Err(_) => return Err(patch_error())
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(patch_error());
+        }
+
+        if conn.commit().await.is_err() {
+            return Err(patch_error());
         }
 
-        let delete_result = sqlx::query!(
-            "DELETE FROM pdfs WHERE id=$1",
+        Ok(Self::to_metadata_dto(pdf_metadata, associated_tags))
+    }
+
+    #[instrument(skip_all)]
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Option<String>, PdfAccessError> {
+        let delete_error = || PdfAccessError::DatabaseError("Failed to delete pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let storage_key_res = sqlx::query_scalar!(
+            "DELETE FROM pdfs WHERE id = $1 RETURNING storage_key",
             pdf_id
         )
-        .execute(self.pool.as_ref())
+        .fetch_one(&mut *conn)
+        .await;
+
+        let storage_key = match storage_key_res {
+            Ok(storage_key) => storage_key,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Delete, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(delete_error());
+        }
+
+        // The file is shared by all pdfs with the same content
+        let still_used_res = sqlx::query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM pdfs WHERE storage_key = $1) AS \"used!\"",
+            storage_key
+        )
+        .fetch_one(&mut *conn)
         .await;
 
-        match delete_result {
-            Ok(_) => Ok(file_name_to_delete_res.unwrap().file_name),
-            Err(_)=> Err("Failed to delete pdf".to_string())
+        let still_used = match still_used_res {
+            Ok(still_used) => still_used,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        match conn.commit().await {
+            Ok(_) if still_used => Ok(None),
+            Ok(_) => Ok(Some(storage_key)),
+            Err(err) => Err(logged(err, delete_error()))
         }
     }
 
-    async fn upload(&self, title: String, filename: String, author: Option<String>, pages: Option<i32>, img: String) -> Result<Pdf, sqlx::Error> {
-        trace!("repository: upload()");
+    #[instrument(skip_all)]
+    async fn bulk(&self, bulk: &BulkOperationDto, user_id: &Uuid) -> Result<(Vec<BulkItemResultDto>, Vec<String>), String> {
+        let bulk_error = || "Error applying bulk operation".to_string();
 
-        let current_time = Local::now();
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
 
-        let upload_query_res = sqlx::query_as!(
-            Pdf,
-            "INSERT INTO pdfs (title, file_name,author, pages, time_added, picture) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
-            title,
-            filename,
-            author,
-            pages,
-            current_time,
-            img
+        // The selected pdfs are locked, so the per item results can't be invalidated by concurrent changes
+        let (requested_ids, selected_ids_res) = match &bulk.selection {
+            BulkSelectionDto::Ids(ids) => {
+                let mut seen_ids = HashSet::new();
+                let requested_ids: Vec<Uuid> = ids.iter().filter(|id| seen_ids.insert(**id)).copied().collect();
+
+                let selected_ids_res = sqlx::query!(
+                    "SELECT id, pdf_access_level(id, $2) AS \"access_level!\" FROM pdfs
+                    WHERE id = ANY($1) AND pdf_access_level(id, $2) > 0
+                    ORDER BY id FOR UPDATE",
+                    &requested_ids[..],
+                    user_id
+                )
+                .fetch_all(&mut *conn)
+                .await
+                .map(|selected| selected.into_iter().map(|record| (record.id, record.access_level)).collect::<Vec<_>>());
+
+                (Some(requested_ids), selected_ids_res)
+            },
+            BulkSelectionDto::Search(search) => {
+                let selected_ids_res = sqlx::query!(
+                    "SELECT id, pdf_access_level(id, $4) AS \"access_level!\" FROM pdfs
+                    WHERE
+                    ($1::TEXT IS NULL OR title ILIKE CONCAT('%', $1, '%'))
+                    AND ($2::TEXT IS NULL OR author ILIKE CONCAT('%', $2, '%'))
+                    AND ($3::TEXT IS NULL OR EXISTS (SELECT 1 FROM tags_to_pdfs WHERE tags_to_pdfs.id = pdfs.id AND tags_to_pdfs.name ILIKE CONCAT('%', $3, '%')))
+                    AND pdf_access_level(id, $4) > 0
+                    ORDER BY time_added, id FOR UPDATE",
+                    search.title,
+                    search.author,
+                    search.tag,
+                    user_id
+                )
+                .fetch_all(&mut *conn)
+                .await
+                .map(|selected| selected.into_iter().map(|record| (record.id, record.access_level)).collect::<Vec<_>>());
+
+                (None, selected_ids_res)
+            }
+        };
+
+        let selected = match selected_ids_res {
+            Ok(selected) => selected,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let required_access = match &bulk.action {
+            BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. } => Access::Annotate,
+            BulkActionDto::SetAuthor { .. } => Access::Edit,
+            BulkActionDto::Delete => Access::Owner
+        };
+
+        let selected_in_order: Vec<Uuid> = selected.iter().map(|(id, _)| *id).collect();
+
+        // The action is only applied to the selected pdfs the user has the permission for
+        let (permitted, forbidden): (Vec<_>, Vec<_>) = selected.into_iter()
+            .partition(|(_, access_level)| Access::from_level(*access_level) >= required_access);
+
+        let selected_ids: Vec<Uuid> = permitted.into_iter().map(|(id, _)| id).collect();
+        let forbidden_ids: HashSet<Uuid> = forbidden.into_iter().map(|(id, _)| id).collect();
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&selected_ids, &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let mut deleted_storage_keys = Vec::new();
+
+        let changed_ids_res: Result<Vec<Uuid>, sqlx::Error> = match &bulk.action {
+            BulkActionDto::AddTags { tags } => {
+                let create_tags_res = sqlx::query!(
+                    "INSERT INTO tags(name) SELECT UNNEST($1::TEXT[]) ON CONFLICT DO NOTHING",
+                    &tags[..]
+                )
+                .execute(&mut *conn)
+                .await;
+
+                match create_tags_res {
+                    Err(err) => Err(err),
+                    Ok(_) => sqlx::query_scalar!(
+                        "INSERT INTO tags_to_pdfs(name, id)
+                        SELECT DISTINCT tag, pdf_id FROM UNNEST($1::TEXT[]) AS tag CROSS JOIN UNNEST($2::UUID[]) AS pdf_id
+                        ON CONFLICT DO NOTHING RETURNING id",
+                        &tags[..],
+                        &selected_ids[..]
+                    )
+                    .fetch_all(&mut *conn)
+                    .await
+                }
+            },
+            BulkActionDto::RemoveTags { tags } => {
+                sqlx::query_scalar!(
+                    "DELETE FROM tags_to_pdfs WHERE id = ANY($1) AND name = ANY($2) RETURNING id",
+                    &selected_ids[..],
+                    &tags[..]
+                )
+                .fetch_all(&mut *conn)
+                .await
+            },
+            BulkActionDto::SetAuthor { author } => {
+                sqlx::query_scalar!(
+                    "UPDATE pdfs SET author = $2, version = version + 1 WHERE id = ANY($1) AND author IS DISTINCT FROM $2 RETURNING id",
+                    &selected_ids[..],
+                    author.as_deref()
+                )
+                .fetch_all(&mut *conn)
+                .await
+            },
+            BulkActionDto::Delete => {
+                let deleted_res = sqlx::query!(
+                    "DELETE FROM pdfs WHERE id = ANY($1) RETURNING id, storage_key",
+                    &selected_ids[..]
+                )
+                .fetch_all(&mut *conn)
+                .await;
+
+                deleted_res.map(|deleted| deleted.into_iter().map(|record| {
+                    deleted_storage_keys.push(record.storage_key);
+                    record.id
+                }).collect())
+            }
+        };
+
+        let changed_ids: HashSet<Uuid> = match changed_ids_res {
+            Ok(ids) => ids.into_iter().collect(),
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        // Tags are part of the pdf details, changing them creates a new version
+        if matches!(bulk.action, BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. }) {
+            let changed_ids_vec: Vec<Uuid> = changed_ids.iter().copied().collect();
+
+            let bump_version_res = sqlx::query!(
+                "UPDATE pdfs SET version = version + 1 WHERE id = ANY($1)",
+                &changed_ids_vec[..]
+            )
+            .execute(&mut *conn)
+            .await;
+
+            if bump_version_res.is_err() {
+                return Err(bulk_error());
+            }
+        }
+
+        // Only the pdfs that actually changed are recorded, a dry run rolls the entries back along with the changes
+        let audit_action = match bulk.action {
+            BulkActionDto::Delete => AuditAction::Delete,
+            _ => AuditAction::Update
+        };
+
+        if Self::audit_changes_with_connection(user_id, audit_action, &before, &selected_ids, &mut conn).await.is_err() {
+            return Err(bulk_error());
+        }
+
+        // Files are shared by all pdfs with the same content, only the ones no pdf uses anymore can be removed
+        let unused_storage_keys_res = sqlx::query_scalar!(
+            "SELECT DISTINCT deleted.storage_key AS \"storage_key!\" FROM UNNEST($1::TEXT[]) AS deleted(storage_key)
+            WHERE NOT EXISTS (SELECT 1 FROM pdfs WHERE pdfs.storage_key = deleted.storage_key)",
+            &deleted_storage_keys[..]
         )
-        .fetch_one(self.pool.as_ref())
+        .fetch_all(&mut *conn)
         .await;
 
-        match upload_query_res {
-            Ok(uploaded_pdf) => Ok(uploaded_pdf),
-            Err(err) => Err(err)
+        let unused_storage_keys = match unused_storage_keys_res {
+            Ok(storage_keys) => storage_keys,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let transaction_res = if bulk.dry_run {
+            conn.rollback().await
+        } else {
+            conn.commit().await
+        };
+
+        if transaction_res.is_err() {
+            return Err(bulk_error());
         }
 
+        let status_of = |id: &Uuid| {
+            if forbidden_ids.contains(id) {
+                BulkItemStatus::Forbidden
+            } else if changed_ids.contains(id) {
+                BulkItemStatus::Changed
+            } else {
+                BulkItemStatus::Unchanged
+            }
+        };
+
+        let results = match requested_ids {
+            Some(requested_ids) => {
+                let selected_ids: HashSet<Uuid> = selected_in_order.into_iter().collect();
+
+                requested_ids.into_iter().map(|id| {
+                    let status = if selected_ids.contains(&id) { status_of(&id) } else { BulkItemStatus::NotFound };
+                    BulkItemResultDto { id, status }
+                }).collect()
+            },
+            None => selected_in_order.into_iter().map(|id| BulkItemResultDto { id, status: status_of(&id) }).collect()
+        };
+
+        Ok((results, unused_storage_keys))
+    }
+
+    #[instrument(skip_all)]
+    async fn upload(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, String> {
+        match self.upload_with_transaction(upload, owner_id).await {
+            Ok(pdf) => Ok(pdf),
+            Err(err) => Err(logged(err, "Error storing the uploaded pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn is_storage_key_used(&self, storage_key: &str) -> Result<bool, String> {
+        let used_res = sqlx::query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM pdfs WHERE storage_key = $1) AS \"used!\"",
+            storage_key
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match used_res {
+            Ok(used) => Ok(used),
+            Err(err) => Err(logged(err, "Error checking the stored files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_stored_pdfs(&self) -> Result<Vec<StoredPdf>, String> {
+        let stored_pdfs_res = sqlx::query_as!(
+            StoredPdf,
+            "SELECT id, storage_key, COALESCE(picture, '') <> '' AS \"has_picture!\", file_missing FROM pdfs ORDER BY storage_key, id"
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match stored_pdfs_res {
+            Ok(stored_pdfs) => Ok(stored_pdfs),
+            Err(err) => Err(logged(err, "Error retrieving the stored pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_file_missing(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), String> {
+        match self.set_file_missing_with_transaction(pdf_id, file_missing, user_id).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error marking the file of the pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_picture(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), String> {
+        match self.set_picture_with_transaction(pdf_id, picture, user_id).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error storing the preview of the pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String> {
+        let sources: Vec<String> = sources.iter().filter(|source| *source != target).cloned().collect();
+
+        match self.merge_tags_with_transaction(&sources, target, user_id).await {
+            Ok(merged) => Ok(merged),
+            Err(err) => Err(logged(err, "Error merging the tags".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_archived_pdfs(&self, since: Option<DateTime<Utc>>) -> Result<Vec<ArchivedPdf>, String> {
+        let pdfs_res = sqlx::query_as!(
+            Pdf,
+            "SELECT * FROM pdfs
+            WHERE $1::timestamptz IS NULL OR time_added >= $1 OR id IN (SELECT target_id FROM audit_log WHERE time_created >= $1)
+            ORDER BY time_added, id",
+            since
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        let pdfs = match pdfs_res {
+            Ok(pdfs) => pdfs,
+            Err(err) => return Err(logged(err, "Error retrieving the pdfs to archive".to_string()))
+        };
+
+        let pdf_ids: Vec<Uuid> = pdfs.iter().filter_map(|pdf| pdf.id).collect();
+
+        let owners_res = sqlx::query!("SELECT id, username FROM users")
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let owners: HashMap<Uuid, String> = match owners_res {
+            Ok(owners) => owners.into_iter().map(|owner| (owner.id, owner.username)).collect(),
+            Err(err) => return Err(logged(err, "Error retrieving the owners of the pdfs to archive".to_string()))
+        };
+
+        let tags_res = sqlx::query!("SELECT id, name FROM tags_to_pdfs WHERE id = ANY($1) ORDER BY name", &pdf_ids)
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        match tags_res {
+            Ok(rows) => rows.into_iter().for_each(|row| tags.entry(row.id).or_default().push(row.name)),
+            Err(err) => return Err(logged(err, "Error retrieving the tags of the pdfs to archive".to_string()))
+        }
+
+        Ok(pdfs.into_iter()
+            .map(|pdf| ArchivedPdf {
+                owner: owners.get(&pdf.owner_id).cloned().unwrap_or_default(),
+                tags: pdf.id.and_then(|pdf_id| tags.remove(&pdf_id)).unwrap_or_default(),
+                pdf
+            })
+            .collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn rebuild_search_index(&self) -> Result<(), String> {
+        // Postgres searches the pdfs and their tags with their plain indexes
+        for table in ["pdfs", "tags_to_pdfs"] {
+            let reindex_res = sqlx::query(&format!("REINDEX TABLE {table}"))
+                .execute(self.pool.as_ref())
+                .await;
+
+            if let Err(err) = reindex_res {
+                return Err(logged(err, "Error rebuilding the search index".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn count_pdfs_and_pages(&self) -> Result<(i64, i64), String> {
+        let count_res = sqlx::query!(
+            "SELECT count(*) AS \"documents!\", COALESCE(sum(pages), 0)::bigint AS \"pages!\" FROM pdfs"
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match count_res {
+            Ok(count) => Ok((count.documents, count.pages)),
+            Err(err) => Err(logged(err, "Error counting the pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as!(
+            PdfFile,
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE owner_id = $1 ORDER BY file_name",
+            owner_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as!(
+            PdfFile,
+            "SELECT pdfs.id, pdfs.file_name, pdfs.time_added, pdfs.storage_key FROM pdfs JOIN tags_to_pdfs ON pdfs.id = tags_to_pdfs.id
+            WHERE tags_to_pdfs.name = $1 AND pdfs.owner_id = $2 ORDER BY pdfs.file_name",
+            tag,
+            owner_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as!(
+            PdfFile,
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE author = $1 AND owner_id = $2 ORDER BY file_name",
+            author,
+            owner_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String> {
+        let pdf_file_res = sqlx::query_as!(
+            PdfFile,
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE file_name = $1 AND owner_id = $2",
+            file_name,
+            owner_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match pdf_file_res {
+            Ok(pdf_file) => Ok(pdf_file),
+            Err(err) => Err(logged(err, "Error retrieving pdf file".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let tags_res = sqlx::query!(
+            "SELECT name FROM tags
+            WHERE EXISTS (SELECT 1 FROM tags_to_pdfs JOIN pdfs ON pdfs.id = tags_to_pdfs.id WHERE tags_to_pdfs.name = tags.name AND pdfs.owner_id = $1)
+            OR NOT EXISTS (SELECT 1 FROM tags_to_pdfs WHERE tags_to_pdfs.name = tags.name)
+            ORDER BY name",
+            owner_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match tags_res {
+            Ok(tags) => Ok(tags.into_iter().map(|t| t.name).collect()),
+            Err(err) => Err(logged(err, "Error retrieving tags".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let authors_res = sqlx::query!(
+            "SELECT DISTINCT author FROM pdfs WHERE owner_id = $1 AND author IS NOT NULL AND author <> '' ORDER BY author",
+            owner_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match authors_res {
+            Ok(authors) => Ok(authors.into_iter().filter_map(|a| a.author).collect()),
+            Err(err) => Err(logged(err, "Error retrieving authors".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, "Error creating tag".to_string()))
+        };
+
+        let created_res = sqlx::query!(
+            "INSERT INTO tags(name) VALUES ($1) ON CONFLICT DO NOTHING",
+            tag
+        )
+        .execute(&mut *conn)
+        .await;
+
+        match created_res {
+            Ok(result) if result.rows_affected() > 0 => {
+                let changes = json!({ "tag": { "old": null, "new": tag } });
+
+                if AuditRepositoryImpl::record_with_connection(user_id, AuditAction::TagCreate, None, changes, &mut conn).await.is_err() {
+                    return Err("Error creating tag".to_string());
+                }
+            },
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, "Error creating tag".to_string()))
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error creating tag".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        };
+
+        self.lock_version_with_connection(pdf_id, user_id, &None, |_| Access::Annotate, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        };
+
+        let create_tag_res = sqlx::query!(
+            "INSERT INTO tags(name) VALUES ($1) ON CONFLICT DO NOTHING",
+            tag
+        )
+        .execute(&mut *conn)
+        .await;
+
+        if create_tag_res.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string()));
+        }
+
+        let add_relation_res = sqlx::query!(
+            "WITH added AS (INSERT INTO tags_to_pdfs(name, id) VALUES ($1, $2) ON CONFLICT DO NOTHING RETURNING id)
+            UPDATE pdfs SET version = version + 1 WHERE id IN (SELECT id FROM added)",
+            tag,
+            pdf_id
+        )
+        .execute(&mut *conn)
+        .await;
+
+        if add_relation_res.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string()));
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string()));
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        };
+
+        self.lock_version_with_connection(pdf_id, user_id, &None, |_| Access::Annotate, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        };
+
+        let remove_relation_res = sqlx::query!(
+            "WITH removed AS (DELETE FROM tags_to_pdfs WHERE id = $1 AND name = $2 RETURNING id)
+            UPDATE pdfs SET version = version + 1 WHERE id IN (SELECT id FROM removed)",
+            pdf_id,
+            tag
+        )
+        .execute(&mut *conn)
+        .await;
+
+        if remove_relation_res.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string()));
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string()));
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShare>, PdfAccessError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the shares of the pdf".to_string())))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let shares_res = sqlx::query_as!(
+            PdfShare,
+            "SELECT pdf_shares.user_id, users.username AS \"username?\", pdf_shares.group_id, groups.name AS \"group_name?\", pdf_shares.permission
+            FROM pdf_shares LEFT JOIN users ON users.id = pdf_shares.user_id LEFT JOIN groups ON groups.id = pdf_shares.group_id
+            WHERE pdf_shares.pdf_id = $1
+            ORDER BY users.username, groups.name",
+            pdf_id
+        )
+        .fetch_all(&mut *conn)
+        .await;
+
+        match shares_res {
+            Ok(shares) => Ok(shares),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the shares of the pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: &PdfShareDto) -> Result<(), PdfAccessError> {
+        let share_error = || PdfAccessError::DatabaseError("Error sharing the pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, share_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, share_error()))
+        };
+
+        let share_res = match (&share.user, &share.group) {
+            (Some(username), None) => {
+                let share_user_id = match sqlx::query_scalar!("SELECT id FROM users WHERE username = $1", username).fetch_optional(&mut *conn).await {
+                    Ok(Some(share_user_id)) => share_user_id,
+                    Ok(None) => return Err(PdfAccessError::InvalidInput(format!("No user named '{}' exists", username))),
+                    Err(err) => return Err(logged(err, share_error()))
+                };
+
+                if share_user_id == *user_id {
+                    return Err(PdfAccessError::InvalidInput("A pdf can't be shared with its owner".to_string()));
+                }
+
+                sqlx::query!(
+                    "INSERT INTO pdf_shares (pdf_id, user_id, permission) VALUES ($1, $2, $3)
+                    ON CONFLICT (pdf_id, user_id) DO UPDATE SET permission = EXCLUDED.permission",
+                    pdf_id,
+                    share_user_id,
+                    share.permission.as_str()
+                )
+                .execute(&mut *conn)
+                .await
+            },
+            (None, Some(group_name)) => {
+                let group_id = match sqlx::query_scalar!("SELECT id FROM groups WHERE name = $1", group_name).fetch_optional(&mut *conn).await {
+                    Ok(Some(group_id)) => group_id,
+                    Ok(None) => return Err(PdfAccessError::InvalidInput(format!("No group named '{}' exists", group_name))),
+                    Err(err) => return Err(logged(err, share_error()))
+                };
+
+                sqlx::query!(
+                    "INSERT INTO pdf_shares (pdf_id, group_id, permission) VALUES ($1, $2, $3)
+                    ON CONFLICT (pdf_id, group_id) DO UPDATE SET permission = EXCLUDED.permission",
+                    pdf_id,
+                    group_id,
+                    share.permission.as_str()
+                )
+                .execute(&mut *conn)
+                .await
+            },
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        if share_res.is_err() {
+            return Err(share_error());
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Share, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(share_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, share_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: &ShareTargetDto) -> Result<bool, PdfAccessError> {
+        let unshare_error = || PdfAccessError::DatabaseError("Error removing the share".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        let delete_res = match (&target.user, &target.group) {
+            (Some(username), None) => sqlx::query!(
+                "DELETE FROM pdf_shares WHERE pdf_id = $1 AND user_id = (SELECT id FROM users WHERE username = $2)",
+                pdf_id,
+                username
+            )
+            .execute(&mut *conn)
+            .await,
+            (None, Some(group_name)) => sqlx::query!(
+                "DELETE FROM pdf_shares WHERE pdf_id = $1 AND group_id = (SELECT id FROM groups WHERE name = $2)",
+                pdf_id,
+                group_name
+            )
+            .execute(&mut *conn)
+            .await,
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        let unshared = match delete_res {
+            Ok(result) => result.rows_affected() > 0,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Unshare, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(unshare_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(unshared),
+            Err(err) => Err(logged(err, unshare_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_share_link(
+        &self,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        token_hash: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<i32>
+    ) -> Result<ShareLink, PdfAccessError> {
+        let create_error = || PdfAccessError::DatabaseError("Error creating the share link".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, create_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let share_link_res = sqlx::query_as!(
+            ShareLink,
+            "INSERT INTO share_links (pdf_id, created_by, token_hash, password_hash, expires_at, max_downloads)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at",
+            pdf_id,
+            user_id,
+            token_hash,
+            password_hash,
+            expires_at,
+            max_downloads
+        )
+        .fetch_one(&mut *conn)
+        .await;
+
+        let share_link = match share_link_res {
+            Ok(share_link) => share_link,
+            Err(err) => return Err(logged(err, create_error()))
+        };
+
+        let changes = json!({ "share_link": { "old": null, "new": share_link_audit_details(&share_link) } });
+
+        if AuditRepositoryImpl::record_with_connection(user_id, AuditAction::ShareLinkCreate, Some(pdf_id), changes, &mut conn).await.is_err() {
+            return Err(create_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(share_link),
+            Err(err) => Err(logged(err, create_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLink>, PdfAccessError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the share links of the pdf".to_string())))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let share_links_res = sqlx::query_as!(
+            ShareLink,
+            "SELECT id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at
+            FROM share_links WHERE pdf_id = $1 ORDER BY time_created DESC",
+            pdf_id
+        )
+        .fetch_all(&mut *conn)
+        .await;
+
+        match share_links_res {
+            Ok(share_links) => Ok(share_links),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the share links of the pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<bool, PdfAccessError> {
+        let revoke_error = || PdfAccessError::DatabaseError("Error revoking the share link".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, revoke_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let revoke_res = sqlx::query_as!(
+            ShareLink,
+            "UPDATE share_links SET revoked_at = now() WHERE id = $1 AND pdf_id = $2 AND revoked_at IS NULL
+            RETURNING id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at",
+            share_link_id,
+            pdf_id
+        )
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let revoked = match revoke_res {
+            Ok(Some(revoked)) => revoked,
+            Ok(None) => return Ok(false),
+            Err(err) => return Err(logged(err, revoke_error()))
+        };
+
+        let changes = json!({ "share_link": { "old": share_link_audit_details(&revoked), "new": null } });
+
+        if AuditRepositoryImpl::record_with_connection(user_id, AuditAction::ShareLinkRevoke, Some(pdf_id), changes, &mut conn).await.is_err() {
+            return Err(revoke_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(true),
+            Err(err) => Err(logged(err, revoke_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Option<Vec<ShareLinkAccess>>, PdfAccessError> {
+        let accesses_error = || PdfAccessError::DatabaseError("Error retrieving the accesses of the share link".to_string());
+
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, accesses_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        match sqlx::query_scalar!("SELECT EXISTS (SELECT 1 FROM share_links WHERE id = $1 AND pdf_id = $2)", share_link_id, pdf_id).fetch_one(&mut *conn).await {
+            Ok(Some(true)) => (),
+            Ok(_) => return Ok(None),
+            Err(err) => return Err(logged(err, accesses_error()))
+        }
+
+        let accesses_res = sqlx::query_as!(
+            ShareLinkAccess,
+            "SELECT time_accessed, ip_address, user_agent, outcome FROM share_link_accesses
+            WHERE share_link_id = $1 ORDER BY time_accessed DESC",
+            share_link_id
+        )
+        .fetch_all(&mut *conn)
+        .await;
+
+        match accesses_res {
+            Ok(accesses) => Ok(Some(accesses)),
+            Err(err) => Err(logged(err, accesses_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link(&self, token_hash: &str) -> Result<Option<(ShareLink, Pdf)>, String> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, "Error retrieving the share link".to_string()))
+        };
+
+        let share_link_res = sqlx::query_as!(
+            ShareLink,
+            "SELECT id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at
+            FROM share_links WHERE token_hash = $1",
+            token_hash
+        )
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let share_link = match share_link_res {
+            Ok(Some(share_link)) => share_link,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(logged(err, "Error retrieving the share link".to_string()))
+        };
+
+        // The link grants access on its own, so the pdf isn't filtered by who may see it
+        let pdf_res = sqlx::query_as!(
+            Pdf,
+            "SELECT * FROM pdfs WHERE id = $1",
+            share_link.pdf_id
+        )
+        .fetch_one(&mut *conn)
+        .await;
+
+        match pdf_res {
+            Ok(pdf) => Ok(Some((share_link, pdf))),
+            Err(err) => Err(logged(err, "Error retrieving the shared pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_access(&self, share_link_id: &Uuid, client: &ShareLinkClient, outcome: ShareLinkOutcome) -> Result<(), String> {
+        let record_res = sqlx::query!(
+            "INSERT INTO share_link_accesses (share_link_id, ip_address, user_agent, outcome) VALUES ($1, $2, $3, $4)",
+            share_link_id,
+            client.ip_address,
+            client.user_agent,
+            outcome.as_str()
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match record_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error recording the access of the share link".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_download(&self, share_link_id: &Uuid, client: &ShareLinkClient) -> Result<bool, String> {
+        let download_error = || "Error recording the download of the share link".to_string();
+
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(err) => return Err(logged(err, download_error()))
+        };
+
+        // Checking the limit in the update itself keeps concurrent downloads from exceeding it
+        let count_res = sqlx::query!(
+            "UPDATE share_links SET download_count = download_count + 1
+            WHERE id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())
+            AND (max_downloads IS NULL OR download_count < max_downloads)",
+            share_link_id
+        )
+        .execute(&mut *tx)
+        .await;
+
+        match count_res {
+            Ok(result) if result.rows_affected() == 0 => return Ok(false),
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, download_error()))
+        }
+
+        let record_res = sqlx::query!(
+            "INSERT INTO share_link_accesses (share_link_id, ip_address, user_agent, outcome) VALUES ($1, $2, $3, $4)",
+            share_link_id,
+            client.ip_address,
+            client.user_agent,
+            ShareLinkOutcome::Downloaded.as_str()
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if record_res.is_err() {
+            return Err(download_error());
+        }
+
+        match tx.commit().await {
+            Ok(_) => Ok(true),
+            Err(err) => Err(logged(err, download_error()))
+        }
     }
-    
 
 }
\ No newline at end of file
@@ -0,0 +1,184 @@
+use std::{sync::Arc, collections::{BTreeMap, BTreeSet, HashMap}};
+
+use chrono::Utc;
+use tracing::instrument;
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use uuid::Uuid;
+
+use crate::api::dto::audit::AuditLogSearchDto;
+use crate::domain::models::audit::{AuditAction, AuditEntry};
+use crate::domain::models::pdf::Pdf;
+use crate::domain::repository::audit::AuditRepository;
+use crate::repository::audit::diff;
+use crate::repository::logged;
+
+use async_trait::async_trait;
+
+
+pub struct SqliteAuditRepository {
+    pub pool: Arc<Pool<Sqlite>>
+}
+
+impl SqliteAuditRepository {
+
+    /// Same as `AuditRepositoryImpl::pdf_snapshots_with_connection`. SQLite runs in the process, so querying the
+    /// pdfs one by one is cheap.
+    #[instrument(skip_all)]
+    pub async fn pdf_snapshots_with_connection(pdf_ids: &[Uuid], conn: &mut SqliteConnection) -> Result<HashMap<Uuid, Value>, sqlx::Error> {
+        let mut snapshots = HashMap::new();
+
+        for pdf_id in pdf_ids {
+            let pdf = sqlx::query_as::<_, Pdf>("SELECT * FROM pdfs WHERE id = ?1")
+                .bind(pdf_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+            let Some(pdf) = pdf else {
+                continue;
+            };
+
+            let tags = sqlx::query_scalar::<_, String>("SELECT name FROM tags_to_pdfs WHERE id = ?1 ORDER BY name")
+                .bind(pdf_id)
+                .fetch_all(&mut *conn)
+                .await?;
+
+            let shares = sqlx::query_as::<_, (String, String)>(
+                "SELECT COALESCE('user:' || users.username, 'group:' || groups.name), pdf_shares.permission
+                FROM pdf_shares LEFT JOIN users ON users.id = pdf_shares.user_id LEFT JOIN groups ON groups.id = pdf_shares.group_id
+                WHERE pdf_shares.pdf_id = ?1"
+            )
+            .bind(pdf_id)
+            .fetch_all(&mut *conn)
+            .await?;
+
+            let shares: BTreeMap<String, String> = shares.into_iter().collect();
+
+            let snapshot = json!({
+                "title": pdf.title,
+                "file_name": pdf.file_name,
+                "author": pdf.author,
+                "pages": pdf.pages,
+                "comments": pdf.comments,
+                "picture": pdf.picture.as_ref().map(|picture| format!("{:x}", Md5::digest(picture.as_bytes()))),
+                "owner_id": pdf.owner_id,
+                "storage_key": pdf.storage_key,
+                "file_missing": pdf.file_missing,
+                "tags": tags,
+                "shares": shares
+            });
+
+            snapshots.insert(*pdf_id, snapshot);
+        }
+
+        Ok(snapshots)
+    }
+
+
+    /// Same as `AuditRepositoryImpl::record_changes_with_connection`
+    #[instrument(skip_all)]
+    pub async fn record_changes_with_connection(
+        actor_id: &Uuid,
+        action: AuditAction,
+        before: &HashMap<Uuid, Value>,
+        after: &HashMap<Uuid, Value>,
+        conn: &mut SqliteConnection
+    ) -> Result<(), sqlx::Error> {
+        let pdf_ids: BTreeSet<&Uuid> = before.keys().chain(after.keys()).collect();
+
+        for pdf_id in pdf_ids {
+            let changes = diff(before.get(pdf_id), after.get(pdf_id));
+
+            if !changes.is_empty() {
+                Self::record_with_connection(actor_id, action, Some(pdf_id), Value::Object(changes), conn).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    #[instrument(skip_all)]
+    pub async fn record_with_connection(
+        actor_id: &Uuid,
+        action: AuditAction,
+        target_id: Option<&Uuid>,
+        changes: Value,
+        conn: &mut SqliteConnection
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO audit_log (time_created, actor_id, actor_name, action, target_id, changes)
+            VALUES (?1, ?2, (SELECT username FROM users WHERE id = ?2), ?3, ?4, ?5)"
+        )
+        .bind(Utc::now())
+        .bind(actor_id)
+        .bind(action.as_str())
+        .bind(target_id)
+        .bind(changes)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+
+#[async_trait]
+impl AuditRepository for SqliteAuditRepository {
+
+    #[instrument(skip_all)]
+    async fn count_entries(&self, search: &AuditLogSearchDto) -> Result<i64, String> {
+        let count_res = sqlx::query_scalar::<_, i64>(
+            "SELECT count(*) FROM audit_log
+            WHERE (?1 IS NULL OR actor_name = ?1)
+            AND (?2 IS NULL OR action = ?2)
+            AND (?3 IS NULL OR target_id = ?3)
+            AND (?4 IS NULL OR time_created >= ?4)
+            AND (?5 IS NULL OR time_created < ?5)"
+        )
+        .bind(&search.actor)
+        .bind(search.action.map(|action| action.as_str()))
+        .bind(search.target_id)
+        .bind(search.from)
+        .bind(search.to)
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match count_res {
+            Ok(count) => Ok(count),
+            Err(err) => Err(logged(err, "Error getting number of audit log entries".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_entries_paged(&self, search: &AuditLogSearchDto) -> Result<Vec<AuditEntry>, String> {
+        let size: i64 = search.size.unwrap() as i64;
+        let offset: i64 = size * (search.page.unwrap() - 1) as i64;
+
+        let entries_res = sqlx::query_as::<_, AuditEntry>(
+            "SELECT id, time_created, actor_id, actor_name, action, target_id, changes FROM audit_log
+            WHERE (?1 IS NULL OR actor_name = ?1)
+            AND (?2 IS NULL OR action = ?2)
+            AND (?3 IS NULL OR target_id = ?3)
+            AND (?4 IS NULL OR time_created >= ?4)
+            AND (?5 IS NULL OR time_created < ?5)
+            ORDER BY id DESC LIMIT ?6 OFFSET ?7"
+        )
+        .bind(&search.actor)
+        .bind(search.action.map(|action| action.as_str()))
+        .bind(search.target_id)
+        .bind(search.from)
+        .bind(search.to)
+        .bind(size)
+        .bind(offset)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match entries_res {
+            Ok(entries) => Ok(entries),
+            Err(err) => Err(logged(err, "Error retrieving audit log entries".to_string()))
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,87 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use tracing::info;
+use sqlx::{Pool, Sqlite, Error};
+use sqlx::migrate::{Migration, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+
+use crate::config::DatabaseConfig;
+use crate::repository::db;
+use crate::repository::logged;
+
+
+/// Same as `pdf_access_level` in the Postgres schema for the pdf of the current row of `pdfs`,
+/// the user is bound as `?1`
+macro_rules! access_level {
+    () => {
+        "(CASE
+            WHEN pdfs.owner_id = ?1 THEN 4
+            ELSE COALESCE((
+                SELECT max(CASE pdf_shares.permission WHEN 'read' THEN 1 WHEN 'annotate' THEN 2 WHEN 'edit' THEN 3 END)
+                FROM pdf_shares
+                WHERE pdf_shares.pdf_id = pdfs.id
+                AND (pdf_shares.user_id = ?1 OR pdf_shares.group_id IN (SELECT group_id FROM group_members WHERE user_id = ?1))
+            ), 0)
+        END)"
+    };
+}
+
+
+pub mod pdfs;
+pub mod users;
+pub mod audit;
+pub mod health;
+
+
+/// The migrations below `backend/migrations/sqlite`, embedded into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+
+/// Creates the database file if it doesn't exist yet. SQLite allows one writer at a time and fails transactions
+/// that read before they write when another one is writing, so the pool has a single connection.
+pub async fn connect(config: &DatabaseConfig) -> Result<Pool<Sqlite>, Error> {
+    info!("Opening the SQLite database");
+
+    let options = SqliteConnectOptions::from_str(&config.url)?
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(30));
+
+    SqlitePoolOptions::new()
+    .max_connections(1)
+    .connect_with(options)
+    .await
+}
+
+
+pub async fn migrate(pool: &Pool<Sqlite>) -> Result<(), String> {
+    info!("Applying database migrations");
+
+    match MIGRATOR.run(pool).await {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("Error applying the database migrations: {}", err))
+    }
+}
+
+
+/// Same as `db::pending_migrations` for a SQLite database
+pub async fn pending_migrations(pool: &Pool<Sqlite>) -> Result<Vec<&'static Migration>, String> {
+    let mut conn = match pool.acquire().await {
+        Ok(conn) => conn,
+        Err(err) => return Err(logged(err, "Error connecting to the database".to_string()))
+    };
+
+    let versioned_res = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')"
+    )
+    .fetch_one(&mut *conn)
+    .await;
+
+    match versioned_res {
+        Ok(true) => db::unapplied_migrations(&MIGRATOR, &mut *conn).await,
+        Ok(false) => Ok(MIGRATOR.iter().collect()),
+        Err(err) => Err(logged(err, "Error inspecting the database schema".to_string()))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,1625 @@
+use std::{sync::Arc, collections::{HashMap, HashSet}};
+
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use serde_json::{json, Value};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use uuid::Uuid;
+
+use crate::{domain::models::pdf::{ArchivedPdf, PdfOverview, Pdf, PdfFile, StoredPdf}, api::dto::{paging::PagingDto, pdf::{PdfSearchDto, PdfOverviewDto, PdfUpdateDto, PdfPatchDto, PdfMetadataDto}}};
+use crate::api::dto::bulk::{BulkOperationDto, BulkSelectionDto, BulkActionDto, BulkItemResultDto, BulkItemStatus};
+use crate::domain::repository::pdf::PdfRepository;
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::domain::models::share::{Access, PdfShare, ShareLink, ShareLinkAccess, ShareLinkClient, ShareLinkOutcome};
+use crate::domain::models::audit::AuditAction;
+use crate::repository::pdfs::share_link_audit_details;
+use crate::repository::sqlite::audit::SqliteAuditRepository;
+use crate::util::PdfUploaded;
+use crate::errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError};
+use crate::repository::logged;
+
+use async_trait::async_trait;
+
+
+/// Same as `title ILIKE ... AND author ILIKE ... AND <some tag> ILIKE ...` of the Postgres search queries for the
+/// title, author and tag bound as `?2`, `?3` and `?4`. The title and author are looked up in the full text index.
+macro_rules! search_filter {
+    () => {
+        "(?2 IS NULL OR pdfs.rowid IN (SELECT rowid FROM pdfs_search WHERE pdfs_search.title LIKE '%' || ?2 || '%' ESCAPE '\\'))
+        AND (?3 IS NULL OR pdfs.rowid IN (SELECT rowid FROM pdfs_search WHERE pdfs_search.author LIKE '%' || ?3 || '%' ESCAPE '\\'))
+        AND (?4 IS NULL OR EXISTS (SELECT 1 FROM tags_to_pdfs WHERE tags_to_pdfs.id = pdfs.id AND tags_to_pdfs.name LIKE '%' || ?4 || '%' ESCAPE '\\'))"
+    };
+}
+
+
+/// Keeps the pdfs in a SQLite database with the same behaviour as `PdfRepositoryImpl`. SQLite runs in the process,
+/// so where the Postgres queries take arrays of IDs or tags the queries are run once per item instead.
+pub struct SqlitePdfRepository {
+    pub pool: Arc<Pool<Sqlite>>
+}
+
+impl SqlitePdfRepository {
+
+    /// Associates the tags with the pdf, tags that don't exist yet are created.
+    /// Returns whether the pdf got a tag it didn't have before.
+    #[instrument(skip_all)]
+    async fn add_tags_with_connection(pdf_id: &Uuid, tags: &[String], conn: &mut SqliteConnection) -> Result<bool, sqlx::Error> {
+        let mut added = false;
+
+        for tag in tags {
+            sqlx::query("INSERT INTO tags(name) VALUES (?1) ON CONFLICT DO NOTHING")
+                .bind(tag)
+                .execute(&mut *conn)
+                .await?;
+
+            let added_res = sqlx::query("INSERT INTO tags_to_pdfs(name, id) VALUES (?1, ?2) ON CONFLICT DO NOTHING")
+                .bind(tag)
+                .bind(pdf_id)
+                .execute(&mut *conn)
+                .await?;
+
+            added |= added_res.rows_affected() > 0;
+        }
+
+        Ok(added)
+    }
+
+
+    /// Returns whether the pdf had one of the tags
+    #[instrument(skip_all)]
+    async fn remove_tags_with_connection(pdf_id: &Uuid, tags: &[String], conn: &mut SqliteConnection) -> Result<bool, sqlx::Error> {
+        let mut removed = false;
+
+        for tag in tags {
+            let removed_res = sqlx::query("DELETE FROM tags_to_pdfs WHERE id = ?1 AND name = ?2")
+                .bind(pdf_id)
+                .bind(tag)
+                .execute(&mut *conn)
+                .await?;
+
+            removed |= removed_res.rows_affected() > 0;
+        }
+
+        Ok(removed)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn access_with_connection(pdf_id: &Uuid, user_id: &Uuid, conn: &mut SqliteConnection) -> Result<Access, sqlx::Error> {
+        let access_level = sqlx::query_scalar::<_, i32>(concat!("SELECT ", access_level!(), " FROM pdfs WHERE id = ?2"))
+            .bind(user_id)
+            .bind(pdf_id)
+            .fetch_optional(conn)
+            .await?;
+
+        Ok(Access::from_level(access_level.unwrap_or(0)))
+    }
+
+
+    /// Fails unless the user owns the pdf, pdfs the user can't see are reported as not found
+    #[instrument(skip_all)]
+    async fn require_owner_with_connection(pdf_id: &Uuid, user_id: &Uuid, conn: &mut SqliteConnection) -> Result<(), PdfAccessError> {
+        match Self::access_with_connection(pdf_id, user_id, conn).await {
+            Ok(Access::Owner) => Ok(()),
+            Ok(Access::None) => Err(PdfAccessError::NotFound("No pdf with given ID exists".to_string())),
+            Ok(_) => Err(PdfAccessError::Forbidden("Only the owner of the pdf may do this".to_string())),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error checking the permissions of the pdf".to_string())))
+        }
+    }
+
+
+    /// Checks that the user may make the change and that the pdf is still at one of the expected versions.
+    /// Without expected versions any version is accepted. SQLite has one writer at a time, so the pdf can't
+    /// change until the end of the transaction without locking it.
+    #[instrument(skip_all)]
+    async fn check_version_with_connection(
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        expected_versions: &Option<Vec<i32>>,
+        required_access: impl FnOnce(&Pdf) -> Access + Send,
+        conn: &mut SqliteConnection
+    ) -> Result<(), PdfUpdateError> {
+        let current_pdf_res = sqlx::query_as::<_, Pdf>("SELECT * FROM pdfs WHERE id = ?1")
+            .bind(pdf_id)
+            .fetch_optional(&mut *conn)
+            .await;
+
+        let current_pdf = match current_pdf_res {
+            Ok(Some(pdf)) => pdf,
+            Ok(None) => return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+        };
+
+        let access = match Self::access_with_connection(pdf_id, user_id, conn).await {
+            Ok(access) => access,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+        };
+
+        if access == Access::None {
+            return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string()));
+        }
+
+        if access < required_access(&current_pdf) {
+            return Err(PdfUpdateError::Forbidden("The pdf is not shared with you with a permission that allows this change".to_string()));
+        }
+
+        match expected_versions {
+            Some(versions) if !versions.contains(&current_pdf.version) => {
+                let associated_tags = match Self::get_associated_tags_of_pdf_with_connection(pdf_id, conn).await {
+                    Ok(tags) => tags,
+                    Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error updating pdf".to_string())))
+                };
+
+                Err(PdfUpdateError::VersionMismatch(Box::new(to_metadata_dto(current_pdf, associated_tags))))
+            },
+            _ => Ok(())
+        }
+    }
+
+
+    /// Records the changes made to the pdfs since the `before` snapshots were taken in the audit log
+    #[instrument(skip_all)]
+    async fn audit_changes_with_connection(
+        user_id: &Uuid,
+        action: AuditAction,
+        before: &HashMap<Uuid, Value>,
+        pdf_ids: &[Uuid],
+        conn: &mut SqliteConnection
+    ) -> Result<(), sqlx::Error> {
+        let after = SqliteAuditRepository::pdf_snapshots_with_connection(pdf_ids, conn).await?;
+
+        SqliteAuditRepository::record_changes_with_connection(user_id, action, before, &after, conn).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_associated_tags_of_pdf_with_connection(pdf_id: &Uuid, conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT name FROM tags_to_pdfs WHERE id = ?1")
+            .bind(pdf_id)
+            .fetch_all(conn)
+            .await
+    }
+
+
+    /// The storage keys that no pdf uses anymore, each only once
+    #[instrument(skip_all)]
+    async fn unused_storage_keys_with_connection(storage_keys: Vec<String>, conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+        let mut seen = HashSet::new();
+        let mut unused_storage_keys = Vec::new();
+
+        for storage_key in storage_keys.into_iter().filter(|storage_key| seen.insert(storage_key.clone())) {
+            let used = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM pdfs WHERE storage_key = ?1)")
+                .bind(&storage_key)
+                .fetch_one(&mut *conn)
+                .await?;
+
+            if !used {
+                unused_storage_keys.push(storage_key);
+            }
+        }
+
+        Ok(unused_storage_keys)
+    }
+
+
+    /// Inserts the pdf along with its tags, which fails if the owner already has a pdf with the same file name
+    #[instrument(skip_all)]
+    async fn upload_with_transaction(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let uploaded_pdf = sqlx::query_as::<_, Pdf>(
+            "INSERT INTO pdfs (id, title, file_name, author, pages, comments, time_added, picture, owner_id, storage_key)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&upload.title)
+        .bind(&upload.filename)
+        .bind(&upload.author)
+        .bind(upload.pages)
+        .bind(&upload.comments)
+        .bind(upload.time_added.unwrap_or_else(Utc::now))
+        .bind(&upload.img)
+        .bind(owner_id)
+        .bind(&upload.storage_key)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if let Some(pdf_id) = &uploaded_pdf.id {
+            Self::add_tags_with_connection(pdf_id, &upload.tags, &mut conn).await?;
+
+            Self::audit_changes_with_connection(owner_id, AuditAction::Upload, &HashMap::new(), &[*pdf_id], &mut conn).await?;
+        }
+
+        conn.commit().await?;
+
+        Ok(uploaded_pdf)
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags_with_transaction(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let mut pdf_ids: Vec<Uuid> = Vec::new();
+
+        for source in sources {
+            let tagged = sqlx::query_scalar::<_, Uuid>("SELECT id FROM tags_to_pdfs WHERE name = ?1")
+                .bind(source)
+                .fetch_all(&mut *conn)
+                .await?;
+
+            pdf_ids.extend(tagged.into_iter().filter(|id| !pdf_ids.contains(id)).collect::<Vec<Uuid>>());
+        }
+
+        let before = SqliteAuditRepository::pdf_snapshots_with_connection(&pdf_ids, &mut conn).await?;
+
+        sqlx::query("INSERT INTO tags(name) VALUES (?1) ON CONFLICT DO NOTHING")
+            .bind(target)
+            .execute(&mut *conn)
+            .await?;
+
+        for source in sources {
+            sqlx::query("INSERT INTO tags_to_pdfs(name, id) SELECT ?1, id FROM tags_to_pdfs WHERE name = ?2 ON CONFLICT DO NOTHING")
+                .bind(target)
+                .bind(source)
+                .execute(&mut *conn)
+                .await?;
+
+            // The associations with the source tag are deleted along with it
+            sqlx::query("DELETE FROM tags WHERE name = ?1")
+                .bind(source)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &pdf_ids, &mut conn).await?;
+
+        conn.commit().await?;
+
+        Ok(pdf_ids.len())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_file_missing_with_transaction(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let before = SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await?;
+
+        sqlx::query("UPDATE pdfs SET file_missing = ?2, version = version + 1 WHERE id = ?1 AND file_missing <> ?2")
+            .bind(pdf_id)
+            .bind(file_missing)
+            .execute(&mut *conn)
+            .await?;
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await?;
+
+        conn.commit().await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_picture_with_transaction(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.begin().await?;
+
+        let before = SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await?;
+
+        sqlx::query("UPDATE pdfs SET picture = ?2, version = version + 1 WHERE id = ?1")
+            .bind(pdf_id)
+            .bind(picture)
+            .execute(&mut *conn)
+            .await?;
+
+        Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await?;
+
+        conn.commit().await
+    }
+}
+
+
+fn to_metadata_dto(pdf: Pdf, tags: Vec<String>) -> PdfMetadataDto {
+    PdfMetadataDto {
+        id: pdf.id,
+        title: pdf.title,
+        file_name: pdf.file_name,
+        author: pdf.author,
+        pages: pdf.pages,
+        comments: pdf.comments,
+        uploaded: pdf.time_added,
+        last_accessed: pdf.last_accessed,
+        picture: pdf.picture,
+        tags: Some(tags),
+        version: pdf.version,
+        owner_id: pdf.owner_id,
+        file_missing: pdf.file_missing
+    }
+}
+
+
+#[async_trait]
+impl PdfRepository for SqlitePdfRepository {
+
+    #[instrument(skip_all)]
+    async fn total_number_of_pdfs(&self, user_id: &Uuid) -> Result<Option<i64>, String> {
+        let pdf_count_res = sqlx::query_scalar::<_, i64>(concat!("SELECT count(*) FROM pdfs WHERE ", access_level!(), " > 0"))
+            .bind(user_id)
+            .fetch_one(self.pool.as_ref())
+            .await;
+
+        match pdf_count_res {
+            Ok(count) => Ok(Some(count)),
+            Err(err) => Err(logged(err, "Error getting number of pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdfs_paged(&self, paging: &PagingDto, user_id: &Uuid) -> Result<Vec<PdfOverview>, String> {
+        let size: i64 = paging.size.unwrap() as i64;
+        let page: i64 = size * (paging.page.unwrap() - 1) as i64;
+
+        let paged_pdfs_res = sqlx::query_as::<_, PdfOverview>(concat!(
+            "SELECT id, title, picture FROM pdfs WHERE ", access_level!(), " > 0 ORDER BY time_added, id LIMIT ?2 OFFSET ?3"
+        ))
+        .bind(user_id)
+        .bind(size)
+        .bind(page)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match paged_pdfs_res {
+            Ok(pdfs) => Ok(pdfs),
+            Err(err) => Err(logged(err, "Error retrieving paginated data".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Pdf, PdfMetadataByIdError> {
+        let pdf_metadata_res = sqlx::query_as::<_, Pdf>(concat!("SELECT * FROM pdfs WHERE id = ?2 AND ", access_level!(), " > 0"))
+            .bind(user_id)
+            .bind(pdf_id)
+            .fetch_optional(self.pool.as_ref())
+            .await;
+
+        match pdf_metadata_res {
+            Ok(Some(pdf)) => Ok(pdf),
+            Ok(None) => Err(PdfMetadataByIdError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving requested pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_associated_tags_of_pdf(&self, pdf_id: &Uuid) -> Result<Vec<String>, PdfMetadataByIdError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving the tags associated with pdf".to_string())))
+        };
+
+        match Self::get_associated_tags_of_pdf_with_connection(pdf_id, &mut conn).await {
+            Ok(tags) => Ok(tags),
+            Err(err) => Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving the tags associated with pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError> {
+        let query_res = sqlx::query_scalar::<_, String>(concat!("SELECT storage_key FROM pdfs WHERE id = ?2 AND ", access_level!(), " > 0"))
+            .bind(user_id)
+            .bind(pdf_id)
+            .fetch_optional(self.pool.as_ref())
+            .await;
+
+        match query_res {
+            Ok(Some(storage_key)) => Ok(storage_key),
+            Ok(None) => Err(PdfMetadataByIdError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => Err(logged(err, PdfMetadataByIdError::DatabaseError("Error retrieving pdf information".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String> {
+        let size: i64 = search.size.unwrap() as i64;
+        let page: i64 = size * (search.page.unwrap() - 1) as i64;
+
+        let search_pdfs_res = sqlx::query_as::<_, PdfOverview>(concat!(
+            "SELECT id, title, picture FROM pdfs WHERE ", search_filter!(), " AND ", access_level!(), " > 0
+            ORDER BY time_added, id LIMIT ?5 OFFSET ?6"
+        ))
+        .bind(user_id)
+        .bind(&search.title)
+        .bind(&search.author)
+        .bind(&search.tag)
+        .bind(size)
+        .bind(page)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        let pdfs_previews = match search_pdfs_res {
+            Ok(pdfs) => pdfs,
+            Err(err) => return Err(logged(err, "An error occured searching the pdfs".to_string()))
+        };
+
+        let search_count_res = sqlx::query_scalar::<_, i64>(concat!(
+            "SELECT count(*) FROM pdfs WHERE ", search_filter!(), " AND ", access_level!(), " > 0"
+        ))
+        .bind(user_id)
+        .bind(&search.title)
+        .bind(&search.author)
+        .bind(&search.tag)
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match search_count_res {
+            Ok(count) => Ok(PdfOverviewDto { pdfs_previews, count: Some(count) }),
+            Err(err) => Err(logged(err, "An error occured searching the pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let update_error = || PdfUpdateError::DatabaseError("Error updating pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, update_error()))
+        };
+
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            if update.title != current_pdf.title || update.author != current_pdf.author || update.picture != current_pdf.picture {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        Self::check_version_with_connection(pdf_id, user_id, &expected_versions, required_access, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, update_error()))
+        };
+
+        // All relations between the pdf and its tags are replaced by the ones of the update
+        let delete_tag_relations_res = sqlx::query("DELETE FROM tags_to_pdfs WHERE id = ?1")
+            .bind(pdf_id)
+            .execute(&mut *conn)
+            .await;
+
+        if delete_tag_relations_res.is_err() {
+            return Err(update_error());
+        }
+
+        if Self::add_tags_with_connection(pdf_id, &update.tags.clone().unwrap_or_default(), &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error updating the pdf details".to_string()));
+        }
+
+        let update_pdf_res = sqlx::query_as::<_, Pdf>(
+            "UPDATE pdfs SET title = ?2, author = ?3, comments = ?4, picture = ?5, version = version + 1 WHERE id = ?1 RETURNING *"
+        )
+        .bind(pdf_id)
+        .bind(&update.title)
+        .bind(&update.author)
+        .bind(&update.comments)
+        .bind(&update.picture)
+        .fetch_one(&mut *conn)
+        .await;
+
+        let pdf_metadata = match update_pdf_res {
+            Ok(pdf) => pdf,
+            Err(err) => return Err(logged(err, update_error()))
+        };
+
+        let associated_tags = match Self::get_associated_tags_of_pdf_with_connection(pdf_id, &mut conn).await {
+            Ok(tags) => tags,
+            Err(err) => return Err(logged(err, update_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(update_error());
+        }
+
+        if conn.commit().await.is_err() {
+            return Err(update_error());
+        }
+
+        Ok(to_metadata_dto(pdf_metadata, associated_tags))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let patch_error = || PdfUpdateError::DatabaseError("Error updating pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        // Comments and tags may be changed by annotators, the other details only by editors
+        let required_access = |current_pdf: &Pdf| {
+            let changes = |field: &Option<Option<String>>, current: &Option<String>| field.as_ref().is_some_and(|value| value != current);
+
+            if changes(&patch.title, &current_pdf.title) || changes(&patch.author, &current_pdf.author) || changes(&patch.picture, &current_pdf.picture) {
+                Access::Edit
+            } else {
+                Access::Annotate
+            }
+        };
+
+        Self::check_version_with_connection(pdf_id, user_id, &expected_versions, required_access, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        // Each field is only overwritten if it is part of the patch, a field set to null is cleared
+        let patch_pdf_res = sqlx::query_as::<_, Pdf>(
+            "UPDATE pdfs SET
+                title = CASE WHEN ?2 THEN ?3 ELSE title END,
+                author = CASE WHEN ?4 THEN ?5 ELSE author END,
+                comments = CASE WHEN ?6 THEN ?7 ELSE comments END,
+                picture = CASE WHEN ?8 THEN ?9 ELSE picture END,
+                version = version + 1
+            WHERE id = ?1 RETURNING *"
+        )
+        .bind(pdf_id)
+        .bind(patch.title.is_some())
+        .bind(patch.title.clone().flatten())
+        .bind(patch.author.is_some())
+        .bind(patch.author.clone().flatten())
+        .bind(patch.comments.is_some())
+        .bind(patch.comments.clone().flatten())
+        .bind(patch.picture.is_some())
+        .bind(patch.picture.clone().flatten())
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let pdf_metadata = match patch_pdf_res {
+            Ok(Some(pdf)) => pdf,
+            Ok(None) => return Err(PdfUpdateError::NotFound("No pdf with given ID exists".to_string())),
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        if let Some(tags) = &patch.tags {
+            let delete_tag_relations_res = sqlx::query("DELETE FROM tags_to_pdfs WHERE id = ?1")
+                .bind(pdf_id)
+                .execute(&mut *conn)
+                .await;
+
+            if delete_tag_relations_res.is_err() {
+                return Err(patch_error());
+            }
+
+            if let Some(tags) = tags {
+                if Self::add_tags_with_connection(pdf_id, tags, &mut conn).await.is_err() {
+                    return Err(patch_error());
+                }
+            }
+        }
+
+        if let Some(add_tags) = &patch.add_tags {
+            if Self::add_tags_with_connection(pdf_id, add_tags, &mut conn).await.is_err() {
+                return Err(patch_error());
+            }
+        }
+
+        if let Some(remove_tags) = &patch.remove_tags {
+            if Self::remove_tags_with_connection(pdf_id, remove_tags, &mut conn).await.is_err() {
+                return Err(patch_error());
+            }
+        }
+
+        let associated_tags = match Self::get_associated_tags_of_pdf_with_connection(pdf_id, &mut conn).await {
+            Ok(tags) => tags,
+            Err(err) => return Err(logged(err, patch_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(patch_error());
+        }
+
+        if conn.commit().await.is_err() {
+            return Err(patch_error());
+        }
+
+        Ok(to_metadata_dto(pdf_metadata, associated_tags))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Option<String>, PdfAccessError> {
+        let delete_error = || PdfAccessError::DatabaseError("Failed to delete pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let storage_key_res = sqlx::query_scalar::<_, String>("DELETE FROM pdfs WHERE id = ?1 RETURNING storage_key")
+            .bind(pdf_id)
+            .fetch_one(&mut *conn)
+            .await;
+
+        let storage_key = match storage_key_res {
+            Ok(storage_key) => storage_key,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Delete, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(delete_error());
+        }
+
+        // The file is shared by all pdfs with the same content
+        let unused_storage_keys = match Self::unused_storage_keys_with_connection(vec![storage_key], &mut conn).await {
+            Ok(storage_keys) => storage_keys,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        match conn.commit().await {
+            Ok(_) => Ok(unused_storage_keys.into_iter().next()),
+            Err(err) => Err(logged(err, delete_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn bulk(&self, bulk: &BulkOperationDto, user_id: &Uuid) -> Result<(Vec<BulkItemResultDto>, Vec<String>), String> {
+        let bulk_error = || "Error applying bulk operation".to_string();
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let (requested_ids, selected_ids_res) = match &bulk.selection {
+            BulkSelectionDto::Ids(ids) => {
+                let mut seen_ids = HashSet::new();
+                let requested_ids: Vec<Uuid> = ids.iter().filter(|id| seen_ids.insert(**id)).copied().collect();
+
+                let mut selected = Vec::new();
+                let mut selected_ids_res = Ok(());
+
+                for pdf_id in &requested_ids {
+                    match Self::access_with_connection(pdf_id, user_id, &mut conn).await {
+                        Ok(Access::None) => (),
+                        Ok(access) => selected.push((*pdf_id, access.level())),
+                        Err(err) => {
+                            selected_ids_res = Err(err);
+                            break;
+                        }
+                    }
+                }
+
+                (Some(requested_ids), selected_ids_res.map(|_| selected))
+            },
+            BulkSelectionDto::Search(search) => {
+                let selected_ids_res = sqlx::query_as::<_, (Uuid, i32)>(concat!(
+                    "SELECT id, ", access_level!(), " FROM pdfs WHERE ", search_filter!(), " AND ", access_level!(), " > 0
+                    ORDER BY time_added, id"
+                ))
+                .bind(user_id)
+                .bind(&search.title)
+                .bind(&search.author)
+                .bind(&search.tag)
+                .fetch_all(&mut *conn)
+                .await;
+
+                (None, selected_ids_res)
+            }
+        };
+
+        let selected = match selected_ids_res {
+            Ok(selected) => selected,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let required_access = match &bulk.action {
+            BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. } => Access::Annotate,
+            BulkActionDto::SetAuthor { .. } => Access::Edit,
+            BulkActionDto::Delete => Access::Owner
+        };
+
+        let selected_in_order: Vec<Uuid> = selected.iter().map(|(id, _)| *id).collect();
+
+        // The action is only applied to the selected pdfs the user has the permission for
+        let (permitted, forbidden): (Vec<_>, Vec<_>) = selected.into_iter()
+            .partition(|(_, access_level)| Access::from_level(*access_level) >= required_access);
+
+        let selected_ids: Vec<Uuid> = permitted.into_iter().map(|(id, _)| id).collect();
+        let forbidden_ids: HashSet<Uuid> = forbidden.into_iter().map(|(id, _)| id).collect();
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&selected_ids, &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let mut changed_ids = HashSet::new();
+        let mut deleted_storage_keys = Vec::new();
+
+        for pdf_id in &selected_ids {
+            let changed_res = match &bulk.action {
+                BulkActionDto::AddTags { tags } => Self::add_tags_with_connection(pdf_id, tags, &mut conn).await,
+                BulkActionDto::RemoveTags { tags } => Self::remove_tags_with_connection(pdf_id, tags, &mut conn).await,
+                BulkActionDto::SetAuthor { author } => {
+                    sqlx::query("UPDATE pdfs SET author = ?2, version = version + 1 WHERE id = ?1 AND author IS NOT ?2")
+                        .bind(pdf_id)
+                        .bind(author.as_deref())
+                        .execute(&mut *conn)
+                        .await
+                        .map(|result| result.rows_affected() > 0)
+                },
+                BulkActionDto::Delete => {
+                    sqlx::query_scalar::<_, String>("DELETE FROM pdfs WHERE id = ?1 RETURNING storage_key")
+                        .bind(pdf_id)
+                        .fetch_optional(&mut *conn)
+                        .await
+                        .map(|storage_key| match storage_key {
+                            Some(storage_key) => {
+                                deleted_storage_keys.push(storage_key);
+                                true
+                            },
+                            None => false
+                        })
+                }
+            };
+
+            match changed_res {
+                Ok(true) => {
+                    changed_ids.insert(*pdf_id);
+                },
+                Ok(false) => (),
+                Err(err) => return Err(logged(err, bulk_error()))
+            }
+        }
+
+        // Tags are part of the pdf details, changing them creates a new version
+        if matches!(bulk.action, BulkActionDto::AddTags { .. } | BulkActionDto::RemoveTags { .. }) {
+            for pdf_id in &changed_ids {
+                let bump_version_res = sqlx::query("UPDATE pdfs SET version = version + 1 WHERE id = ?1")
+                    .bind(pdf_id)
+                    .execute(&mut *conn)
+                    .await;
+
+                if bump_version_res.is_err() {
+                    return Err(bulk_error());
+                }
+            }
+        }
+
+        // Only the pdfs that actually changed are recorded, a dry run rolls the entries back along with the changes
+        let audit_action = match bulk.action {
+            BulkActionDto::Delete => AuditAction::Delete,
+            _ => AuditAction::Update
+        };
+
+        if Self::audit_changes_with_connection(user_id, audit_action, &before, &selected_ids, &mut conn).await.is_err() {
+            return Err(bulk_error());
+        }
+
+        // Files are shared by all pdfs with the same content, only the ones no pdf uses anymore can be removed
+        let unused_storage_keys = match Self::unused_storage_keys_with_connection(deleted_storage_keys, &mut conn).await {
+            Ok(storage_keys) => storage_keys,
+            Err(err) => return Err(logged(err, bulk_error()))
+        };
+
+        let transaction_res = if bulk.dry_run {
+            conn.rollback().await
+        } else {
+            conn.commit().await
+        };
+
+        if transaction_res.is_err() {
+            return Err(bulk_error());
+        }
+
+        let status_of = |id: &Uuid| {
+            if forbidden_ids.contains(id) {
+                BulkItemStatus::Forbidden
+            } else if changed_ids.contains(id) {
+                BulkItemStatus::Changed
+            } else {
+                BulkItemStatus::Unchanged
+            }
+        };
+
+        let results = match requested_ids {
+            Some(requested_ids) => {
+                let selected_ids: HashSet<Uuid> = selected_in_order.into_iter().collect();
+
+                requested_ids.into_iter().map(|id| {
+                    let status = if selected_ids.contains(&id) { status_of(&id) } else { BulkItemStatus::NotFound };
+                    BulkItemResultDto { id, status }
+                }).collect()
+            },
+            None => selected_in_order.into_iter().map(|id| BulkItemResultDto { id, status: status_of(&id) }).collect()
+        };
+
+        Ok((results, unused_storage_keys))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn upload(&self, upload: &PdfUploaded, owner_id: &Uuid) -> Result<Pdf, String> {
+        match self.upload_with_transaction(upload, owner_id).await {
+            Ok(pdf) => Ok(pdf),
+            Err(err) => Err(logged(err, "Error storing the uploaded pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn is_storage_key_used(&self, storage_key: &str) -> Result<bool, String> {
+        let used_res = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM pdfs WHERE storage_key = ?1)")
+            .bind(storage_key)
+            .fetch_one(self.pool.as_ref())
+            .await;
+
+        match used_res {
+            Ok(used) => Ok(used),
+            Err(err) => Err(logged(err, "Error checking the stored files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_stored_pdfs(&self) -> Result<Vec<StoredPdf>, String> {
+        let stored_pdfs_res = sqlx::query_as::<_, StoredPdf>(
+            "SELECT id, storage_key, COALESCE(picture, '') <> '' AS has_picture, file_missing FROM pdfs ORDER BY storage_key, id"
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match stored_pdfs_res {
+            Ok(stored_pdfs) => Ok(stored_pdfs),
+            Err(err) => Err(logged(err, "Error retrieving the stored pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_file_missing(&self, pdf_id: &Uuid, file_missing: bool, user_id: &Uuid) -> Result<(), String> {
+        match self.set_file_missing_with_transaction(pdf_id, file_missing, user_id).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error marking the file of the pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_picture(&self, pdf_id: &Uuid, picture: &str, user_id: &Uuid) -> Result<(), String> {
+        match self.set_picture_with_transaction(pdf_id, picture, user_id).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error storing the preview of the pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String> {
+        let sources: Vec<String> = sources.iter().filter(|source| *source != target).cloned().collect();
+
+        match self.merge_tags_with_transaction(&sources, target, user_id).await {
+            Ok(merged) => Ok(merged),
+            Err(err) => Err(logged(err, "Error merging the tags".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_archived_pdfs(&self, since: Option<DateTime<Utc>>) -> Result<Vec<ArchivedPdf>, String> {
+        let pdfs_res = sqlx::query_as::<_, Pdf>(
+            "SELECT * FROM pdfs
+            WHERE ?1 IS NULL OR time_added >= ?1 OR id IN (SELECT target_id FROM audit_log WHERE time_created >= ?1)
+            ORDER BY time_added, id"
+        )
+        .bind(since)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        let pdfs = match pdfs_res {
+            Ok(pdfs) => pdfs,
+            Err(err) => return Err(logged(err, "Error retrieving the pdfs to archive".to_string()))
+        };
+
+        let owners_res = sqlx::query_as::<_, (Uuid, String)>("SELECT id, username FROM users")
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let owners: HashMap<Uuid, String> = match owners_res {
+            Ok(owners) => owners.into_iter().collect(),
+            Err(err) => return Err(logged(err, "Error retrieving the owners of the pdfs to archive".to_string()))
+        };
+
+        let tags_res = sqlx::query_as::<_, (Uuid, String)>("SELECT id, name FROM tags_to_pdfs ORDER BY name")
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+        match tags_res {
+            Ok(rows) => rows.into_iter().for_each(|(pdf_id, name)| tags.entry(pdf_id).or_default().push(name)),
+            Err(err) => return Err(logged(err, "Error retrieving the tags of the pdfs to archive".to_string()))
+        }
+
+        Ok(pdfs.into_iter()
+            .map(|pdf| ArchivedPdf {
+                owner: owners.get(&pdf.owner_id).cloned().unwrap_or_default(),
+                tags: pdf.id.and_then(|pdf_id| tags.remove(&pdf_id)).unwrap_or_default(),
+                pdf
+            })
+            .collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn rebuild_search_index(&self) -> Result<(), String> {
+        // The full text index of the title and author is filled by triggers, which a change by hand may have bypassed
+        let rebuild_res = sqlx::query("INSERT INTO pdfs_search (pdfs_search) VALUES ('rebuild'); REINDEX pdfs; REINDEX tags_to_pdfs")
+            .execute(self.pool.as_ref())
+            .await;
+
+        match rebuild_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error rebuilding the search index".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn count_pdfs_and_pages(&self) -> Result<(i64, i64), String> {
+        let count_res = sqlx::query_as::<_, (i64, i64)>("SELECT count(*), COALESCE(sum(pages), 0) FROM pdfs")
+            .fetch_one(self.pool.as_ref())
+            .await;
+
+        match count_res {
+            Ok(count) => Ok(count),
+            Err(err) => Err(logged(err, "Error counting the pdfs".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as::<_, PdfFile>(
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE owner_id = ?1 ORDER BY file_name"
+        )
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as::<_, PdfFile>(
+            "SELECT pdfs.id, pdfs.file_name, pdfs.time_added, pdfs.storage_key FROM pdfs JOIN tags_to_pdfs ON pdfs.id = tags_to_pdfs.id
+            WHERE tags_to_pdfs.name = ?1 AND pdfs.owner_id = ?2 ORDER BY pdfs.file_name"
+        )
+        .bind(tag)
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        let pdf_files_res = sqlx::query_as::<_, PdfFile>(
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE author = ?1 AND owner_id = ?2 ORDER BY file_name"
+        )
+        .bind(author)
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match pdf_files_res {
+            Ok(pdf_files) => Ok(pdf_files),
+            Err(err) => Err(logged(err, "Error retrieving pdf files".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String> {
+        let pdf_file_res = sqlx::query_as::<_, PdfFile>(
+            "SELECT id, file_name, time_added, storage_key FROM pdfs WHERE file_name = ?1 AND owner_id = ?2"
+        )
+        .bind(file_name)
+        .bind(owner_id)
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match pdf_file_res {
+            Ok(pdf_file) => Ok(pdf_file),
+            Err(err) => Err(logged(err, "Error retrieving pdf file".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let tags_res = sqlx::query_scalar::<_, String>(
+            "SELECT name FROM tags
+            WHERE EXISTS (SELECT 1 FROM tags_to_pdfs JOIN pdfs ON pdfs.id = tags_to_pdfs.id WHERE tags_to_pdfs.name = tags.name AND pdfs.owner_id = ?1)
+            OR NOT EXISTS (SELECT 1 FROM tags_to_pdfs WHERE tags_to_pdfs.name = tags.name)
+            ORDER BY name"
+        )
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match tags_res {
+            Ok(tags) => Ok(tags),
+            Err(err) => Err(logged(err, "Error retrieving tags".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        let authors_res = sqlx::query_scalar::<_, String>(
+            "SELECT DISTINCT author FROM pdfs WHERE owner_id = ?1 AND author IS NOT NULL AND author <> '' ORDER BY author"
+        )
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match authors_res {
+            Ok(authors) => Ok(authors),
+            Err(err) => Err(logged(err, "Error retrieving authors".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, "Error creating tag".to_string()))
+        };
+
+        let created_res = sqlx::query("INSERT INTO tags(name) VALUES (?1) ON CONFLICT DO NOTHING")
+            .bind(tag)
+            .execute(&mut *conn)
+            .await;
+
+        match created_res {
+            Ok(result) if result.rows_affected() > 0 => {
+                let changes = json!({ "tag": { "old": null, "new": tag } });
+
+                if SqliteAuditRepository::record_with_connection(user_id, AuditAction::TagCreate, None, changes, &mut conn).await.is_err() {
+                    return Err("Error creating tag".to_string());
+                }
+            },
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, "Error creating tag".to_string()))
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error creating tag".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        };
+
+        Self::check_version_with_connection(pdf_id, user_id, &None, |_| Access::Annotate, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        };
+
+        let added = match Self::add_tags_with_connection(pdf_id, &[tag.to_string()], &mut conn).await {
+            Ok(added) => added,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        };
+
+        if added {
+            let bump_version_res = sqlx::query("UPDATE pdfs SET version = version + 1 WHERE id = ?1")
+                .bind(pdf_id)
+                .execute(&mut *conn)
+                .await;
+
+            if bump_version_res.is_err() {
+                return Err(PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string()));
+            }
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string()));
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, PdfUpdateError::DatabaseError("Error adding tag to pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        };
+
+        Self::check_version_with_connection(pdf_id, user_id, &None, |_| Access::Annotate, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        };
+
+        let removed = match Self::remove_tags_with_connection(pdf_id, &[tag.to_string()], &mut conn).await {
+            Ok(removed) => removed,
+            Err(err) => return Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        };
+
+        if removed {
+            let bump_version_res = sqlx::query("UPDATE pdfs SET version = version + 1 WHERE id = ?1")
+                .bind(pdf_id)
+                .execute(&mut *conn)
+                .await;
+
+            if bump_version_res.is_err() {
+                return Err(PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string()));
+            }
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Update, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string()));
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, PdfUpdateError::DatabaseError("Error removing tag from pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShare>, PdfAccessError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the shares of the pdf".to_string())))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let shares_res = sqlx::query_as::<_, PdfShare>(
+            "SELECT pdf_shares.user_id, users.username, pdf_shares.group_id, groups.name AS group_name, pdf_shares.permission
+            FROM pdf_shares LEFT JOIN users ON users.id = pdf_shares.user_id LEFT JOIN groups ON groups.id = pdf_shares.group_id
+            WHERE pdf_shares.pdf_id = ?1
+            ORDER BY users.username IS NULL, users.username, groups.name"
+        )
+        .bind(pdf_id)
+        .fetch_all(&mut *conn)
+        .await;
+
+        match shares_res {
+            Ok(shares) => Ok(shares),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the shares of the pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: &PdfShareDto) -> Result<(), PdfAccessError> {
+        let share_error = || PdfAccessError::DatabaseError("Error sharing the pdf".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, share_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, share_error()))
+        };
+
+        let share_res = match (&share.user, &share.group) {
+            (Some(username), None) => {
+                let share_user_id_res = sqlx::query_scalar::<_, Uuid>("SELECT id FROM users WHERE username = ?1")
+                    .bind(username)
+                    .fetch_optional(&mut *conn)
+                    .await;
+
+                let share_user_id = match share_user_id_res {
+                    Ok(Some(share_user_id)) => share_user_id,
+                    Ok(None) => return Err(PdfAccessError::InvalidInput(format!("No user named '{}' exists", username))),
+                    Err(err) => return Err(logged(err, share_error()))
+                };
+
+                if share_user_id == *user_id {
+                    return Err(PdfAccessError::InvalidInput("A pdf can't be shared with its owner".to_string()));
+                }
+
+                sqlx::query(
+                    "INSERT INTO pdf_shares (pdf_id, user_id, permission) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (pdf_id, user_id) DO UPDATE SET permission = excluded.permission"
+                )
+                .bind(pdf_id)
+                .bind(share_user_id)
+                .bind(share.permission.as_str())
+                .execute(&mut *conn)
+                .await
+            },
+            (None, Some(group_name)) => {
+                let group_id_res = sqlx::query_scalar::<_, Uuid>("SELECT id FROM groups WHERE name = ?1")
+                    .bind(group_name)
+                    .fetch_optional(&mut *conn)
+                    .await;
+
+                let group_id = match group_id_res {
+                    Ok(Some(group_id)) => group_id,
+                    Ok(None) => return Err(PdfAccessError::InvalidInput(format!("No group named '{}' exists", group_name))),
+                    Err(err) => return Err(logged(err, share_error()))
+                };
+
+                sqlx::query(
+                    "INSERT INTO pdf_shares (pdf_id, group_id, permission) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (pdf_id, group_id) DO UPDATE SET permission = excluded.permission"
+                )
+                .bind(pdf_id)
+                .bind(group_id)
+                .bind(share.permission.as_str())
+                .execute(&mut *conn)
+                .await
+            },
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        if share_res.is_err() {
+            return Err(share_error());
+        }
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Share, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(share_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, share_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: &ShareTargetDto) -> Result<bool, PdfAccessError> {
+        let unshare_error = || PdfAccessError::DatabaseError("Error removing the share".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&[*pdf_id], &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        let delete_res = match (&target.user, &target.group) {
+            (Some(username), None) => sqlx::query("DELETE FROM pdf_shares WHERE pdf_id = ?1 AND user_id = (SELECT id FROM users WHERE username = ?2)")
+                .bind(pdf_id)
+                .bind(username)
+                .execute(&mut *conn)
+                .await,
+            (None, Some(group_name)) => sqlx::query("DELETE FROM pdf_shares WHERE pdf_id = ?1 AND group_id = (SELECT id FROM groups WHERE name = ?2)")
+                .bind(pdf_id)
+                .bind(group_name)
+                .execute(&mut *conn)
+                .await,
+            _ => return Err(PdfAccessError::InvalidInput("Either a user or a group has to be given".to_string()))
+        };
+
+        let unshared = match delete_res {
+            Ok(result) => result.rows_affected() > 0,
+            Err(err) => return Err(logged(err, unshare_error()))
+        };
+
+        if Self::audit_changes_with_connection(user_id, AuditAction::Unshare, &before, &[*pdf_id], &mut conn).await.is_err() {
+            return Err(unshare_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(unshared),
+            Err(err) => Err(logged(err, unshare_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_share_link(
+        &self,
+        pdf_id: &Uuid,
+        user_id: &Uuid,
+        token_hash: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        max_downloads: Option<i32>
+    ) -> Result<ShareLink, PdfAccessError> {
+        let create_error = || PdfAccessError::DatabaseError("Error creating the share link".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, create_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let share_link_res = sqlx::query_as::<_, ShareLink>(
+            "INSERT INTO share_links (id, pdf_id, created_by, token_hash, password_hash, expires_at, max_downloads, time_created)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            RETURNING id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at"
+        )
+        .bind(Uuid::new_v4())
+        .bind(pdf_id)
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(password_hash)
+        .bind(expires_at)
+        .bind(max_downloads)
+        .bind(Utc::now())
+        .fetch_one(&mut *conn)
+        .await;
+
+        let share_link = match share_link_res {
+            Ok(share_link) => share_link,
+            Err(err) => return Err(logged(err, create_error()))
+        };
+
+        let changes = json!({ "share_link": { "old": null, "new": share_link_audit_details(&share_link) } });
+
+        if SqliteAuditRepository::record_with_connection(user_id, AuditAction::ShareLinkCreate, Some(pdf_id), changes, &mut conn).await.is_err() {
+            return Err(create_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(share_link),
+            Err(err) => Err(logged(err, create_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLink>, PdfAccessError> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the share links of the pdf".to_string())))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let share_links_res = sqlx::query_as::<_, ShareLink>(
+            "SELECT id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at
+            FROM share_links WHERE pdf_id = ?1 ORDER BY time_created DESC"
+        )
+        .bind(pdf_id)
+        .fetch_all(&mut *conn)
+        .await;
+
+        match share_links_res {
+            Ok(share_links) => Ok(share_links),
+            Err(err) => Err(logged(err, PdfAccessError::DatabaseError("Error retrieving the share links of the pdf".to_string())))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<bool, PdfAccessError> {
+        let revoke_error = || PdfAccessError::DatabaseError("Error revoking the share link".to_string());
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, revoke_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let revoke_res = sqlx::query_as::<_, ShareLink>(
+            "UPDATE share_links SET revoked_at = ?3 WHERE id = ?1 AND pdf_id = ?2 AND revoked_at IS NULL
+            RETURNING id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at"
+        )
+        .bind(share_link_id)
+        .bind(pdf_id)
+        .bind(Utc::now())
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let revoked = match revoke_res {
+            Ok(Some(revoked)) => revoked,
+            Ok(None) => return Ok(false),
+            Err(err) => return Err(logged(err, revoke_error()))
+        };
+
+        let changes = json!({ "share_link": { "old": share_link_audit_details(&revoked), "new": null } });
+
+        if SqliteAuditRepository::record_with_connection(user_id, AuditAction::ShareLinkRevoke, Some(pdf_id), changes, &mut conn).await.is_err() {
+            return Err(revoke_error());
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(true),
+            Err(err) => Err(logged(err, revoke_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Option<Vec<ShareLinkAccess>>, PdfAccessError> {
+        let accesses_error = || PdfAccessError::DatabaseError("Error retrieving the accesses of the share link".to_string());
+
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, accesses_error()))
+        };
+
+        Self::require_owner_with_connection(pdf_id, user_id, &mut conn).await?;
+
+        let exists_res = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM share_links WHERE id = ?1 AND pdf_id = ?2)")
+            .bind(share_link_id)
+            .bind(pdf_id)
+            .fetch_one(&mut *conn)
+            .await;
+
+        match exists_res {
+            Ok(true) => (),
+            Ok(false) => return Ok(None),
+            Err(err) => return Err(logged(err, accesses_error()))
+        }
+
+        let accesses_res = sqlx::query_as::<_, ShareLinkAccess>(
+            "SELECT time_accessed, ip_address, user_agent, outcome FROM share_link_accesses
+            WHERE share_link_id = ?1 ORDER BY time_accessed DESC"
+        )
+        .bind(share_link_id)
+        .fetch_all(&mut *conn)
+        .await;
+
+        match accesses_res {
+            Ok(accesses) => Ok(Some(accesses)),
+            Err(err) => Err(logged(err, accesses_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link(&self, token_hash: &str) -> Result<Option<(ShareLink, Pdf)>, String> {
+        let mut conn = match self.pool.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, "Error retrieving the share link".to_string()))
+        };
+
+        let share_link_res = sqlx::query_as::<_, ShareLink>(
+            "SELECT id, pdf_id, created_by, password_hash, expires_at, max_downloads, download_count, time_created, revoked_at
+            FROM share_links WHERE token_hash = ?1"
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *conn)
+        .await;
+
+        let share_link = match share_link_res {
+            Ok(Some(share_link)) => share_link,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(logged(err, "Error retrieving the share link".to_string()))
+        };
+
+        // The link grants access on its own, so the pdf isn't filtered by who may see it
+        let pdf_res = sqlx::query_as::<_, Pdf>("SELECT * FROM pdfs WHERE id = ?1")
+            .bind(share_link.pdf_id)
+            .fetch_one(&mut *conn)
+            .await;
+
+        match pdf_res {
+            Ok(pdf) => Ok(Some((share_link, pdf))),
+            Err(err) => Err(logged(err, "Error retrieving the shared pdf".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_access(&self, share_link_id: &Uuid, client: &ShareLinkClient, outcome: ShareLinkOutcome) -> Result<(), String> {
+        let record_res = sqlx::query(
+            "INSERT INTO share_link_accesses (id, share_link_id, time_accessed, ip_address, user_agent, outcome) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+        )
+        .bind(Uuid::new_v4())
+        .bind(share_link_id)
+        .bind(Utc::now())
+        .bind(&client.ip_address)
+        .bind(&client.user_agent)
+        .bind(outcome.as_str())
+        .execute(self.pool.as_ref())
+        .await;
+
+        match record_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error recording the access of the share link".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn record_share_link_download(&self, share_link_id: &Uuid, client: &ShareLinkClient) -> Result<bool, String> {
+        let download_error = || "Error recording the download of the share link".to_string();
+
+        let now = Utc::now();
+
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(err) => return Err(logged(err, download_error()))
+        };
+
+        // Checking the limit in the update itself keeps concurrent downloads from exceeding it
+        let count_res = sqlx::query(
+            "UPDATE share_links SET download_count = download_count + 1
+            WHERE id = ?1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > ?2)
+            AND (max_downloads IS NULL OR download_count < max_downloads)"
+        )
+        .bind(share_link_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await;
+
+        match count_res {
+            Ok(result) if result.rows_affected() == 0 => return Ok(false),
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, download_error()))
+        }
+
+        let record_res = sqlx::query(
+            "INSERT INTO share_link_accesses (id, share_link_id, time_accessed, ip_address, user_agent, outcome) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+        )
+        .bind(Uuid::new_v4())
+        .bind(share_link_id)
+        .bind(now)
+        .bind(&client.ip_address)
+        .bind(&client.user_agent)
+        .bind(ShareLinkOutcome::Downloaded.as_str())
+        .execute(&mut *tx)
+        .await;
+
+        if record_res.is_err() {
+            return Err(download_error());
+        }
+
+        match tx.commit().await {
+            Ok(_) => Ok(true),
+            Err(err) => Err(logged(err, download_error()))
+        }
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,472 @@
+use std::{sync::Arc, collections::{HashMap, HashSet}};
+
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use sqlx::{FromRow, Pool, Sqlite, types::Json};
+use uuid::Uuid;
+
+use crate::domain::models::audit::AuditAction;
+use crate::domain::models::user::{ApiToken, Group, User};
+use crate::domain::repository::user::UserRepository;
+use crate::repository::sqlite::audit::SqliteAuditRepository;
+use crate::repository::logged;
+
+use async_trait::async_trait;
+
+
+pub struct SqliteUserRepository {
+    pub pool: Arc<Pool<Sqlite>>
+}
+
+
+/// The scopes of an API token and the members of a group are stored as JSON arrays
+#[derive(FromRow)]
+struct ApiTokenRow {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    token_hash: String,
+    scopes: Json<Vec<String>>,
+    time_created: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used: Option<DateTime<Utc>>
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        ApiToken {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            token_hash: row.token_hash,
+            scopes: row.scopes.0,
+            time_created: row.time_created,
+            expires_at: row.expires_at,
+            last_used: row.last_used
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct GroupRow {
+    id: Uuid,
+    name: String,
+    members: Json<Vec<String>>,
+    time_created: DateTime<Utc>
+}
+
+impl From<GroupRow> for Group {
+    fn from(row: GroupRow) -> Self {
+        Group { id: row.id, name: row.name, members: row.members.0, time_created: row.time_created }
+    }
+}
+
+
+#[async_trait]
+impl UserRepository for SqliteUserRepository {
+
+    #[instrument(skip_all)]
+    async fn count_users(&self) -> Result<i64, String> {
+        let user_count_res = sqlx::query_scalar::<_, i64>("SELECT count(*) FROM users")
+            .fetch_one(self.pool.as_ref())
+            .await;
+
+        match user_count_res {
+            Ok(count) => Ok(count),
+            Err(err) => Err(logged(err, "Error getting number of users".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_users(&self) -> Result<Vec<User>, String> {
+        let users_res = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY username")
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        match users_res {
+            Ok(users) => Ok(users),
+            Err(err) => Err(logged(err, "Error retrieving users".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?1")
+            .bind(user_id)
+            .fetch_optional(self.pool.as_ref())
+            .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?1")
+            .bind(username)
+            .fetch_optional(self.pool.as_ref())
+            .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_user(&self, username: &str, password_hash: &str, is_admin: bool) -> Result<Option<User>, String> {
+        let create_user_res = sqlx::query_as::<_, User>(
+            "INSERT INTO users(id, username, password_hash, is_admin, time_created) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (username) DO NOTHING RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(username)
+        .bind(password_hash)
+        .bind(is_admin)
+        .bind(Utc::now())
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match create_user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error creating user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn update_password(&self, user_id: &Uuid, password_hash: &str) -> Result<(), String> {
+        let update_password_res = sqlx::query("UPDATE users SET password_hash = ?2 WHERE id = ?1")
+            .bind(user_id)
+            .bind(password_hash)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match update_password_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error updating password".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<Option<Vec<String>>, String> {
+        let delete_error = || "Error deleting user".to_string();
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let pdfs_res = sqlx::query_as::<_, (Uuid, String)>("SELECT id, storage_key FROM pdfs WHERE owner_id = ?1")
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await;
+
+        let (pdf_ids, storage_keys): (Vec<Uuid>, Vec<String>) = match pdfs_res {
+            Ok(pdfs) => pdfs.into_iter().unzip(),
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let before = match SqliteAuditRepository::pdf_snapshots_with_connection(&pdf_ids, &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        // The pdfs of the user are deleted along with the user
+        let delete_user_res = sqlx::query("DELETE FROM users WHERE id = ?1")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await;
+
+        match delete_user_res {
+            Ok(result) if result.rows_affected() == 0 => return Ok(None),
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, delete_error()))
+        }
+
+        if SqliteAuditRepository::record_changes_with_connection(actor_id, AuditAction::Delete, &before, &HashMap::new(), &mut conn).await.is_err() {
+            return Err(delete_error());
+        }
+
+        let mut unused_storage_keys = Vec::new();
+        let mut seen_storage_keys = HashSet::new();
+
+        for storage_key in storage_keys.into_iter().filter(|storage_key| seen_storage_keys.insert(storage_key.clone())) {
+            let used_res = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM pdfs WHERE storage_key = ?1)")
+                .bind(&storage_key)
+                .fetch_one(&mut *conn)
+                .await;
+
+            match used_res {
+                Ok(false) => unused_storage_keys.push(storage_key),
+                Ok(true) => (),
+                Err(err) => return Err(logged(err, delete_error()))
+            }
+        }
+
+        match conn.commit().await {
+            Ok(_) => Ok(Some(unused_storage_keys)),
+            Err(err) => Err(logged(err, delete_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_session(&self, token_hash: &str, user_id: &Uuid, expires_at: DateTime<Utc>) -> Result<(), String> {
+        let create_session_res = sqlx::query("INSERT INTO sessions(token_hash, user_id, time_created, expires_at) VALUES (?1, ?2, ?3, ?4)")
+            .bind(token_hash)
+            .bind(user_id)
+            .bind(Utc::now())
+            .bind(expires_at)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match create_session_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error creating session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_session(&self, token_hash: &str) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as::<_, User>(
+            "SELECT users.* FROM users JOIN sessions ON users.id = sessions.user_id WHERE sessions.token_hash = ?1 AND sessions.expires_at > ?2"
+        )
+        .bind(token_hash)
+        .bind(Utc::now())
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_session(&self, token_hash: &str) -> Result<(), String> {
+        // Expired sessions are cleaned up along the way
+        let delete_session_res = sqlx::query("DELETE FROM sessions WHERE token_hash = ?1 OR expires_at <= ?2")
+            .bind(token_hash)
+            .bind(Utc::now())
+            .execute(self.pool.as_ref())
+            .await;
+
+        match delete_session_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error deleting session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_sessions_of_user(&self, user_id: &Uuid) -> Result<(), String> {
+        let delete_sessions_res = sqlx::query("DELETE FROM sessions WHERE user_id = ?1")
+            .bind(user_id)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match delete_sessions_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error deleting sessions".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_api_token(&self, user_id: &Uuid, name: &str, token_hash: &str, scopes: &[String], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, String> {
+        let create_token_res = sqlx::query_as::<_, ApiTokenRow>(
+            "INSERT INTO api_tokens(id, user_id, name, token_hash, scopes, time_created, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) RETURNING *"
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(Json(scopes))
+        .bind(Utc::now())
+        .bind(expires_at)
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match create_token_res {
+            Ok(api_token) => Ok(api_token.into()),
+            Err(err) => Err(logged(err, "Error creating API token".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiToken>, String> {
+        let tokens_res = sqlx::query_as::<_, ApiTokenRow>("SELECT * FROM api_tokens WHERE user_id = ?1 ORDER BY time_created")
+            .bind(user_id)
+            .fetch_all(self.pool.as_ref())
+            .await;
+
+        match tokens_res {
+            Ok(api_tokens) => Ok(api_tokens.into_iter().map(ApiToken::from).collect()),
+            Err(err) => Err(logged(err, "Error retrieving API tokens".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<bool, String> {
+        let delete_token_res = sqlx::query("DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2")
+            .bind(token_id)
+            .bind(user_id)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match delete_token_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error deleting API token".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_api_token(&self, token_hash: &str) -> Result<Option<(User, ApiToken)>, String> {
+        let now = Utc::now();
+
+        let api_token_res = sqlx::query_as::<_, ApiTokenRow>(
+            "UPDATE api_tokens SET last_used = ?2 WHERE token_hash = ?1 AND (expires_at IS NULL OR expires_at > ?2) RETURNING *"
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        let api_token: ApiToken = match api_token_res {
+            Ok(Some(api_token)) => api_token.into(),
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(logged(err, "Error retrieving API token".to_string()))
+        };
+
+        match self.get_user_by_id(&api_token.user_id).await? {
+            Some(user) => Ok(Some((user, api_token))),
+            None => Ok(None)
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_groups(&self) -> Result<Vec<Group>, String> {
+        let groups_res = sqlx::query_as::<_, GroupRow>(
+            "SELECT groups.id, groups.name, groups.time_created, (
+                SELECT json_group_array(username) FROM (
+                    SELECT users.username FROM group_members JOIN users ON users.id = group_members.user_id
+                    WHERE group_members.group_id = groups.id ORDER BY users.username
+                )
+            ) AS members
+            FROM groups
+            ORDER BY groups.name"
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match groups_res {
+            Ok(groups) => Ok(groups.into_iter().map(Group::from).collect()),
+            Err(err) => Err(logged(err, "Error retrieving groups".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_group(&self, name: &str) -> Result<Option<Group>, String> {
+        let create_group_res = sqlx::query_as::<_, GroupRow>(
+            "INSERT INTO groups(id, name, time_created) VALUES (?1, ?2, ?3) ON CONFLICT (name) DO NOTHING
+            RETURNING id, name, time_created, '[]' AS members"
+        )
+        .bind(Uuid::new_v4())
+        .bind(name)
+        .bind(Utc::now())
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match create_group_res {
+            Ok(group) => Ok(group.map(Group::from)),
+            Err(err) => Err(logged(err, "Error creating group".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_group(&self, group_id: &Uuid) -> Result<bool, String> {
+        let delete_group_res = sqlx::query("DELETE FROM groups WHERE id = ?1")
+            .bind(group_id)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match delete_group_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error deleting group".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        let add_member_error = || "Error adding group member".to_string();
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, add_member_error()))
+        };
+
+        // Adding a user that is already a member inserts nothing, but still counts as success
+        let add_member_res = sqlx::query(
+            "INSERT INTO group_members(group_id, user_id)
+            SELECT groups.id, users.id FROM groups, users WHERE groups.id = ?1 AND users.id = ?2
+            ON CONFLICT DO NOTHING"
+        )
+        .bind(group_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await;
+
+        if add_member_res.is_err() {
+            return Err(add_member_error());
+        }
+
+        let is_member_res = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM group_members WHERE group_id = ?1 AND user_id = ?2)")
+            .bind(group_id)
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await;
+
+        let is_member = match is_member_res {
+            Ok(is_member) => is_member,
+            Err(err) => return Err(logged(err, add_member_error()))
+        };
+
+        match conn.commit().await {
+            Ok(_) => Ok(is_member),
+            Err(err) => Err(logged(err, add_member_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        let remove_member_res = sqlx::query("DELETE FROM group_members WHERE group_id = ?1 AND user_id = ?2")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(self.pool.as_ref())
+            .await;
+
+        match remove_member_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error removing group member".to_string()))
+        }
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,428 @@
+use std::{sync::Arc, collections::HashMap};
+
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::models::audit::AuditAction;
+use crate::domain::models::user::{ApiToken, Group, User};
+use crate::domain::repository::user::UserRepository;
+use crate::repository::audit::AuditRepositoryImpl;
+use crate::repository::logged;
+
+use async_trait::async_trait;
+
+
+pub struct UserRepositoryImpl {
+    pub pool: Arc<Pool<Postgres>>
+}
+
+
+#[async_trait]
+impl UserRepository for UserRepositoryImpl {
+
+    #[instrument(skip_all)]
+    async fn count_users(&self) -> Result<i64, String> {
+        let user_count_res = sqlx::query_scalar!(
+            "SELECT count(*) FROM users"
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match user_count_res {
+            Ok(count) => Ok(count.unwrap_or(0)),
+            Err(err) => Err(logged(err, "Error getting number of users".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_users(&self) -> Result<Vec<User>, String> {
+        let users_res = sqlx::query_as!(
+            User,
+            "SELECT * FROM users ORDER BY username"
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match users_res {
+            Ok(users) => Ok(users),
+            Err(err) => Err(logged(err, "Error retrieving users".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_id(&self, user_id: &Uuid) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as!(
+            User,
+            "SELECT * FROM users WHERE username = $1",
+            username
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_user(&self, username: &str, password_hash: &str, is_admin: bool) -> Result<Option<User>, String> {
+        let create_user_res = sqlx::query_as!(
+            User,
+            "INSERT INTO users(username, password_hash, is_admin) VALUES ($1, $2, $3) ON CONFLICT (username) DO NOTHING RETURNING *",
+            username,
+            password_hash,
+            is_admin
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match create_user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error creating user".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn update_password(&self, user_id: &Uuid, password_hash: &str) -> Result<(), String> {
+        let update_password_res = sqlx::query!(
+            "UPDATE users SET password_hash = $2 WHERE id = $1",
+            user_id,
+            password_hash
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match update_password_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error updating password".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<Option<Vec<String>>, String> {
+        let delete_error = || "Error deleting user".to_string();
+
+        let mut conn = match self.pool.begin().await {
+            Ok(conn) => conn,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let pdfs_res = sqlx::query!(
+            "SELECT id, storage_key FROM pdfs WHERE owner_id = $1",
+            user_id
+        )
+        .fetch_all(&mut *conn)
+        .await;
+
+        let (pdf_ids, storage_keys): (Vec<Uuid>, Vec<String>) = match pdfs_res {
+            Ok(pdfs) => pdfs.into_iter().map(|record| (record.id, record.storage_key)).unzip(),
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        let before = match AuditRepositoryImpl::pdf_snapshots_with_connection(&pdf_ids, &mut conn).await {
+            Ok(before) => before,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        // The pdfs of the user are deleted along with the user
+        let delete_user_res = sqlx::query!(
+            "DELETE FROM users WHERE id = $1",
+            user_id
+        )
+        .execute(&mut *conn)
+        .await;
+
+        match delete_user_res {
+            Ok(result) if result.rows_affected() == 0 => return Ok(None),
+            Ok(_) => (),
+            Err(err) => return Err(logged(err, delete_error()))
+        }
+
+        if AuditRepositoryImpl::record_changes_with_connection(actor_id, AuditAction::Delete, &before, &HashMap::new(), &mut conn).await.is_err() {
+            return Err(delete_error());
+        }
+
+        let unused_storage_keys_res = sqlx::query_scalar!(
+            "SELECT DISTINCT deleted.storage_key AS \"storage_key!\" FROM UNNEST($1::TEXT[]) AS deleted(storage_key)
+            WHERE NOT EXISTS (SELECT 1 FROM pdfs WHERE pdfs.storage_key = deleted.storage_key)",
+            &storage_keys[..]
+        )
+        .fetch_all(&mut *conn)
+        .await;
+
+        let unused_storage_keys = match unused_storage_keys_res {
+            Ok(storage_keys) => storage_keys,
+            Err(err) => return Err(logged(err, delete_error()))
+        };
+
+        match conn.commit().await {
+            Ok(_) => Ok(Some(unused_storage_keys)),
+            Err(err) => Err(logged(err, delete_error()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_session(&self, token_hash: &str, user_id: &Uuid, expires_at: DateTime<Utc>) -> Result<(), String> {
+        let create_session_res = sqlx::query!(
+            "INSERT INTO sessions(token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match create_session_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error creating session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_session(&self, token_hash: &str) -> Result<Option<User>, String> {
+        let user_res = sqlx::query_as!(
+            User,
+            "SELECT users.* FROM users JOIN sessions ON users.id = sessions.user_id WHERE sessions.token_hash = $1 AND sessions.expires_at > now()",
+            token_hash
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match user_res {
+            Ok(user) => Ok(user),
+            Err(err) => Err(logged(err, "Error retrieving session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_session(&self, token_hash: &str) -> Result<(), String> {
+        // Expired sessions are cleaned up along the way
+        let delete_session_res = sqlx::query!(
+            "DELETE FROM sessions WHERE token_hash = $1 OR expires_at <= now()",
+            token_hash
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match delete_session_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error deleting session".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_sessions_of_user(&self, user_id: &Uuid) -> Result<(), String> {
+        let delete_sessions_res = sqlx::query!(
+            "DELETE FROM sessions WHERE user_id = $1",
+            user_id
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match delete_sessions_res {
+            Ok(_) => Ok(()),
+            Err(err) => Err(logged(err, "Error deleting sessions".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_api_token(&self, user_id: &Uuid, name: &str, token_hash: &str, scopes: &[String], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, String> {
+        let create_token_res = sqlx::query_as!(
+            ApiToken,
+            "INSERT INTO api_tokens(user_id, name, token_hash, scopes, expires_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            user_id,
+            name,
+            token_hash,
+            scopes,
+            expires_at
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match create_token_res {
+            Ok(api_token) => Ok(api_token),
+            Err(err) => Err(logged(err, "Error creating API token".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiToken>, String> {
+        let tokens_res = sqlx::query_as!(
+            ApiToken,
+            "SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY time_created",
+            user_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match tokens_res {
+            Ok(api_tokens) => Ok(api_tokens),
+            Err(err) => Err(logged(err, "Error retrieving API tokens".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<bool, String> {
+        let delete_token_res = sqlx::query!(
+            "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2",
+            token_id,
+            user_id
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match delete_token_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error deleting API token".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_user_by_api_token(&self, token_hash: &str) -> Result<Option<(User, ApiToken)>, String> {
+        let api_token_res = sqlx::query_as!(
+            ApiToken,
+            "UPDATE api_tokens SET last_used = now() WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > now()) RETURNING *",
+            token_hash
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        let api_token = match api_token_res {
+            Ok(Some(api_token)) => api_token,
+            Ok(None) => return Ok(None),
+            Err(err) => return Err(logged(err, "Error retrieving API token".to_string()))
+        };
+
+        match self.get_user_by_id(&api_token.user_id).await? {
+            Some(user) => Ok(Some((user, api_token))),
+            None => Ok(None)
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_groups(&self) -> Result<Vec<Group>, String> {
+        let groups_res = sqlx::query_as!(
+            Group,
+            "SELECT groups.id, groups.name, groups.time_created,
+                COALESCE(array_agg(users.username ORDER BY users.username) FILTER (WHERE users.username IS NOT NULL), '{}') AS \"members!\"
+            FROM groups LEFT JOIN group_members ON group_members.group_id = groups.id LEFT JOIN users ON users.id = group_members.user_id
+            GROUP BY groups.id
+            ORDER BY groups.name"
+        )
+        .fetch_all(self.pool.as_ref())
+        .await;
+
+        match groups_res {
+            Ok(groups) => Ok(groups),
+            Err(err) => Err(logged(err, "Error retrieving groups".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_group(&self, name: &str) -> Result<Option<Group>, String> {
+        let create_group_res = sqlx::query_as!(
+            Group,
+            "INSERT INTO groups(name) VALUES ($1) ON CONFLICT (name) DO NOTHING RETURNING id, name, time_created, '{}'::TEXT[] AS \"members!\"",
+            name
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await;
+
+        match create_group_res {
+            Ok(group) => Ok(group),
+            Err(err) => Err(logged(err, "Error creating group".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_group(&self, group_id: &Uuid) -> Result<bool, String> {
+        let delete_group_res = sqlx::query!(
+            "DELETE FROM groups WHERE id = $1",
+            group_id
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match delete_group_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error deleting group".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        // Adding a user that is already a member inserts nothing, but still counts as success
+        let add_member_res = sqlx::query_scalar!(
+            "WITH added AS (
+                INSERT INTO group_members(group_id, user_id)
+                SELECT groups.id, users.id FROM groups, users WHERE groups.id = $1 AND users.id = $2
+                ON CONFLICT DO NOTHING RETURNING user_id
+            )
+            SELECT EXISTS (SELECT 1 FROM added) OR EXISTS (SELECT 1 FROM group_members WHERE group_id = $1 AND user_id = $2) AS \"is_member!\"",
+            group_id,
+            user_id
+        )
+        .fetch_one(self.pool.as_ref())
+        .await;
+
+        match add_member_res {
+            Ok(is_member) => Ok(is_member),
+            Err(err) => Err(logged(err, "Error adding group member".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<bool, String> {
+        let remove_member_res = sqlx::query!(
+            "DELETE FROM group_members WHERE group_id = $1 AND user_id = $2",
+            group_id,
+            user_id
+        )
+        .execute(self.pool.as_ref())
+        .await;
+
+        match remove_member_res {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(err) => Err(logged(err, "Error removing group member".to_string()))
+        }
+    }
+
+}
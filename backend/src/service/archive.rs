@@ -0,0 +1,384 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use tempfile::NamedTempFile;
+use tracing::{instrument, warn};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::archive::{
+    ArchiveConflict, ArchiveImportItemDto, ArchiveImportReportDto, ArchiveImportStatus, ArchiveManifestDto, ArchivePdfDto,
+    ARCHIVE_FORMAT, ARCHIVE_MANIFEST, ARCHIVE_VERSION
+};
+use crate::api::dto::pdf::PdfPatchDto;
+use crate::config::StorageConfig;
+use crate::domain::models::archive::ArchiveExport;
+use crate::domain::models::user::User;
+use crate::domain::repository::pdf::PdfRepository;
+use crate::domain::repository::user::UserRepository;
+use crate::domain::service::archive::ArchiveService;
+use crate::engine::{analyze, PdfEngine};
+use crate::errors::{ArchiveError, PdfUpdateError};
+use crate::util::{is_content_key, remove_stored_pdf, storage_key_of, store_pdf, PdfUploaded};
+
+/// Folder of the archive the pdfs are stored in, each under its storage key
+const ARCHIVE_FILES_DIR: &str = "files/";
+
+
+#[derive(Clone)]
+pub struct ArchiveServiceImpl {
+    pub repository: Arc<dyn PdfRepository>,
+    pub user_repository: Arc<dyn UserRepository>,
+    pub engine: Arc<dyn PdfEngine>,
+    pub storage: Arc<StorageConfig>
+}
+
+
+/// What is read from an archive: its manifest and its files, each received in the tmp directory with its storage key
+struct UnpackedArchive {
+    manifest: ArchiveManifestDto,
+    files: HashMap<String, (NamedTempFile, String)>
+}
+
+
+impl ArchiveServiceImpl {
+
+    /// Storage key of the file derived from its content, files stored under their file name before pdfs were
+    /// stored by their content are hashed
+    async fn content_key(&self, storage_key: &str) -> Result<String, String> {
+        if is_content_key(storage_key) {
+            return Ok(storage_key.to_string());
+        }
+
+        let path = self.storage.upload_dir.join(storage_key);
+
+        let hash_res = web::block(move || {
+            match std::fs::File::open(path) {
+                Ok(mut file) => storage_key_of(&mut file),
+                Err(_) => Err("Error reading pdf".to_string())
+            }
+        }).await;
+
+        match hash_res {
+            Ok(res) => res,
+            Err(_) => Err("Error reading pdf".to_string())
+        }
+    }
+
+
+    /// Restores a pdf of the manifest for the owner. The pdfs the owner had before the import are identified by
+    /// their storage key, pdfs of the archive with the same content are all restored.
+    async fn import_pdf(
+        &self,
+        archived: &ArchivePdfDto,
+        archive: &mut UnpackedArchive,
+        on_conflict: ArchiveConflict,
+        owner_id: &Uuid,
+        owner_pdfs: &HashMap<String, Uuid>,
+        newly_stored: &mut HashSet<String>
+    ) -> Result<(ArchiveImportStatus, Uuid), String> {
+        let storage_key = format!("{}.pdf", archived.sha256);
+
+        if let Some(pdf_id) = owner_pdfs.get(&storage_key) {
+            if on_conflict == ArchiveConflict::Skip {
+                return Ok((ArchiveImportStatus::Skipped, *pdf_id));
+            }
+
+            let patch = PdfPatchDto {
+                title: Some(archived.title.clone()),
+                author: Some(archived.author.clone()),
+                comments: Some(archived.comments.clone()),
+                picture: None,
+                tags: Some(Some(archived.tags.clone())),
+                add_tags: None,
+                remove_tags: None
+            };
+
+            return match self.repository.patch(patch, pdf_id, owner_id, None).await {
+                Ok(_) => Ok((ArchiveImportStatus::Updated, *pdf_id)),
+                Err(PdfUpdateError::NotFound(msg)) | Err(PdfUpdateError::Forbidden(msg)) | Err(PdfUpdateError::DatabaseError(msg)) => Err(msg),
+                Err(PdfUpdateError::VersionMismatch(_)) => Err("The pdf was modified while it was overwritten".to_string())
+            };
+        }
+
+        let is_stored = self.storage.upload_dir.join(&storage_key).exists();
+
+        // Pdfs with the same content share the file of the archive, it is only moved to the upload directory once
+        let file = if is_stored {
+            None
+        } else {
+            match archive.files.remove(&archived.file) {
+                Some((file, key)) if key == storage_key => Some(file),
+                Some(_) => return Err(format!("The content of '{}' doesn't match its checksum", archived.file)),
+                None => return Err(format!("The archive has no file '{}'", archived.file))
+            }
+        };
+
+        let picture = match &archived.picture {
+            Some(picture) => picture.clone(),
+            None => {
+                let path = match &file {
+                    Some(file) => file.path().to_string_lossy().to_string(),
+                    None => self.storage.upload_path(&storage_key)
+                };
+
+                analyze(self.engine.as_ref(), &path).await?.preview
+            }
+        };
+
+        let upload = PdfUploaded {
+            title: archived.title.clone().unwrap_or(archived.file_name.clone()),
+            filename: archived.file_name.clone(),
+            author: archived.author.clone(),
+            pages: archived.pages,
+            img: picture,
+            file,
+            storage_key: storage_key.clone(),
+            tags: archived.tags.clone(),
+            comments: archived.comments.clone(),
+            time_added: archived.time_added
+        };
+
+        if store_pdf(&upload, &self.storage).await? {
+            newly_stored.insert(storage_key.clone());
+        }
+
+        match self.repository.upload(&upload, owner_id).await?.id {
+            Some(pdf_id) => Ok((ArchiveImportStatus::Created, pdf_id)),
+            None => Err("Error storing the uploaded pdf".to_string())
+        }
+    }
+
+}
+
+
+/// Reads the manifest and the files of the archive, the files are received in the tmp directory
+fn unpack(archive: &Path, tmp_dir: PathBuf) -> Result<UnpackedArchive, ArchiveError> {
+    let invalid = |err: std::io::Error| ArchiveError::InvalidArchive(format!("The archive can't be read: {err}"));
+
+    let file = std::fs::File::open(archive).map_err(invalid)?;
+    let mut tar = tar::Archive::new(file);
+
+    let mut manifest = None;
+    let mut files = HashMap::new();
+
+    for entry in tar.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path().map_err(invalid)?.to_string_lossy().to_string();
+
+        if name == ARCHIVE_MANIFEST {
+            let mut content = String::new();
+            entry.read_to_string(&mut content).map_err(invalid)?;
+
+            match serde_json::from_str::<ArchiveManifestDto>(&content) {
+                Ok(parsed) => manifest = Some(parsed),
+                Err(err) => return Err(ArchiveError::InvalidArchive(format!("The manifest can't be read: {err}")))
+            }
+        } else if name.starts_with(ARCHIVE_FILES_DIR) {
+            let mut received = match NamedTempFile::new_in(&tmp_dir) {
+                Ok(received) => received,
+                Err(err) => return Err(ArchiveError::DatabaseError(format!("Error creating a file in the tmp directory: {err}")))
+            };
+
+            std::io::copy(&mut entry, received.as_file_mut()).map_err(invalid)?;
+
+            let storage_key = storage_key_of(received.as_file_mut()).map_err(ArchiveError::DatabaseError)?;
+            files.insert(name, (received, storage_key));
+        }
+    }
+
+    let Some(manifest) = manifest else {
+        return Err(ArchiveError::InvalidArchive(format!("The archive has no {ARCHIVE_MANIFEST}")));
+    };
+
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(ArchiveError::InvalidArchive(format!("The archive is no {ARCHIVE_FORMAT}")));
+    }
+
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(ArchiveError::InvalidArchive(format!(
+            "The archive is of version {}, only archives up to version {} can be restored", manifest.version, ARCHIVE_VERSION
+        )));
+    }
+
+    Ok(UnpackedArchive { manifest, files })
+}
+
+
+#[async_trait]
+impl ArchiveService for ArchiveServiceImpl {
+
+    #[instrument(skip_all)]
+    async fn export(&self, since: Option<DateTime<Utc>>) -> Result<ArchiveExport, String> {
+        let exported_at = Utc::now();
+        let archived_pdfs = self.repository.get_archived_pdfs(since).await?;
+
+        let mut pdfs = Vec::new();
+        let mut files = Vec::new();
+
+        // Pdfs with the same content are archived with one file
+        let mut archived_files: HashMap<String, String> = HashMap::new();
+
+        for archived in archived_pdfs {
+            let pdf = archived.pdf;
+
+            if pdf.file_missing || !self.storage.upload_dir.join(&pdf.storage_key).exists() {
+                warn!("Leaving '{}' of {} out of the archive, its file is missing", pdf.file_name, archived.owner);
+                continue;
+            }
+
+            let content_key = match archived_files.get(&pdf.storage_key) {
+                Some(content_key) => content_key.clone(),
+                None => {
+                    let content_key = match self.content_key(&pdf.storage_key).await {
+                        Ok(content_key) => content_key,
+                        Err(msg) => {
+                            warn!("Leaving '{}' of {} out of the archive: {}", pdf.file_name, archived.owner, msg);
+                            continue;
+                        }
+                    };
+
+                    files.push((format!("{ARCHIVE_FILES_DIR}{content_key}"), self.storage.upload_dir.join(&pdf.storage_key)));
+                    archived_files.insert(pdf.storage_key.clone(), content_key.clone());
+                    content_key
+                }
+            };
+
+            pdfs.push(ArchivePdfDto {
+                owner: archived.owner,
+                file_name: pdf.file_name,
+                title: pdf.title,
+                author: pdf.author,
+                pages: pdf.pages,
+                comments: pdf.comments,
+                time_added: pdf.time_added,
+                tags: archived.tags,
+                picture: pdf.picture,
+                sha256: content_key.trim_end_matches(".pdf").to_string(),
+                file: format!("{ARCHIVE_FILES_DIR}{content_key}")
+            });
+        }
+
+        // A file of a legacy key and a content key with the same content is only archived once
+        let mut seen = HashSet::new();
+        files.retain(|(name, _)| seen.insert(name.clone()));
+
+        Ok(ArchiveExport {
+            manifest: ArchiveManifestDto {
+                format: ARCHIVE_FORMAT.to_string(),
+                version: ARCHIVE_VERSION,
+                exported_at,
+                since,
+                pdfs
+            },
+            files
+        })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn import(&self, archive: &Path, on_conflict: ArchiveConflict, user_id: &Uuid) -> Result<ArchiveImportReportDto, ArchiveError> {
+        let archive_path = archive.to_path_buf();
+        let tmp_dir = self.storage.tmp_dir.clone();
+
+        let mut unpacked = match web::block(move || unpack(&archive_path, tmp_dir)).await {
+            Ok(res) => res?,
+            Err(_) => return Err(ArchiveError::DatabaseError("Error reading the archive".to_string()))
+        };
+
+        let user = match self.user_repository.get_user_by_id(user_id).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Err(ArchiveError::DatabaseError("The importing user doesn't exist".to_string())),
+            Err(msg) => return Err(ArchiveError::DatabaseError(msg))
+        };
+
+        let users: HashMap<String, User> = match self.user_repository.get_users().await {
+            Ok(users) => users.into_iter().map(|user| (user.username.clone(), user)).collect(),
+            Err(msg) => return Err(ArchiveError::DatabaseError(msg))
+        };
+
+        let mut report = ArchiveImportReportDto {
+            on_conflict,
+            created: 0,
+            updated: 0,
+            skipped: 0,
+            failed: 0,
+            pdfs: Vec::new()
+        };
+
+        // Pdfs of each owner by storage key, to recognize those the owner has already
+        let mut pdfs_by_owner: HashMap<Uuid, HashMap<String, Uuid>> = HashMap::new();
+        let mut newly_stored = HashSet::new();
+
+        let archived_pdfs = std::mem::take(&mut unpacked.manifest.pdfs);
+
+        for archived in &archived_pdfs {
+            let owner = users.get(&archived.owner).unwrap_or(&user);
+
+            if let Entry::Vacant(entry) = pdfs_by_owner.entry(owner.id) {
+                let files = match self.repository.get_pdf_files(&owner.id).await {
+                    Ok(files) => files,
+                    Err(msg) => return Err(ArchiveError::DatabaseError(msg))
+                };
+
+                let owner_pdfs = files.into_iter()
+                    .filter_map(|file| file.id.map(|pdf_id| (file.storage_key, pdf_id)))
+                    .collect();
+
+                entry.insert(owner_pdfs);
+            }
+
+            let owner_pdfs = &pdfs_by_owner[&owner.id];
+
+            let import_res = self.import_pdf(archived, &mut unpacked, on_conflict, &owner.id, owner_pdfs, &mut newly_stored).await;
+
+            let item = match import_res {
+                Ok((status, pdf_id)) => {
+                    match status {
+                        ArchiveImportStatus::Created => report.created += 1,
+                        ArchiveImportStatus::Updated => report.updated += 1,
+                        ArchiveImportStatus::Skipped => report.skipped += 1,
+                        ArchiveImportStatus::Failed => report.failed += 1
+                    }
+
+                    ArchiveImportItemDto { file_name: archived.file_name.clone(), owner: owner.username.clone(), status, pdf_id: Some(pdf_id), error: None }
+                },
+                Err(msg) => {
+                    warn!("Error restoring '{}' of {} from the archive: {}", archived.file_name, archived.owner, msg);
+                    report.failed += 1;
+
+                    ArchiveImportItemDto {
+                        file_name: archived.file_name.clone(),
+                        owner: owner.username.clone(),
+                        status: ArchiveImportStatus::Failed,
+                        pdf_id: None,
+                        error: Some(msg)
+                    }
+                }
+            };
+
+            report.pdfs.push(item);
+        }
+
+        // The files of pdfs that couldn't be restored are removed once no other pdf of the archive needs them
+        for storage_key in newly_stored {
+            match self.repository.is_storage_key_used(&storage_key).await {
+                Ok(false) => remove_stored_pdf(&self.storage, &storage_key).await,
+                Ok(true) => (),
+                Err(msg) => warn!("Keeping the file '{}' of a rejected pdf, whether it is used couldn't be checked: {}", storage_key, msg)
+            }
+        }
+
+        Ok(report)
+    }
+
+}
@@ -0,0 +1,30 @@
+use tracing::instrument;
+use std::sync::Arc;
+use async_trait::async_trait;
+
+use crate::api::dto::audit::{AuditEntryDto, AuditLogDto, AuditLogSearchDto};
+use crate::domain::repository::audit::AuditRepository;
+use crate::domain::service::audit::AuditService;
+
+
+#[derive(Clone)]
+pub struct AuditServiceImpl {
+    pub repository: Arc<dyn AuditRepository>
+}
+
+
+#[async_trait]
+impl AuditService for AuditServiceImpl {
+
+    #[instrument(skip_all)]
+    async fn get_entries(&self, search: AuditLogSearchDto) -> Result<AuditLogDto, String> {
+        let count = self.repository.count_entries(&search).await?;
+
+        let entries = self.repository.get_entries_paged(&search).await?;
+
+        match entries.into_iter().map(AuditEntryDto::try_from).collect() {
+            Ok(entries) => Ok(AuditLogDto { entries, count }),
+            Err(msg) => Err(msg)
+        }
+    }
+}
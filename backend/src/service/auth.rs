@@ -0,0 +1,413 @@
+use actix_web::web;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use base64::Engine as _;
+use base64::engine::general_purpose;
+use chrono::{Duration, Utc};
+use tracing::{info, warn, instrument};
+use rand::{RngCore, rngs::OsRng};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::user::{CreateUserDto, LoginDto, PasswordChangeDto, SessionDto, UserDto};
+use crate::api::dto::api_token::{ApiTokenDto, CreateApiTokenDto, CreatedApiTokenDto};
+use crate::api::dto::group::{CreateGroupDto, GroupDto};
+use crate::domain::models::user::{AuthenticatedUser, Scope, User};
+use crate::domain::repository::user::UserRepository;
+use crate::domain::service::auth::AuthService;
+use crate::errors::AuthError;
+use crate::config::{AdminConfig, StorageConfig};
+use crate::util::remove_stored_pdf;
+
+
+const SESSION_DURATION_DAYS: i64 = 14;
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Tells API tokens apart from session tokens
+const API_TOKEN_PREFIX: &str = "pst_";
+
+
+#[derive(Clone)]
+pub struct AuthServiceImpl {
+    pub repository: Arc<dyn UserRepository>,
+    pub storage: Arc<StorageConfig>
+}
+
+
+/// Random token that is handed out to clients, only its hash is stored
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+
+/// Hashing is deliberately slow, so it runs on the blocking thread pool
+pub async fn hash_password(password: String) -> Result<String, String> {
+    let hash_res = web::block(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default().hash_password(password.as_bytes(), &salt).map(|hash| hash.to_string())
+    }).await;
+
+    match hash_res {
+        Ok(Ok(hash)) => Ok(hash),
+        _ => Err("Error hashing password".to_string())
+    }
+}
+
+
+pub async fn verify_password(password: String, password_hash: String) -> bool {
+    let verify_res = web::block(move || {
+        match PasswordHash::new(&password_hash) {
+            Ok(parsed_hash) => Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok(),
+            Err(_) => false
+        }
+    }).await;
+
+    verify_res.unwrap_or(false)
+}
+
+
+fn validate_password(password: &str) -> Result<(), AuthError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::InvalidInput(format!("Password must have at least {} characters", MIN_PASSWORD_LENGTH)));
+    }
+
+    Ok(())
+}
+
+
+#[async_trait]
+impl AuthService for AuthServiceImpl {
+
+    #[instrument(skip_all)]
+    async fn login(&self, login: LoginDto) -> Result<SessionDto, AuthError> {
+        let user = match self.authenticate_password(&login.username, &login.password).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Err(AuthError::InvalidCredentials("Invalid username or password".to_string())),
+            Err(msg) => return Err(AuthError::DatabaseError(msg))
+        };
+
+        let token = generate_token();
+        let expires_at = Utc::now() + Duration::days(SESSION_DURATION_DAYS);
+
+        if let Err(msg) = self.repository.create_session(&hash_token(&token), &user.id, expires_at).await {
+            return Err(AuthError::DatabaseError(msg));
+        }
+
+        Ok(SessionDto {
+            token,
+            expires_at,
+            user: UserDto::from(user)
+        })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn logout(&self, token: &str) -> Result<(), String> {
+        self.repository.delete_session(&hash_token(token)).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn authenticate_token(&self, token: &str) -> Result<Option<AuthenticatedUser>, String> {
+        if token.starts_with(API_TOKEN_PREFIX) {
+            let (user, api_token) = match self.repository.get_user_by_api_token(&hash_token(token)).await? {
+                Some(user_and_token) => user_and_token,
+                None => return Ok(None)
+            };
+
+            // A token can't do more than its user, e.g. after the user lost the admin role
+            let scopes = Scope::all_of(&user)
+                .into_iter()
+                .filter(|scope| api_token.scopes.iter().any(|token_scope| token_scope == scope.as_str()))
+                .collect();
+
+            return Ok(Some(AuthenticatedUser { user, scopes, api_token_id: Some(api_token.id) }));
+        }
+
+        let user = self.repository.get_user_by_session(&hash_token(token)).await?;
+
+        Ok(user.map(|user| AuthenticatedUser { scopes: Scope::all_of(&user), user, api_token_id: None }))
+    }
+
+
+    #[instrument(skip_all)]
+    async fn authenticate_password(&self, username: &str, password: &str) -> Result<Option<User>, String> {
+        let user = match self.repository.get_user_by_username(username).await? {
+            Some(user) => user,
+            None => {
+                // Spend the same time as for an existing user, so usernames can't be probed by timing
+                let _ = hash_password(password.to_string()).await;
+                return Ok(None);
+            }
+        };
+
+        if verify_password(password.to_string(), user.password_hash.clone()).await {
+            Ok(Some(user))
+        } else {
+            Ok(None)
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_users(&self) -> Result<Vec<UserDto>, String> {
+        let users = self.repository.get_users().await?;
+
+        Ok(users.into_iter().map(UserDto::from).collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn find_user_or_admin(&self, username: Option<&str>) -> Result<UserDto, String> {
+        let users = self.get_users().await?;
+
+        match username {
+            Some(username) => users.into_iter()
+                .find(|user| user.username == username)
+                .ok_or(format!("There is no user '{username}'")),
+            None => users.into_iter()
+                .filter(|user| user.is_admin)
+                .min_by_key(|user| user.time_created)
+                .ok_or("There is no admin account to act as".to_string())
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_user(&self, user: CreateUserDto) -> Result<UserDto, AuthError> {
+        let username = user.username.trim();
+
+        if username.is_empty() {
+            return Err(AuthError::InvalidInput("Username must not be empty".to_string()));
+        }
+
+        validate_password(&user.password)?;
+
+        let password_hash = match hash_password(user.password).await {
+            Ok(hash) => hash,
+            Err(msg) => return Err(AuthError::DatabaseError(msg))
+        };
+
+        match self.repository.create_user(username, &password_hash, user.is_admin).await {
+            Ok(Some(created_user)) => Ok(UserDto::from(created_user)),
+            Ok(None) => Err(AuthError::AlreadyExists("A user with the given username already exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_user(&self, user_id: &Uuid, actor_id: &Uuid) -> Result<(), AuthError> {
+        match self.repository.delete_user(user_id, actor_id).await {
+            Ok(Some(unused_storage_keys)) => {
+                for storage_key in unused_storage_keys {
+                    remove_stored_pdf(&self.storage, &storage_key).await;
+                }
+                Ok(())
+            },
+            Ok(None) => Err(AuthError::NotFound("No user with given ID exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn change_password(&self, user_id: &Uuid, change: PasswordChangeDto) -> Result<(), AuthError> {
+        let user = match self.repository.get_user_by_id(user_id).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Err(AuthError::NotFound("No user with given ID exists".to_string())),
+            Err(msg) => return Err(AuthError::DatabaseError(msg))
+        };
+
+        if !verify_password(change.current_password, user.password_hash).await {
+            return Err(AuthError::InvalidCredentials("The current password is wrong".to_string()));
+        }
+
+        validate_password(&change.new_password)?;
+
+        let password_hash = match hash_password(change.new_password).await {
+            Ok(hash) => hash,
+            Err(msg) => return Err(AuthError::DatabaseError(msg))
+        };
+
+        if let Err(msg) = self.repository.update_password(user_id, &password_hash).await {
+            return Err(AuthError::DatabaseError(msg));
+        }
+
+        // Sessions that were opened with the old password end
+        match self.repository.delete_sessions_of_user(user_id).await {
+            Ok(_) => Ok(()),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_api_token(&self, user: &User, api_token: CreateApiTokenDto) -> Result<CreatedApiTokenDto, AuthError> {
+        let name = api_token.name.trim();
+
+        if name.is_empty() {
+            return Err(AuthError::InvalidInput("Token name must not be empty".to_string()));
+        }
+
+        if api_token.scopes.is_empty() {
+            return Err(AuthError::InvalidInput("At least one scope is required".to_string()));
+        }
+
+        let allowed_scopes = Scope::all_of(user);
+
+        if let Some(scope) = api_token.scopes.iter().find(|scope| !allowed_scopes.contains(scope)) {
+            return Err(AuthError::InvalidInput(format!("The scope '{}' is not available to the user", scope.as_str())));
+        }
+
+        if api_token.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(AuthError::InvalidInput("The expiry date must be in the future".to_string()));
+        }
+
+        let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+        let scopes: Vec<String> = api_token.scopes.iter().map(|scope| scope.as_str().to_string()).collect();
+
+        match self.repository.create_api_token(&user.id, name, &hash_token(&token), &scopes, api_token.expires_at).await {
+            Ok(created_token) => Ok(CreatedApiTokenDto { token, api_token: ApiTokenDto::from(created_token) }),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_api_tokens(&self, user_id: &Uuid) -> Result<Vec<ApiTokenDto>, String> {
+        let api_tokens = self.repository.get_api_tokens(user_id).await?;
+
+        Ok(api_tokens.into_iter().map(ApiTokenDto::from).collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_api_token(&self, user_id: &Uuid, token_id: &Uuid) -> Result<(), AuthError> {
+        match self.repository.delete_api_token(user_id, token_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AuthError::NotFound("No API token with given ID exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_groups(&self) -> Result<Vec<GroupDto>, String> {
+        let groups = self.repository.get_groups().await?;
+
+        Ok(groups.into_iter().map(GroupDto::from).collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_group(&self, group: CreateGroupDto) -> Result<GroupDto, AuthError> {
+        let name = group.name.trim();
+
+        if name.is_empty() {
+            return Err(AuthError::InvalidInput("Group name must not be empty".to_string()));
+        }
+
+        match self.repository.create_group(name).await {
+            Ok(Some(created_group)) => Ok(GroupDto::from(created_group)),
+            Ok(None) => Err(AuthError::AlreadyExists("A group with the given name already exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_group(&self, group_id: &Uuid) -> Result<(), AuthError> {
+        match self.repository.delete_group(group_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AuthError::NotFound("No group with given ID exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<(), AuthError> {
+        match self.repository.add_group_member(group_id, user_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AuthError::NotFound("No group or user with given ID exists".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_group_member(&self, group_id: &Uuid, user_id: &Uuid) -> Result<(), AuthError> {
+        match self.repository.remove_group_member(group_id, user_id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AuthError::NotFound("The user is not a member of the group".to_string())),
+            Err(msg) => Err(AuthError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn bootstrap_admin(&self, config: &AdminConfig) -> Result<(), String> {
+        let (password, generated) = match &config.password {
+            Some(password) => (password.clone(), false),
+            None => (generate_token(), true)
+        };
+
+        // The migration that assigns the existing pdfs to an admin creates it without a password
+        let users = self.repository.get_users().await?;
+
+        if let Some(admin) = users.iter().find(|user| user.is_admin && user.password_hash.is_empty()) {
+            if let Err(AuthError::InvalidInput(msg)) = validate_password(&password) {
+                return Err(format!("Invalid admin.password (ADMIN_PASSWORD): {}", msg));
+            }
+
+            let password_hash = hash_password(password.clone()).await?;
+            self.repository.update_password(&admin.id, &password_hash).await?;
+
+            let username = &admin.username;
+
+            if generated {
+                warn!("Set the generated password '{password}' for the admin account '{username}', change it after logging in");
+            } else {
+                info!("Set the password of the admin account '{username}'");
+            }
+
+            return Ok(());
+        }
+
+        if !users.is_empty() {
+            return Ok(());
+        }
+
+        let username = config.username.clone();
+
+        let admin = CreateUserDto {
+            username: username.clone(),
+            password: password.clone(),
+            is_admin: true
+        };
+
+        match self.create_user(admin).await {
+            Ok(_) => (),
+            Err(AuthError::InvalidInput(msg)) => return Err(format!("Invalid admin.password (ADMIN_PASSWORD): {}", msg)),
+            Err(_) => return Err("Error creating the admin account".to_string())
+        }
+
+        if generated {
+            warn!("Created admin account '{username}' with the generated password '{password}', change it after logging in");
+        } else {
+            info!("Created admin account '{username}'");
+        }
+
+        Ok(())
+    }
+
+}
@@ -0,0 +1,237 @@
+use actix_web::web;
+use tracing::{instrument, warn};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::fsck::{FsckIssueDto, FsckIssueKind, FsckRepairStatus, FsckReportDto};
+use crate::config::StorageConfig;
+use crate::domain::models::pdf::StoredPdf;
+use crate::domain::repository::pdf::PdfRepository;
+use crate::domain::service::fsck::FsckService;
+use crate::engine::{analyze, PdfEngine};
+use crate::util::{is_content_key, storage_key_of, PdfUploaded};
+
+
+#[derive(Clone)]
+pub struct FsckServiceImpl {
+    pub repository: Arc<dyn PdfRepository>,
+    pub engine: Arc<dyn PdfEngine>,
+    pub storage: Arc<StorageConfig>
+}
+
+
+impl FsckServiceImpl {
+
+    /// Names of the files in the upload directory, without the hidden probe files of the health check
+    async fn stored_files(&self) -> Result<HashSet<String>, String> {
+        let mut entries = match tokio::fs::read_dir(&self.storage.upload_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return Err("Error reading the upload directory".to_string())
+        };
+
+        let mut files = HashSet::new();
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            let is_file = entry.metadata().await.is_ok_and(|metadata| metadata.is_file());
+
+            if is_file && !file_name.starts_with('.') {
+                files.insert(file_name);
+            }
+        }
+
+        Ok(files)
+    }
+
+
+    /// Whether the content of the file still has the hash its name was derived from.
+    /// Files stored under their file name before pdfs were stored by their content can't be checked.
+    async fn matches_checksum(&self, storage_key: &str) -> Result<bool, String> {
+        if !is_content_key(storage_key) {
+            return Ok(true);
+        }
+
+        let path = self.storage.upload_path(storage_key);
+
+        let hash_res = web::block(move || {
+            match std::fs::File::open(path) {
+                Ok(mut file) => storage_key_of(&mut file),
+                Err(_) => Err("Error reading pdf".to_string())
+            }
+        }).await;
+
+        match hash_res {
+            Ok(Ok(actual_key)) => Ok(actual_key == storage_key),
+            Ok(Err(msg)) => Err(msg),
+            Err(_) => Err("Error reading pdf".to_string())
+        }
+    }
+
+
+    async fn render_thumbnail(&self, pdf: &StoredPdf, user_id: &Uuid) -> Result<(), String> {
+        let analysis = analyze(self.engine.as_ref(), &self.storage.upload_path(&pdf.storage_key)).await?;
+
+        self.repository.set_picture(&pdf.id, &analysis.preview, user_id).await
+    }
+
+
+    /// Imports a file no pdf uses as is, its name is kept as file name and storage key
+    async fn import_orphan(&self, storage_key: &str, user_id: &Uuid) -> Result<Uuid, String> {
+        let analysis = analyze(self.engine.as_ref(), &self.storage.upload_path(storage_key)).await?;
+
+        let upload = PdfUploaded {
+            title: analysis.title.unwrap_or(storage_key.to_string()),
+            filename: storage_key.to_string(),
+            author: analysis.author,
+            pages: Some(analysis.pages),
+            img: analysis.preview,
+            file: None,
+            storage_key: storage_key.to_string(),
+            tags: Vec::new(),
+            comments: None,
+            time_added: None
+        };
+
+        match self.repository.upload(&upload, user_id).await?.id {
+            Some(pdf_id) => Ok(pdf_id),
+            None => Err("Error storing the uploaded pdf".to_string())
+        }
+    }
+
+}
+
+
+fn issue(kind: FsckIssueKind, pdf_id: Option<Uuid>, storage_key: &str, repair: FsckRepairStatus) -> FsckIssueDto {
+    FsckIssueDto { kind, pdf_id, storage_key: storage_key.to_string(), repair, error: None }
+}
+
+
+/// Records the outcome of a repair in the issue, which is left pending in a dry run
+fn repaired<T>(mut issue: FsckIssueDto, dry_run: bool, repair_res: Result<T, String>) -> FsckIssueDto {
+    if dry_run {
+        return issue;
+    }
+
+    match repair_res {
+        Ok(_) => issue.repair = FsckRepairStatus::Repaired,
+        Err(msg) => {
+            warn!("Error repairing the {:?} of '{}': {}", issue.kind, issue.storage_key, msg);
+            issue.repair = FsckRepairStatus::Failed;
+            issue.error = Some(msg);
+        }
+    }
+
+    issue
+}
+
+
+#[async_trait]
+impl FsckService for FsckServiceImpl {
+
+    #[instrument(skip_all)]
+    async fn check(&self, dry_run: bool, user_id: &Uuid) -> Result<FsckReportDto, String> {
+        let stored_pdfs = self.repository.get_stored_pdfs().await?;
+        let files = self.stored_files().await?;
+
+        let mut issues = Vec::new();
+
+        // Pdfs with the same content share a file, which is only hashed once
+        let mut checksums: HashMap<String, Result<bool, String>> = HashMap::new();
+
+        for pdf in &stored_pdfs {
+            let file_exists = files.contains(&pdf.storage_key);
+
+            match (file_exists, pdf.file_missing) {
+                (false, false) => {
+                    let missing = issue(FsckIssueKind::MissingFile, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Pending);
+                    let repair_res = if dry_run { Ok(()) } else { self.repository.set_file_missing(&pdf.id, true, user_id).await };
+                    issues.push(repaired(missing, dry_run, repair_res));
+                },
+                (false, true) => issues.push(issue(FsckIssueKind::MissingFile, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Manual)),
+                (true, true) => {
+                    let restored = issue(FsckIssueKind::RestoredFile, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Pending);
+                    let repair_res = if dry_run { Ok(()) } else { self.repository.set_file_missing(&pdf.id, false, user_id).await };
+                    issues.push(repaired(restored, dry_run, repair_res));
+                },
+                (true, false) => ()
+            }
+
+            if file_exists {
+                if !checksums.contains_key(&pdf.storage_key) {
+                    checksums.insert(pdf.storage_key.clone(), self.matches_checksum(&pdf.storage_key).await);
+                }
+
+                match &checksums[&pdf.storage_key] {
+                    Ok(true) => (),
+                    Ok(false) => issues.push(issue(FsckIssueKind::ChecksumMismatch, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Manual)),
+                    Err(msg) => issues.push(FsckIssueDto {
+                        error: Some(msg.clone()),
+                        ..issue(FsckIssueKind::ChecksumMismatch, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Manual)
+                    })
+                }
+            }
+
+            if !pdf.has_picture {
+                // Without its file the preview can't be rendered
+                if !file_exists {
+                    issues.push(issue(FsckIssueKind::MissingThumbnail, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Manual));
+                    continue;
+                }
+
+                let missing = issue(FsckIssueKind::MissingThumbnail, Some(pdf.id), &pdf.storage_key, FsckRepairStatus::Pending);
+                let repair_res = if dry_run { Ok(()) } else { self.render_thumbnail(pdf, user_id).await };
+                issues.push(repaired(missing, dry_run, repair_res));
+            }
+        }
+
+        let used_keys: HashSet<&String> = stored_pdfs.iter().map(|pdf| &pdf.storage_key).collect();
+
+        let mut orphans: Vec<&String> = files.iter().filter(|file| !used_keys.contains(file)).collect();
+        orphans.sort();
+
+        for storage_key in orphans {
+            let orphan = issue(FsckIssueKind::OrphanFile, None, storage_key, FsckRepairStatus::Pending);
+
+            if dry_run {
+                issues.push(orphan);
+                continue;
+            }
+
+            let import_res = self.import_orphan(storage_key, user_id).await;
+            let pdf_id = import_res.as_ref().ok().copied();
+
+            issues.push(FsckIssueDto { pdf_id, ..repaired(orphan, dry_run, import_res) });
+        }
+
+        Ok(FsckReportDto { dry_run, pdfs: stored_pdfs.len(), files: files.len(), issues })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn regenerate_thumbnails(&self, missing_only: bool, user_id: &Uuid) -> Result<(usize, Vec<String>), String> {
+        let stored_pdfs = self.repository.get_stored_pdfs().await?;
+
+        let mut rendered = 0;
+        let mut errors = Vec::new();
+
+        for pdf in stored_pdfs {
+            if (missing_only && pdf.has_picture) || pdf.file_missing {
+                continue;
+            }
+
+            match self.render_thumbnail(&pdf, user_id).await {
+                Ok(_) => rendered += 1,
+                Err(msg) => {
+                    warn!("Error rendering the preview of '{}': {}", pdf.id, msg);
+                    errors.push(format!("{}: {}", pdf.id, msg));
+                }
+            }
+        }
+
+        Ok((rendered, errors))
+    }
+
+}
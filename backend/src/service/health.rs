@@ -0,0 +1,159 @@
+use tracing::instrument;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use crate::api::dto::health::{HealthCheckDto, HealthStatus, ReadinessChecksDto, ReadinessDto};
+use crate::config::{Config, PdfEngineKind};
+use crate::domain::repository::health::HealthRepository;
+use crate::domain::service::health::HealthService;
+use crate::engine::PdfEngine;
+
+/// A check that takes longer fails, so an orchestrator polling the readiness gets an answer in time
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PROBE_CONTENT: &[u8] = b"pdf-store health probe";
+
+const MIB: u64 = 1024 * 1024;
+
+
+#[derive(Clone)]
+pub struct HealthServiceImpl {
+    pub repository: Arc<dyn HealthRepository>,
+    pub engine: Arc<dyn PdfEngine>,
+    pub config: Arc<Config>
+}
+
+
+impl HealthServiceImpl {
+
+    async fn check_database(&self) -> (HealthStatus, String) {
+        match self.repository.ping().await {
+            Ok(_) => (HealthStatus::Ok, "The database answers queries".to_string()),
+            Err(msg) => (HealthStatus::Fail, msg)
+        }
+    }
+
+    async fn check_migrations(&self) -> (HealthStatus, String) {
+        match self.repository.pending_migrations().await {
+            Ok(0) => (HealthStatus::Ok, "The database schema is up to date".to_string()),
+            Ok(pending) => (HealthStatus::Fail, format!("{} migrations haven't been applied", pending)),
+            Err(msg) => (HealthStatus::Fail, msg)
+        }
+    }
+
+    async fn check_storage(&self) -> (HealthStatus, String) {
+        for dir in [&self.config.storage.upload_dir, &self.config.storage.tmp_dir] {
+            if let Err(msg) = probe_directory(dir).await {
+                return (HealthStatus::Fail, msg);
+            }
+        }
+
+        (HealthStatus::Ok, "The upload and tmp directory can be written and read".to_string())
+    }
+
+    async fn check_pdf_engine(&self) -> (HealthStatus, String) {
+        if let Err(msg) = self.engine.check().await {
+            return (HealthStatus::Fail, msg);
+        }
+
+        // With `auto` an engine that can't render means the pdfium library couldn't be loaded
+        if !self.engine.can_render() && !matches!(self.config.pdf.engine, PdfEngineKind::Lopdf) {
+            return (HealthStatus::Warn, format!("Using the {} engine, the pdfium library couldn't be loaded", self.engine.name()));
+        }
+
+        (HealthStatus::Ok, format!("Using the {} engine", self.engine.name()))
+    }
+
+    async fn check_disk_space(&self) -> (HealthStatus, String) {
+        let min_free_space = self.config.storage.min_free_space;
+
+        match fs2::available_space(&self.config.storage.upload_dir) {
+            Ok(free_space) if free_space < min_free_space => (
+                HealthStatus::Fail,
+                format!("{} MiB free in the upload directory, at least {} MiB are required", free_space / MIB, min_free_space / MIB)
+            ),
+            Ok(free_space) => (HealthStatus::Ok, format!("{} MiB free in the upload directory", free_space / MIB)),
+            Err(err) => (HealthStatus::Fail, format!("Error reading the free space of the upload directory: {}", err))
+        }
+    }
+
+    async fn check_job_queue(&self) -> (HealthStatus, String) {
+        let queued_jobs = self.engine.queued_jobs();
+        let max_queued_jobs = self.config.pdf.max_queued_jobs;
+
+        if queued_jobs > max_queued_jobs {
+            (HealthStatus::Warn, format!("{} jobs are waiting for the pdf engine, more than {}", queued_jobs, max_queued_jobs))
+        } else {
+            (HealthStatus::Ok, format!("{} jobs are waiting for the pdf engine", queued_jobs))
+        }
+    }
+
+}
+
+
+/// Writes a file to the directory, reads it back and removes it again
+async fn probe_directory(dir: &Path) -> Result<(), String> {
+    let probe_path = dir.join(format!(".health-probe-{}", Uuid::new_v4().simple()));
+
+    if let Err(err) = tokio::fs::write(&probe_path, PROBE_CONTENT).await {
+        return Err(format!("Error writing to '{}': {}", dir.display(), err));
+    }
+
+    let read_res = tokio::fs::read(&probe_path).await;
+
+    let _ = tokio::fs::remove_file(&probe_path).await;
+
+    match read_res {
+        Ok(content) if content == PROBE_CONTENT => Ok(()),
+        Ok(_) => Err(format!("A file written to '{}' was read back with a different content", dir.display())),
+        Err(err) => Err(format!("Error reading from '{}': {}", dir.display(), err))
+    }
+}
+
+
+async fn run_check(critical: bool, check: impl Future<Output = (HealthStatus, String)>) -> HealthCheckDto {
+    let started = Instant::now();
+
+    let (status, message) = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(outcome) => outcome,
+        Err(_) => (HealthStatus::Fail, format!("The check didn't finish within {} seconds", CHECK_TIMEOUT.as_secs()))
+    };
+
+    HealthCheckDto { status, critical, latency_ms: started.elapsed().as_millis() as u64, message }
+}
+
+
+#[async_trait]
+impl HealthService for HealthServiceImpl {
+
+    #[instrument(skip_all)]
+    async fn readiness(&self) -> ReadinessDto {
+        let (database, migrations, storage, pdf_engine, disk_space, job_queue) = futures_util::join!(
+            run_check(true, self.check_database()),
+            run_check(true, self.check_migrations()),
+            run_check(true, self.check_storage()),
+            run_check(true, self.check_pdf_engine()),
+            run_check(true, self.check_disk_space()),
+            run_check(false, self.check_job_queue())
+        );
+
+        let checks = [&database, &migrations, &storage, &pdf_engine, &disk_space, &job_queue];
+
+        let status = if checks.iter().any(|check| check.critical && check.status == HealthStatus::Fail) {
+            HealthStatus::Fail
+        } else if checks.iter().any(|check| check.status != HealthStatus::Ok) {
+            HealthStatus::Warn
+        } else {
+            HealthStatus::Ok
+        };
+
+        ReadinessDto {
+            status,
+            checks: ReadinessChecksDto { database, migrations, storage, pdf_engine, disk_space, job_queue }
+        }
+    }
+}
@@ -1 +1,6 @@
-pub mod pdf;
\ No newline at end of file
+pub mod pdf;
+pub mod auth;
+pub mod audit;
+pub mod health;
+pub mod fsck;
+pub mod archive;
\ No newline at end of file
@@ -1,11 +1,20 @@
 use actix_web::web;
 use base64::Engine as _;
 use base64::engine::general_purpose;
-use log::trace;
+use chrono::Utc;
+use tracing::{instrument, warn};
 use std::sync::Arc;
 use async_trait::async_trait;
 use uuid::Uuid;
-use crate::{api::dto::{paging::PagingDto, pdf::{PdfOverviewDto, PdfMetadataDto, PdfDto, PdfSearchDto, PdfUpdateDto}}, errors::PdfMetadataByIdError, util::PdfUploaded};
+use crate::{api::dto::{paging::PagingDto, pdf::{PdfOverviewDto, PdfMetadataDto, PdfDto, PdfSearchDto, PdfUpdateDto, PdfPatchDto}}, errors::{PdfAccessError, PdfMetadataByIdError, PdfUpdateError}, util::{PdfUploaded, remove_stored_pdf, store_pdf}, domain::models::pdf::{LibraryStats, Pdf, PdfFile}};
+use crate::api::dto::bulk::{BulkOperationDto, BulkResultDto, BulkItemStatus};
+use crate::api::dto::share::{PdfShareDto, ShareTargetDto};
+use crate::api::dto::share_link::{CreateShareLinkDto, CreatedShareLinkDto, ShareLinkAccessDto, ShareLinkDto, SharedPdfDto};
+use crate::domain::models::share::{ShareLink, ShareLinkClient, ShareLinkOutcome};
+use crate::errors::ShareLinkError;
+use crate::metrics::{METRICS, UploadRejection};
+use crate::config::StorageConfig;
+use crate::service::auth::{generate_token, hash_token, hash_password, verify_password};
 use crate::domain::service::pdf::PdfService;
 use crate::domain::repository::pdf::PdfRepository;
 
@@ -13,48 +22,135 @@ use crate::domain::repository::pdf::PdfRepository;
 #[derive(Clone)]
 pub struct PdfServiceImpl {
     pub repository: Arc<dyn PdfRepository>,
+    pub storage: Arc<StorageConfig>
+}
+
+
+impl PdfServiceImpl {
+
+    /// Rolls back storing the file of a rejected upload. Another upload with the same content may have been stored
+    /// in the meantime, so the file is kept if a pdf uses it or if that can't be checked, since an unused file only
+    /// takes space while a missing one breaks a pdf.
+    async fn remove_unused_file(&self, storage_key: &str) {
+        match self.repository.is_storage_key_used(storage_key).await {
+            Ok(false) => remove_stored_pdf(&self.storage, storage_key).await,
+            Ok(true) => (),
+            Err(msg) => warn!("Keeping the file '{}' of a rejected upload, whether it is used couldn't be checked: {}", storage_key, msg)
+        }
+    }
+
+
+    /// Looks up the share link of the token and records the access if the link can't be used anymore
+    #[instrument(skip_all)]
+    async fn usable_share_link(&self, token: &str, client: &ShareLinkClient) -> Result<(ShareLink, Pdf), ShareLinkError> {
+        let (share_link, pdf) = match self.repository.get_share_link(&hash_token(token)).await {
+            Ok(Some(shared)) => shared,
+            Ok(None) => return Err(ShareLinkError::NotFound("No share link with the given token exists".to_string())),
+            Err(msg) => return Err(ShareLinkError::DatabaseError(msg))
+        };
+
+        let Some(outcome) = share_link.unusable_reason() else {
+            return Ok((share_link, pdf));
+        };
+
+        if let Err(msg) = self.repository.record_share_link_access(&share_link.id, client, outcome).await {
+            return Err(ShareLinkError::DatabaseError(msg));
+        }
+
+        Err(ShareLinkError::Gone(share_link_gone_message(outcome)))
+    }
+
+}
+
+
+fn share_link_gone_message(outcome: ShareLinkOutcome) -> String {
+    match outcome {
+        ShareLinkOutcome::Revoked => "The share link was revoked".to_string(),
+        ShareLinkOutcome::Expired => "The share link has expired".to_string(),
+        _ => "The share link can't be used to download the file anymore".to_string()
+    }
 }
 
 
 #[async_trait]
 impl PdfService for PdfServiceImpl {
 
-    async fn upload(&self, to_upload: Vec<PdfUploaded>) -> Result<(), String> {
-        trace!("service: upload()");
+    #[instrument(skip_all)]
+    async fn upload(&self, to_upload: Vec<PdfUploaded>, owner_id: &Uuid) -> Result<Vec<Uuid>, String> {
+        let mut uploaded_ids: Vec<Uuid> = Vec::new();
 
         for upload in to_upload {
 
-            let res = self.repository.upload(upload.title, upload.filename, upload.author, upload.pages, upload.img).await;
+            // The file is in place before the pdf is stored, so no stored pdf is ever without its file
+            let newly_stored = match store_pdf(&upload, &self.storage).await {
+                Ok(newly_stored) => newly_stored,
+                Err(msg) => {
+                    warn!("{msg}");
+                    METRICS.record_upload_rejected(UploadRejection::Storage);
+                    continue;
+                }
+            };
+
+            if !newly_stored {
+                METRICS.record_upload_duplicate();
+            }
+
+            let res = self.repository.upload(&upload, owner_id).await;
             match res {
-                Ok(_) => (),
-                Err(err) => {
-                    if err.as_database_error().is_some() {
-                        let code = err.as_database_error().unwrap().code().unwrap();
-                        if code != "23505" {
-                            std::fs::remove_file(upload.path).unwrap()
-                        }            
-                    } else {
-                        std::fs::remove_file(upload.path).unwrap();
+                Ok(pdf) => {
+                    METRICS.record_upload_accepted();
+
+                    if let Some(pdf_id) = pdf.id {
+                        uploaded_ids.push(pdf_id);
+                    }
+                },
+                Err(_) => {
+                    METRICS.record_upload_rejected(UploadRejection::Database);
+
+                    if newly_stored {
+                        self.remove_unused_file(&upload.storage_key).await;
                     }
                 }
             }
         }
 
-        Ok(())
+        Ok(uploaded_ids)
     }
 
 
-    async fn get_all(&self, paging: PagingDto) -> Result<PdfOverviewDto, String> {
-        trace!("service: get_all()");
+    #[instrument(skip_all)]
+    async fn get_library_stats(&self) -> Result<LibraryStats, String> {
+        let (documents, pages) = self.repository.count_pdfs_and_pages().await?;
+
+        let mut entries = match tokio::fs::read_dir(&self.storage.upload_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return Err("Error reading the upload directory".to_string())
+        };
+
+        let mut bytes = 0;
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(metadata) = entry.metadata().await {
+                if metadata.is_file() {
+                    bytes += metadata.len();
+                }
+            }
+        }
+
+        Ok(LibraryStats { documents, pages, bytes })
+    }
+
 
-        let pdf_count_result = self.repository.total_number_of_pdfs().await;
+    #[instrument(skip_all)]
+    async fn get_all(&self, paging: PagingDto, user_id: &Uuid) -> Result<PdfOverviewDto, String> {
+        let pdf_count_result = self.repository.total_number_of_pdfs(user_id).await;
         
         match pdf_count_result {
             Err(msg) => return Err(msg),
             _ => ()
         }
 
-        let paged_pdfs_res = self.repository.get_pdfs_paged(&paging).await;
+        let paged_pdfs_res = self.repository.get_pdfs_paged(&paging, user_id).await;
 
         match paged_pdfs_res {
             Err(msg) => return Err(msg),
@@ -67,10 +163,9 @@ impl PdfService for PdfServiceImpl {
     }
 
 
-    async fn get_pdf_metadata(&self, pdf_id: &Uuid) -> Result<PdfMetadataDto, PdfMetadataByIdError> {
-        trace!("service: get_pdf_metadata");
-
-        let pdf_metadata_res = self.repository.get_pdf_metadata(pdf_id).await;
+    #[instrument(skip_all)]
+    async fn get_pdf_metadata(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<PdfMetadataDto, PdfMetadataByIdError> {
+        let pdf_metadata_res = self.repository.get_pdf_metadata(pdf_id, user_id).await;
 
         match pdf_metadata_res {
             Err(err) => return Err(err),
@@ -96,7 +191,10 @@ impl PdfService for PdfServiceImpl {
             uploaded: pdf_metadata.time_added,
             last_accessed: pdf_metadata.last_accessed,
             picture: pdf_metadata.picture,
-            tags: Some(associated_tags.unwrap())
+            tags: Some(associated_tags.unwrap()),
+            version: pdf_metadata.version,
+            owner_id: pdf_metadata.owner_id,
+            file_missing: pdf_metadata.file_missing
         };
 
         return Ok(metadata_dto);
@@ -104,28 +202,31 @@ impl PdfService for PdfServiceImpl {
     }
 
 
-    async fn get_by_id(&self, pdf_id: &Uuid) -> Result<PdfDto, String> {
-        trace!("service: get_by_id()");
+    #[instrument(skip_all)]
+    async fn get_by_id(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<PdfDto, PdfMetadataByIdError> {
+        let storage_key = self.repository.get_by_id(pdf_id, user_id).await?;
 
-        let file_name_res = self.repository.get_by_id(pdf_id).await;
+        let path = self.storage.upload_path(&storage_key);
+        let pdf_content = web::block(|| std::fs::read(path)).await;
 
-        match file_name_res {
-            Err(msg) => return Err(msg),
-            _ => ()
+        match pdf_content {
+            Ok(Ok(pdf_content)) => Ok(PdfDto { pdf: general_purpose::STANDARD.encode(&pdf_content) }),
+            _ => Err(PdfMetadataByIdError::DatabaseError("Error reading the file of the pdf".to_string()))
         }
+    }
 
-        let path = format!("./upload/{}", file_name_res.as_ref().unwrap());
-        let pdf_content = web::block(|| std::fs::read(path)).await.unwrap();
-        let to_base64 = general_purpose::STANDARD.encode(&pdf_content.unwrap());
 
-        return Ok(PdfDto { pdf: to_base64 });
-    }
+    #[instrument(skip_all)]
+    async fn get_file_path(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<String, PdfMetadataByIdError> {
+        let storage_key = self.repository.get_by_id(pdf_id, user_id).await?;
 
+        Ok(self.storage.upload_path(&storage_key))
+    }
 
-    async fn search(&self, search: &PdfSearchDto) -> Result<PdfOverviewDto, String> {
-        trace!("service: search()");
 
-        let search_res = self.repository.search(search).await;
+    #[instrument(skip_all)]
+    async fn search(&self, search: &PdfSearchDto, user_id: &Uuid) -> Result<PdfOverviewDto, String> {
+        let search_res = self.repository.search(search, user_id).await;
 
         match search_res {
             Ok(search_res_deto) => Ok(search_res_deto),
@@ -134,10 +235,9 @@ impl PdfService for PdfServiceImpl {
     }
 
 
-    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid) -> Result<PdfMetadataDto, String> {
-        trace!("service: update()");
-
-        let update_res = self.repository.update(update, pdf_id).await;
+    #[instrument(skip_all)]
+    async fn update(&self, update: PdfUpdateDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        let update_res = self.repository.update(update, pdf_id, user_id, expected_versions).await;
 
         match update_res {
             Err(msg) => Err(msg),
@@ -146,23 +246,261 @@ impl PdfService for PdfServiceImpl {
     }
 
 
-    async fn delete(&self, pdf_id: &Uuid) -> Result<(), String> {
-        trace!("service: delete()");
+    #[instrument(skip_all)]
+    async fn patch(&self, patch: PdfPatchDto, pdf_id: &Uuid, user_id: &Uuid, expected_versions: Option<Vec<i32>>) -> Result<PdfMetadataDto, PdfUpdateError> {
+        self.repository.patch(patch, pdf_id, user_id, expected_versions).await
+    }
 
-        let delete_from_db_res = self.repository.delete(pdf_id).await;
 
-        let file_name;
+    #[instrument(skip_all)]
+    async fn delete(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<(), PdfAccessError> {
+        let unused_storage_key = self.repository.delete(pdf_id, user_id).await?;
 
-        match delete_from_db_res {
-            Err(msg) => return Err(msg),
-            Ok(name) => file_name = name
+        // The file stays as long as another pdf has the same content
+        if let Some(storage_key) = unused_storage_key {
+            remove_stored_pdf(&self.storage, &storage_key).await;
         }
 
-        let path = format!("./upload/{}", file_name);
-        let _ = std::fs::remove_file(path); //Not too important whether this succeeds or not
-
         Ok(())
     }
 
-    
+
+    #[instrument(skip_all)]
+    async fn bulk(&self, bulk: BulkOperationDto, user_id: &Uuid) -> Result<BulkResultDto, String> {
+        let (results, unused_storage_keys) = self.repository.bulk(&bulk, user_id).await?;
+
+        // In a dry run nothing was deleted from the database, so the files have to stay as well
+        if !bulk.dry_run {
+            for storage_key in unused_storage_keys {
+                remove_stored_pdf(&self.storage, &storage_key).await;
+            }
+        }
+
+        Ok(BulkResultDto {
+            dry_run: bulk.dry_run,
+            selected: results.iter().filter(|result| result.status != BulkItemStatus::NotFound).count(),
+            changed: results.iter().filter(|result| result.status == BulkItemStatus::Changed).count(),
+            results
+        })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files(&self, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        self.repository.get_pdf_files(owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_tag(&self, tag: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        self.repository.get_pdf_files_by_tag(tag, owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_files_by_author(&self, author: &str, owner_id: &Uuid) -> Result<Vec<PdfFile>, String> {
+        self.repository.get_pdf_files_by_author(author, owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_pdf_file_by_name(&self, file_name: &str, owner_id: &Uuid) -> Result<Option<PdfFile>, String> {
+        self.repository.get_pdf_file_by_name(file_name, owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_tags(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        self.repository.get_tags(owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_authors(&self, owner_id: &Uuid) -> Result<Vec<String>, String> {
+        self.repository.get_authors(owner_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_tag(&self, tag: &str, user_id: &Uuid) -> Result<(), String> {
+        self.repository.create_tag(tag, user_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn add_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        self.repository.add_tag(pdf_id, tag, user_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn remove_tag(&self, pdf_id: &Uuid, tag: &str, user_id: &Uuid) -> Result<(), PdfUpdateError> {
+        self.repository.remove_tag(pdf_id, tag, user_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn merge_tags(&self, sources: &[String], target: &str, user_id: &Uuid) -> Result<usize, String> {
+        if target.trim().is_empty() {
+            return Err("The target tag must not be empty".to_string());
+        }
+
+        self.repository.merge_tags(sources, target, user_id).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn rebuild_search_index(&self) -> Result<(), String> {
+        self.repository.rebuild_search_index().await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_shares(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<PdfShareDto>, PdfAccessError> {
+        let shares = self.repository.get_shares(pdf_id, user_id).await?;
+
+        match shares.into_iter().map(PdfShareDto::try_from).collect() {
+            Ok(shares) => Ok(shares),
+            Err(msg) => Err(PdfAccessError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn set_share(&self, pdf_id: &Uuid, user_id: &Uuid, share: PdfShareDto) -> Result<(), PdfAccessError> {
+        self.repository.set_share(pdf_id, user_id, &share).await
+    }
+
+
+    #[instrument(skip_all)]
+    async fn delete_share(&self, pdf_id: &Uuid, user_id: &Uuid, target: ShareTargetDto) -> Result<(), PdfAccessError> {
+        match self.repository.delete_share(pdf_id, user_id, &target).await? {
+            true => Ok(()),
+            false => Err(PdfAccessError::NotFound("The pdf is not shared with the given user or group".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn create_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link: CreateShareLinkDto) -> Result<CreatedShareLinkDto, PdfAccessError> {
+        if share_link.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(PdfAccessError::InvalidInput("The expiry date has to be in the future".to_string()));
+        }
+
+        if share_link.max_downloads.is_some_and(|max_downloads| max_downloads < 1) {
+            return Err(PdfAccessError::InvalidInput("The download limit has to be at least 1".to_string()));
+        }
+
+        let password_hash = match share_link.password {
+            Some(password) if password.is_empty() => return Err(PdfAccessError::InvalidInput("The password must not be empty".to_string())),
+            Some(password) => match hash_password(password).await {
+                Ok(password_hash) => Some(password_hash),
+                Err(msg) => return Err(PdfAccessError::DatabaseError(msg))
+            },
+            None => None
+        };
+
+        let token = generate_token();
+
+        let created = self.repository.create_share_link(
+            pdf_id,
+            user_id,
+            &hash_token(&token),
+            password_hash.as_deref(),
+            share_link.expires_at,
+            share_link.max_downloads
+        ).await?;
+
+        Ok(CreatedShareLinkDto {
+            url: format!("/s/{}", token),
+            token,
+            share_link: ShareLinkDto::from(created)
+        })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_links(&self, pdf_id: &Uuid, user_id: &Uuid) -> Result<Vec<ShareLinkDto>, PdfAccessError> {
+        let share_links = self.repository.get_share_links(pdf_id, user_id).await?;
+
+        Ok(share_links.into_iter().map(ShareLinkDto::from).collect())
+    }
+
+
+    #[instrument(skip_all)]
+    async fn revoke_share_link(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<(), PdfAccessError> {
+        match self.repository.revoke_share_link(pdf_id, user_id, share_link_id).await? {
+            true => Ok(()),
+            false => Err(PdfAccessError::NotFound("The pdf has no share link with the given ID that wasn't revoked yet".to_string()))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn get_share_link_accesses(&self, pdf_id: &Uuid, user_id: &Uuid, share_link_id: &Uuid) -> Result<Vec<ShareLinkAccessDto>, PdfAccessError> {
+        let accesses = match self.repository.get_share_link_accesses(pdf_id, user_id, share_link_id).await? {
+            Some(accesses) => accesses,
+            None => return Err(PdfAccessError::NotFound("The pdf has no share link with the given ID".to_string()))
+        };
+
+        match accesses.into_iter().map(ShareLinkAccessDto::try_from).collect() {
+            Ok(accesses) => Ok(accesses),
+            Err(msg) => Err(PdfAccessError::DatabaseError(msg))
+        }
+    }
+
+
+    #[instrument(skip_all)]
+    async fn open_share_link(&self, token: &str, client: ShareLinkClient) -> Result<SharedPdfDto, ShareLinkError> {
+        let (share_link, pdf) = self.usable_share_link(token, &client).await?;
+
+        if let Err(msg) = self.repository.record_share_link_access(&share_link.id, &client, ShareLinkOutcome::Viewed).await {
+            return Err(ShareLinkError::DatabaseError(msg));
+        }
+
+        Ok(SharedPdfDto {
+            title: pdf.title,
+            author: pdf.author,
+            pages: pdf.pages,
+            file_name: pdf.file_name,
+            password_protected: share_link.password_hash.is_some(),
+            expires_at: share_link.expires_at,
+            downloads_left: share_link.max_downloads.map(|max_downloads| max_downloads - share_link.download_count)
+        })
+    }
+
+
+    #[instrument(skip_all)]
+    async fn download_share_link(&self, token: &str, password: Option<String>, client: ShareLinkClient) -> Result<(String, String), ShareLinkError> {
+        let (share_link, pdf) = self.usable_share_link(token, &client).await?;
+
+        if let Some(password_hash) = share_link.password_hash {
+            let password_matches = match password {
+                Some(password) => verify_password(password, password_hash).await,
+                None => false
+            };
+
+            if !password_matches {
+                if let Err(msg) = self.repository.record_share_link_access(&share_link.id, &client, ShareLinkOutcome::WrongPassword).await {
+                    return Err(ShareLinkError::DatabaseError(msg));
+                }
+
+                return Err(ShareLinkError::PasswordRequired("The share link is password protected and no or a wrong password was given".to_string()));
+            }
+        }
+
+        match self.repository.record_share_link_download(&share_link.id, &client).await {
+            Ok(true) => Ok((self.storage.upload_path(&pdf.storage_key), pdf.file_name)),
+            Ok(false) => {
+                // Another download used up the link since it was looked up
+                if let Err(msg) = self.repository.record_share_link_access(&share_link.id, &client, ShareLinkOutcome::LimitReached).await {
+                    return Err(ShareLinkError::DatabaseError(msg));
+                }
+
+                Err(ShareLinkError::Gone(share_link_gone_message(ShareLinkOutcome::LimitReached)))
+            },
+            Err(msg) => Err(ShareLinkError::DatabaseError(msg))
+        }
+    }
+
 }
\ No newline at end of file
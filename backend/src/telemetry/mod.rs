@@ -0,0 +1,81 @@
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Name of the backend in the exported traces
+const SERVICE_NAME: &str = "pdf-store";
+
+/// Logged when `RUST_LOG` isn't set
+const DEFAULT_FILTER: &str = "info";
+
+
+/// Keeps the exporter of the traces running, `shutdown` sends the spans that are still buffered
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>
+}
+
+
+impl Telemetry {
+
+    pub fn shutdown(self) {
+        if let Some(tracer_provider) = self.tracer_provider {
+            if let Err(err) = tracer_provider.shutdown() {
+                eprintln!("Error exporting the remaining traces: {}", err);
+            }
+        }
+    }
+
+}
+
+
+/// Installs the subscriber that logs the events of the backend and its libraries, including those logged through
+/// the `log` crate, and exports the spans to the OTLP endpoint if one is configured
+pub fn init(config: &LoggingConfig) -> Result<Telemetry, String> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    let fmt_layer = match config.format {
+        LogFormat::Text => fmt::layer().boxed(),
+        // The fields of all spans an event happened in are listed, so every event of a request has its ID
+        LogFormat::Json => fmt::layer().json().flatten_event(true).with_span_list(true).with_current_span(false).boxed()
+    };
+
+    let tracer_provider = match &config.otlp_endpoint {
+        Some(endpoint) => Some(tracer_provider(endpoint)?),
+        None => None
+    };
+
+    let otlp_layer = tracer_provider.as_ref()
+        .map(|tracer_provider| tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(SERVICE_NAME)));
+
+    let init_res = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .try_init();
+
+    match init_res {
+        Ok(_) => Ok(Telemetry { tracer_provider }),
+        Err(err) => Err(format!("Error setting up the logging: {}", err))
+    }
+}
+
+
+fn tracer_provider(endpoint: &str) -> Result<SdkTracerProvider, String> {
+    let exporter_res = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build();
+
+    let exporter = match exporter_res {
+        Ok(exporter) => exporter,
+        Err(err) => return Err(format!("Error setting up the export of the traces to '{}': {}", endpoint, err))
+    };
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build())
+}
\ No newline at end of file
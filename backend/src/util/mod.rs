@@ -1,10 +1,23 @@
-use log::trace;
+use tracing::{trace, warn};
 use actix_multipart::form::{MultipartForm, tempfile::TempFile};
-use base64::{engine::general_purpose, Engine};
-use image::ImageOutputFormat;
+use actix_web::web;
 use mime::APPLICATION_PDF;
-use pdfium_render::{prelude::{Pdfium, PdfiumError, PdfDocumentMetadataTagType}, render_config::PdfRenderConfig};
+use std::io::Seek;
+use std::path::{Path, PathBuf};
 use std::result::Result;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use uuid::Uuid;
+use crate::config::StorageConfig;
+use crate::domain::repository::pdf::PdfRepository;
+use crate::domain::service::pdf::PdfService;
+use crate::engine::{analyze, PdfEngine};
+use crate::metrics::{METRICS, UploadRejection};
+
+/// Files in the tmp and upload directory that weren't modified for this long are left over from an interrupted upload
+pub const STALE_FILE_AGE: Duration = Duration::from_secs(60 * 60);
 
 #[derive(Debug, MultipartForm)]
 pub struct UploadForm {
@@ -19,106 +32,265 @@ pub struct PdfUploaded {
     pub author: Option<String>,
     pub pages: Option<i32>,
     pub img: String,
-    pub path: String
+    /// The received pdf in the tmp directory, it is only moved to the upload directory by `store_pdf`.
+    /// `None` for pdfs whose file is in the upload directory already, like those re-imported by the storage check.
+    pub file: Option<NamedTempFile>,
+    /// Name of the file in the upload directory, derived from the content of the pdf
+    pub storage_key: String,
+    pub tags: Vec<String>,
+    /// Only set for pdfs restored from an archive
+    pub comments: Option<String>,
+    /// When the pdf was added, the time it is stored when it isn't set
+    pub time_added: Option<DateTime<Utc>>
 }
 
 
-pub fn map_pdfs(MultipartForm(form): MultipartForm<UploadForm>) -> Result<Vec<PdfUploaded>, String> {
+pub async fn map_pdfs(MultipartForm(form): MultipartForm<UploadForm>, engine: &dyn PdfEngine) -> Result<Vec<PdfUploaded>, String> {
     trace!("map_pdfs()");
 
-    let binding_res = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"));
-
-    if binding_res.is_err() {
-        return Err("Error mapping pdf".to_string());
-    }
-
-    let pdfium = &Pdfium::new(binding_res.unwrap());
-
     let mut pdf_to_upload: Vec<PdfUploaded> = Vec::new();
     
 
     for file in form.files {
 
         if file.content_type.is_none() {
+            METRICS.record_upload_rejected(UploadRejection::NotPdf);
             continue;
         }
 
         if !(file.content_type.unwrap() == APPLICATION_PDF) {
+            METRICS.record_upload_rejected(UploadRejection::NotPdf);
             continue;
         }
 
         let file_name = file.file_name.unwrap();
-        let file_name_temp = file_name.clone();
 
-        let path = format!("./upload/{}", file_name);
-        let path_cloned = path.clone();
-        let persist_file_fs = file.file.persist(path);
+        match map_pdf(engine, file.file, file_name).await {
+            Ok(pdf_uploaded) => pdf_to_upload.push(pdf_uploaded),
+            Err(_) => continue
+        }
+    }
 
 
-        if persist_file_fs.is_err() {
-            continue;
-        }
+    Ok(pdf_to_upload)
 
-        let pdf_info_res = get_preview_of_pdf_pdfium(&pdfium, &path_cloned, String::as_str(&file_name_temp));
+}
 
-        if pdf_info_res.is_err() {
-            std::fs::remove_file(path_cloned).unwrap();
-            continue;
-        }
 
-        let (img, title, author, pages) = pdf_info_res.unwrap();
+/// Name under which a pdf is stored in the upload directory. The name is the hash of the content,
+/// so pdfs with the same content are stored only once.
+pub fn storage_key_of(file: &mut std::fs::File) -> Result<String, String> {
+    trace!("storage_key_of()");
+
+    let mut hasher = Sha256::new();
 
-        pdf_to_upload.push(PdfUploaded { title: title, filename: file_name_temp, author: author, pages: pages, img: img, path: path_cloned });
+    // Files written through the same handle, like those of WebDAV clients, are positioned at their end
+    if file.rewind().is_err() {
+        return Err("Error reading pdf".to_string());
     }
 
+    match std::io::copy(file, &mut hasher) {
+        Ok(_) => Ok(format!("{:x}.pdf", hasher.finalize())),
+        Err(_) => Err("Error reading pdf".to_string())
+    }
+}
 
-    Ok(pdf_to_upload)
 
+/// Storage keys derived from the content are the sha256 hash of the pdf, older pdfs are stored under their file name
+pub fn is_content_key(storage_key: &str) -> bool {
+    storage_key.strip_suffix(".pdf")
+        .is_some_and(|hash| hash.len() == 64 && hash.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f')))
 }
 
 
-fn get_preview_of_pdf_pdfium(pdfium: &Pdfium, location: &String, filename: &str) -> std::result::Result<(String, String, Option<String>, Option<i32>), PdfiumError> {
-    
+/// Extracts the information that is stored about a received pdf. The pdf stays in the tmp directory,
+/// so nothing is left in the upload directory when it is rejected.
+pub async fn map_pdf(engine: &dyn PdfEngine, file: NamedTempFile, file_name: String) -> Result<PdfUploaded, String> {
+    trace!("map_pdf()");
 
-    let document = pdfium.load_pdf_from_file(String::as_str(location), None);
+    let hash_res = web::block(move || {
+        let mut file = file;
+        storage_key_of(file.as_file_mut()).map(|storage_key| (file, storage_key))
+    }).await;
 
-    if document.is_err() {
-        return Err(document.err().unwrap());
-    }
-    let document = document.unwrap();
-
-    let pages = Some(document.pages().len() as i32);
-    let title = match document.metadata().get(PdfDocumentMetadataTagType::Title) {
-        Some(title2) => {
-            if String::from(title2.value()).trim().len() == 0 {
-                String::from(filename)
-            } else {
-                String::from(title2.value().trim())
-            }
+    let (file, storage_key) = match hash_res {
+        Ok(Ok(hashed)) => hashed,
+        Ok(Err(msg)) => {
+            METRICS.record_upload_rejected(UploadRejection::Storage);
+            return Err(msg);
         },
-        _ => String::from(filename)
+        Err(_) => {
+            METRICS.record_upload_rejected(UploadRejection::Storage);
+            return Err("Error reading pdf".to_string());
+        }
     };
 
-    let author = match document.metadata().get(PdfDocumentMetadataTagType::Author) {
-        Some(author2) => Some(String::from(author2.value())),
-        _ => None
+    match analyze(engine, &file.path().to_string_lossy()).await {
+        Ok(analysis) => Ok(PdfUploaded {
+            title: analysis.title.unwrap_or(file_name.clone()),
+            filename: file_name,
+            author: analysis.author,
+            pages: Some(analysis.pages),
+            img: analysis.preview,
+            file: Some(file),
+            storage_key,
+            tags: Vec::new(),
+            comments: None,
+            time_added: None
+        }),
+        Err(msg) => {
+            METRICS.record_upload_rejected(UploadRejection::Unreadable);
+            Err(msg)
+        }
+    }
+}
+
+
+/// Copies a pdf from the file system to the tmp directory and uploads it like a received pdf, used by the
+/// admin command line and the inbox. The file itself is left in place.
+pub async fn import_pdf(engine: &dyn PdfEngine, service: &dyn PdfService, storage: &StorageConfig, path: &Path, tags: Vec<String>, owner_id: &Uuid) -> Result<Uuid, String> {
+    trace!("import_pdf()");
+
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name.to_string_lossy().to_string(),
+        None => return Err("The file has no name".to_string())
     };
 
-    let render_config = PdfRenderConfig::new()
-        .scale_page_by_factor(0.8);
+    let source = path.to_path_buf();
+    let tmp_dir = storage.tmp_dir.clone();
+
+    let copy_res = web::block(move || {
+        let mut received = NamedTempFile::new_in(tmp_dir)?;
+        std::fs::File::open(source).and_then(|mut file| std::io::copy(&mut file, received.as_file_mut()))?;
+        Ok::<NamedTempFile, std::io::Error>(received)
+    }).await;
 
-    let first_page = document.pages().first();
+    let received = match copy_res {
+        Ok(Ok(received)) => received,
+        Ok(Err(err)) => return Err(format!("Error copying the file to the tmp directory: {err}")),
+        Err(_) => return Err("Error reading pdf".to_string())
+    };
 
-     if first_page.is_err() {
-        return Err(first_page.err().unwrap());
-     }
+    let mut upload = map_pdf(engine, received, file_name).await?;
+    upload.tags = tags;
 
-     let first_page = first_page.unwrap();
+    match service.upload(vec![upload], owner_id).await?.first() {
+        Some(pdf_id) => Ok(*pdf_id),
+        None => Err("The pdf was rejected, the owner may have a pdf with the same file name already".to_string())
+    }
+}
+
+
+/// Names of the folders between the directory and the file below it, which the file is tagged with on import
+pub fn folder_tags(dir: &Path, path: &Path) -> Vec<String> {
+    let Some(folder) = path.parent().and_then(|parent| parent.strip_prefix(dir).ok()) else {
+        return Vec::new();
+    };
+
+    folder.components()
+        .map(|component| component.as_os_str().to_string_lossy().trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+
+/// Moves the received pdf to its storage key in the upload directory. Returns whether no file with the same
+/// content was stored before, otherwise the received file is left to be discarded. A pdf without a received
+/// file is in place already. The move is a rename, so the upload directory never has a partially written pdf.
+pub async fn store_pdf(upload: &PdfUploaded, storage: &StorageConfig) -> Result<bool, String> {
+    trace!("store_pdf()");
+
+    let Some(file) = &upload.file else {
+        return Ok(false);
+    };
 
-    let rendered_image = first_page.render_with_config(&render_config).unwrap().as_image();
-    let mut bytes = Vec::new();
-    let _ = rendered_image.write_to(&mut std::io::Cursor::new(&mut bytes), ImageOutputFormat::Jpeg(80));
-    let base64image = general_purpose::STANDARD.encode(bytes);
+    let received_path = file.path().to_path_buf();
+    let path = storage.upload_dir.join(&upload.storage_key);
 
-    Ok((base64image, title, author, pages))
-}
\ No newline at end of file
+    let store_res = web::block(move || {
+        if path.exists() {
+            return Ok(false);
+        }
+
+        std::fs::rename(received_path, path).map(|_| true)
+    }).await;
+
+    match store_res {
+        Ok(Ok(newly_stored)) => Ok(newly_stored),
+        Ok(Err(err)) => Err(format!("Error moving the pdf to the upload directory: {}", err)),
+        Err(_) => Err("Error storing pdf".to_string())
+    }
+}
+
+
+/// Removes a pdf from the upload directory, a failure is only logged as the pdf is gone from the database already
+pub async fn remove_stored_pdf(storage: &StorageConfig, storage_key: &str) {
+    if let Err(err) = tokio::fs::remove_file(storage.upload_path(storage_key)).await {
+        warn!("Error removing the unused file '{}': {}", storage_key, err);
+    }
+}
+
+
+/// Removes what an interrupted upload or health check left behind: received files in the tmp directory, probe
+/// files and stored files that no pdf uses in the upload directory, if they weren't modified for `STALE_FILE_AGE`.
+/// Younger files may still belong to another instance sharing the directories. Returns the number of removed files.
+pub async fn sweep_stale_files(storage: &StorageConfig, repository: &dyn PdfRepository) -> usize {
+    trace!("sweep_stale_files()");
+
+    let is_probe = |name: &str| name.starts_with(".health-probe-");
+
+    let mut stale = stale_files(&storage.tmp_dir, |_| true);
+    stale.extend(stale_files(&storage.upload_dir, is_probe));
+
+    // A file is stored before the pdf is inserted, a crash in between leaves it behind without a pdf
+    for path in stale_files(&storage.upload_dir, |name| !is_probe(name)) {
+        let storage_key = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        match repository.is_storage_key_used(&storage_key).await {
+            Ok(false) => stale.push(path),
+            Ok(true) => (),
+            Err(msg) => warn!("Keeping the file '{}', whether it is used couldn't be checked: {}", storage_key, msg)
+        }
+    }
+
+    let mut removed = 0;
+
+    for path in stale {
+        match std::fs::remove_file(&path) {
+            Ok(_) => removed += 1,
+            Err(err) => warn!("Error removing the stale file '{}': {}", path.display(), err)
+        }
+    }
+
+    removed
+}
+
+
+/// Files in the directory with a matching name that weren't modified for `STALE_FILE_AGE`
+fn stale_files(dir: &Path, matches: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Error reading '{}' to remove stale files: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+
+    let mut stale = Vec::new();
+
+    for entry in entries.flatten() {
+        if !matches(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        let is_stale = entry.metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age >= STALE_FILE_AGE));
+
+        if entry.path().is_file() && is_stale {
+            stale.push(entry.path());
+        }
+    }
+
+    stale
+}
@@ -0,0 +1,302 @@
+//! User and group management, the audit log and the storage check, which are restricted to admins
+#[macro_use]
+mod common;
+
+use actix_web::http::{header, Method, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use common::*;
+
+
+scenarios!(
+    users,
+    deleting_a_user_deletes_their_pdfs,
+    groups,
+    audit_log,
+    fsck,
+    admin_routes_need_an_admin
+);
+
+
+async fn users(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let (alice_id, _) = create_user(&app, &token, "alice").await;
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/users", &token).set_json(json!({ "username": "alice", "password": "another-password" }))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/users", &token).set_json(json!({ "username": "bob", "password": "short" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = send(&app, request(Method::POST, "/api/v1/users", &token).set_json(json!({ "username": "carol", "password": "carol-password", "is_admin": true }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["is_admin"], true);
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/users", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let usernames: Vec<&str> = body.as_array().unwrap().iter().map(|user| user["username"].as_str().unwrap()).collect();
+    assert_eq!(usernames, vec!["admin", "alice", "carol"]);
+
+    let (_, me) = send(&app, request(Method::GET, "/api/v1/auth/me", &token)).await;
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/users/{}", me["id"].as_str().unwrap()), &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/users/{alice_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/users/{alice_id}"), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+
+async fn deleting_a_user_deletes_their_pdfs(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (alice_id, alice_token) = create_user(&app, &token, "alice").await;
+
+    let own = upload_pdf(&app, &alice_token, "own.pdf", "Own", "Alice").await;
+    let shared = upload_pdf(&app, &token, "shared.pdf", "Shared", "Admin").await;
+
+    let share = request(Method::PUT, &format!("/api/v1/documents/{shared}/permissions"), &token)
+        .set_json(json!({ "user": "alice", "permission": "read" }));
+    assert_eq!(send(&app, share).await.0, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/users/{alice_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The session of the user ended, the pdfs and files of the user are gone and the shares with the user removed
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/auth/me", &alice_token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 1);
+
+    let (_, body) = send(&app, request(Method::GET, &format!("/api/v1/documents/{shared}/permissions"), &token)).await;
+    assert_eq!(body, json!([]));
+
+    let (_, body) = send(&app, request(Method::GET, &format!("/api/v1/audit?target_id={own}&action=delete&page=1&size=10"), &token)).await;
+    assert_eq!(body["count"], 1);
+    assert_eq!(body["entries"][0]["actor"], ADMIN);
+}
+
+
+async fn groups(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (alice_id, _) = create_user(&app, &token, "alice").await;
+    let (bob_id, _) = create_user(&app, &token, "bob").await;
+
+    let (status, body) = send(&app, request(Method::POST, "/api/v1/groups", &token).set_json(json!({ "name": "team" }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let group_id = body["id"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/groups", &token).set_json(json!({ "name": "team" }))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    for user_id in [bob_id, alice_id, alice_id] {
+        let (status, _) = send(&app, request(Method::PUT, &format!("/api/v1/groups/{group_id}/members/{user_id}"), &token)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _) = send(&app, request(Method::PUT, &format!("/api/v1/groups/{group_id}/members/{}", Uuid::new_v4()), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/groups", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["name"], "team");
+    assert_eq!(body[0]["members"], json!(["alice", "bob"]));
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/groups/{group_id}/members/{bob_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/groups/{group_id}/members/{bob_id}"), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/groups", &token)).await;
+    assert_eq!(body[0]["members"], json!(["alice"]));
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/groups/{group_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/groups/{group_id}"), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/groups", &token)).await;
+    assert_eq!(body, json!([]));
+}
+
+
+async fn audit_log(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    let pdf_id = upload_pdf(&app, &alice_token, "report.pdf", "Report", "Ada").await;
+
+    let update = request(Method::PATCH, &format!("/api/v1/documents/{pdf_id}"), &alice_token)
+        .insert_header((header::IF_MATCH, "*"))
+        .set_json(json!({ "title": "Annual report", "tags": ["finance"] }));
+    assert_eq!(send(&app, update).await.0, StatusCode::OK);
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/audit?page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 2);
+
+    // The newest entry comes first and only contains what changed
+    let update = &body["entries"][0];
+    assert_eq!(update["action"], "update");
+    assert_eq!(update["actor"], "alice");
+    assert_eq!(update["target_id"], pdf_id.to_string());
+    assert_eq!(update["changes"]["title"], json!({ "old": "Report", "new": "Annual report" }));
+    assert_eq!(update["changes"]["tags"], json!({ "old": [], "new": ["finance"] }));
+    assert!(update["changes"].get("author").is_none());
+
+    let upload = &body["entries"][1];
+    assert_eq!(upload["action"], "upload");
+    assert_eq!(upload["changes"]["file_name"], json!({ "old": null, "new": "report.pdf" }));
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/audit?action=upload&actor=alice&page=1&size=10", &token)).await;
+    assert_eq!(body["count"], 1);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/audit?actor=admin&page=1&size=10", &token)).await;
+    assert_eq!(body["count"], 0);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/audit?page=2&size=1", &token)).await;
+    assert_eq!(body["count"], 2);
+    assert_eq!(body["entries"][0]["action"], "upload");
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/audit?from=2999-01-01T00:00:00Z&page=1&size=10", &token)).await;
+    assert_eq!(body["count"], 0);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/audit", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/audit?action=unknown&page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+
+async fn fsck(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let upload_dir = &backend.state.config.storage.upload_dir;
+
+    let lost_id = upload_pdf(&app, &token, "lost.pdf", "Lost", "Ada").await;
+    upload_pdf(&app, &token, "tampered.pdf", "Tampered", "Ada").await;
+    let thumbless_id = upload_pdf(&app, &token, "thumbless.pdf", "Thumbless", "Ada").await;
+
+    // `upload_pdf` uploads pdfs with two pages, which are stored by the hash of their content
+    let lost_content = pdf_bytes("Lost", "Ada", 2);
+    let lost_key = format!("{:x}.pdf", Sha256::digest(&lost_content));
+    let tampered_key = format!("{:x}.pdf", Sha256::digest(pdf_bytes("Tampered", "Ada", 2)));
+    assert!(backend.is_stored(&lost_key) && backend.is_stored(&tampered_key));
+
+    std::fs::remove_file(upload_dir.join(&lost_key)).unwrap();
+    std::fs::write(upload_dir.join(&tampered_key), pdf_bytes("Overwritten", "Bob", 1)).unwrap();
+    std::fs::write(upload_dir.join("orphan.pdf"), pdf_bytes("Orphan", "Nobody", 1)).unwrap();
+
+    let uri = format!("/api/v1/documents/{thumbless_id}");
+    send(&app, request(Method::PATCH, &uri, &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "picture": null }))).await;
+
+    // Without `dry_run=false` nothing is repaired
+    let (status, report) = send(&app, request(Method::POST, "/api/v1/fsck", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["pdfs"], 3);
+    assert_eq!(report["files"], 3);
+    assert_eq!(issue_summary(&report), vec![
+        ("checksum_mismatch".to_string(), "manual".to_string()),
+        ("missing_file".to_string(), "pending".to_string()),
+        ("missing_thumbnail".to_string(), "pending".to_string()),
+        ("orphan_file".to_string(), "pending".to_string())
+    ]);
+
+    let (lost, _) = metadata(&app, &token, &lost_id).await;
+    assert_eq!(lost["file_missing"], false);
+
+    let (status, report) = send(&app, request(Method::POST, "/api/v1/fsck?dry_run=false", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["dry_run"], false);
+    assert_eq!(issue_summary(&report), vec![
+        ("checksum_mismatch".to_string(), "manual".to_string()),
+        ("missing_file".to_string(), "repaired".to_string()),
+        ("missing_thumbnail".to_string(), "repaired".to_string()),
+        ("orphan_file".to_string(), "repaired".to_string())
+    ]);
+
+    let (lost, _) = metadata(&app, &token, &lost_id).await;
+    assert_eq!(lost["file_missing"], true);
+
+    let (thumbless, _) = metadata(&app, &token, &thumbless_id).await;
+    assert!(!thumbless["picture"].as_str().unwrap().is_empty());
+
+    // The orphan file is imported as it is, for the admin that ran the check
+    let orphan = report["issues"].as_array().unwrap().iter().find(|issue| issue["kind"] == "orphan_file").unwrap();
+    let (imported, _) = metadata(&app, &token, &orphan["pdf_id"].as_str().unwrap().parse().unwrap()).await;
+    assert_eq!(imported["title"], "Orphan");
+    assert_eq!(imported["file_name"], "orphan.pdf");
+    assert!(backend.is_stored("orphan.pdf"));
+
+    let (_, report) = send(&app, request(Method::POST, "/api/v1/fsck", &token)).await;
+    assert_eq!(report["pdfs"], 4);
+    assert_eq!(issue_summary(&report), vec![
+        ("checksum_mismatch".to_string(), "manual".to_string()),
+        ("missing_file".to_string(), "manual".to_string())
+    ]);
+
+    // Once the file is back, the mark is removed again
+    std::fs::write(upload_dir.join(&lost_key), &lost_content).unwrap();
+
+    let (_, report) = send(&app, request(Method::POST, "/api/v1/fsck?dry_run=false", &token)).await;
+    assert_eq!(issue_summary(&report), vec![
+        ("checksum_mismatch".to_string(), "manual".to_string()),
+        ("restored_file".to_string(), "repaired".to_string())
+    ]);
+
+    let (lost, _) = metadata(&app, &token, &lost_id).await;
+    assert_eq!(lost["file_missing"], false);
+
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/fsck", &alice_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+
+/// Kind and repair status of the issues of a storage check, sorted by kind
+fn issue_summary(report: &Value) -> Vec<(String, String)> {
+    let mut summary: Vec<(String, String)> = report["issues"].as_array().unwrap().iter()
+        .map(|issue| (issue["kind"].as_str().unwrap().to_string(), issue["repair"].as_str().unwrap().to_string()))
+        .collect();
+
+    summary.sort();
+    summary
+}
+
+
+async fn admin_routes_need_an_admin(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    for uri in ["/api/v1/users", "/api/v1/groups", "/api/v1/audit?page=1&size=10"] {
+        let (status, _) = send(&app, request(Method::GET, uri, &alice_token)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{uri}");
+    }
+
+    // API tokens of admins need the admin scope
+    let (_, body) = send(&app, request(Method::POST, "/api/v1/tokens", &token).set_json(json!({ "name": "reader", "scopes": ["read"] }))).await;
+    let reader = body["token"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/users", &reader)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (_, body) = send(&app, request(Method::POST, "/api/v1/tokens", &token).set_json(json!({ "name": "admin", "scopes": ["admin"] }))).await;
+    let admin = body["token"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/users", &admin)).await;
+    assert_eq!(status, StatusCode::OK);
+}
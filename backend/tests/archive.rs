@@ -0,0 +1,267 @@
+//! Exporting the library as archive and restoring it
+#[macro_use]
+mod common;
+
+use std::io::Read;
+use std::time::Duration;
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test;
+use chrono::{SecondsFormat, Utc};
+use pdf_store::api::dto::archive::ArchiveConflict;
+use pdf_store::cli;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use common::*;
+
+
+scenarios!(
+    export_and_restore_the_library,
+    incremental_export,
+    conflicts_are_skipped_or_overwritten,
+    invalid_archives_are_rejected,
+    cli_round_trip
+);
+
+
+/// Exports the archive and returns it along with its manifest
+async fn export(app: &impl TestApp, token: &str, since: Option<&str>) -> (Vec<u8>, Value) {
+    let uri = match since {
+        Some(since) => format!("/api/v1/archive?since={since}"),
+        None => "/api/v1/archive".to_string()
+    };
+
+    let response = test::call_service(app, request(Method::GET, &uri, token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-tar");
+
+    let archive = test::read_body(response).await.to_vec();
+
+    (archive.clone(), manifest_of(&archive))
+}
+
+
+fn manifest_of(archive: &[u8]) -> Value {
+    let mut tar = tar::Archive::new(archive);
+    let mut manifest = tar.entries().unwrap().next().unwrap().unwrap();
+    assert_eq!(manifest.path().unwrap().to_string_lossy(), "manifest.json");
+
+    let mut content = String::new();
+    manifest.read_to_string(&mut content).unwrap();
+    serde_json::from_str(&content).unwrap()
+}
+
+
+async fn import(app: &impl TestApp, token: &str, archive: Vec<u8>, on_conflict: &str) -> (StatusCode, Value) {
+    let request = request(Method::POST, &format!("/api/v1/archive?on_conflict={on_conflict}"), token)
+        .insert_header((header::CONTENT_TYPE, "application/x-tar"))
+        .set_payload(archive);
+
+    send(app, request).await
+}
+
+
+async fn delete_pdf(app: &impl TestApp, token: &str, pdf_id: &Uuid) {
+    let (status, _) = send(app, request(Method::DELETE, &format!("/api/v1/documents/{pdf_id}"), token)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+
+async fn patch_pdf(app: &impl TestApp, token: &str, pdf_id: &Uuid, patch: Value) {
+    let request = request(Method::PATCH, &format!("/api/v1/documents/{pdf_id}"), token)
+        .insert_header((header::IF_MATCH, "*"))
+        .set_json(patch);
+
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::OK, "patching {pdf_id} failed: {body}");
+}
+
+
+fn titles(manifest: &Value) -> Vec<String> {
+    let mut titles: Vec<String> = manifest["pdfs"].as_array().unwrap().iter()
+        .map(|pdf| pdf["title"].as_str().unwrap().to_string())
+        .collect();
+    titles.sort();
+    titles
+}
+
+
+async fn export_and_restore_the_library(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    let admin_id = upload_pdf(&app, &token, "admin.pdf", "Admin", "Ada").await;
+    patch_pdf(&app, &token, &admin_id, json!({ "comments": "Read twice", "tags": ["Math", "Logic"] })).await;
+    let (admin_before, _) = metadata(&app, &token, &admin_id).await;
+
+    let alice_id = upload_pdf(&app, &alice_token, "alice.pdf", "Alice", "Alice").await;
+
+    let (archive, manifest) = export(&app, &token, None).await;
+    assert_eq!(manifest["format"], "pdf-store-archive");
+    assert_eq!(manifest["version"], 1);
+    assert_eq!(titles(&manifest), vec!["Admin", "Alice"]);
+
+    let archived_admin = manifest["pdfs"].as_array().unwrap().iter().find(|pdf| pdf["title"] == "Admin").unwrap();
+    assert_eq!(archived_admin["owner"], "admin");
+    assert_eq!(archived_admin["file"], format!("files/{}.pdf", archived_admin["sha256"].as_str().unwrap()));
+
+    delete_pdf(&app, &token, &admin_id).await;
+    delete_pdf(&app, &alice_token, &alice_id).await;
+
+    let (status, report) = import(&app, &token, archive, "skip").await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+    assert_eq!((report["created"].as_u64(), report["failed"].as_u64()), (Some(2), Some(0)));
+
+    // Each pdf is restored for its owner with its details and its file
+    assert!(find_pdf(&app, &token, "Alice").await.is_none());
+    let alice_id = find_pdf(&app, &alice_token, "Alice").await.unwrap();
+    assert_eq!(metadata(&app, &alice_token, &alice_id).await.0["author"], "Alice");
+
+    let admin_id = find_pdf(&app, &token, "Admin").await.unwrap();
+    let (admin_after, _) = metadata(&app, &token, &admin_id).await;
+    assert_eq!(admin_after["comments"], "Read twice");
+    assert_eq!(admin_after["time_added"], admin_before["time_added"]);
+
+    let mut tags: Vec<String> = serde_json::from_value(admin_after["tags"].clone()).unwrap();
+    tags.sort();
+    assert_eq!(tags, vec!["Logic", "Math"]);
+
+    let response = test::call_service(&app, request(Method::GET, &format!("/api/v1/documents/{admin_id}/content"), &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(test::read_body(response).await.to_vec(), pdf_bytes("Admin", "Ada", 2));
+}
+
+
+async fn incremental_export(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let old_id = upload_pdf(&app, &token, "old.pdf", "Old", "Ada").await;
+    upload_pdf(&app, &token, "unchanged.pdf", "Unchanged", "Ada").await;
+
+    actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+    let since = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+    actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+
+    upload_pdf(&app, &token, "new.pdf", "New", "Ada").await;
+
+    let (_, manifest) = export(&app, &token, Some(&since)).await;
+    assert_eq!(titles(&manifest), vec!["New"]);
+    assert_eq!(manifest["since"], json!(since));
+
+    // Changed pdfs are archived again
+    patch_pdf(&app, &token, &old_id, json!({ "comments": "Changed" })).await;
+
+    let (_, manifest) = export(&app, &token, Some(&since)).await;
+    assert_eq!(titles(&manifest), vec!["New", "Old"]);
+
+    let (_, manifest) = export(&app, &token, None).await;
+    assert_eq!(titles(&manifest), vec!["New", "Old", "Unchanged"]);
+}
+
+
+async fn conflicts_are_skipped_or_overwritten(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (alice_user_id, alice_token) = create_user(&app, &token, "alice").await;
+
+    let admin_id = upload_pdf(&app, &token, "admin.pdf", "Admin", "Ada").await;
+    upload_pdf(&app, &alice_token, "alice.pdf", "Alice", "Alice").await;
+
+    let (archive, _) = export(&app, &token, None).await;
+
+    patch_pdf(&app, &token, &admin_id, json!({ "title": "Renamed", "tags": ["Later"] })).await;
+
+    // The pdfs with the same content are kept as they are
+    let (status, report) = import(&app, &token, archive.clone(), "skip").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((report["created"].as_u64(), report["skipped"].as_u64()), (Some(0), Some(2)));
+    assert_eq!(metadata(&app, &token, &admin_id).await.0["title"], "Renamed");
+
+    // or get the details from the archive back
+    let (status, report) = import(&app, &token, archive.clone(), "overwrite").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((report["updated"].as_u64(), report["failed"].as_u64()), (Some(2), Some(0)));
+
+    let (admin, _) = metadata(&app, &token, &admin_id).await;
+    assert_eq!(admin["title"], "Admin");
+    assert_eq!(admin["tags"], json!([]));
+
+    // Pdfs of owners without an account are restored for the admin restoring the archive
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/users/{alice_user_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, report) = import(&app, &token, archive, "skip").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((report["created"].as_u64(), report["skipped"].as_u64()), (Some(1), Some(1)));
+
+    let restored = report["pdfs"].as_array().unwrap().iter().find(|pdf| pdf["file_name"] == "alice.pdf").unwrap();
+    assert_eq!(restored["owner"], "admin");
+    assert!(find_pdf(&app, &token, "Alice").await.is_some());
+}
+
+
+async fn invalid_archives_are_rejected(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    let (status, _) = import(&app, &token, b"not an archive".repeat(100), "skip").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A tar without a manifest
+    let mut builder = tar::Builder::new(Vec::new());
+    let content = pdf_bytes("Lost", "Ada", 1);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, "files/lost.pdf", content.as_slice()).unwrap();
+
+    let (status, body) = import(&app, &token, builder.into_inner().unwrap(), "skip").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("manifest.json"));
+
+    // A manifest of a newer version
+    let manifest = serde_json::to_vec(&json!({
+        "format": "pdf-store-archive", "version": 99, "exported_at": Utc::now(), "since": null, "pdfs": []
+    })).unwrap();
+
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, "manifest.json", manifest.as_slice()).unwrap();
+
+    let (status, _) = import(&app, &token, builder.into_inner().unwrap(), "skip").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/archive", &alice_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = import(&app, &alice_token, Vec::new(), "skip").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+
+async fn cli_round_trip(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let pdf_id = upload_pdf(&app, &token, "kept.pdf", "Kept", "Ada").await;
+
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("library.tar");
+
+    assert_eq!(cli::export_archive(&backend.state, &file, None).await, Ok(true));
+    assert_eq!(titles(&manifest_of(&std::fs::read(&file).unwrap())), vec!["Kept"]);
+
+    delete_pdf(&app, &token, &pdf_id).await;
+
+    assert_eq!(cli::import_archive(&backend.state, &file, ArchiveConflict::Skip).await, Ok(true));
+    assert!(find_pdf(&app, &token, "Kept").await.is_some());
+
+    assert!(cli::import_archive(&backend.state, &dir.path().join("missing.tar"), ArchiveConflict::Skip).await.is_err());
+}
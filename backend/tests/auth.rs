@@ -0,0 +1,285 @@
+//! Logging in and out, passwords and API tokens
+#[macro_use]
+mod common;
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use common::*;
+
+
+scenarios!(
+    login_and_logout,
+    session_cookie,
+    basic_auth_only_for_webdav,
+    change_password,
+    api_tokens,
+    api_token_scopes,
+    api_tokens_expire,
+    public_routes,
+    health_checks,
+    request_ids
+);
+
+
+async fn login_and_logout(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let wrong = test::TestRequest::post()
+        .uri("/api/v1/auth/login")
+        .set_json(json!({ "username": ADMIN, "password": "wrong-password" }));
+    assert_eq!(send(&app, wrong).await.0, StatusCode::UNAUTHORIZED);
+
+    let unknown = test::TestRequest::post()
+        .uri("/api/v1/auth/login")
+        .set_json(json!({ "username": "nobody", "password": ADMIN_PASSWORD }));
+    assert_eq!(send(&app, unknown).await.0, StatusCode::UNAUTHORIZED);
+
+    let token = login_admin(&app).await;
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/auth/me", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], ADMIN);
+    assert_eq!(body["is_admin"], true);
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/auth/logout", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/auth/me", &token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/auth/me", "not-a-token")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+
+async fn session_cookie(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let login = test::TestRequest::post()
+        .uri("/api/v1/auth/login")
+        .set_json(json!({ "username": ADMIN, "password": ADMIN_PASSWORD }));
+
+    let response = test::call_service(&app, login.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let cookie = response.response().cookies().find(|cookie| cookie.name() == "pdfstore_session").unwrap().into_owned();
+    assert!(cookie.http_only().unwrap_or(false));
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/api/v1/auth/me").cookie(cookie)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], ADMIN);
+}
+
+
+async fn basic_auth_only_for_webdav(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let me = test::TestRequest::get()
+        .uri("/api/v1/auth/me")
+        .insert_header((header::AUTHORIZATION, basic_auth(ADMIN, ADMIN_PASSWORD)));
+
+    let response = test::call_service(&app, me.to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer");
+
+    let propfind = test::TestRequest::default()
+        .method(Method::from_bytes(b"PROPFIND").unwrap())
+        .uri("/dav/")
+        .insert_header(("Depth", "0"))
+        .insert_header((header::AUTHORIZATION, basic_auth(ADMIN, ADMIN_PASSWORD)));
+
+    let response = test::call_service(&app, propfind.to_request()).await;
+    assert_eq!(response.status().as_u16(), 207);
+}
+
+
+async fn change_password(backend: TestBackend) {
+    let app = backend.app().await;
+    let admin_token = login_admin(&app).await;
+    let (_, token) = create_user(&app, &admin_token, "alice").await;
+    let other_session = login(&app, "alice", &password_of("alice")).await;
+
+    let change = |current: &str, new: &str| request(Method::PUT, "/api/v1/auth/password", &token)
+        .set_json(json!({ "current_password": current, "new_password": new }));
+
+    assert_eq!(send(&app, change("wrong-password", "new-password")).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(send(&app, change(&password_of("alice"), "short")).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(send(&app, change(&password_of("alice"), "new-password")).await.0, StatusCode::OK);
+
+    // All sessions end with the change
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/auth/me", &other_session)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let old = test::TestRequest::post()
+        .uri("/api/v1/auth/login")
+        .set_json(json!({ "username": "alice", "password": password_of("alice") }));
+    assert_eq!(send(&app, old).await.0, StatusCode::UNAUTHORIZED);
+
+    login(&app, "alice", "new-password").await;
+}
+
+
+async fn api_tokens(backend: TestBackend) {
+    let app = backend.app().await;
+    let session = login_admin(&app).await;
+
+    let (status, body) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "scanner", "scopes": ["read", "upload"] }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let api_token = body["token"].as_str().unwrap().to_string();
+    let token_id = body["id"].as_str().unwrap().to_string();
+    assert!(api_token.starts_with("pst_"));
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/auth/me", &api_token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], ADMIN);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/tokens", &session)).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["name"], "scanner");
+    assert!(body[0]["last_used"].is_string());
+
+    // API tokens can't manage API tokens
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/tokens", &api_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "", "scopes": ["read"] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "none", "scopes": [] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/tokens/{token_id}"), &session)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/tokens/{token_id}"), &session)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/auth/me", &api_token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+
+async fn api_token_scopes(backend: TestBackend) {
+    let app = backend.app().await;
+    let admin_token = login_admin(&app).await;
+    let (_, session) = create_user(&app, &admin_token, "alice").await;
+
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "admin", "scopes": ["admin"] }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, body) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "reader", "scopes": ["read"] }))).await;
+    let reader = body["token"].as_str().unwrap().to_string();
+
+    let (_, body) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "uploader", "scopes": ["upload"] }))).await;
+    let uploader = body["token"].as_str().unwrap().to_string();
+
+    let content = pdf_bytes("Scanned", "Ada", 1);
+    assert_eq!(upload_to(&app, "/api/v1/documents", &reader, &[("scan.pdf", &content)]).await, StatusCode::FORBIDDEN);
+    assert_eq!(upload_to(&app, "/api/v1/documents", &uploader, &[("scan.pdf", &content)]).await, StatusCode::CREATED);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/documents?page=1&size=10", &uploader)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let pdf_id = find_pdf(&app, &reader, "Scanned").await.unwrap();
+
+    let (status, _) = send(&app, request(Method::PUT, &format!("/api/v1/documents/{pdf_id}"), &reader).insert_header((header::IF_MATCH, "*")).set_json(json!({ "title": "Changed" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/documents/{pdf_id}"), &reader)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+
+async fn api_tokens_expire(backend: TestBackend) {
+    let app = backend.app().await;
+    let session = login_admin(&app).await;
+
+    let past = Utc::now() - Duration::hours(1);
+    let (status, _) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "old", "scopes": ["read"], "expires_at": past }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let future = Utc::now() + Duration::hours(1);
+    let (status, body) = send(&app, request(Method::POST, "/api/v1/tokens", &session).set_json(json!({ "name": "new", "scopes": ["read"], "expires_at": future }))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let api_token = body["token"].as_str().unwrap().to_string();
+    assert_eq!(send(&app, request(Method::GET, "/api/v1/auth/me", &api_token)).await.0, StatusCode::OK);
+}
+
+
+async fn public_routes(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/health")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.is_null());
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/openapi.json")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["paths"]["/api/v1/documents"].is_object());
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/docs").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/html"));
+}
+
+
+async fn health_checks(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/health/live")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/health/ready")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["checks"]["database"]["status"], "ok");
+    assert_eq!(body["checks"]["migrations"]["status"], "ok");
+    assert_eq!(body["checks"]["storage"]["status"], "ok");
+    assert_eq!(body["checks"]["pdf_engine"]["status"], "ok");
+    assert_eq!(body["checks"]["job_queue"]["status"], "ok");
+    assert!(body["checks"]["database"]["latency_ms"].is_u64());
+
+    // The probe files are removed again
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 0);
+
+    std::fs::remove_dir_all(&backend.state.config.storage.upload_dir).unwrap();
+
+    let (status, body) = send(&app, test::TestRequest::get().uri("/health/ready")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["checks"]["storage"]["status"], "fail");
+    assert_eq!(body["checks"]["database"]["status"], "ok");
+}
+
+
+async fn request_ids(backend: TestBackend) {
+    let app = backend.app().await;
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/api/v1/auth/me").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request_id = response.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+    assert!(Uuid::parse_str(&request_id).is_ok());
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["message"], "Authentication required");
+    assert_eq!(body["request_id"], request_id);
+
+    // An ID from the client or a proxy is kept, unless it could break the log lines
+    let response = test::call_service(&app, test::TestRequest::get().uri("/api/v1/auth/me").insert_header(("X-Request-Id", "proxy-42")).to_request()).await;
+    assert_eq!(response.headers().get("x-request-id").unwrap(), "proxy-42");
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["request_id"], "proxy-42");
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/health/live").insert_header(("X-Request-Id", "two words")).to_request()).await;
+    assert_ne!(response.headers().get("x-request-id").unwrap(), "two words");
+
+    // Successful responses only get the header
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body, json!({ "status": "ok" }));
+}
@@ -0,0 +1,163 @@
+//! The commands of `pdf-store-admin`, run against the state of the backend
+#[macro_use]
+mod common;
+
+use actix_web::http::{header, Method};
+use pdf_store::cli;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+use common::*;
+
+
+scenarios!(
+    import_tags_pdfs_by_folder,
+    export_copies_pdfs_per_user,
+    merge_tags,
+    regen_thumbnails,
+    reindex_keeps_the_search_working,
+    user_create
+);
+
+
+fn sorted_tags(metadata: &Value) -> Vec<String> {
+    let mut tags: Vec<String> = serde_json::from_value(metadata["tags"].clone()).unwrap();
+    tags.sort();
+    tags
+}
+
+
+async fn import_tags_pdfs_by_folder(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let inbox = TempDir::new().unwrap();
+    std::fs::create_dir_all(inbox.path().join("Physics/Quantum")).unwrap();
+    std::fs::write(inbox.path().join("Physics/Quantum/spin.pdf"), pdf_bytes("Spin", "Pauli", 1)).unwrap();
+    std::fs::write(inbox.path().join("loose.PDF"), pdf_bytes("Loose", "Ada", 1)).unwrap();
+    std::fs::write(inbox.path().join("notes.txt"), "not a pdf").unwrap();
+
+    assert_eq!(cli::import(&backend.state, inbox.path(), None, true).await, Ok(true));
+
+    let spin_id = find_pdf(&app, &token, "Spin").await.unwrap();
+    let (spin, _) = metadata(&app, &token, &spin_id).await;
+    assert_eq!(sorted_tags(&spin), vec!["Physics", "Quantum"]);
+    assert_eq!(spin["author"], "Pauli");
+
+    let loose_id = find_pdf(&app, &token, "Loose").await.unwrap();
+    assert_eq!(metadata(&app, &token, &loose_id).await.0["tags"], json!([]));
+
+    // The owner has pdfs with these file names already
+    assert_eq!(cli::import(&backend.state, inbox.path(), None, true).await, Ok(false));
+
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+    assert_eq!(cli::import(&backend.state, inbox.path(), Some("alice"), false).await, Ok(true));
+    assert!(find_pdf(&app, &alice_token, "Spin").await.is_some());
+
+    assert!(cli::import(&backend.state, inbox.path(), Some("nobody"), false).await.is_err());
+}
+
+
+async fn export_copies_pdfs_per_user(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    upload_pdf(&app, &token, "admin.pdf", "Admin", "Ada").await;
+    upload_pdf(&app, &alice_token, "alice.pdf", "Alice", "Alice").await;
+
+    let target = TempDir::new().unwrap();
+
+    assert_eq!(cli::export(&backend.state, target.path(), None).await, Ok(true));
+    assert_eq!(std::fs::read(target.path().join("admin/admin.pdf")).unwrap(), pdf_bytes("Admin", "Ada", 2));
+    assert_eq!(std::fs::read(target.path().join("alice/alice.pdf")).unwrap(), pdf_bytes("Alice", "Alice", 2));
+
+    // Existing files are not overwritten
+    assert_eq!(cli::export(&backend.state, target.path(), None).await, Ok(false));
+
+    let alice_only = TempDir::new().unwrap();
+    assert_eq!(cli::export(&backend.state, alice_only.path(), Some("alice")).await, Ok(true));
+    assert!(alice_only.path().join("alice.pdf").exists());
+    assert!(!alice_only.path().join("admin.pdf").exists());
+}
+
+
+async fn merge_tags(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+
+    let both = upload_pdf(&app, &token, "both.pdf", "Both", "Ada").await;
+    let other = upload_pdf(&app, &alice_token, "other.pdf", "Other", "Alice").await;
+
+    let tag = |pdf_id, user_token: &str, tags: Value| {
+        request(Method::PATCH, &format!("/api/v1/documents/{pdf_id}"), user_token)
+            .insert_header((header::IF_MATCH, "*"))
+            .set_json(json!({ "add_tags": tags }))
+    };
+    send(&app, tag(both, &token, json!(["ml", "machine-learning", "papers"]))).await;
+    send(&app, tag(other, &alice_token, json!(["ML"]))).await;
+
+    let sources = vec!["ml".to_string(), "ML".to_string()];
+    assert_eq!(cli::merge_tags(&backend.state, &sources, "machine-learning").await, Ok(true));
+
+    assert_eq!(sorted_tags(&metadata(&app, &token, &both).await.0), vec!["machine-learning", "papers"]);
+    assert_eq!(sorted_tags(&metadata(&app, &alice_token, &other).await.0), vec!["machine-learning"]);
+
+    let (_, audit) = send(&app, request(Method::GET, &format!("/api/v1/audit?target_id={other}&action=update&page=1&size=10"), &token)).await;
+    assert_eq!(audit["count"], 2);
+
+    assert!(cli::merge_tags(&backend.state, &sources, " ").await.is_err());
+}
+
+
+async fn regen_thumbnails(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let with_picture = upload_pdf(&app, &token, "with.pdf", "With", "Ada").await;
+    let without_picture = upload_pdf(&app, &token, "without.pdf", "Without", "Ada").await;
+
+    let uri = format!("/api/v1/documents/{without_picture}");
+    send(&app, request(Method::PATCH, &uri, &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "picture": null }))).await;
+
+    let (before, _) = metadata(&app, &token, &with_picture).await;
+
+    assert_eq!(cli::regen_thumbnails(&backend.state, true).await, Ok(true));
+
+    let (without, _) = metadata(&app, &token, &without_picture).await;
+    assert!(without["picture"].is_string());
+
+    // Only the pdf without a preview was rendered
+    assert_eq!(metadata(&app, &token, &with_picture).await.0["version"], before["version"]);
+
+    assert_eq!(cli::regen_thumbnails(&backend.state, false).await, Ok(true));
+    assert_ne!(metadata(&app, &token, &with_picture).await.0["version"], before["version"]);
+}
+
+
+async fn reindex_keeps_the_search_working(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let pdf_id = upload_pdf(&app, &token, "indexed.pdf", "Indexed", "Ada").await;
+
+    assert_eq!(cli::reindex(&backend.state).await, Ok(true));
+    assert_eq!(find_pdf(&app, &token, "Indexed").await, Some(pdf_id));
+}
+
+
+async fn user_create(backend: TestBackend) {
+    let app = backend.app().await;
+
+    assert_eq!(cli::create_user(&backend.state, "dave".to_string(), true, Some("dave-password".to_string())).await, Ok(true));
+
+    let dave_token = login(&app, "dave", "dave-password").await;
+    let (_, me) = send(&app, request(Method::GET, "/api/v1/auth/me", &dave_token)).await;
+    assert_eq!(me["is_admin"], true);
+
+    assert!(cli::create_user(&backend.state, "dave".to_string(), false, None).await.is_err());
+
+    // Without a password a random one is generated
+    assert_eq!(cli::create_user(&backend.state, "erin".to_string(), false, None).await, Ok(true));
+}
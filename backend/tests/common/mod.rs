@@ -0,0 +1,534 @@
+//! Shared setup of the integration tests. Every scenario runs against a fresh backend, once with the in-memory
+//! repositories, once with a SQLite database in the storage directory and once with a disposable Postgres database
+//! that is dropped afterwards.
+//! The Postgres runs use `TEST_DATABASE_URL`, or `DATABASE_URL` if it isn't set, to create the database
+//! and are skipped when neither is set.
+#![allow(dead_code, unused_macros)]
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::web::Data;
+use actix_web::middleware::from_fn;
+use actix_web::{test, App};
+use base64::Engine;
+use futures_util::FutureExt;
+use lopdf::{dictionary, Document, Object, Stream};
+use serde_json::{json, Value};
+use sqlx::migrate::Migrate;
+use sqlx::{Connection, Executor, PgConnection, Pool, Postgres};
+use tempfile::TempDir;
+use uuid::Uuid;
+
+use pdf_store::api::middleware::metrics::record_metrics;
+use pdf_store::api::middleware::request_id::request_id;
+use pdf_store::config::{AdminConfig, Config, DatabaseConfig, PdfEngineKind};
+use pdf_store::domain::repository::health::HealthRepository;
+use pdf_store::domain::service::auth::AuthService;
+use pdf_store::engine::PdfEngine;
+use pdf_store::engine::lopdf::LopdfEngine;
+use pdf_store::repository::audit::AuditRepositoryImpl;
+use pdf_store::repository::health::HealthRepositoryImpl;
+use pdf_store::repository::memory::MemoryDatabase;
+use pdf_store::repository::memory::audit::MemoryAuditRepository;
+use pdf_store::repository::memory::health::MemoryHealthRepository;
+use pdf_store::repository::memory::pdfs::MemoryPdfRepository;
+use pdf_store::repository::memory::users::MemoryUserRepository;
+use pdf_store::repository::pdfs::PdfRepositoryImpl;
+use pdf_store::repository::users::UserRepositoryImpl;
+use pdf_store::repository::sqlite;
+use pdf_store::repository::sqlite::audit::SqliteAuditRepository;
+use pdf_store::repository::sqlite::health::SqliteHealthRepository;
+use pdf_store::repository::sqlite::pdfs::SqlitePdfRepository;
+use pdf_store::repository::sqlite::users::SqliteUserRepository;
+use pdf_store::service::audit::AuditServiceImpl;
+use pdf_store::service::auth::AuthServiceImpl;
+use pdf_store::service::fsck::FsckServiceImpl;
+use pdf_store::service::archive::ArchiveServiceImpl;
+use pdf_store::service::health::HealthServiceImpl;
+use pdf_store::service::pdf::PdfServiceImpl;
+use pdf_store::{configure, repository, AppState};
+
+
+pub const ADMIN: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "admin-password";
+
+/// The pdf and its tag that are stored in the databases that are set up with the original schema
+pub const BASELINE_TITLE: &str = "Baseline";
+pub const BASELINE_TAG: &str = "Classics";
+
+
+/// Generates a test per scenario and store. The scenarios are async fns that take the `TestBackend`.
+macro_rules! scenarios {
+    ($($scenario:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[actix_web::test]
+                async fn $scenario() {
+                    $crate::common::run($crate::common::Store::Memory, super::$scenario).await;
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[actix_web::test]
+                async fn $scenario() {
+                    $crate::common::run($crate::common::Store::Sqlite, super::$scenario).await;
+                }
+            )*
+        }
+
+        mod postgres {
+            $(
+                #[actix_web::test]
+                async fn $scenario() {
+                    $crate::common::run($crate::common::Store::Postgres, super::$scenario).await;
+                }
+            )*
+        }
+    };
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub enum Store {
+    Memory,
+    Sqlite,
+    Postgres
+}
+
+
+/// The app a scenario sends its requests to
+pub trait TestApp: Service<Request, Response = ServiceResponse, Error = actix_web::Error> {}
+
+impl<S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>> TestApp for S {}
+
+
+#[derive(Clone)]
+pub struct TestBackend {
+    pub state: Data<AppState>,
+    storage: Arc<TempDir>,
+    database: Option<Arc<DisposableDatabase>>
+}
+
+struct DisposableDatabase {
+    server_url: String,
+    name: String,
+    pool: Pool<Postgres>
+}
+
+
+/// Runs the scenario against a fresh backend and removes the backend afterwards, also if the scenario panics
+pub async fn run<F, Fut>(store: Store, scenario: F)
+where
+    F: FnOnce(TestBackend) -> Fut,
+    Fut: Future<Output = ()>
+{
+    run_on(store, false, scenario).await;
+}
+
+
+/// Runs the scenario against a backend whose database has the original schema and holds a tagged pdf. The Postgres
+/// database is set up without migrations like the ones that were created from `schema.sql`.
+pub async fn run_upgraded<F, Fut>(store: Store, scenario: F)
+where
+    F: FnOnce(TestBackend) -> Fut,
+    Fut: Future<Output = ()>
+{
+    run_on(store, true, scenario).await;
+}
+
+
+async fn run_on<F, Fut>(store: Store, baseline: bool, scenario: F)
+where
+    F: FnOnce(TestBackend) -> Fut,
+    Fut: Future<Output = ()>
+{
+    let Some(backend) = TestBackend::start(store, baseline).await else {
+        eprintln!("Skipping the Postgres run, neither TEST_DATABASE_URL nor DATABASE_URL is set");
+        return;
+    };
+
+    let result = AssertUnwindSafe(scenario(backend.clone())).catch_unwind().await;
+
+    backend.stop().await;
+
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+
+impl TestBackend {
+
+    async fn start(store: Store, baseline: bool) -> Option<TestBackend> {
+        let storage = TempDir::new().expect("Error creating the storage directory");
+
+        let mut config = Config::default();
+        config.storage.upload_dir = storage.path().join("upload");
+        config.storage.tmp_dir = storage.path().join("tmp");
+        config.pdf.engine = PdfEngineKind::Lopdf;
+        config.admin = AdminConfig { username: ADMIN.to_string(), password: Some(ADMIN_PASSWORD.to_string()) };
+
+        std::fs::create_dir_all(&config.storage.upload_dir).expect("Error creating the upload directory");
+        std::fs::create_dir_all(&config.storage.tmp_dir).expect("Error creating the tmp directory");
+
+        if baseline {
+            std::fs::write(config.storage.upload_dir.join("baseline.pdf"), pdf_bytes(BASELINE_TITLE, "Ada", 1))
+                .expect("Error storing the baseline pdf");
+        }
+
+        let storage_config = Arc::new(config.storage.clone());
+
+        let (database, service, auth_service, audit_service, health_repository) = match store {
+            Store::Memory => {
+                let database = Arc::new(MemoryDatabase::new());
+
+                (
+                    None,
+                    PdfServiceImpl { repository: Arc::new(MemoryPdfRepository { database: database.clone() }), storage: storage_config.clone() },
+                    AuthServiceImpl { repository: Arc::new(MemoryUserRepository { database: database.clone() }), storage: storage_config.clone() },
+                    AuditServiceImpl { repository: Arc::new(MemoryAuditRepository { database: database.clone() }) },
+                    Arc::new(MemoryHealthRepository { database }) as Arc<dyn HealthRepository>
+                )
+            },
+            Store::Sqlite => {
+                let database_config = DatabaseConfig {
+                    url: format!("sqlite://{}", storage.path().join("pdf-store.db").display()),
+                    max_connections: 1
+                };
+
+                let pool = sqlite::connect(&database_config).await.expect("Error opening the test database");
+
+                // SQLite databases were always migrated, the oldest ones are at the first migration
+                if baseline {
+                    let mut connection = pool.acquire().await.expect("Error opening the test database");
+                    connection.ensure_migrations_table().await.expect("Error creating the migrations table");
+                    connection.apply(sqlite::MIGRATOR.iter().next().unwrap()).await.expect("Error applying the first migration");
+
+                    let seed = format!(
+                        "INSERT INTO pdfs (id, title, file_name, author, pages, time_added) VALUES (x'{}', '{BASELINE_TITLE}', 'baseline.pdf', 'Ada', 1, '2020-01-01T00:00:00Z');
+                        INSERT INTO tags (name) VALUES ('{BASELINE_TAG}');
+                        INSERT INTO tags_to_pdfs (name, id) SELECT '{BASELINE_TAG}', id FROM pdfs;",
+                        Uuid::new_v4().simple()
+                    );
+                    connection.execute(seed.as_str()).await.expect("Error storing the baseline pdf");
+                }
+
+                sqlite::migrate(&pool).await.expect("Error migrating the test database");
+
+                let pool = Arc::new(pool);
+
+                (
+                    None,
+                    PdfServiceImpl { repository: Arc::new(SqlitePdfRepository { pool: pool.clone() }), storage: storage_config.clone() },
+                    AuthServiceImpl { repository: Arc::new(SqliteUserRepository { pool: pool.clone() }), storage: storage_config.clone() },
+                    AuditServiceImpl { repository: Arc::new(SqliteAuditRepository { pool: pool.clone() }) },
+                    Arc::new(SqliteHealthRepository { pool }) as Arc<dyn HealthRepository>
+                )
+            },
+            Store::Postgres => {
+                let database = Arc::new(DisposableDatabase::create(baseline).await?);
+                let pool = Arc::new(database.pool.clone());
+
+                (
+                    Some(database),
+                    PdfServiceImpl { repository: Arc::new(PdfRepositoryImpl { pool: pool.clone() }), storage: storage_config.clone() },
+                    AuthServiceImpl { repository: Arc::new(UserRepositoryImpl { pool: pool.clone() }), storage: storage_config.clone() },
+                    AuditServiceImpl { repository: Arc::new(AuditRepositoryImpl { pool: pool.clone() }) },
+                    Arc::new(HealthRepositoryImpl { pool }) as Arc<dyn HealthRepository>
+                )
+            }
+        };
+
+        auth_service.bootstrap_admin(&config.admin).await.expect("Error creating the admin account");
+
+        let config = Arc::new(config);
+        let engine: Arc<dyn PdfEngine> = Arc::new(LopdfEngine);
+
+        let health_service = HealthServiceImpl { repository: health_repository, engine: engine.clone(), config: config.clone() };
+
+        let fsck_service = FsckServiceImpl { repository: service.repository.clone(), engine: engine.clone(), storage: storage_config.clone() };
+
+        let archive_service = ArchiveServiceImpl {
+            repository: service.repository.clone(),
+            user_repository: auth_service.repository.clone(),
+            engine: engine.clone(),
+            storage: storage_config
+        };
+
+        let state = Data::new(AppState {
+            config,
+            engine,
+            service,
+            auth_service,
+            audit_service,
+            health_service,
+            fsck_service,
+            archive_service
+        });
+
+        Some(TestBackend { state, storage: Arc::new(storage), database })
+    }
+
+
+    async fn stop(self) {
+        if let Some(database) = self.database {
+            database.remove().await;
+        }
+    }
+
+
+    pub async fn app(&self) -> impl TestApp {
+        test::init_service(App::new().wrap(from_fn(request_id)).wrap(from_fn(record_metrics)).configure(configure(self.state.clone()))).await
+    }
+
+
+    /// Whether the upload directory contains a file with the given storage key
+    pub fn is_stored(&self, storage_key: &str) -> bool {
+        self.state.config.storage.upload_dir.join(storage_key).exists()
+    }
+
+}
+
+
+impl DisposableDatabase {
+
+    async fn create(baseline: bool) -> Option<DisposableDatabase> {
+        dotenv::dotenv().ok();
+
+        let server_url = std::env::var("TEST_DATABASE_URL").or_else(|_| std::env::var("DATABASE_URL")).ok()?;
+        let name = format!("pdfstore_test_{}", Uuid::new_v4().simple());
+
+        let mut connection = PgConnection::connect(&server_url).await.expect("Error connecting to the test database server");
+        sqlx::query(&format!("CREATE DATABASE \"{name}\""))
+            .execute(&mut connection)
+            .await
+            .expect("Error creating the test database");
+        let _ = connection.close().await;
+
+        let database_config = DatabaseConfig { url: with_database_name(&server_url, &name), max_connections: 5 };
+
+        let pool = repository::db::connect(&database_config).await.expect("Error connecting to the test database");
+
+        if baseline {
+            let seed = format!(
+                "{}
+                INSERT INTO pdfs (title, file_name, author, pages, time_added) VALUES ('{BASELINE_TITLE}', 'baseline.pdf', 'Ada', 1, '2020-01-01T00:00:00Z');
+                INSERT INTO tags (name) VALUES ('{BASELINE_TAG}');
+                INSERT INTO tags_to_pdfs (name, id) SELECT '{BASELINE_TAG}', id FROM pdfs;",
+                include_str!("../../migrations/0001_initial_schema.sql")
+            );
+            pool.execute(seed.as_str()).await.expect("Error setting up the baseline schema");
+        }
+
+        repository::db::migrate(&pool).await.expect("Error migrating the test database");
+
+        Some(DisposableDatabase { server_url, name, pool })
+    }
+
+
+    async fn remove(&self) {
+        self.pool.close().await;
+
+        if let Ok(mut connection) = PgConnection::connect(&self.server_url).await {
+            let _ = sqlx::query(&format!("DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)", self.name))
+                .execute(&mut connection)
+                .await;
+            let _ = connection.close().await;
+        }
+    }
+
+}
+
+
+/// Replaces the database name in the path of the connection URL
+fn with_database_name(url: &str, name: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None)
+    };
+
+    let server = match base.rsplit_once('/') {
+        Some((server, _)) if !server.ends_with('/') => server,
+        _ => base.trim_end_matches('/')
+    };
+
+    match query {
+        Some(query) => format!("{server}/{name}?{query}"),
+        None => format!("{server}/{name}")
+    }
+}
+
+
+/// A valid pdf with the given number of empty pages, title and author are stored in its info dictionary
+pub fn pdf_bytes(title: &str, author: &str, pages: usize) -> Vec<u8> {
+    let mut document = Document::with_version("1.5");
+
+    let pages_id = document.new_object_id();
+
+    let kids: Vec<Object> = (0..pages)
+        .map(|_| {
+            let content_id = document.add_object(Stream::new(dictionary! {}, Vec::new()));
+            document.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()]
+            }).into()
+        })
+        .collect();
+
+    document.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids,
+        "Count" => pages as i64
+    }));
+
+    let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    let info_id = document.add_object(dictionary! {
+        "Title" => Object::string_literal(title),
+        "Author" => Object::string_literal(author)
+    });
+
+    document.trailer.set("Root", catalog_id);
+    document.trailer.set("Info", info_id);
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).expect("Error writing the test pdf");
+    bytes
+}
+
+
+/// Request with the session or API token as bearer token
+pub fn request(method: Method, uri: &str, token: &str) -> test::TestRequest {
+    test::TestRequest::default()
+        .method(method)
+        .uri(uri)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+}
+
+
+pub fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}")))
+}
+
+
+/// Sends the request and parses the body as JSON, an empty body is `null`
+pub async fn send(app: &impl TestApp, request: test::TestRequest) -> (StatusCode, Value) {
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+
+    match body.is_empty() {
+        true => (status, Value::Null),
+        false => (status, serde_json::from_slice(&body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned())))
+    }
+}
+
+
+/// Logs in and returns the session token
+pub async fn login(app: &impl TestApp, username: &str, password: &str) -> String {
+    let request = test::TestRequest::post()
+        .uri("/api/v1/auth/login")
+        .set_json(json!({ "username": username, "password": password }));
+
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::OK, "login of {username} failed: {body}");
+
+    body["token"].as_str().unwrap().to_string()
+}
+
+
+pub async fn login_admin(app: &impl TestApp) -> String {
+    login(app, ADMIN, ADMIN_PASSWORD).await
+}
+
+
+/// Password of the users created by `create_user`
+pub fn password_of(username: &str) -> String {
+    format!("{username}-password")
+}
+
+
+/// Creates a user that isn't an admin and returns the ID and a session token of the user
+pub async fn create_user(app: &impl TestApp, admin_token: &str, username: &str) -> (Uuid, String) {
+    let request = request(Method::POST, "/api/v1/users", admin_token)
+        .set_json(json!({ "username": username, "password": password_of(username) }));
+
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::CREATED, "creating {username} failed: {body}");
+
+    let user_id = body["id"].as_str().unwrap().parse().unwrap();
+
+    (user_id, login(app, username, &password_of(username)).await)
+}
+
+
+/// Multipart body with the files as `file` fields, returns the content type and the body
+pub fn multipart(files: &[(&str, &[u8])]) -> (String, Vec<u8>) {
+    let boundary = "pdfstore-test-boundary";
+    let mut body = Vec::new();
+
+    for (file_name, content) in files {
+        body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+        body.extend_from_slice(format!("Content-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n").as_bytes());
+        body.extend_from_slice(b"Content-Type: application/pdf\r\n\r\n");
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+    (format!("multipart/form-data; boundary={boundary}"), body)
+}
+
+
+/// Uploads the files to the given route and returns the response status
+pub async fn upload_to(app: &impl TestApp, uri: &str, token: &str, files: &[(&str, &[u8])]) -> StatusCode {
+    let (content_type, body) = multipart(files);
+
+    let request = request(Method::POST, uri, token)
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .set_payload(body);
+
+    send(app, request).await.0
+}
+
+
+/// Uploads a pdf with the given title and returns its ID
+pub async fn upload_pdf(app: &impl TestApp, token: &str, file_name: &str, title: &str, author: &str) -> Uuid {
+    let content = pdf_bytes(title, author, 2);
+
+    let status = upload_to(app, "/api/v1/documents", token, &[(file_name, &content)]).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    find_pdf(app, token, title).await.expect("The uploaded pdf is not listed")
+}
+
+
+/// ID of the pdf with the given title among the pdfs the user can see
+pub async fn find_pdf(app: &impl TestApp, token: &str, title: &str) -> Option<Uuid> {
+    let (_, body) = send(app, request(Method::GET, "/api/v1/documents?page=1&size=100", token)).await;
+
+    body["pdfs_previews"].as_array().unwrap().iter()
+        .find(|preview| preview["title"] == title)
+        .map(|preview| preview["id"].as_str().unwrap().parse().unwrap())
+}
+
+
+/// Details of the pdf along with the ETag they were returned with
+pub async fn metadata(app: &impl TestApp, token: &str, pdf_id: &Uuid) -> (Value, String) {
+    let response = test::call_service(app, request(Method::GET, &format!("/api/v1/documents/{pdf_id}"), token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let etag = response.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string();
+    let body: Value = test::read_body_json(response).await;
+
+    (body, etag)
+}
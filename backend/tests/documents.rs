@@ -0,0 +1,564 @@
+//! Upload, listing, search, updates, deletion and bulk operations of pdfs
+#[macro_use]
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test;
+use pdf_store::util::{sweep_stale_files, STALE_FILE_AGE};
+use serde_json::json;
+
+use common::*;
+
+
+scenarios!(
+    upload_and_list,
+    paging,
+    metadata_and_content,
+    duplicate_file_names,
+    identical_content_is_stored_once,
+    search,
+    update_replaces_tags,
+    update_requires_matching_version,
+    patch,
+    delete,
+    bulk_by_ids,
+    bulk_by_search,
+    bulk_dry_run,
+    pdfs_of_other_users_are_hidden,
+    legacy_routes,
+    stale_files_are_swept,
+    metrics
+);
+
+
+async fn upload_and_list(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let first = pdf_bytes("First", "Ada", 3);
+    let second = pdf_bytes("Second", "Grace", 1);
+
+    let status = upload_to(&app, "/api/v1/documents", &token, &[("first.pdf", &first), ("second.pdf", &second), ("notes.txt", b"no pdf")]).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/documents?page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 2);
+
+    let titles: Vec<&str> = body["pdfs_previews"].as_array().unwrap().iter().map(|preview| preview["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["First", "Second"]);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/documents", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, test::TestRequest::get().uri("/api/v1/documents?page=1&size=10")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+
+async fn paging(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    for number in 1..=5 {
+        upload_pdf(&app, &token, &format!("{number}.pdf"), &format!("Pdf {number}"), "Ada").await;
+    }
+
+    let (_, first_page) = send(&app, request(Method::GET, "/api/v1/documents?page=1&size=2", &token)).await;
+    let (_, last_page) = send(&app, request(Method::GET, "/api/v1/documents?page=3&size=2", &token)).await;
+    let (_, beyond) = send(&app, request(Method::GET, "/api/v1/documents?page=4&size=2", &token)).await;
+
+    assert_eq!(first_page["count"], 5);
+    assert_eq!(first_page["pdfs_previews"].as_array().unwrap().len(), 2);
+    assert_eq!(first_page["pdfs_previews"][0]["title"], "Pdf 1");
+    assert_eq!(last_page["pdfs_previews"].as_array().unwrap().len(), 1);
+    assert_eq!(last_page["pdfs_previews"][0]["title"], "Pdf 5");
+    assert!(beyond["pdfs_previews"].as_array().unwrap().is_empty());
+}
+
+
+async fn metadata_and_content(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let content = pdf_bytes("Report", "Ada", 3);
+    upload_to(&app, "/api/v1/documents", &token, &[("report.pdf", &content)]).await;
+    let pdf_id = find_pdf(&app, &token, "Report").await.unwrap();
+
+    let (metadata, etag) = metadata(&app, &token, &pdf_id).await;
+    assert_eq!(metadata["file_name"], "report.pdf");
+    assert_eq!(metadata["author"], "Ada");
+    assert_eq!(metadata["pages"], 3);
+    assert_eq!(etag, format!("\"{}\"", metadata["version"]));
+
+    let response = test::call_service(&app, request(Method::GET, &format!("/api/v1/documents/{pdf_id}/content"), &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/pdf");
+    assert_eq!(test::read_body(response).await.to_vec(), content);
+
+    let (status, _) = send(&app, request(Method::GET, &format!("/api/v1/documents/{}", uuid::Uuid::new_v4()), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/documents/not-an-id", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+
+async fn duplicate_file_names(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, other_token) = create_user(&app, &token, "bob").await;
+
+    upload_pdf(&app, &token, "report.pdf", "Original", "Ada").await;
+
+    // A second pdf with the same file name is rejected for the same owner, but not for another one
+    let duplicate = pdf_bytes("Duplicate", "Ada", 1);
+    upload_to(&app, "/api/v1/documents", &token, &[("report.pdf", &duplicate)]).await;
+    assert!(find_pdf(&app, &token, "Duplicate").await.is_none());
+
+    // Neither the upload nor the tmp directory keep the file of the rejected pdf
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 1);
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.tmp_dir).unwrap().count(), 0);
+
+    upload_to(&app, "/api/v1/documents", &other_token, &[("report.pdf", &duplicate)]).await;
+    assert!(find_pdf(&app, &other_token, "Duplicate").await.is_some());
+}
+
+
+async fn identical_content_is_stored_once(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let content = pdf_bytes("Same", "Ada", 1);
+    upload_to(&app, "/api/v1/documents", &token, &[("a.pdf", &content), ("b.pdf", &content)]).await;
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=Same&page=1&size=10", &token)).await;
+    let ids: Vec<String> = body["pdfs_previews"].as_array().unwrap().iter().map(|preview| preview["id"].as_str().unwrap().to_string()).collect();
+    assert_eq!(ids.len(), 2);
+
+    let stored = std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count();
+    assert_eq!(stored, 1);
+
+    // The file is kept until the last pdf using it is deleted
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/api/v1/documents/{}", ids[0]), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 1);
+
+    send(&app, request(Method::DELETE, &format!("/api/v1/documents/{}", ids[1]), &token)).await;
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 0);
+}
+
+
+async fn search(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let rust = upload_pdf(&app, &token, "rust.pdf", "Rust in Action", "Tim").await;
+    upload_pdf(&app, &token, "go.pdf", "Go in Practice", "Matt").await;
+    upload_pdf(&app, &token, "percent.pdf", "100% Rust", "Tim").await;
+
+    let (_, etag) = metadata(&app, &token, &rust).await;
+    let update = request(Method::PATCH, &format!("/api/v1/documents/{rust}"), &token)
+        .insert_header((header::IF_MATCH, etag))
+        .set_json(json!({ "tags": ["Programming"] }));
+    assert_eq!(send(&app, update).await.0, StatusCode::OK);
+
+    let titles = |body: serde_json::Value| -> Vec<String> {
+        body["pdfs_previews"].as_array().unwrap().iter().map(|preview| preview["title"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=rust&page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 2);
+    assert_eq!(titles(body), vec!["Rust in Action", "100% Rust"]);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?author=TIM&title=action&page=1&size=10", &token)).await;
+    assert_eq!(titles(body), vec!["Rust in Action"]);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?tag=programming&page=1&size=10", &token)).await;
+    assert_eq!(titles(body), vec!["Rust in Action"]);
+
+    // Wildcards of the search term are matched as in ILIKE
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=100%25&page=1&size=10", &token)).await;
+    assert_eq!(titles(body), vec!["100% Rust"]);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=G_%20in&page=1&size=10", &token)).await;
+    assert_eq!(titles(body), vec!["Go in Practice"]);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=rust&page=2&size=1", &token)).await;
+    assert_eq!(body["count"], 2);
+    assert_eq!(titles(body), vec!["100% Rust"]);
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/documents/search?page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+
+async fn update_replaces_tags(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+
+    let (_, etag) = metadata(&app, &token, &pdf_id).await;
+
+    let update = request(Method::PUT, &format!("/api/v1/documents/{pdf_id}"), &token)
+        .insert_header((header::IF_MATCH, etag))
+        .set_json(json!({ "title": "Annual report", "author": "Grace", "comments": "Draft", "tags": ["finance", "2023"] }));
+
+    let (status, body) = send(&app, update).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["title"], "Annual report");
+    assert_eq!(body["author"], "Grace");
+    assert_eq!(body["comments"], "Draft");
+
+    let mut tags: Vec<String> = serde_json::from_value(body["tags"].clone()).unwrap();
+    tags.sort();
+    assert_eq!(tags, vec!["2023", "finance"]);
+
+    // The tags are replaced, not merged
+    let update = request(Method::PUT, &format!("/api/v1/documents/{pdf_id}"), &token)
+        .insert_header((header::IF_MATCH, "*"))
+        .set_json(json!({ "title": "Annual report", "tags": ["archive"] }));
+
+    let (status, body) = send(&app, update).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tags"], json!(["archive"]));
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?tag=finance&page=1&size=10", &token)).await;
+    assert_eq!(body["count"], 0);
+}
+
+
+async fn update_requires_matching_version(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let uri = format!("/api/v1/documents/{pdf_id}");
+
+    let (metadata, etag) = metadata(&app, &token, &pdf_id).await;
+
+    let (status, _) = send(&app, request(Method::PUT, &uri, &token).set_json(json!({ "title": "Changed" }))).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+
+    let (status, body) = send(&app, request(Method::PUT, &uri, &token).insert_header((header::IF_MATCH, etag.clone())).set_json(json!({ "title": "Changed" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], metadata["version"].as_i64().unwrap() + 1);
+
+    // The old version is outdated now, the response contains the current details
+    let (status, body) = send(&app, request(Method::PUT, &uri, &token).insert_header((header::IF_MATCH, etag)).set_json(json!({ "title": "Lost" }))).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(body["title"], "Changed");
+
+    let (status, _) = send(&app, request(Method::PUT, &uri, &token).insert_header((header::IF_MATCH, "W/\"1\"")).set_json(json!({ "title": "Lost" }))).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let missing = format!("/api/v1/documents/{}", uuid::Uuid::new_v4());
+    let (status, _) = send(&app, request(Method::PUT, &missing, &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "title": "Lost" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+
+async fn patch(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let uri = format!("/api/v1/documents/{pdf_id}");
+
+    let (_, body) = send(&app, request(Method::PATCH, &uri, &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "comments": "Read twice", "add_tags": ["b", "a"] }))).await;
+    assert_eq!(body["title"], "Report");
+    assert_eq!(body["author"], "Ada");
+    assert_eq!(body["comments"], "Read twice");
+
+    // `null` clears a field, missing fields are kept
+    let (status, body) = send(&app, request(Method::PATCH, &uri, &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "author": null, "remove_tags": ["b"], "add_tags": ["c"] }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["author"], json!(null));
+    assert_eq!(body["comments"], "Read twice");
+
+    let mut tags: Vec<String> = serde_json::from_value(body["tags"].clone()).unwrap();
+    tags.sort();
+    assert_eq!(tags, vec!["a", "c"]);
+
+    let (status, _) = send(&app, request(Method::PATCH, &uri, &token).set_json(json!({ "comments": null }))).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+}
+
+
+async fn delete(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, other_token) = create_user(&app, &token, "bob").await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let uri = format!("/api/v1/documents/{pdf_id}");
+
+    let (status, _) = send(&app, request(Method::DELETE, &uri, &other_token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, request(Method::DELETE, &uri, &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::GET, &uri, &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(std::fs::read_dir(&backend.state.config.storage.upload_dir).unwrap().count(), 0);
+
+    let (status, _) = send(&app, request(Method::DELETE, &uri, &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+
+async fn bulk_by_ids(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let first = upload_pdf(&app, &token, "first.pdf", "First", "Ada").await;
+    let second = upload_pdf(&app, &token, "second.pdf", "Second", "Ada").await;
+    let missing = uuid::Uuid::new_v4();
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [first, second, missing] }, "action": { "type": "add_tags", "tags": ["todo"] } }));
+
+    let (status, body) = send(&app, bulk).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["dry_run"], false);
+    assert_eq!(body["selected"], 2);
+    assert_eq!(body["changed"], 2);
+
+    let status_of = |body: &serde_json::Value, id: &uuid::Uuid| body["results"].as_array().unwrap().iter()
+        .find(|result| result["id"] == id.to_string())
+        .map(|result| result["status"].as_str().unwrap().to_string())
+        .unwrap();
+
+    assert_eq!(status_of(&body, &first), "changed");
+    assert_eq!(status_of(&body, &missing), "not_found");
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [first] }, "action": { "type": "add_tags", "tags": ["todo"] } }));
+
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(status_of(&body, &first), "unchanged");
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [first, second] }, "action": { "type": "set_author", "author": "Grace" } }));
+
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(body["changed"], 2);
+    assert_eq!(metadata(&app, &token, &second).await.0["author"], "Grace");
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [second] }, "action": { "type": "delete" } }));
+
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(body["changed"], 1);
+    assert!(find_pdf(&app, &token, "Second").await.is_none());
+}
+
+
+async fn bulk_by_search(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let rust = upload_pdf(&app, &token, "rust.pdf", "Rust in Action", "Tim").await;
+    let go = upload_pdf(&app, &token, "go.pdf", "Go in Practice", "Matt").await;
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "search": { "author": "tim" } }, "action": { "type": "add_tags", "tags": ["rust"] } }));
+
+    let (status, body) = send(&app, bulk).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["selected"], 1);
+
+    assert_eq!(metadata(&app, &token, &rust).await.0["tags"], json!(["rust"]));
+    assert_eq!(metadata(&app, &token, &go).await.0["tags"], json!([]));
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "search": { "tag": "rust" } }, "action": { "type": "remove_tags", "tags": ["rust"] } }));
+
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(body["changed"], 1);
+    assert_eq!(metadata(&app, &token, &rust).await.0["tags"], json!([]));
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "search": {} }, "action": { "type": "delete" } }));
+
+    let (status, _) = send(&app, bulk).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+
+async fn bulk_dry_run(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [pdf_id] }, "action": { "type": "delete" }, "dry_run": true }));
+
+    let (status, body) = send(&app, bulk).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["dry_run"], true);
+    assert_eq!(body["changed"], 1);
+
+    // Nothing was changed or recorded
+    assert!(find_pdf(&app, &token, "Report").await.is_some());
+
+    let (_, audit) = send(&app, request(Method::GET, &format!("/api/v1/audit?target_id={pdf_id}&action=delete&page=1&size=10"), &token)).await;
+    assert_eq!(audit["count"], 0);
+}
+
+
+async fn pdfs_of_other_users_are_hidden(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, other_token) = create_user(&app, &token, "bob").await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents?page=1&size=10", &other_token)).await;
+    assert_eq!(body["count"], 0);
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=report&page=1&size=10", &other_token)).await;
+    assert_eq!(body["count"], 0);
+
+    for uri in [format!("/api/v1/documents/{pdf_id}"), format!("/api/v1/documents/{pdf_id}/content")] {
+        let (status, _) = send(&app, request(Method::GET, &uri, &other_token)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &other_token)
+        .set_json(json!({ "selection": { "ids": [pdf_id] }, "action": { "type": "delete" } }));
+
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(body["results"][0]["status"], "not_found");
+}
+
+
+async fn legacy_routes(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let content = pdf_bytes("Legacy", "Ada", 1);
+    let status = upload_to(&app, "/pdfs/upload", &token, &[("legacy.pdf", &content)]).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let response = test::call_service(&app, request(Method::GET, "/pdfs?page=1&size=10", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("Deprecation").unwrap(), "true");
+
+    let body: serde_json::Value = test::read_body_json(response).await;
+    let pdf_id = body["pdfs_previews"][0]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, request(Method::GET, &format!("/pdfs/{pdf_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["pdf"].as_str().is_some_and(|pdf| !pdf.is_empty()));
+
+    let (status, body) = send(&app, request(Method::GET, &format!("/pdfs/metadata/{pdf_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["title"], "Legacy");
+
+    let (_, body) = send(&app, request(Method::GET, "/pdfs/search?title=leg&page=1&size=10", &token)).await;
+    assert_eq!(body["count"], 1);
+
+    let (status, _) = send(&app, request(Method::PUT, &format!("/pdfs/{pdf_id}"), &token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "title": "Renamed" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let bulk = request(Method::POST, "/pdfs/bulk", &token)
+        .set_json(json!({ "selection": { "ids": [pdf_id] }, "action": { "type": "add_tags", "tags": ["legacy"] } }));
+
+    let (status, body) = send(&app, bulk).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["changed"], 1);
+
+    let (status, body) = send(&app, request(Method::POST, &format!("/pdfs/{pdf_id}/shares"), &token).set_json(json!({}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let share_link_id = body["id"].as_str().unwrap().to_string();
+
+    let (_, body) = send(&app, request(Method::GET, &format!("/pdfs/{pdf_id}/shares"), &token)).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/pdfs/{pdf_id}/shares/{share_link_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("/pdfs/{pdf_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+
+async fn metrics(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let content = pdf_bytes("Measured", "Ada", 2);
+    let status = upload_to(&app, "/api/v1/documents", &token, &[("a.pdf", &content), ("b.pdf", &content), ("notes.txt", b"no pdf")]).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = send(&app, test::TestRequest::get().uri("/metrics")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+    let (status, _) = send(&app, request(Method::GET, "/metrics", &alice_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Prometheus scrapes with an API token of an admin
+    let (_, body) = send(&app, request(Method::POST, "/api/v1/tokens", &token).set_json(json!({ "name": "prometheus", "scopes": ["admin"] }))).await;
+    let scrape_token = body["token"].as_str().unwrap();
+
+    let response = test::call_service(&app, request(Method::GET, "/metrics", scrape_token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap().starts_with("text/plain"));
+
+    let text = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+
+    // The counters are shared by the tests running in parallel, the gauges are the same for every run of this test
+    assert!(metric(&text, "pdfstore_uploads_accepted_total") >= 2.0);
+    assert!(metric(&text, "pdfstore_uploads_duplicate_total") >= 1.0);
+    assert!(metric(&text, "pdfstore_uploads_rejected_total{reason=\"unreadable\"}") >= 1.0);
+    assert!(metric(&text, "pdfstore_pdf_engine_duration_seconds_count{engine=\"lopdf\",operation=\"load\"}") >= 2.0);
+    assert!(metric(&text, "pdfstore_http_requests_total{method=\"POST\",route=\"/api/v1/documents\",status=\"201\"}") >= 1.0);
+    assert_eq!(metric(&text, "pdfstore_library_documents"), 2.0);
+    assert_eq!(metric(&text, "pdfstore_library_pages"), 4.0);
+    assert_eq!(metric(&text, "pdfstore_library_bytes"), content.len() as f64);
+    assert_eq!(metric(&text, "pdfstore_pdf_engine_queued_jobs"), 0.0);
+}
+
+
+async fn stale_files_are_swept(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    upload_pdf(&app, &token, "kept.pdf", "Kept", "Ada").await;
+
+    let storage = &backend.state.config.storage;
+    let stale = SystemTime::now() - STALE_FILE_AGE - Duration::from_secs(60);
+
+    let stored_pdf = std::fs::read_dir(&storage.upload_dir).unwrap().next().unwrap().unwrap().path();
+    let interrupted_upload = storage.tmp_dir.join(".tmpInterrupted");
+    let running_upload = storage.tmp_dir.join(".tmpRunning");
+    let probe = storage.upload_dir.join(".health-probe-0");
+    let unreferenced_pdf = storage.upload_dir.join("0.pdf");
+    let storing_pdf = storage.upload_dir.join("1.pdf");
+
+    for path in [&interrupted_upload, &running_upload, &probe, &unreferenced_pdf, &storing_pdf] {
+        std::fs::write(path, b"left over").unwrap();
+    }
+
+    for path in [&interrupted_upload, &probe, &unreferenced_pdf, &stored_pdf] {
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(stale).unwrap();
+    }
+
+    assert_eq!(sweep_stale_files(storage, backend.state.service.repository.as_ref()).await, 3);
+
+    assert!(!interrupted_upload.exists());
+    assert!(!probe.exists());
+    assert!(!unreferenced_pdf.exists());
+    assert!(running_upload.exists());
+    assert!(storing_pdf.exists());
+    assert!(stored_pdf.exists());
+}
+
+
+/// Value of the sample with the given name and labels
+fn metric(text: &str, sample: &str) -> f64 {
+    text.lines()
+        .find_map(|line| line.strip_prefix(sample).and_then(|value| value.strip_prefix(' ')))
+        .unwrap_or_else(|| panic!("No sample {sample} in the metrics"))
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,168 @@
+//! Importing the pdfs dropped into the watched inbox
+#[macro_use]
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use actix_web::web::Data;
+use pdf_store::config::InboxConfig;
+use pdf_store::inbox::{self, InboxScan};
+use pdf_store::AppState;
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::*;
+
+
+scenarios!(
+    scan_moves_files_by_outcome,
+    scan_waits_for_files_to_settle,
+    stopped_scan_leaves_files,
+    watcher_imports_new_files
+);
+
+
+/// The state of the backend with the inbox in the given directory
+fn with_inbox(backend: &TestBackend, dir: &Path, settle_time: u64) -> Data<AppState> {
+    let state = &backend.state;
+
+    let mut config = (*state.config).clone();
+    config.inbox = InboxConfig {
+        dir: Some(dir.to_path_buf()),
+        owner: None,
+        tags_from_folders: true,
+        settle_time,
+        scan_interval: 60
+    };
+
+    Data::new(AppState {
+        config: Arc::new(config),
+        engine: state.engine.clone(),
+        service: state.service.clone(),
+        auth_service: state.auth_service.clone(),
+        audit_service: state.audit_service.clone(),
+        health_service: state.health_service.clone(),
+        fsck_service: state.fsck_service.clone(),
+        archive_service: state.archive_service.clone()
+    })
+}
+
+
+fn sorted_tags(metadata: &Value) -> Vec<String> {
+    let mut tags: Vec<String> = serde_json::from_value(metadata["tags"].clone()).unwrap();
+    tags.sort();
+    tags
+}
+
+
+async fn scan_moves_files_by_outcome(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let dir = TempDir::new().unwrap();
+    let inbox = dir.path();
+    let state = with_inbox(&backend, inbox, 0);
+
+    std::fs::create_dir_all(inbox.join("Invoices/2024")).unwrap();
+    std::fs::write(inbox.join("Invoices/2024/scan.pdf"), pdf_bytes("Scan", "Scanner", 1)).unwrap();
+    std::fs::write(inbox.join("broken.pdf"), "not a pdf").unwrap();
+    std::fs::write(inbox.join("notes.txt"), "left alone").unwrap();
+    std::fs::write(inbox.join(".partial.pdf"), "still syncing").unwrap();
+
+    let scan = inbox::scan(&state, inbox, &AtomicBool::new(false)).await.unwrap();
+    assert_eq!(scan, InboxScan { imported: 1, failed: 1, pending: 0 });
+
+    let scan_id = find_pdf(&app, &token, "Scan").await.unwrap();
+    assert_eq!(sorted_tags(&metadata(&app, &token, &scan_id).await.0), vec!["2024", "Invoices"]);
+
+    assert!(!inbox.join("Invoices/2024/scan.pdf").exists());
+    assert!(inbox.join("processed/Invoices/2024/scan.pdf").exists());
+
+    assert!(inbox.join("failed/broken.pdf").exists());
+    let report = std::fs::read_to_string(inbox.join("failed/broken.pdf.error.txt")).unwrap();
+    assert!(report.starts_with("File: broken.pdf\n"));
+    assert!(report.contains("Error: "));
+
+    assert!(inbox.join("notes.txt").exists());
+    assert!(inbox.join(".partial.pdf").exists());
+
+    // The processed and failed files are not imported again
+    assert_eq!(inbox::scan(&state, inbox, &AtomicBool::new(false)).await.unwrap(), InboxScan::default());
+
+    // The owner has a pdf with this file name already, the report of the earlier failure is kept
+    std::fs::write(inbox.join("Invoices/2024/scan.pdf"), pdf_bytes("Scan", "Scanner", 1)).unwrap();
+    std::fs::write(inbox.join("broken.pdf"), "still not a pdf").unwrap();
+
+    assert_eq!(inbox::scan(&state, inbox, &AtomicBool::new(false)).await.unwrap(), InboxScan { imported: 0, failed: 2, pending: 0 });
+    assert!(inbox.join("failed/Invoices/2024/scan.pdf.error.txt").exists());
+    assert!(inbox.join("failed/broken (1).pdf.error.txt").exists());
+    assert!(inbox.join("failed/broken.pdf.error.txt").exists());
+}
+
+
+async fn scan_waits_for_files_to_settle(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let dir = TempDir::new().unwrap();
+    let inbox = dir.path();
+    let state = with_inbox(&backend, inbox, 3600);
+
+    std::fs::write(inbox.join("writing.pdf"), pdf_bytes("Writing", "Scanner", 1)).unwrap();
+
+    assert_eq!(inbox::scan(&state, inbox, &AtomicBool::new(false)).await.unwrap(), InboxScan { imported: 0, failed: 0, pending: 1 });
+    assert!(inbox.join("writing.pdf").exists());
+    assert!(find_pdf(&app, &token, "Writing").await.is_none());
+}
+
+
+async fn stopped_scan_leaves_files(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let dir = TempDir::new().unwrap();
+    let inbox = dir.path();
+    let state = with_inbox(&backend, inbox, 0);
+
+    std::fs::write(inbox.join("later.pdf"), pdf_bytes("Later", "Scanner", 1)).unwrap();
+
+    assert_eq!(inbox::scan(&state, inbox, &AtomicBool::new(true)).await.unwrap(), InboxScan::default());
+    assert!(inbox.join("later.pdf").exists());
+    assert!(!inbox.join("failed/later.pdf").exists());
+    assert!(find_pdf(&app, &token, "Later").await.is_none());
+}
+
+
+async fn watcher_imports_new_files(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+
+    let dir = TempDir::new().unwrap();
+    let inbox = dir.path();
+    let watcher = inbox::start(with_inbox(&backend, inbox, 0)).await.unwrap();
+    assert!(watcher.is_some());
+
+    std::fs::write(inbox.join("dropped.pdf"), pdf_bytes("Dropped", "Scanner", 1)).unwrap();
+
+    let mut imported = None;
+    for _ in 0..100 {
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+
+        imported = find_pdf(&app, &token, "Dropped").await;
+        if imported.is_some() {
+            break;
+        }
+    }
+
+    assert!(imported.is_some());
+
+    // Files dropped after the watcher stopped stay in the inbox
+    watcher.unwrap().stop().await;
+    std::fs::write(inbox.join("after.pdf"), pdf_bytes("After", "Scanner", 1)).unwrap();
+    actix_web::rt::time::sleep(Duration::from_millis(300)).await;
+
+    assert!(inbox.join("after.pdf").exists());
+}
@@ -0,0 +1,50 @@
+//! Upgrading databases that were set up from the original schema before there were migrations
+mod common;
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::test;
+use serde_json::json;
+
+use common::*;
+
+
+#[actix_web::test]
+async fn sqlite_database_from_the_original_schema() {
+    run_upgraded(Store::Sqlite, original_library_is_kept).await;
+}
+
+
+#[actix_web::test]
+async fn postgres_database_from_the_original_schema() {
+    run_upgraded(Store::Postgres, original_library_is_kept).await;
+}
+
+
+async fn original_library_is_kept(backend: TestBackend) {
+    let app = backend.app().await;
+
+    // The admin that was created for the existing pdfs got its password on start
+    let token = login_admin(&app).await;
+
+    let pdf_id = find_pdf(&app, &token, BASELINE_TITLE).await.expect("The existing pdf belongs to the admin");
+    let (pdf, _) = metadata(&app, &token, &pdf_id).await;
+    assert_eq!(pdf["author"], "Ada");
+    assert_eq!(pdf["tags"], json!([BASELINE_TAG]));
+
+    let response = test::call_service(&app, request(Method::GET, &format!("/api/v1/documents/{pdf_id}/content"), &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(test::read_body(response).await.to_vec(), pdf_bytes(BASELINE_TITLE, "Ada", 1));
+
+    // The search index covers the existing pdfs
+    let (status, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=aseli&page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body.to_string().matches(&pdf_id.to_string()).count(), 1, "{body}");
+
+    // and the upgraded schema is complete
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+    upload_pdf(&app, &alice_token, "baseline.pdf", "Alice", "Alice").await;
+    assert!(find_pdf(&app, &alice_token, BASELINE_TITLE).await.is_none());
+
+    let (status, _) = send(&app, request(Method::GET, "/api/v1/audit?page=1&size=10", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+}
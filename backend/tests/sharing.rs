@@ -0,0 +1,311 @@
+//! Sharing pdfs with users and groups, share links for people without an account and the WebDAV library
+#[macro_use]
+mod common;
+
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::test;
+use pdf_store::domain::service::pdf::PdfService;
+use pdf_store::errors::PdfUpdateError;
+use serde_json::json;
+
+use common::*;
+
+
+scenarios!(
+    share_with_user,
+    share_with_group,
+    share_permissions,
+    share_links,
+    share_link_password,
+    share_link_limits,
+    webdav
+);
+
+
+async fn share_with_user(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (_, alice_token) = create_user(&app, &token, "alice").await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let permissions = format!("/api/v1/documents/{pdf_id}/permissions");
+
+    let (status, _) = send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "user": "alice", "permission": "read" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, request(Method::GET, &permissions, &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([{ "user": "alice", "group": null, "permission": "read" }]));
+
+    assert_eq!(find_pdf(&app, &alice_token, "Report").await, Some(pdf_id));
+
+    let (_, body) = send(&app, request(Method::GET, "/api/v1/documents/search?title=rep&page=1&size=10", &alice_token)).await;
+    assert_eq!(body["count"], 1);
+
+    // Sharing again replaces the permission
+    send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "user": "alice", "permission": "edit" }))).await;
+    let (_, body) = send(&app, request(Method::GET, &permissions, &token)).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["permission"], "edit");
+
+    // Only the owner manages the shares
+    let (status, _) = send(&app, request(Method::PUT, &permissions, &alice_token).set_json(json!({ "user": "admin", "permission": "read" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    for share in [json!({ "user": "nobody", "permission": "read" }), json!({ "user": "admin", "permission": "read" }), json!({ "permission": "read" })] {
+        let (status, _) = send(&app, request(Method::PUT, &permissions, &token).set_json(share)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("{permissions}?user=alice"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("{permissions}?user=alice"), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    assert!(find_pdf(&app, &alice_token, "Report").await.is_none());
+}
+
+
+async fn share_with_group(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (alice_id, alice_token) = create_user(&app, &token, "alice").await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let permissions = format!("/api/v1/documents/{pdf_id}/permissions");
+
+    let (_, group) = send(&app, request(Method::POST, "/api/v1/groups", &token).set_json(json!({ "name": "team" }))).await;
+    let members = format!("/api/v1/groups/{}/members/{alice_id}", group["id"].as_str().unwrap());
+
+    let (status, _) = send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "group": "team", "permission": "annotate" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert!(find_pdf(&app, &alice_token, "Report").await.is_none());
+
+    send(&app, request(Method::PUT, &members, &token)).await;
+    assert_eq!(find_pdf(&app, &alice_token, "Report").await, Some(pdf_id));
+
+    send(&app, request(Method::DELETE, &members, &token)).await;
+    assert!(find_pdf(&app, &alice_token, "Report").await.is_none());
+
+    send(&app, request(Method::PUT, &members, &token)).await;
+    let (status, _) = send(&app, request(Method::DELETE, &format!("{permissions}?group=team"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(find_pdf(&app, &alice_token, "Report").await.is_none());
+}
+
+
+async fn share_permissions(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let (alice_id, alice_token) = create_user(&app, &token, "alice").await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let uri = format!("/api/v1/documents/{pdf_id}");
+    let permissions = format!("{uri}/permissions");
+
+    let annotate = || request(Method::PATCH, &uri, &alice_token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "comments": "Seen", "add_tags": ["todo"] }));
+    let edit = || request(Method::PATCH, &uri, &alice_token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "title": "Renamed" }));
+
+    send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "user": "alice", "permission": "read" }))).await;
+
+    let (status, _) = send(&app, request(Method::GET, &format!("{uri}/content"), &alice_token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, annotate()).await.0, StatusCode::FORBIDDEN);
+
+    // Neither through the tag folders of WebDAV, which only show the pdfs of the user, nor through the service behind them
+    let notes_id = upload_pdf(&app, &alice_token, "notes.pdf", "Notes", "Alice").await;
+    for (pdf_id, token) in [(notes_id, &alice_token), (pdf_id, &token)] {
+        let tag = request(Method::PATCH, &format!("/api/v1/documents/{pdf_id}"), token).insert_header((header::IF_MATCH, "*")).set_json(json!({ "add_tags": ["todo"] }));
+        assert_eq!(send(&app, tag).await.0, StatusCode::OK);
+    }
+
+    let dav = |method: &[u8], path: &str| request(Method::from_bytes(method).unwrap(), path, &alice_token);
+    assert_eq!(send(&app, dav(b"MKCOL", "/dav/Tags/later")).await.0, StatusCode::CREATED);
+
+    let copy = dav(b"COPY", "/dav/All/report.pdf").insert_header(("Destination", "/dav/Tags/later/report.pdf"));
+    assert_eq!(send(&app, copy).await.0, StatusCode::NOT_FOUND);
+
+    let moved = dav(b"MOVE", "/dav/Tags/todo/report.pdf").insert_header(("Destination", "/dav/Tags/later/report.pdf"));
+    assert_eq!(send(&app, moved).await.0, StatusCode::NOT_FOUND);
+
+    let service = &backend.state.service;
+    assert!(matches!(service.add_tag(&pdf_id, "later", &alice_id).await, Err(PdfUpdateError::Forbidden(_))));
+    assert!(matches!(service.remove_tag(&pdf_id, "todo", &alice_id).await, Err(PdfUpdateError::Forbidden(_))));
+
+    assert_eq!(metadata(&app, &token, &pdf_id).await.0["tags"], json!(["todo"]));
+
+    send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "user": "alice", "permission": "annotate" }))).await;
+    assert_eq!(send(&app, annotate()).await.0, StatusCode::OK);
+    assert_eq!(send(&app, edit()).await.0, StatusCode::FORBIDDEN);
+
+    send(&app, request(Method::PUT, &permissions, &token).set_json(json!({ "user": "alice", "permission": "edit" }))).await;
+    assert_eq!(send(&app, edit()).await.0, StatusCode::OK);
+
+    // Deleting and sharing stays with the owner
+    let (status, _) = send(&app, request(Method::DELETE, &uri, &alice_token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send(&app, request(Method::POST, &format!("{uri}/shares"), &alice_token).set_json(json!({}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let bulk = request(Method::POST, "/api/v1/documents/bulk", &alice_token)
+        .set_json(json!({ "selection": { "ids": [pdf_id] }, "action": { "type": "delete" } }));
+    let (_, body) = send(&app, bulk).await;
+    assert_eq!(body["results"][0]["status"], "forbidden");
+}
+
+
+async fn share_links(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let content = pdf_bytes("Report", "Ada", 2);
+    upload_to(&app, "/api/v1/documents", &token, &[("report.pdf", &content)]).await;
+    let pdf_id = find_pdf(&app, &token, "Report").await.unwrap();
+    let shares = format!("/api/v1/documents/{pdf_id}/shares");
+
+    let (status, created) = send(&app, request(Method::POST, &shares, &token).set_json(json!({}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["active"], true);
+
+    let share_token = created["token"].as_str().unwrap();
+    let share_link_id = created["id"].as_str().unwrap();
+    assert!(created["url"].as_str().unwrap().ends_with(&format!("/s/{share_token}")));
+
+    // Browsers get a page with the details, other clients the file
+    let page = test::TestRequest::get().uri(&format!("/s/{share_token}")).insert_header((header::ACCEPT, "text/html"));
+    let response = test::call_service(&app, page.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::CACHE_CONTROL).unwrap(), "no-store");
+    let html = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    assert!(html.contains("<h1>Report</h1>"));
+
+    let response = test::call_service(&app, test::TestRequest::get().uri(&format!("/s/{share_token}")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(header::CONTENT_DISPOSITION).unwrap().to_str().unwrap().contains("report.pdf"));
+    assert_eq!(test::read_body(response).await.to_vec(), content);
+
+    let (_, body) = send(&app, request(Method::GET, &shares, &token)).await;
+    assert_eq!(body[0]["download_count"], 1);
+
+    let (status, body) = send(&app, request(Method::GET, &format!("{shares}/{share_link_id}/accesses"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let outcomes: Vec<&str> = body.as_array().unwrap().iter().map(|access| access["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, vec!["downloaded", "viewed"]);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("{shares}/{share_link_id}"), &token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, request(Method::DELETE, &format!("{shares}/{share_link_id}"), &token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, test::TestRequest::get().uri(&format!("/s/{share_token}"))).await;
+    assert_eq!(status, StatusCode::GONE);
+
+    let (status, _) = send(&app, test::TestRequest::get().uri("/s/unknown-token")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = send(&app, request(Method::GET, &shares, &token)).await;
+    assert_eq!(body[0]["active"], false);
+}
+
+
+async fn share_link_password(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let shares = format!("/api/v1/documents/{pdf_id}/shares");
+
+    let (_, created) = send(&app, request(Method::POST, &shares, &token).set_json(json!({ "password": "secret-password" }))).await;
+    assert_eq!(created["password_protected"], true);
+
+    let link = format!("/s/{}", created["token"].as_str().unwrap());
+
+    let (status, _) = send(&app, test::TestRequest::get().uri(&link)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, test::TestRequest::post().uri(&link).set_form([("password", "wrong")])).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let response = test::call_service(&app, test::TestRequest::post().uri(&link).set_form([("password", "secret-password")]).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/pdf");
+
+    let (_, body) = send(&app, request(Method::GET, &format!("{shares}/{}/accesses", created["id"].as_str().unwrap()), &token)).await;
+    let outcomes: Vec<&str> = body.as_array().unwrap().iter().map(|access| access["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, vec!["downloaded", "wrong_password", "wrong_password"]);
+}
+
+
+async fn share_link_limits(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let pdf_id = upload_pdf(&app, &token, "report.pdf", "Report", "Ada").await;
+    let shares = format!("/api/v1/documents/{pdf_id}/shares");
+
+    let (status, _) = send(&app, request(Method::POST, &shares, &token).set_json(json!({ "max_downloads": 0 }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(&app, request(Method::POST, &shares, &token).set_json(json!({ "expires_at": "2000-01-01T00:00:00Z" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, created) = send(&app, request(Method::POST, &shares, &token).set_json(json!({ "max_downloads": 1, "expires_at": "2999-01-01T00:00:00Z" }))).await;
+    let link = format!("/s/{}", created["token"].as_str().unwrap());
+
+    let (status, _) = send(&app, test::TestRequest::get().uri(&link)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, test::TestRequest::get().uri(&link)).await;
+    assert_eq!(status, StatusCode::GONE);
+
+    let (_, body) = send(&app, request(Method::GET, &shares, &token)).await;
+    assert_eq!(body[0]["download_count"], 1);
+    assert_eq!(body[0]["active"], false);
+
+    // Deleting the pdf deletes its share links
+    send(&app, request(Method::DELETE, &format!("/api/v1/documents/{pdf_id}"), &token)).await;
+    let (status, _) = send(&app, test::TestRequest::get().uri(&link)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+
+async fn webdav(backend: TestBackend) {
+    let app = backend.app().await;
+    let token = login_admin(&app).await;
+    let content = pdf_bytes("Report", "Ada", 1);
+    upload_to(&app, "/api/v1/documents", &token, &[("report.pdf", &content)]).await;
+
+    let (status, _) = send(&app, test::TestRequest::default().method(Method::from_bytes(b"PROPFIND").unwrap()).uri("/dav/")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let propfind = test::TestRequest::default()
+        .method(Method::from_bytes(b"PROPFIND").unwrap())
+        .uri("/dav/All/")
+        .insert_header(("Depth", "1"))
+        .insert_header((header::AUTHORIZATION, basic_auth(ADMIN, ADMIN_PASSWORD)));
+
+    let response = test::call_service(&app, propfind.to_request()).await;
+    assert_eq!(response.status().as_u16(), 207);
+    let listing = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    assert!(listing.contains("report.pdf"));
+
+    let get = test::TestRequest::get()
+        .uri("/dav/All/report.pdf")
+        .insert_header((header::AUTHORIZATION, basic_auth(ADMIN, ADMIN_PASSWORD)));
+
+    let response = test::call_service(&app, get.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(test::read_body(response).await.to_vec(), content);
+
+    let response = test::call_service(&app, request(Method::GET, "/dav/All/report.pdf", &token).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Files written by WebDAV clients are stored under the hash of their whole content
+    for (file_name, title) in [("first.pdf", "First"), ("second.pdf", "Second")] {
+        let content = pdf_bytes(title, "Ada", 1);
+
+        let response = test::call_service(&app, request(Method::PUT, &format!("/dav/All/{file_name}"), &token).set_payload(content.clone()).to_request()).await;
+        assert!(response.status().is_success());
+
+        let response = test::call_service(&app, request(Method::GET, &format!("/dav/All/{file_name}"), &token).to_request()).await;
+        assert_eq!(test::read_body(response).await.to_vec(), content);
+    }
+}
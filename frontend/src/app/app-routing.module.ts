@@ -3,12 +3,14 @@ import { RouterModule, Routes } from '@angular/router';
 import {UploadComponent} from "./components/upload/upload.component";
 import {OverviewComponent} from "./components/overview/overview.component";
 import {PdfDetailViewComponent} from "./components/pdf-detail-view/pdf-detail-view.component";
+import {LoginComponent} from "./components/login/login.component";
 
 const routes: Routes = [
   {path: '', redirectTo: '/overview', pathMatch: 'full'},
   {path: 'upload', component: UploadComponent},
   {path: 'overview', component: OverviewComponent},
   {path: 'detail/:id', component: PdfDetailViewComponent},
+  {path: 'login', component: LoginComponent},
   {path: '*', component: OverviewComponent},
 ];
 
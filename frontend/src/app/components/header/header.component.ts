@@ -1,4 +1,6 @@
 import { Component } from '@angular/core';
+import {Router} from "@angular/router";
+import {AuthService} from "../../services/auth.service";
 
 @Component({
   selector: 'app-header',
@@ -7,4 +9,16 @@ import { Component } from '@angular/core';
 })
 export class HeaderComponent {
 
+  constructor(
+    private authService: AuthService,
+    private router: Router
+  ) {
+  }
+
+  logout() {
+    this.authService.logout().subscribe({
+      complete: () => this.router.navigate(["/login"]),
+      error: _ => this.router.navigate(["/login"])
+    });
+  }
 }
@@ -0,0 +1,45 @@
+import { Component } from '@angular/core';
+import {Router} from "@angular/router";
+import {MatSnackBar, MatSnackBarConfig} from "@angular/material/snack-bar";
+import {AuthService} from "../../services/auth.service";
+import {Login} from "../../dtos/login";
+
+@Component({
+  selector: 'app-login',
+  templateUrl: './login.component.html',
+  styleUrls: ['./login.component.css']
+})
+export class LoginComponent {
+
+  login: Login = {
+    username: '',
+    password: ''
+  };
+
+  private snackbarConfig: MatSnackBarConfig = {
+    duration: 2000,
+  };
+
+  constructor(
+    private authService: AuthService,
+    private router: Router,
+    private _snackBar: MatSnackBar
+  ) {
+  }
+
+  submit() {
+    this.authService.login(this.login).subscribe({
+      next: _ => {
+        this.router.navigate(["/overview"]);
+      },
+      error: err => {
+        if (err.status === 401) {
+          this._snackBar.open("Invalid username or password", "Close", this.snackbarConfig);
+        } else {
+          this._snackBar.open("There was an error logging in", "Close", this.snackbarConfig);
+        }
+        this.login.password = '';
+      }
+    });
+  }
+}
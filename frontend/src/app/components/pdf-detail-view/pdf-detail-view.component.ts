@@ -202,13 +202,24 @@ export class PdfDetailViewComponent implements OnInit {
   }
 
   saveEdit() {
-    this.pdfService.updatePdf(this.pdfUpdate).subscribe({
-      next: _ => {
+    if (!this.pdfDetails) {
+      return;
+    }
+
+    this.pdfService.updatePdf(this.pdfUpdate, this.pdfDetails.version).subscribe({
+      next: value => {
+        this.pdfDetails = value;
         this.editMode = false;
         this._snackBar.open("Successfully updated pdf", "Close", this.snackbarConfig);
       },
       error: err => {
-        this._snackBar.open("There was an error updating the pdf.", "Close", this.snackbarConfig);
+        if (err.status === 412) {
+          // Someone else changed the pdf, keep the edits so they can be reviewed against the latest details
+          this.pdfDetails = err.error;
+          this._snackBar.open("The pdf was changed in the meantime. Check the latest details and save again.", "Close", this.snackbarConfig);
+        } else {
+          this._snackBar.open("There was an error updating the pdf.", "Close", this.snackbarConfig);
+        }
       }
     });
   }
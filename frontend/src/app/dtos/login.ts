@@ -0,0 +1,4 @@
+export interface Login {
+  username: string,
+  password: string,
+}
@@ -9,4 +9,6 @@ export interface PdfDetails {
   last_accessed: Date,
   uploaded: Date,
   picture: string,
+  version: number,
+  owner_id: string,
 }
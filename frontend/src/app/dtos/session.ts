@@ -0,0 +1,7 @@
+import {User} from "./user";
+
+export interface Session {
+  token: string,
+  expires_at: Date,
+  user: User,
+}
@@ -0,0 +1,6 @@
+export interface User {
+  id: string,
+  username: string,
+  is_admin: boolean,
+  time_created: Date,
+}
@@ -0,0 +1,26 @@
+import {Injectable} from '@angular/core';
+import {HttpErrorResponse, HttpEvent, HttpHandler, HttpInterceptor, HttpRequest} from "@angular/common/http";
+import {Router} from "@angular/router";
+import {Observable, catchError, throwError} from "rxjs";
+
+/**
+ * Sends the session cookie along with every request to the backend and
+ * redirects to the login page once the session is no longer valid.
+ */
+@Injectable()
+export class AuthInterceptor implements HttpInterceptor {
+
+  constructor(private router: Router) {
+  }
+
+  intercept(request: HttpRequest<unknown>, next: HttpHandler): Observable<HttpEvent<unknown>> {
+    return next.handle(request.clone({withCredentials: true})).pipe(
+      catchError((err: HttpErrorResponse) => {
+        if (err.status === 401 && !request.url.endsWith('/auth/login')) {
+          this.router.navigate(["/login"]);
+        }
+        return throwError(() => err);
+      })
+    );
+  }
+}
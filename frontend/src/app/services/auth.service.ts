@@ -0,0 +1,32 @@
+import { Injectable } from '@angular/core';
+import {HttpClient} from "@angular/common/http";
+import {Observable} from "rxjs";
+import {Globals} from "../global/globals";
+import {Login} from "../dtos/login";
+import {Session} from "../dtos/session";
+import {User} from "../dtos/user";
+
+@Injectable({
+  providedIn: 'root'
+})
+export class AuthService {
+
+  private authBaseUri: string = this.globals.backendUri + '/api/v1/auth';
+
+  constructor(
+    private httpClient: HttpClient,
+    private globals: Globals
+  ) { }
+
+  login(login: Login): Observable<Session> {
+    return this.httpClient.post<Session>(`${this.authBaseUri}/login`, login);
+  }
+
+  logout(): Observable<void> {
+    return this.httpClient.post<void>(`${this.authBaseUri}/logout`, null);
+  }
+
+  getCurrentUser(): Observable<User> {
+    return this.httpClient.get<User>(`${this.authBaseUri}/me`);
+  }
+}
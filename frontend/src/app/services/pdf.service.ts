@@ -1,5 +1,5 @@
 import { Injectable } from '@angular/core';
-import {HttpClient, HttpParams} from "@angular/common/http";
+import {HttpClient, HttpHeaders, HttpParams} from "@angular/common/http";
 import {Observable} from "rxjs";
 import {Globals} from "../global/globals";
 import {PdfOverviewInfo} from "../dtos/pdfOverview";
@@ -71,8 +71,9 @@ export class PdfService {
 
   }
 
-  updatePdf(update: UpdatePdf): Observable<void> {
-    return this.httpClient.put<void>(`${this.pdfBaseUri}pdfs/${update.id}`, update);
+  updatePdf(update: UpdatePdf, version: number): Observable<PdfDetails> {
+    const headers = new HttpHeaders({'If-Match': `"${version}"`});
+    return this.httpClient.put<PdfDetails>(`${this.pdfBaseUri}pdfs/${update.id}`, update, {headers});
   }
 
   deletePdf(id: string): Observable<void> {